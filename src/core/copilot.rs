@@ -3,6 +3,46 @@
 //! Provides integration with external code assistance APIs for enhanced
 //! code completion and AI-powered development workflows.
 
+use std::{cell::Cell, sync::Arc};
+
+use crate::{
+    core::{
+        external_llm::{Response, parse_completion, post_json_body, post_stream_body, read_stream},
+        retry::RetryPolicy,
+    },
+    errors::LlmError,
+    essentia::{
+        http::{Request, Timeouts},
+        json::{ToJson, Value, to_json_string},
+        proxy::ProxySetting,
+        tls::TlsConfig,
+        trace::HttpTrace,
+    },
+    traits::{ChatProvider, CompletionProvider, EmbeddingProvider, Provider},
+    types::{ChatDelta, ChatMessage, ChatRequest, ChatResponse},
+};
+
+/// Default External Code Assist completions endpoint.
+pub const DEFAULT_ENDPOINT: &str = "https://api.essentia.ai/code_assist/v2/completions";
+
+/// Request body of the Code Assist endpoint.
+struct AssistRequest {
+    model:       String,
+    messages:    Vec<AssistMessage>,
+    stream:      bool,
+    temperature: f32,
+    max_tokens:  Option<u32>,
+}
+
+/// Messages are sent as plain text only.
+struct AssistMessage {
+    role:    String,
+    content: String,
+}
+
+crate::impl_json!(AssistRequest { model, messages, stream, temperature = 0.7, max_tokens });
+crate::impl_json!(AssistMessage { role, content });
+
 #[allow(dead_code)]
 #[derive(Clone)]
 pub struct ExternalCodeAssistModels;
@@ -19,77 +59,187 @@ impl ExternalCodeAssistModels {
     }
 }
 
-#[allow(dead_code)]
 pub struct ExternalCodeAssist {
-    model: String,
+    model:      String,
+    api_token:  String,
+    endpoint:   String,
+    proxy:      String,
+    timeouts:   Timeouts,
+    retry:      RetryPolicy,
+    tls:        TlsConfig,
+    trace:      Option<Arc<HttpTrace>>,
+    /// Merge patch applied to every request body.
+    extra_body: Option<Value>,
 }
 
-#[allow(dead_code)]
 impl ExternalCodeAssist {
     pub fn new(model: &str) -> Self {
-        Self { model: model.to_string() }
+        Self {
+            model:      model.to_string(),
+            api_token:  String::new(),
+            endpoint:   DEFAULT_ENDPOINT.to_string(),
+            proxy:      String::new(),
+            timeouts:   Timeouts::default(),
+            retry:      RetryPolicy::default(),
+            tls:        TlsConfig::default(),
+            trace:      HttpTrace::from_env(),
+            extra_body: None,
+        }
+    }
+
+    /// Sets the token used by the [`ChatProvider`] and
+    /// [`CompletionProvider`] implementations.
+    pub fn with_api_token(mut self, api_token: &str) -> Self {
+        self.api_token = api_token.to_string();
+        self
+    }
+
+    /// Overrides the completions endpoint (full URL).
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    /// Routes API calls through `proxy`: a `http://` or `socks5://` URL, or
+    /// `direct`. Empty (the default) follows `HTTPS_PROXY` / `NO_PROXY`.
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = proxy.to_string();
+        self
+    }
+
+    /// Sets the connect, read and total deadlines for every API call.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets how transient failures (429, 5xx, resets, timeouts) are retried.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets the trusted roots and certificate pins for API calls.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
     }
 
+    /// Writes API calls, with credentials redacted, to `trace`.
+    pub fn with_trace(mut self, trace: Arc<HttpTrace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Merges `patch` into every request body as an RFC 7386 merge patch.
+    pub fn with_extra_body(mut self, patch: Value) -> Self {
+        self.extra_body = Some(patch);
+        self
+    }
+
+    /// Sends the conversation in `request`, preserving each message's role.
+    ///
+    /// Code Assist only accepts text, so multi-part messages are sent as their
+    /// concatenated text parts.
     pub fn chat_with_api(
-        &self, api_token: &str, message: &str, context: &[String],
-    ) -> Result<String, String> {
-        // External Code Assist API endpoint for completions
-        let url = "https://api.essentia.ai/code_assist/v2/completions";
-
-        // Build JSON body manually
-        let mut body = format!(r#"{{"model":"{}","messages":["#, self.model);
-
-        // Add context messages
-        for (i, ctx) in context.iter().enumerate() {
-            if i > 0 {
-                body.push(',');
-            }
-            body.push_str(&format!(
-                r#"{{"role":"system","content":"{}"}}"#,
-                escape_json(ctx)
-            ));
-        }
+        &self, api_token: &str, request: &ChatRequest,
+    ) -> Result<ChatResponse, LlmError> {
+        let body = self.build_body(request, false);
+        let http_request = self.request()?;
+        let json = self.retry.run(|| post_json_body(&http_request, api_token, &body))?;
+        parse_completion(&json)
+    }
 
-        // Add user message
-        if !context.is_empty() {
-            body.push(',');
-        }
-        body.push_str(&format!(
-            r#"{{"role":"user","content":"{}"}}"#,
-            escape_json(message)
-        ));
-        body.push_str(r#"],"stream":false,"temperature":0.7}"#);
-
-        // Use HTTPS client with auth
-        let response =
-            crate::essentia::http::post_with_auth(url, &format!("Bearer {}", api_token), &body)
-                .map_err(|e| format!("HTTP request failed: {}", e))?;
-
-        // Parse the response body as JSON
-        let response_text = std::str::from_utf8(&response.body)
-            .map_err(|_| "Invalid UTF-8 in response".to_string())?;
-
-        let json = crate::essentia::json::parse(response_text)
-            .map_err(|_| "Failed to parse JSON response".to_string())?;
-
-        // Extract the completion
-        if let Some(choices) = json.get("choices")
-            && let Some(choice) = choices.get_index(0)
-            && let Some(message) = choice.get("message")
-            && let Some(content) = message.get("content")
-        {
-            return Ok(content.as_str().unwrap_or("").to_string());
+    /// Streams the reply to `request`, calling `on_delta` per chunk. Failures
+    /// are only retried until the first delta has been delivered.
+    pub fn chat_stream_with_api(
+        &self, api_token: &str, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        let body = self.build_body(request, true);
+        let http_request = self.request()?;
+        let emitted = Cell::new(false);
+        self.retry.run_with(
+            || {
+                let response = post_stream_body(&http_request, api_token, &body)?;
+                read_stream(response, &mut |delta| {
+                    emitted.set(true);
+                    on_delta(delta)
+                })
+            },
+            |_| !emitted.get(),
+        )
+    }
+
+    /// POST to the endpoint with this client's timeouts, proxy, TLS
+    /// settings and trace.
+    fn request(&self) -> Result<Request, LlmError> {
+        Ok(Request::post(&self.endpoint)
+            .timeouts(self.timeouts)
+            .proxy(ProxySetting::parse(&self.proxy)?)
+            .tls(self.tls.clone())
+            .trace(self.trace.clone()))
+    }
+
+    fn build_body(&self, request: &ChatRequest, stream: bool) -> String {
+        let model = if request.model.is_empty() {
+            &self.model
+        } else {
+            &request.model
+        };
+
+        let body = AssistRequest {
+            model: model.clone(),
+            messages: request
+                .messages
+                .iter()
+                .map(|message| AssistMessage {
+                    role:    message.role.as_str().to_string(),
+                    content: message.text(),
+                })
+                .collect(),
+            stream,
+            temperature: request.temperature.unwrap_or(0.7),
+            max_tokens: request.max_tokens,
+        };
+        let mut body = body.to_json();
+        if let Some(patch) = &self.extra_body {
+            body.merge_patch(patch);
         }
+        to_json_string(&body)
+    }
+}
 
-        Err("Failed to extract completion from response".to_string())
+impl Provider for ExternalCodeAssist {
+    fn name(&self) -> &str {
+        "external_code_assist"
     }
 }
 
-#[allow(unused)]
-fn escape_json(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t")
+impl ChatProvider for ExternalCodeAssist {
+    fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        self.chat_with_api(&self.api_token, request)
+    }
+
+    fn chat_stream(
+        &self, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        self.chat_stream_with_api(&self.api_token, request, on_delta)
+    }
+}
+
+impl CompletionProvider for ExternalCodeAssist {
+    /// Code Assist only exposes a chat-style endpoint, so a completion is a
+    /// single-turn chat with the prompt as the user message.
+    fn complete(&self, prompt: &str) -> Result<Response, LlmError> {
+        let request = ChatRequest::new(vec![ChatMessage::user(prompt)]);
+        self.chat_with_api(&self.api_token, &request).map(Response::from)
+    }
+}
+
+impl EmbeddingProvider for ExternalCodeAssist {
+    /// Code Assist has no embeddings endpoint; always fails with
+    /// [`LlmError::Unsupported`].
+    fn embed(&self, _inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::Unsupported { provider: "external_code_assist", operation: "embeddings" })
+    }
 }
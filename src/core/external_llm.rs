@@ -1,8 +1,27 @@
-use std::collections::HashMap;
+//! External LLM API Client
+//!
+//! Talks to OpenAI-compatible `/v1/chat/completions` endpoints using the
+//! bespoke `essentia::http` and `essentia::json` implementations.
 
-use essentia_core_utils::crypto::sha256;
+use std::{cell::Cell, collections::HashMap, sync::Arc};
 
-use crate::core::anon::Anon;
+use crate::{
+    core::retry::RetryPolicy,
+    errors::LlmError,
+    essentia::{
+        http::{Request, StreamingResponse, Timeouts},
+        json::{Object, Value},
+        proxy::ProxySetting,
+        sse::SseReader,
+        tls::TlsConfig,
+        trace::HttpTrace,
+    },
+    traits::{ChatProvider, CompletionProvider, EmbeddingProvider, Provider},
+    types::{ChatDelta, ChatRequest, ChatResponse, StreamAccumulator, Usage},
+};
+
+/// Default chat completions endpoint.
+pub const DEFAULT_ENDPOINT: &str = "https://api.x.ai/v1/chat/completions";
 
 #[derive(Clone)]
 pub struct Models;
@@ -26,116 +45,306 @@ impl Models {
 }
 
 pub struct ExternalLlm {
-    model_mode: String,
-    model:      String,
-    mode:       String,
-    c_run:      i32,
-    keys:       HashMap<String, String>,
-    proxy:      String,
+    model:       String,
+    api_key:     String,
+    endpoint:    String,
+    temperature: f32,
+    max_tokens:  u32,
+    proxy:       String,
+    timeouts:    Timeouts,
+    retry:       RetryPolicy,
+    /// Request bodies of at least this many bytes are gzipped.
+    compress:    Option<usize>,
+    tls:         TlsConfig,
+    trace:       Option<Arc<HttpTrace>>,
+    /// Merge patch applied to every request body.
+    extra_body:  Option<Value>,
 }
 
 impl ExternalLlm {
     pub fn new(model: &str, proxy: &str) -> Self {
         Self {
-            model_mode: Models::get_model_mode(model, 0),
-            model:      model.to_string(),
-            mode:       Models::get_model_mode(model, 1),
-            c_run:      0,
-            keys:       Anon::generate_keys(),
-            proxy:      proxy.to_string(),
+            model:       model.to_string(),
+            api_key:     String::new(),
+            endpoint:    DEFAULT_ENDPOINT.to_string(),
+            temperature: 0.7,
+            max_tokens:  2048,
+            proxy:       proxy.to_string(),
+            timeouts:    Timeouts::default(),
+            retry:       RetryPolicy::default(),
+            compress:    None,
+            tls:         TlsConfig::default(),
+            trace:       HttpTrace::from_env(),
+            extra_body:  None,
         }
     }
 
+    /// Sets the key used by the [`ChatProvider`], [`CompletionProvider`] and
+    /// [`EmbeddingProvider`] implementations.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    /// Overrides the chat completions endpoint (full URL).
+    ///
+    /// The completions and embeddings endpoints are derived from it by
+    /// replacing the trailing `chat/completions` segment; with any other
+    /// endpoint those calls fail with [`LlmError::Config`].
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Sets the connect, read and total deadlines for every API call.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets how transient failures (429, 5xx, resets, timeouts) are retried.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Gzips request bodies of at least `min_len` bytes, e.g. large
+    /// embedding batches. Only for endpoints that accept
+    /// `Content-Encoding: gzip` requests.
+    pub fn with_request_compression(mut self, min_len: usize) -> Self {
+        self.compress = Some(min_len);
+        self
+    }
+
+    /// Sets the trusted roots and certificate pins for API calls.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Writes API calls, with credentials redacted, to `trace`.
+    pub fn with_trace(mut self, trace: Arc<HttpTrace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Merges `patch` into every request body as an RFC 7386 merge patch,
+    /// for provider-specific parameters; `null` members remove defaults.
+    pub fn with_extra_body(mut self, patch: Value) -> Self {
+        self.extra_body = Some(patch);
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Proxy given to [`new`](Self::new): empty to follow `HTTPS_PROXY` /
+    /// `NO_PROXY`, `direct`, or a `http://` / `socks5://` URL.
+    pub fn proxy(&self) -> &str {
+        &self.proxy
+    }
+
+    /// POST to `url` with this client's timeouts, proxy, TLS settings,
+    /// trace and compression.
+    fn request(&self, url: &str) -> Result<Request, LlmError> {
+        let request = Request::post(url)
+            .timeouts(self.timeouts)
+            .proxy(ProxySetting::parse(&self.proxy)?)
+            .tls(self.tls.clone())
+            .trace(self.trace.clone());
+        Ok(match self.compress {
+            Some(min_len) => request.compress_body(min_len),
+            None => request,
+        })
+    }
+
+    /// Sends `request` to the chat completions endpoint and returns the
+    /// first choice.
     pub fn chat_with_api(
-        &self, api_key: &str, message: &str, _context: &[String],
-    ) -> Result<String, String> {
-        // Use struct fields
-        let _model_mode = &self.model_mode;
-        let _model = &self.model;
-        let _mode = &self.mode;
-        let _c_run = self.c_run;
-        let _keys = &self.keys;
-        let _proxy = &self.proxy;
-        crate::core::logger::Log::error("Test error message");
-        crate::core::logger::Log::success("Test success message");
-
-        // Use crypto for API key validation
-        let _key_hash = sha256(api_key.as_bytes());
-
-        // Use anon for key generation
-        let keys = crate::core::anon::Anon::generate_keys();
-        let challenge = b"api_challenge";
-        let _signed_challenge = crate::core::anon::Anon::sign_challenge(
-            challenge,
-            keys.get("private").unwrap_or(&"".to_string()),
-        );
+        &self, api_key: &str, request: &ChatRequest,
+    ) -> Result<ChatResponse, LlmError> {
+        let payload = self.patched(self.build_request(request));
+        let http_request = self.request(&self.endpoint)?;
+        let json = self.retry.run(|| post_json(&http_request, api_key, &payload))?;
+        let response = parse_completion(&json)?;
+        crate::core::logger::Log::info(&format!(
+            "External LLM API response: {} chars, finish_reason={}",
+            response.text().len(),
+            response.finish_reason.as_ref().map_or("none", |r| r.as_str())
+        ));
+        Ok(response)
+    }
 
-        // Use parser for extracting data
-        let _parsed_values =
-            crate::core::parser::Parser::parse_values(message, "loading", "script");
-        let _anim_data = crate::core::parser::Parser::get_anim(message, "verification");
-        let scripts = vec![message.to_string()];
-        let _llm_data = crate::core::parser::Parser::parse_external_llm(scripts);
-
-        // Use xctid for signature generation
-        let _signature =
-            crate::core::xctid::Signature::generate_sign("/api", "POST", api_key, "svg_data", None);
-
-        // Use runtime Run struct
-        let run = crate::core::runtime::Run;
-        run.execute();
-
-        // Use HTTP client (dummy usage)
-        let http_response = crate::essentia::http::Response {
-            status:  200,
-            headers: std::collections::HashMap::new(),
-            body:    vec![],
-        };
-        let _status = http_response.status;
-        let _headers = &http_response.headers;
-        let _body = &http_response.body;
-
-        // Use TLS (dummy)
-        let _tls_result = crate::essentia::tls::tls_connect("api.x.ai", 443);
-
-        // Dummy response structure matching official API
-        let dummy_response = match message.to_lowercase().as_str() {
-            "hello" | "hi" => "Hello! I'm an Essentia AI assistant. How can I help you today?",
-            "what is rust?" => {
-                "Rust is a systems programming language focused on safety, speed, and concurrency. \
-                 It prevents common programming errors like null pointer dereferences and data \
-                 races at compile time."
-            },
-            "tell me a joke" => {
-                "Why did the Rust programmer go broke? Because they kept borrowing without \
-                 returning!"
-            },
-            _ => {
-                // Use regex search
-                if crate::essentia::regex::search(r"rust", message).is_some() {
-                    "I see you're asking about Rust! It's a great language for systems programming."
-                } else if crate::essentia::regex::search(r"ai|artificial", message).is_some() {
-                    "As an Essentia AI assistant, I'm here to help with any questions you have!"
-                } else {
-                    "I'm a pure Rust implementation with zero external dependencies. This is a \
-                     dummy response. Full API access requires HTTPS/TLS implementation in std, \
-                     which is not yet available. The framework is ready for official API \
-                     integration."
-                }
+    /// Streams `request`, calling `on_delta` for every chunk as it arrives.
+    ///
+    /// Returning `false` from `on_delta` stops reading; the response
+    /// accumulated so far is returned either way. Failures are only retried
+    /// until the first delta has been delivered.
+    pub fn chat_stream_with_api(
+        &self, api_key: &str, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        let mut payload = self.build_request(&ChatRequest { stream: true, ..request.clone() });
+        if let Value::Object(obj) = &mut payload {
+            // Ask for a trailing usage chunk
+            let mut options = Object::new();
+            options.insert("include_usage".to_string(), Value::Bool(true));
+            obj.insert("stream_options".to_string(), Value::Object(options));
+        }
+
+        let body = crate::essentia::json::to_json_string(&self.patched(payload));
+        let http_request = self.request(&self.endpoint)?;
+        let emitted = Cell::new(false);
+        self.retry.run_with(
+            || {
+                let response = post_stream_body(&http_request, api_key, &body)?;
+                read_stream(response, &mut |delta| {
+                    emitted.set(true);
+                    on_delta(delta)
+                })
             },
+            |_| !emitted.get(),
+        )
+    }
+
+    /// Sends `prompt` to the legacy `/v1/completions` endpoint.
+    pub fn complete_with_api(&self, api_key: &str, prompt: &str) -> Result<Response, LlmError> {
+        let mut obj = Object::new();
+        obj.insert("model".to_string(), Value::String(self.model.clone()));
+        obj.insert("prompt".to_string(), Value::String(prompt.to_string()));
+        obj.insert("temperature".to_string(), Value::from(self.temperature));
+        obj.insert("max_tokens".to_string(), Value::from(self.max_tokens));
+
+        let http_request = self.request(&self.sibling_endpoint("completions")?)?;
+        let payload = self.patched(Value::Object(obj));
+        let json = self.retry.run(|| post_json(&http_request, api_key, &payload))?;
+        let choice = json
+            .pointer("/choices/0")
+            .ok_or_else(|| LlmError::invalid_response(Some(200), "Response has no choices"))?;
+
+        let mut response = self
+            .create_response(choice.get("text").and_then(|t| t.as_str()).unwrap_or("").to_string());
+        response.finish_reason =
+            choice.get("finish_reason").and_then(|f| f.as_str()).map(String::from);
+        response.usage = json.get("usage").map(Usage::from_value);
+        Ok(response)
+    }
+
+    /// Embeds `inputs` via the `/v1/embeddings` endpoint.
+    pub fn embed_with_api(
+        &self, api_key: &str, inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        let mut obj = Object::new();
+        obj.insert("model".to_string(), Value::String(self.model.clone()));
+        obj.insert(
+            "input".to_string(),
+            Value::Array(inputs.iter().map(|i| Value::String(i.clone())).collect()),
+        );
+
+        let http_request = self.request(&self.sibling_endpoint("embeddings")?)?;
+        let payload = self.patched(Value::Object(obj));
+        let json = self.retry.run(|| post_json(&http_request, api_key, &payload))?;
+        let Some(Value::Array(data)) = json.get("data") else {
+            return Err(LlmError::invalid_response(
+                Some(200),
+                "Response has no data",
+            ));
         };
 
-        crate::core::logger::Log::info(&format!("External LLM API response: {}", dummy_response));
+        let invalid = |message: &str| LlmError::invalid_response(Some(200), message);
+        if data.len() != inputs.len() {
+            return Err(invalid(&format!(
+                "Expected {} embeddings, got {}",
+                inputs.len(),
+                data.len()
+            )));
+        }
 
-        // Create response using the method
-        let _response_obj = self.create_response(dummy_response.to_string());
+        // Entries carry an `index`; order by it rather than trusting array order.
+        let mut embeddings: Vec<Option<Vec<f32>>> = vec![None; data.len()];
+        for (position, entry) in data.iter().enumerate() {
+            let index = match entry.get("index") {
+                None => position,
+                Some(index) => index
+                    .as_u64()
+                    .and_then(|index| usize::try_from(index).ok())
+                    .ok_or_else(|| invalid("Embedding index is not a non-negative integer"))?,
+            };
+            let Some(Value::Array(values)) = entry.get("embedding") else {
+                return Err(invalid("Entry has no embedding"));
+            };
+            let vector = values
+                .iter()
+                .map(|v| v.as_f64().map(|f| f as f32))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("Embedding has a non-numeric value"))?;
+            match embeddings.get_mut(index) {
+                None => return Err(invalid("Embedding index out of range")),
+                Some(Some(_)) => {
+                    return Err(invalid(&format!("Duplicate embedding index {}", index)));
+                },
+                Some(slot) => *slot = Some(vector),
+            }
+        }
+        // Every index is in range and unique, so none are missing
+        embeddings
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("Missing embedding"))
+    }
+
+    /// `payload` with the configured extra body merged in.
+    fn patched(&self, mut payload: Value) -> Value {
+        if let Some(patch) = &self.extra_body {
+            payload.merge_patch(patch);
+        }
+        payload
+    }
+
+    /// Swaps the trailing `chat/completions` of the endpoint for `resource`,
+    /// rather than posting to an endpoint that serves something else.
+    fn sibling_endpoint(&self, resource: &str) -> Result<String, LlmError> {
+        match self.endpoint.strip_suffix("chat/completions") {
+            Some(base) => Ok(format!("{}{}", base, resource)),
+            None => Err(LlmError::Config(format!(
+                "Cannot derive the {} endpoint from {}, which does not end in chat/completions",
+                resource, self.endpoint
+            ))),
+        }
+    }
 
-        Ok(dummy_response.to_string())
+    /// Builds the `/v1/chat/completions` request payload, filling the model
+    /// and sampling parameters the request leaves unset from this client.
+    pub fn build_request(&self, request: &ChatRequest) -> Value {
+        let mut request = request.clone();
+        if request.model.is_empty() {
+            request.model = self.model.clone();
+        }
+        request.temperature = request.temperature.or(Some(self.temperature));
+        request.max_tokens = request.max_tokens.or(Some(self.max_tokens));
+        request.to_value()
     }
 
     pub fn create_response(&self, response: String) -> Response {
         Response {
             response,
+            role: String::from("assistant"),
+            finish_reason: None,
+            usage: None,
             stream_response: vec![],
             images: None,
             extra_data: std::collections::HashMap::new(),
@@ -143,9 +352,428 @@ impl ExternalLlm {
     }
 }
 
+impl Provider for ExternalLlm {
+    fn name(&self) -> &str {
+        "external_llm"
+    }
+}
+
+impl ChatProvider for ExternalLlm {
+    fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        self.chat_with_api(&self.api_key, request)
+    }
+
+    fn chat_stream(
+        &self, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        self.chat_stream_with_api(&self.api_key, request, on_delta)
+    }
+}
+
+impl CompletionProvider for ExternalLlm {
+    fn complete(&self, prompt: &str) -> Result<Response, LlmError> {
+        self.complete_with_api(&self.api_key, prompt)
+    }
+}
+
+impl EmbeddingProvider for ExternalLlm {
+    fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.embed_with_api(&self.api_key, inputs)
+    }
+}
+
+/// Sends `payload` on `request` with a bearer token and returns the parsed
+/// 2xx body.
+fn post_json(request: &Request, api_key: &str, payload: &Value) -> Result<Value, LlmError> {
+    post_json_body(
+        request,
+        api_key,
+        &crate::essentia::json::to_json_string(payload),
+    )
+}
+
+/// Sends an already-serialized JSON `body` on `request` with a bearer token
+/// and returns the parsed 2xx body.
+pub(crate) fn post_json_body(
+    request: &Request, api_key: &str, body: &str,
+) -> Result<Value, LlmError> {
+    let http_response = request.clone().bearer_auth(api_key).json(body).send()?;
+    let status = http_response.status;
+
+    if !(200..300).contains(&status) {
+        return Err(LlmError::from_response(
+            status,
+            &http_response.headers,
+            &String::from_utf8_lossy(&http_response.body),
+        ));
+    }
+
+    let text = std::str::from_utf8(&http_response.body)
+        .map_err(|_| LlmError::invalid_response(Some(status), "Invalid UTF-8 in response"))?;
+
+    crate::essentia::json::parse(text).map_err(|e| LlmError::InvalidResponse {
+        status:  Some(status),
+        message: "Failed to parse JSON response".to_string(),
+        source:  Some(Box::new(e)),
+    })
+}
+
+/// Sends a streaming JSON `body` on `request` with a bearer token, returning
+/// once the response head arrives.
+pub(crate) fn post_stream_body(
+    request: &Request, api_key: &str, body: &str,
+) -> Result<StreamingResponse, LlmError> {
+    request
+        .clone()
+        .header("Accept", "text/event-stream")
+        .bearer_auth(api_key)
+        .json(body)
+        .send_streaming()
+}
+
+/// Reads an SSE chat completions stream to the end (or until `on_delta`
+/// returns `false`) and folds the deltas into a response.
+pub(crate) fn read_stream(
+    response: StreamingResponse, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+) -> Result<ChatResponse, LlmError> {
+    let status = response.status;
+    if !(200..300).contains(&status) {
+        let response = response.into_response()?;
+        return Err(LlmError::from_response(
+            status,
+            &response.headers,
+            &String::from_utf8_lossy(&response.body),
+        ));
+    }
+
+    let mut accumulator = StreamAccumulator::new();
+    for event in SseReader::new(response) {
+        let event = event?;
+        let json =
+            crate::essentia::json::parse(&event.data).map_err(|e| LlmError::InvalidResponse {
+                status:  Some(status),
+                message: "Failed to parse stream chunk".to_string(),
+                source:  Some(Box::new(e)),
+            })?;
+        // Providers report mid-stream failures as an `error` event payload
+        if json.get("error").is_some() {
+            let error = crate::errors::ProviderError::from_body(&event.data);
+            return Err(LlmError::invalid_response(Some(status), error.summary()));
+        }
+        let delta = ChatDelta::from_value(&json)
+            .map_err(|e| LlmError::invalid_response(Some(status), &e))?;
+        accumulator.push(&delta)?;
+        if !on_delta(&delta) {
+            break;
+        }
+    }
+    Ok(accumulator.finish())
+}
+
+pub(crate) fn parse_completion(json: &Value) -> Result<ChatResponse, LlmError> {
+    ChatResponse::from_value(json).map_err(|e| LlmError::invalid_response(Some(200), &e))
+}
+
+/// Flattened reply shape kept for callers that predate [`ChatResponse`].
 pub struct Response {
     pub response:        String,
+    pub role:            String,
+    pub finish_reason:   Option<String>,
+    pub usage:           Option<Usage>,
     pub stream_response: Vec<String>,
     pub images:          Option<String>,
     pub extra_data:      HashMap<String, String>,
 }
+
+impl From<ChatResponse> for Response {
+    fn from(chat: ChatResponse) -> Self {
+        let mut extra_data = HashMap::new();
+        if let Some(id) = chat.id {
+            extra_data.insert("id".to_string(), id);
+        }
+        if let Some(model) = chat.model {
+            extra_data.insert("model".to_string(), model);
+        }
+        Self {
+            response: chat.message.text(),
+            role: chat.message.role.as_str().to_string(),
+            finish_reason: chat.finish_reason.map(|r| r.to_string()),
+            usage: chat.usage,
+            stream_response: vec![],
+            images: None,
+            extra_data,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::{
+        errors::ErrorKind,
+        types::{ChatMessage, FinishReason, Role},
+    };
+
+    /// Serves one canned HTTP response on a loopback port and hands back the
+    /// raw request it received.
+    fn serve_once(status: &str, body: &str) -> (String, thread::JoinHandle<String>) {
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: \
+             close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        serve_parts(vec![response.into_bytes()])
+    }
+
+    /// Like [`serve_once`], but writes the raw response in separate pieces so
+    /// the client sees them arrive incrementally.
+    fn serve_parts(parts: Vec<Vec<u8>>) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).expect("read");
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .and_then(|v| v.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            for part in parts {
+                stream.write_all(&part).expect("write");
+                stream.flush().expect("flush");
+                thread::sleep(std::time::Duration::from_millis(5));
+            }
+            String::from_utf8_lossy(&request).into_owned()
+        });
+        (format!("http://{}/v1/chat/completions", addr), handle)
+    }
+
+    #[test]
+    fn test_chat_with_api_parses_completion() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"id":"chatcmpl-1","model":"test-model","choices":[{"index":0,"message":{"role":"assistant","content":"Hi there"},"finish_reason":"stop"}],"usage":{"prompt_tokens":9,"completion_tokens":2,"total_tokens":11}}"#,
+        );
+        let llm = ExternalLlm::new("test-model", "")
+            .with_endpoint(&url)
+            .with_temperature(0.2)
+            .with_max_tokens(64);
+
+        let request = ChatRequest::new(vec![
+            ChatMessage::system("Be brief"),
+            ChatMessage::user("hello"),
+            ChatMessage::assistant("hey"),
+            ChatMessage::user("how are you"),
+        ]);
+        let response = llm.chat_with_api("sk-test", &request).expect("chat");
+
+        assert_eq!(response.text(), "Hi there");
+        assert_eq!(response.message.role, Role::Assistant);
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(
+            response.usage,
+            Some(Usage { prompt_tokens: 9, completion_tokens: 2, total_tokens: 11 })
+        );
+        assert_eq!(response.id.as_deref(), Some("chatcmpl-1"));
+
+        let request = server.join().expect("server");
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1\r\n"));
+        assert!(request.contains("Authorization: Bearer sk-test\r\n"));
+        let body = &request[request.find("\r\n\r\n").expect("body") + 4..];
+        let json = crate::essentia::json::parse(body).expect("request json");
+        assert_eq!(
+            json.get("model").and_then(|v| v.as_str()),
+            Some("test-model")
+        );
+        assert!(matches!(json.get("temperature"), Some(t) if t.as_f64() == Some(0.2)));
+        assert!(matches!(json.get("max_tokens"), Some(t) if t.as_u64() == Some(64)));
+        let roles: Vec<_> = (0..4)
+            .filter_map(|i| json.get("messages")?.get_index(i)?.get("role")?.as_str())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+    }
+
+    #[test]
+    fn test_embeddings_are_validated() {
+        let embed = |body: &str| {
+            let (url, _server) = serve_once("200 OK", body);
+            let inputs = ["a".to_string(), "b".to_string()];
+            ExternalLlm::new("test-model", "")
+                .with_endpoint(&url)
+                .embed_with_api("sk", &inputs)
+        };
+
+        let embeddings =
+            embed(r#"{"data":[{"index":1,"embedding":[0.5]},{"index":0,"embedding":[1,-2]}]}"#)
+                .expect("embeddings");
+        assert_eq!(embeddings, vec![vec![1.0, -2.0], vec![0.5]]);
+
+        let rejected = [
+            r#"{"data":[{"index":0,"embedding":[1]}]}"#,
+            r#"{"data":[{"index":0,"embedding":[1]},{"index":0,"embedding":[2]}]}"#,
+            r#"{"data":[{"index":0,"embedding":[1]},{"index":2,"embedding":[2]}]}"#,
+            r#"{"data":[{"index":-1,"embedding":[1]},{"index":1,"embedding":[2]}]}"#,
+            r#"{"data":[{"index":0,"embedding":[1]},{"index":1,"embedding":["2"]}]}"#,
+            r#"{"data":[{"index":0,"embedding":[1]},{"index":1,"embedding":[null]}]}"#,
+        ];
+        for body in rejected {
+            let err = embed(body).expect_err(body);
+            assert_eq!(err.kind(), ErrorKind::InvalidResponse, "{}", body);
+        }
+
+        // A gateway URL without chat/completions has no known siblings
+        let gateway = ExternalLlm::new("test-model", "").with_endpoint("http://127.0.0.1:1/llm");
+        let err = gateway.embed_with_api("sk", &["a".to_string()]).expect_err("embeddings");
+        assert_eq!(err.kind(), ErrorKind::Config);
+        let err = gateway.complete_with_api("sk", "a").err().map(|e| e.kind());
+        assert_eq!(err, Some(ErrorKind::Config));
+    }
+
+    #[test]
+    fn test_extra_body_is_merged_into_payload() {
+        let (url, server) = serve_once(
+            "200 OK",
+            r#"{"choices":[{"message":{"role":"assistant","content":"ok"}}]}"#,
+        );
+        let patch = crate::essentia::json::parse(
+            r#"{"max_tokens": null, "top_k": 5, "provider": {"order": ["a", "b"]}}"#,
+        )
+        .expect("patch");
+        let llm = ExternalLlm::new("test-model", "").with_endpoint(&url).with_extra_body(patch);
+        let request = ChatRequest::new(vec![ChatMessage::user("hello")]);
+        llm.chat_with_api("sk-test", &request).expect("chat");
+
+        let request = server.join().expect("server");
+        let body = &request[request.find("\r\n\r\n").expect("body") + 4..];
+        let json = crate::essentia::json::parse(body).expect("request json");
+        assert_eq!(json.get("max_tokens"), None);
+        assert_eq!(json.pointer("/top_k").and_then(Value::as_i64), Some(5));
+        assert_eq!(
+            json.pointer("/provider/order/1").and_then(Value::as_str),
+            Some("b")
+        );
+        assert_eq!(
+            json.pointer("/messages/0/content").and_then(Value::as_str),
+            Some("hello")
+        );
+    }
+
+    #[test]
+    fn test_chat_stream_delivers_deltas() {
+        // SSE over chunked encoding, with a multi-byte character split
+        // across two chunks
+        let events = concat!(
+            "data: {\"id\":\"s1\",\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\
+             Gr\u{fc}\"}}]}\n\n",
+            "data: {\"id\":\"s1\",\"choices\":[{\"delta\":{\"content\":\"\u{df}e\"},\"\
+             finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"id\":\"s1\",\"choices\":[],\"usage\":{\"prompt_tokens\":4,\"\
+             completion_tokens\":2,\"total_tokens\":6}}\n\n",
+            "data: [DONE]\n\n",
+        )
+        .as_bytes();
+        let split = events.iter().position(|&b| b == 0xC3).expect("multi-byte char") + 1;
+        let chunk = |data: &[u8]| {
+            let mut out = format!("{:x}\r\n", data.len()).into_bytes();
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\r\n");
+            out
+        };
+        let (url, server) = serve_parts(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: \
+              chunked\r\n\r\n"
+                .to_vec(),
+            chunk(&events[..split]),
+            chunk(&events[split..]),
+            b"0\r\n\r\n".to_vec(),
+        ]);
+        let llm = ExternalLlm::new("test-model", "").with_endpoint(&url);
+
+        let mut deltas = Vec::new();
+        let response = llm
+            .chat_stream_with_api(
+                "sk-test",
+                &ChatRequest::new(vec![ChatMessage::user("hi")]),
+                &mut |delta| {
+                    deltas.push(delta.content.clone());
+                    true
+                },
+            )
+            .expect("stream");
+
+        assert_eq!(deltas, ["Gr\u{fc}", "\u{df}e", ""]);
+        assert_eq!(response.text(), "Gr\u{fc}\u{df}e");
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.usage.map(|u| u.total_tokens), Some(6));
+
+        let request = server.join().expect("server");
+        assert!(request.contains("Accept: text/event-stream\r\n"));
+        assert!(request.contains("\"stream\":true"));
+    }
+
+    #[test]
+    fn test_chat_with_api_reports_provider_error() {
+        let (url, server) = serve_once(
+            "401 Unauthorized",
+            r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#,
+        );
+        let llm = ExternalLlm::new("test-model", "").with_endpoint(&url);
+
+        let err = llm
+            .chat_with_api("bad", &ChatRequest::new(vec![ChatMessage::user("hello")]))
+            .expect_err("error");
+        server.join().expect("server");
+
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert_eq!(err.status(), Some(401));
+        assert!(!err.is_retryable());
+        let provider = err.provider_error().expect("provider error");
+        assert_eq!(provider.summary(), "Incorrect API key provided");
+        assert_eq!(
+            provider.error_type.as_deref(),
+            Some("invalid_request_error")
+        );
+        assert_eq!(provider.code.as_deref(), Some("invalid_api_key"));
+    }
+
+    #[test]
+    fn test_chat_with_api_reports_transport_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().expect("addr")
+        );
+        drop(listener);
+
+        let llm = ExternalLlm::new("test-model", "").with_endpoint(&url);
+        let err = llm
+            .chat_with_api("key", &ChatRequest::new(vec![ChatMessage::user("hello")]))
+            .expect_err("error");
+        assert_eq!(err.kind(), ErrorKind::Connect);
+        assert!(err.is_retryable());
+        assert_eq!(err.status(), None);
+    }
+}
@@ -1,3 +1,7 @@
+/// Environment variable that turns on [`Log::debug`] output when set to
+/// anything but `0` or an empty string.
+pub const DEBUG_ENV: &str = "ESSENTIA_DEBUG";
+
 pub struct Log;
 
 impl Log {
@@ -12,4 +16,19 @@ impl Log {
     pub fn success(msg: &str) {
         println!("SUCCESS: {}", msg);
     }
+
+    /// Diagnostic detail, written to stderr only when [`DEBUG_ENV`] is set.
+    pub fn debug(msg: &str) {
+        if Self::debug_enabled() {
+            eprintln!("DEBUG: {}", msg);
+        }
+    }
+
+    /// Whether [`debug`](Self::debug) output is on, read once per process.
+    pub fn debug_enabled() -> bool {
+        static ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        *ENABLED.get_or_init(|| {
+            std::env::var(DEBUG_ENV).is_ok_and(|value| !value.is_empty() && value != "0")
+        })
+    }
 }
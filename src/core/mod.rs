@@ -3,5 +3,7 @@ pub mod copilot;
 pub mod external_llm;
 pub mod logger;
 pub mod parser;
+pub mod retry;
 pub mod runtime;
+pub mod stream;
 pub mod xctid;
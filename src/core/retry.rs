@@ -0,0 +1,460 @@
+//! Retry policy for provider calls.
+//!
+//! Retries transient failures (see [`LlmError::is_retryable`]) with
+//! exponential backoff and jitter. A server-provided delay — `Retry-After` or
+//! the `x-ratelimit-reset-*` headers — takes precedence over the computed
+//! backoff, up to the same maximum. Both the number of attempts and the total
+//! time spent are capped.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{core::logger::Log, errors::LlmError, essentia::http::Headers};
+
+/// How provider calls are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts including the first; `1` disables retries.
+    pub max_attempts: u32,
+    /// Backoff before the second attempt; doubles on every further attempt.
+    pub base_delay:   Duration,
+    /// Upper bound on the backoff, including delays the server asks for.
+    pub max_delay:    Duration,
+    /// Give up instead of waiting past this much total time.
+    pub max_elapsed:  Option<Duration>,
+    /// Randomize each backoff between half and all of its computed value, so
+    /// concurrent clients do not retry in lockstep.
+    pub jitter:       bool,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        base_delay:   Duration::ZERO,
+        max_delay:    Duration::ZERO,
+        max_elapsed:  None,
+        jitter:       false,
+    };
+
+    /// Runs `op` until it succeeds, fails with a non-retryable error, or the
+    /// attempt/time budget is exhausted. Returns the last error in that case.
+    pub fn run<T>(&self, op: impl FnMut() -> Result<T, LlmError>) -> Result<T, LlmError> {
+        self.run_with(op, |_| true)
+    }
+
+    /// Like [`run`](Self::run), but only retries while `can_retry` agrees,
+    /// e.g. not after a stream has already delivered output.
+    pub fn run_with<T>(
+        &self, mut op: impl FnMut() -> Result<T, LlmError>, can_retry: impl Fn(&LlmError) -> bool,
+    ) -> Result<T, LlmError> {
+        let started = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let error = match op() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if attempt >= self.max_attempts || !error.is_retryable() || !can_retry(&error) {
+                return Err(error);
+            }
+
+            let delay = match error.retry_after() {
+                Some(hint) => hint.min(self.max_delay),
+                None => self.backoff(attempt),
+            };
+            if self.max_elapsed.is_some_and(|max| started.elapsed() + delay > max) {
+                return Err(error);
+            }
+            Log::info(&format!(
+                "Retrying in {} ms (attempt {} of {}): {}",
+                delay.as_millis(),
+                attempt + 1,
+                self.max_attempts,
+                error
+            ));
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Backoff to wait after failed attempt number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+        if self.jitter {
+            delay.div_f64(2.0).mul_f64(1.0 + random_fraction())
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay:   Duration::from_millis(500),
+            max_delay:    Duration::from_secs(30),
+            max_elapsed:  Some(Duration::from_secs(120)),
+            jitter:       true,
+        }
+    }
+}
+
+/// Delay the server asked for before retrying, from `Retry-After` (seconds
+/// or HTTP-date) or, failing that, the `x-ratelimit-reset-*` header of an
+/// exhausted `x-ratelimit-remaining-*` budget.
+pub fn retry_hint(headers: &Headers) -> Option<Duration> {
+    if let Some(value) = headers.get("Retry-After") {
+        let value = value.trim();
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        let at = parse_http_date(value)?;
+        return Some(at.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO));
+    }
+
+    ["requests", "tokens"]
+        .iter()
+        .filter(|budget| {
+            // Without a remaining count, assume the reset applies
+            headers
+                .get(&format!("x-ratelimit-remaining-{}", budget))
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .is_none_or(|remaining| remaining == 0)
+        })
+        .filter_map(|budget| headers.get(&format!("x-ratelimit-reset-{}", budget)))
+        .filter_map(parse_reset)
+        .max()
+}
+
+/// Parses an `x-ratelimit-reset-*` value: plain seconds (`"1.5"`) or a Go
+/// style duration (`"1m30s"`, `"250ms"`). `None` if it is negative or does
+/// not fit a [`Duration`].
+fn parse_reset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        total += number * scale;
+        rest = &rest[unit_len..];
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+/// Parses an IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT`, for years
+/// 1970 through 9999.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut clock = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    if parts.next()? != "GMT"
+        || !(1970..=9999).contains(&year)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days.checked_mul(86_400)?.checked_add(hour * 3600 + minute * 60 + second)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Uniform value in `[0, 1)`, seeded from the per-process random hasher keys.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+    hasher.write_u128(nanos);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+    use crate::{
+        core::external_llm::ExternalLlm,
+        errors::ErrorKind,
+        types::{ChatMessage, ChatRequest},
+    };
+
+    /// What the scripted server does with one incoming connection.
+    enum Step {
+        /// Replies with this raw response and closes.
+        Reply(String),
+        /// Closes without replying, like a reset connection.
+        Drop,
+    }
+
+    /// Serves one connection per step, in order, and reports how many
+    /// requests arrived.
+    fn scripted_server(steps: Vec<Step>) -> (String, JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().expect("addr")
+        );
+        let handle = thread::spawn(move || {
+            let mut served = 0;
+            for step in steps {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("read");
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().expect("length");
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).expect("body");
+                served += 1;
+                if let Step::Reply(response) = &step {
+                    stream.write_all(response.as_bytes()).expect("write");
+                }
+            }
+            served
+        });
+        (url, handle)
+    }
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            max_elapsed: Some(Duration::from_secs(5)),
+            jitter: true,
+        }
+    }
+
+    fn reply(status: &str, headers: &str, body: &str) -> Step {
+        Step::Reply(format!(
+            "HTTP/1.1 {}\r\n{}Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        ))
+    }
+
+    fn ok() -> Step {
+        reply(
+            "200 OK",
+            "",
+            r#"{"choices":[{"message":{"role":"assistant","content":"ok"}}]}"#,
+        )
+    }
+
+    #[test]
+    fn test_retries_transient_failures() {
+        let (url, server) = scripted_server(vec![
+            reply("503 Service Unavailable", "", ""),
+            Step::Drop,
+            reply("429 Too Many Requests", "Retry-After: 0\r\n", ""),
+            ok(),
+        ]);
+        let llm = ExternalLlm::new("m", "").with_endpoint(&url).with_retry_policy(fast_policy(4));
+        let request = ChatRequest::new(vec![ChatMessage::user("hi")]);
+
+        let response = llm.chat_with_api("k", &request).expect("response");
+        assert_eq!(response.text(), "ok");
+        assert_eq!(server.join().expect("server"), 4);
+    }
+
+    #[test]
+    fn test_gives_up_on_permanent_errors_and_budget() {
+        let (url, server) = scripted_server(vec![reply("400 Bad Request", "", "")]);
+        let llm = ExternalLlm::new("m", "").with_endpoint(&url).with_retry_policy(fast_policy(4));
+        let request = ChatRequest::new(vec![ChatMessage::user("hi")]);
+        let err = llm.chat_with_api("k", &request).expect_err("400");
+        assert_eq!(err.kind(), ErrorKind::Http);
+        assert_eq!(server.join().expect("server"), 1);
+
+        let (url, server) = scripted_server(vec![
+            reply("502 Bad Gateway", "", ""),
+            reply("502 Bad Gateway", "", ""),
+        ]);
+        let llm = ExternalLlm::new("m", "").with_endpoint(&url).with_retry_policy(fast_policy(2));
+        let err = llm.chat_with_api("k", &request).expect_err("502");
+        assert_eq!(err.status(), Some(502));
+        assert_eq!(server.join().expect("server"), 2);
+    }
+
+    #[test]
+    fn test_stream_is_not_retried_after_tokens() {
+        // Promises more than it sends, then closes mid-stream
+        let partial = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 500\r\n\r\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"par\"}}]}\n\n",
+        );
+        let (url, server) = scripted_server(vec![Step::Reply(partial.to_string())]);
+        let llm = ExternalLlm::new("m", "").with_endpoint(&url).with_retry_policy(fast_policy(3));
+        let request = ChatRequest::new(vec![ChatMessage::user("hi")]);
+
+        let mut tokens = String::new();
+        let err = llm
+            .chat_stream_with_api("k", &request, &mut |delta| {
+                tokens.push_str(&delta.content);
+                true
+            })
+            .expect_err("truncated stream");
+        // A retry would have hit the closed listener and failed to connect
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert!(err.is_retryable());
+        assert_eq!(tokens, "par");
+        assert_eq!(server.join().expect("server"), 1);
+    }
+
+    #[test]
+    fn test_retry_hints() {
+        let mut headers = Headers::new();
+        headers.append("Retry-After", "7");
+        assert_eq!(retry_hint(&headers), Some(Duration::from_secs(7)));
+
+        let mut headers = Headers::new();
+        headers.append("Retry-After", "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(retry_hint(&headers), Some(Duration::ZERO));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+        );
+
+        let mut headers = Headers::new();
+        headers.append("x-ratelimit-remaining-requests", "5");
+        headers.append("x-ratelimit-reset-requests", "1s");
+        headers.append("x-ratelimit-remaining-tokens", "0");
+        headers.append("x-ratelimit-reset-tokens", "1m30.5s");
+        assert_eq!(retry_hint(&headers), Some(Duration::from_millis(90_500)));
+
+        assert_eq!(parse_reset("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_reset("soon"), None);
+    }
+
+    #[test]
+    fn test_hostile_retry_hints() {
+        for reset in [
+            "1e30",
+            "99999999999999999999h",
+            "-1",
+            "NaN",
+            "inf",
+            "1e300ms",
+        ] {
+            assert_eq!(parse_reset(reset), None, "{}", reset);
+        }
+        for date in [
+            "Sun, 06 Nov 9999999999999999 08:49:37 GMT",
+            "Sun, 06 Nov 10000 08:49:37 GMT",
+            "Sun, 06 Nov 1969 08:49:37 GMT",
+            "Sun, 06 Nov -5 08:49:37 GMT",
+        ] {
+            assert_eq!(parse_http_date(date), None, "{}", date);
+        }
+        assert!(parse_http_date("Fri, 31 Dec 9999 23:59:59 GMT").is_some());
+
+        let mut headers = Headers::new();
+        headers.append("x-ratelimit-reset-requests", "1e30");
+        assert_eq!(retry_hint(&headers), None);
+
+        // Hints are capped at the policy's maximum delay
+        let (url, server) = scripted_server(vec![
+            reply(
+                "429 Too Many Requests",
+                &format!("Retry-After: {}\r\n", u64::MAX),
+                "",
+            ),
+            ok(),
+        ]);
+        let llm = ExternalLlm::new("test-model", "")
+            .with_endpoint(&url)
+            .with_retry_policy(fast_policy(2));
+        let started = Instant::now();
+        llm.chat_with_api("sk-test", &ChatRequest::new(vec![ChatMessage::user("hi")]))
+            .expect("retried");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.join().expect("server"), 2);
+    }
+
+    #[test]
+    fn test_backoff_is_capped_and_jittered() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+
+        let jittered = RetryPolicy { jitter: true, ..policy };
+        for _ in 0..20 {
+            let delay = jittered.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+}
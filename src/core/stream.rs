@@ -0,0 +1,432 @@
+//! Token frame streaming
+//!
+//! Runs a provider stream on a worker thread and hands its output to the UI
+//! as [`TokenFrame`]s through a bounded queue. The queue applies backpressure:
+//! when the consumer falls behind, the worker blocks on send and stops reading
+//! from the socket until frames are drained.
+//!
+//! Tool calls stream as [`TokenFrame::ToolCall`] fragments;
+//! [`StreamedToolCall`] reassembles them and parses the arguments as they
+//! arrive.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TryRecvError},
+    },
+    thread,
+};
+
+use crate::{
+    errors::ErrorKind,
+    essentia::json::{Event, StreamParser, Value},
+    traits::ChatProvider,
+    types::{ChatRequest, FinishReason, Usage},
+};
+
+/// Frames buffered between the worker and the consumer before the worker
+/// blocks.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+/// One unit of streamed output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenFrame {
+    /// Text appended to the reply.
+    Token { sequence: u64, text: String },
+    /// Part of tool call `index`: its name when first seen, and argument
+    /// text to append.
+    ToolCall {
+        sequence:  u64,
+        index:     usize,
+        name:      Option<String>,
+        arguments: String,
+    },
+    /// The reply finished; always the last frame of a successful stream.
+    Final {
+        sequence:      u64,
+        finish_reason: Option<FinishReason>,
+        usage:         Option<Usage>,
+    },
+    /// The request failed; always the last frame of a failed stream.
+    Error {
+        sequence: u64,
+        kind:     ErrorKind,
+        message:  String,
+    },
+}
+
+impl TokenFrame {
+    /// Position of the frame within its stream, starting at 0.
+    pub fn sequence(&self) -> u64 {
+        match self {
+            Self::Token { sequence, .. }
+            | Self::ToolCall { sequence, .. }
+            | Self::Final { sequence, .. }
+            | Self::Error { sequence, .. } => *sequence,
+        }
+    }
+
+    /// Whether no further frames follow this one.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, Self::Token { .. } | Self::ToolCall { .. })
+    }
+}
+
+/// A tool call reassembled from [`TokenFrame::ToolCall`] frames.
+#[derive(Debug, Default)]
+pub struct StreamedToolCall {
+    pub name:      String,
+    /// Argument text received so far.
+    pub arguments: String,
+    parser:        StreamParser,
+    document:      Option<Value>,
+}
+
+impl StreamedToolCall {
+    pub fn push(&mut self, name: Option<&str>, arguments: &str) {
+        if let Some(name) = name {
+            self.name.push_str(name);
+        }
+        self.arguments.push_str(arguments);
+        // A parse failure is remembered by the parser and ends the preview
+        if let Ok(events) = self.parser.push(arguments.as_bytes()) {
+            for event in events {
+                if let Event::Document(value) = event {
+                    self.document.get_or_insert(value);
+                }
+            }
+        }
+    }
+
+    /// The arguments as structured data: the whole document once it has
+    /// arrived, otherwise a best-effort value of the text so far. `None`
+    /// before any value starts, or if the text is not JSON.
+    pub fn arguments_value(&self) -> Option<Value> {
+        self.document.clone().or_else(|| self.parser.partial())
+    }
+}
+
+/// Handle to a request streaming on a worker thread.
+///
+/// Dropping the handle cancels the request.
+pub struct TokenStream {
+    receiver:  Receiver<TokenFrame>,
+    cancelled: Arc<AtomicBool>,
+    finished:  bool,
+}
+
+impl TokenStream {
+    /// Starts `request` on a worker thread.
+    ///
+    /// With `incremental` set the provider's `chat_stream` is used; otherwise
+    /// the reply is fetched with `chat` and delivered as a single token frame.
+    /// `chat` cannot be interrupted, so cancelling that request only discards
+    /// its reply.
+    pub fn spawn(
+        provider: Arc<dyn ChatProvider>, request: ChatRequest, incremental: bool, capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancelled);
+
+        thread::spawn(move || run(provider.as_ref(), &request, incremental, &sender, &flag));
+
+        Self { receiver, cancelled, finished: false }
+    }
+
+    /// Asks the worker to stop. The provider stream is abandoned at the next
+    /// chunk and no further frames are delivered.
+    ///
+    /// A non-incremental request keeps its connection until `chat` returns;
+    /// the provider has no way to abort it early, so the worker waits for the
+    /// reply and drops it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether a terminal frame has been received, or the worker went away.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns up to `max` queued frames without blocking.
+    pub fn drain(&mut self, max: usize) -> Vec<TokenFrame> {
+        let mut frames = Vec::new();
+        while !self.finished && frames.len() < max {
+            match self.receiver.try_recv() {
+                Ok(frame) => {
+                    self.finished = frame.is_terminal();
+                    frames.push(frame);
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.finished = true,
+            }
+        }
+        frames
+    }
+}
+
+impl Drop for TokenStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn run(
+    provider: &dyn ChatProvider, request: &ChatRequest, incremental: bool,
+    sender: &SyncSender<TokenFrame>, cancelled: &AtomicBool,
+) {
+    let mut sequence = 0u64;
+    let mut next = || {
+        let current = sequence;
+        sequence += 1;
+        current
+    };
+
+    let result = if incremental {
+        provider.chat_stream(request, &mut |delta| {
+            if cancelled.load(Ordering::SeqCst) {
+                return false;
+            }
+            // Blocks while the queue is full; fails once the consumer is gone
+            if !delta.content.is_empty()
+                && sender
+                    .send(TokenFrame::Token { sequence: next(), text: delta.content.clone() })
+                    .is_err()
+            {
+                return false;
+            }
+            delta.tool_calls.iter().all(|call| {
+                sender
+                    .send(TokenFrame::ToolCall {
+                        sequence:  next(),
+                        index:     call.index,
+                        name:      call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    })
+                    .is_ok()
+            })
+        })
+    } else {
+        provider.chat(request).inspect(|response| {
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            let text = response.text();
+            if !text.is_empty() {
+                let _ = sender.send(TokenFrame::Token { sequence: next(), text });
+            }
+            for (index, call) in response.message.tool_calls.iter().enumerate() {
+                let _ = sender.send(TokenFrame::ToolCall {
+                    sequence: next(),
+                    index,
+                    name: Some(call.name.clone()),
+                    arguments: call.arguments.clone(),
+                });
+            }
+        })
+    };
+
+    if cancelled.load(Ordering::SeqCst) {
+        return;
+    }
+    let last = match result {
+        Ok(response) => TokenFrame::Final {
+            sequence:      next(),
+            finish_reason: response.finish_reason,
+            usage:         response.usage,
+        },
+        Err(e) => {
+            TokenFrame::Error { sequence: next(), kind: e.kind(), message: e.to_string() }
+        },
+    };
+    let _ = sender.send(last);
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use super::*;
+    use crate::{
+        errors::LlmError,
+        traits::Provider,
+        types::{ChatDelta, ChatMessage, ChatResponse},
+    };
+
+    /// Emits `tokens` one delta at a time, recording how many were produced.
+    struct Scripted {
+        tokens:  Vec<&'static str>,
+        fail:    bool,
+        emitted: Arc<Mutex<usize>>,
+    }
+
+    impl Provider for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    impl ChatProvider for Scripted {
+        /// The whole script as one reply, after a delay long enough to
+        /// cancel during.
+        fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+            thread::sleep(Duration::from_millis(50));
+            self.chat_stream(request, &mut |_| true)
+        }
+
+        fn chat_stream(
+            &self, _request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+        ) -> Result<ChatResponse, LlmError> {
+            let mut text = String::new();
+            for token in &self.tokens {
+                *self.emitted.lock().expect("lock") += 1;
+                text.push_str(token);
+                let delta = ChatDelta { content: token.to_string(), ..ChatDelta::default() };
+                if !on_delta(&delta) {
+                    break;
+                }
+            }
+            if self.fail {
+                return Err(LlmError::from_status(503, "overloaded"));
+            }
+            Ok(ChatResponse {
+                id:            None,
+                model:         None,
+                message:       ChatMessage::assistant(&text),
+                finish_reason: Some(FinishReason::Stop),
+                usage:         Some(Usage {
+                    prompt_tokens:     1,
+                    completion_tokens: 2,
+                    total_tokens:      3,
+                }),
+            })
+        }
+    }
+
+    fn scripted(tokens: Vec<&'static str>, fail: bool) -> (Arc<Scripted>, Arc<Mutex<usize>>) {
+        let emitted = Arc::new(Mutex::new(0));
+        (
+            Arc::new(Scripted { tokens, fail, emitted: Arc::clone(&emitted) }),
+            emitted,
+        )
+    }
+
+    fn collect(stream: &mut TokenStream) -> Vec<TokenFrame> {
+        let mut frames = Vec::new();
+        for _ in 0..500 {
+            frames.extend(stream.drain(usize::MAX));
+            if stream.is_finished() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        frames
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest::new(vec![ChatMessage::user("hi")])
+    }
+
+    #[test]
+    fn test_tokens_then_final_frame() {
+        let (provider, _) = scripted(vec!["Hel", "lo"], false);
+        let mut stream = TokenStream::spawn(provider, request(), true, 8);
+        let frames = collect(&mut stream);
+
+        assert_eq!(frames, [
+            TokenFrame::Token { sequence: 0, text: "Hel".to_string() },
+            TokenFrame::Token { sequence: 1, text: "lo".to_string() },
+            TokenFrame::Final {
+                sequence:      2,
+                finish_reason: Some(FinishReason::Stop),
+                usage:         Some(Usage {
+                    prompt_tokens:     1,
+                    completion_tokens: 2,
+                    total_tokens:      3,
+                }),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_error_frame() {
+        let (provider, _) = scripted(vec!["partial"], true);
+        let mut stream = TokenStream::spawn(provider, request(), true, 8);
+        let frames = collect(&mut stream);
+
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(&frames[1], TokenFrame::Error {
+            sequence: 1,
+            kind: ErrorKind::Server,
+            ..
+        }));
+    }
+
+    #[test]
+    fn test_backpressure_and_cancel() {
+        let (provider, emitted) = scripted(vec!["t"; 100], false);
+        let mut stream = TokenStream::spawn(provider, request(), true, 2);
+        thread::sleep(Duration::from_millis(50));
+
+        // The worker is parked on the full queue rather than racing ahead
+        assert!(*emitted.lock().expect("lock") <= 4);
+        assert_eq!(stream.drain(1).len(), 1);
+
+        stream.cancel();
+        let frames = collect(&mut stream);
+        assert!(stream.is_finished());
+        assert!(frames.iter().all(|f| !f.is_terminal()));
+        assert!(*emitted.lock().expect("lock") < 100);
+    }
+
+    #[test]
+    fn test_non_incremental_reply_and_cancel() {
+        let (provider, _) = scripted(vec!["Hel", "lo"], false);
+        let mut stream = TokenStream::spawn(provider, request(), false, 8);
+        let frames = collect(&mut stream);
+        assert_eq!(frames[0], TokenFrame::Token {
+            sequence: 0,
+            text:     "Hello".to_string(),
+        });
+        assert!(frames[1].is_terminal());
+
+        // The reply still arrives, but nothing is delivered after cancel
+        let (provider, emitted) = scripted(vec!["Hel", "lo"], false);
+        let mut stream = TokenStream::spawn(provider, request(), false, 8);
+        stream.cancel();
+        let frames = collect(&mut stream);
+        assert!(stream.is_finished());
+        assert!(frames.is_empty());
+        assert_eq!(*emitted.lock().expect("lock"), 2);
+    }
+
+    #[test]
+    fn test_streamed_tool_call_arguments() {
+        let mut call = StreamedToolCall::default();
+        assert_eq!(call.arguments_value(), None);
+        call.push(Some("get_weather"), "");
+        call.push(None, "{\"city\": \"Par");
+        assert_eq!(
+            call.arguments_value().map(|v| v.to_string()).as_deref(),
+            Some(r#"{"city":"Par"}"#)
+        );
+        call.push(None, "is\", \"days\": [1, 2]}");
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(call.arguments, r#"{"city": "Paris", "days": [1, 2]}"#);
+        assert_eq!(
+            call.arguments_value().map(|v| v.to_string()).as_deref(),
+            Some(r#"{"city":"Paris","days":[1,2]}"#)
+        );
+
+        let mut invalid = StreamedToolCall::default();
+        invalid.push(Some("f"), "{oops");
+        assert_eq!(invalid.arguments_value(), None);
+    }
+}
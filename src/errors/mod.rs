@@ -1,6 +1,358 @@
 //! # LLM Plugin - Errors Module
 //!
 //! Defines error types for LLM operations.
+//!
+//! Every layer (`essentia::http`, `essentia::tls`, `essentia::json` and the
+//! provider clients) reports failures as [`LlmError`], so callers can tell an
+//! auth failure from a rate limit, a timeout or a malformed body and decide
+//! whether to retry.
+
+use std::{error::Error, fmt, io, time::Duration};
+
+use crate::essentia::{http::Headers, json::Value};
+
+/// Coarse classification of an [`LlmError`], for callers that only need to
+/// branch on the category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    InvalidUrl,
+    Connect,
+    Proxy,
+    Tls,
+    PinMismatch,
+    Timeout,
+    Io,
+    Protocol,
+    Json,
+    Auth,
+    RateLimited,
+    Server,
+    Http,
+    InvalidResponse,
+    Config,
+    Unsupported,
+}
+
+/// Error envelope returned by the provider on a non-2xx response.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProviderError {
+    /// Raw response body.
+    pub body:        String,
+    /// `error.message`, when the body used the OpenAI-style envelope.
+    pub message:     Option<String>,
+    /// `error.type`
+    pub error_type:  Option<String>,
+    /// `error.code`
+    pub code:        Option<String>,
+    /// Delay requested via `Retry-After` or `x-ratelimit-reset-*`.
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderError {
+    /// Reads the `{"error": {"message", "type", "code"}}` envelope, keeping
+    /// the raw body either way.
+    pub fn from_body(body: &str) -> Self {
+        let error = crate::essentia::json::parse(body).ok().and_then(|v| v.get("error").cloned());
+        let field = |key: &str| {
+            error.as_ref().and_then(|e| e.get(key)).and_then(|v| match v {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+        };
+        Self {
+            body:        body.to_string(),
+            message:     field("message"),
+            error_type:  field("type"),
+            code:        field("code"),
+            retry_after: None,
+        }
+    }
+
+    /// Human-readable summary: the envelope message, or the trimmed body.
+    pub fn summary(&self) -> &str {
+        self.message.as_deref().unwrap_or_else(|| self.body.trim())
+    }
+}
+
+/// Failure from any layer of an LLM call.
+#[derive(Debug)]
+pub enum LlmError {
+    /// The URL could not be parsed or uses an unsupported scheme.
+    InvalidUrl(String),
+    /// The TCP connection could not be established.
+    Connect { address: String, source: io::Error },
+    /// The proxy refused or failed to set up the tunnel.
+    Proxy { proxy: String, message: String },
+    /// The TLS handshake or record layer failed.
+    Tls {
+        message: String,
+        source:  Option<Box<LlmError>>,
+    },
+    /// The server's validated chain has none of the keys pinned for the
+    /// host.
+    PinMismatch {
+        host:      String,
+        /// `sha256/<base64>` pins of the chain's keys, leaf first.
+        presented: Vec<String>,
+    },
+    /// A deadline elapsed before the operation finished.
+    Timeout { operation: &'static str },
+    /// Socket read/write failure after the connection was established.
+    Io {
+        operation: &'static str,
+        source:    io::Error,
+    },
+    /// The peer violated HTTP framing (bad status line, truncated body, ...).
+    Protocol(String),
+    /// A document was not valid JSON.
+    Json(String),
+    /// 401/403 from the provider.
+    Auth { status: u16, error: ProviderError },
+    /// 429 from the provider.
+    RateLimited { status: u16, error: ProviderError },
+    /// 5xx from the provider.
+    Server { status: u16, error: ProviderError },
+    /// Any other non-2xx status.
+    Http { status: u16, error: ProviderError },
+    /// A 2xx body that was not a usable completion.
+    InvalidResponse {
+        status:  Option<u16>,
+        message: String,
+        source:  Option<Box<LlmError>>,
+    },
+    /// The client is missing required configuration and was never called.
+    Config(String),
+    /// The provider has no API for the operation.
+    Unsupported {
+        provider:  &'static str,
+        operation: &'static str,
+    },
+}
+
+impl LlmError {
+    /// Classifies a non-2xx response.
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let error = ProviderError::from_body(body);
+        match status {
+            401 | 403 => Self::Auth { status, error },
+            429 => Self::RateLimited { status, error },
+            500..=599 => Self::Server { status, error },
+            _ => Self::Http { status, error },
+        }
+    }
+
+    /// Classifies a non-2xx response, keeping any retry delay the server
+    /// asked for in its headers.
+    pub fn from_response(status: u16, headers: &Headers, body: &str) -> Self {
+        let mut error = Self::from_status(status, body);
+        if let Self::Auth { error, .. }
+        | Self::RateLimited { error, .. }
+        | Self::Server { error, .. }
+        | Self::Http { error, .. } = &mut error
+        {
+            error.retry_after = crate::core::retry::retry_hint(headers);
+        }
+        error
+    }
+
+    /// Wraps a socket error, reporting timeouts as [`LlmError::Timeout`].
+    pub fn io(operation: &'static str, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Self::Timeout { operation },
+            _ => Self::Io { operation, source },
+        }
+    }
+
+    pub fn tls(message: &str) -> Self {
+        Self::Tls { message: message.to_string(), source: None }
+    }
+
+    pub fn invalid_response(status: Option<u16>, message: &str) -> Self {
+        Self::InvalidResponse { status, message: message.to_string(), source: None }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidUrl(_) => ErrorKind::InvalidUrl,
+            Self::Connect { .. } => ErrorKind::Connect,
+            Self::Proxy { .. } => ErrorKind::Proxy,
+            Self::Tls { .. } => ErrorKind::Tls,
+            Self::PinMismatch { .. } => ErrorKind::PinMismatch,
+            Self::Timeout { .. } => ErrorKind::Timeout,
+            Self::Io { .. } => ErrorKind::Io,
+            Self::Protocol(_) => ErrorKind::Protocol,
+            Self::Json(_) => ErrorKind::Json,
+            Self::Auth { .. } => ErrorKind::Auth,
+            Self::RateLimited { .. } => ErrorKind::RateLimited,
+            Self::Server { .. } => ErrorKind::Server,
+            Self::Http { .. } => ErrorKind::Http,
+            Self::InvalidResponse { .. } => ErrorKind::InvalidResponse,
+            Self::Config(_) => ErrorKind::Config,
+            Self::Unsupported { .. } => ErrorKind::Unsupported,
+        }
+    }
+
+    /// HTTP status, when the failure came from a response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Auth { status, .. }
+            | Self::RateLimited { status, .. }
+            | Self::Server { status, .. }
+            | Self::Http { status, .. } => Some(*status),
+            Self::InvalidResponse { status, .. } => *status,
+            _ => None,
+        }
+    }
+
+    /// Provider error envelope, when the failure came from a non-2xx response.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            Self::Auth { error, .. }
+            | Self::RateLimited { error, .. }
+            | Self::Server { error, .. }
+            | Self::Http { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Delay the server asked for before a retry.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.provider_error().and_then(|e| e.retry_after)
+    }
+
+    /// Whether repeating the same request may succeed.
+    ///
+    /// Rate limits, 500/502/503/504, timeouts, refused/reset connections and
+    /// mid-stream socket failures are transient; auth, client errors and
+    /// malformed input are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Timeout { .. } | Self::Connect { .. } | Self::RateLimited { .. } => true,
+            Self::Server { status, .. } => matches!(status, 500 | 502 | 503 | 504),
+            Self::Io { source, .. } => matches!(
+                source.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::Interrupted
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUrl(message) => write!(f, "Invalid URL: {}", message),
+            Self::Connect { address, source } => {
+                write!(f, "Connect to {} failed: {}", address, source)
+            },
+            Self::Proxy { proxy, message } => write!(f, "Proxy {}: {}", proxy, message),
+            Self::Tls { message, .. } => write!(f, "TLS error: {}", message),
+            Self::PinMismatch { host, presented } => write!(
+                f,
+                "Certificate pin mismatch for {}: chain keys are {}",
+                host,
+                presented.join(", ")
+            ),
+            Self::Timeout { operation } => write!(f, "{} timed out", operation),
+            Self::Io { operation, source } => write!(f, "{} failed: {}", operation, source),
+            Self::Protocol(message) => write!(f, "HTTP protocol error: {}", message),
+            Self::Json(message) => write!(f, "Invalid JSON: {}", message),
+            Self::Auth { status, error }
+            | Self::RateLimited { status, error }
+            | Self::Server { status, error }
+            | Self::Http { status, error } => write!(f, "HTTP {}: {}", status, error.summary()),
+            Self::InvalidResponse { status: Some(status), message, .. } => {
+                write!(f, "Invalid response (HTTP {}): {}", status, message)
+            },
+            Self::InvalidResponse { status: None, message, .. } => {
+                write!(f, "Invalid response: {}", message)
+            },
+            Self::Config(message) => write!(f, "Configuration error: {}", message),
+            Self::Unsupported { provider, operation } => {
+                write!(f, "{} does not support {}", provider, operation)
+            },
+        }
+    }
+}
+
+impl Error for LlmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Connect { source, .. } | Self::Io { source, .. } => Some(source),
+            Self::Tls { source: Some(source), .. }
+            | Self::InvalidResponse { source: Some(source), .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        let body = r#"{"error":{"message":"Slow down","type":"rate_limit","code":"rl"}}"#;
+        let err = LlmError::from_status(429, body);
+        assert_eq!(err.kind(), ErrorKind::RateLimited);
+        assert_eq!(err.status(), Some(429));
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.provider_error().and_then(|e| e.code.as_deref()),
+            Some("rl")
+        );
+        assert_eq!(err.to_string(), "HTTP 429: Slow down");
+
+        assert_eq!(LlmError::from_status(401, "nope").kind(), ErrorKind::Auth);
+        assert!(!LlmError::from_status(401, "nope").is_retryable());
+        assert!(LlmError::from_status(503, "").is_retryable());
+        assert!(!LlmError::from_status(501, "").is_retryable());
+        assert_eq!(
+            LlmError::from_status(404, "gone").to_string(),
+            "HTTP 404: gone"
+        );
+    }
+
+    #[test]
+    fn test_io_errors_chain_source() {
+        let timeout = LlmError::io("read", io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(timeout.kind(), ErrorKind::Timeout);
+        assert!(timeout.is_retryable());
+
+        let reset = LlmError::io("read", io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!(reset.kind(), ErrorKind::Io);
+        assert!(reset.is_retryable());
+        assert!(reset.source().is_some());
+
+        let wrapped = LlmError::InvalidResponse {
+            status:  Some(200),
+            message: "bad body".to_string(),
+            source:  Some(Box::new(LlmError::Json("Unclosed string".to_string()))),
+        };
+        assert_eq!(
+            wrapped.source().map(ToString::to_string).as_deref(),
+            Some("Invalid JSON: Unclosed string")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_operations() {
+        use crate::{core::copilot::ExternalCodeAssist, traits::EmbeddingProvider};
 
-// Re-export error types when implemented
-// pub mod types;
+        let err = ExternalCodeAssist::new("m").embed(&["x".to_string()]).err();
+        let err = err.map(|e| (e.kind(), e.is_retryable(), e.to_string()));
+        assert_eq!(
+            err,
+            Some((
+                ErrorKind::Unsupported,
+                false,
+                "external_code_assist does not support embeddings".to_string()
+            ))
+        );
+    }
+}
@@ -58,10 +58,10 @@ pub fn decode(input: &str) -> Result<Vec<u8>, &'static str> {
             }
         }
         result.push((n >> 16) as u8);
-        if bits > 8 {
+        if bits >= 16 {
             result.push((n >> 8) as u8);
         }
-        if bits > 16 {
+        if bits >= 24 {
             result.push(n as u8);
         }
         i += 4;
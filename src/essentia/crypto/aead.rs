@@ -0,0 +1,509 @@
+//! AEAD ciphers for TLS 1.3 records: AES-GCM (NIST SP 800-38D) and
+//! ChaCha20-Poly1305 (RFC 8439).
+
+use super::ct_eq;
+
+/// Authentication tag length shared by every supported cipher.
+pub const TAG_LEN: usize = 16;
+
+/// Nonce length shared by every supported cipher.
+pub const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AeadAlgorithm {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl AeadAlgorithm {
+    pub const fn key_len(self) -> usize {
+        match self {
+            Self::Aes128Gcm => 16,
+            Self::Aes256Gcm | Self::ChaCha20Poly1305 => 32,
+        }
+    }
+}
+
+/// An AEAD cipher keyed for one direction of a connection.
+#[derive(Clone)]
+pub struct Aead {
+    cipher: Cipher,
+}
+
+#[derive(Clone)]
+enum Cipher {
+    Gcm { aes: Aes, h: u128 },
+    ChaCha([u8; 32]),
+}
+
+impl Aead {
+    /// Keys `algorithm`; `None` if `key` has the wrong length.
+    pub fn new(algorithm: AeadAlgorithm, key: &[u8]) -> Option<Self> {
+        if key.len() != algorithm.key_len() {
+            return None;
+        }
+        let cipher = match algorithm {
+            AeadAlgorithm::Aes128Gcm | AeadAlgorithm::Aes256Gcm => {
+                let aes = Aes::new(key);
+                let h = u128::from_be_bytes(aes.encrypt([0; 16]));
+                Cipher::Gcm { aes, h }
+            },
+            AeadAlgorithm::ChaCha20Poly1305 => {
+                let mut k = [0u8; 32];
+                k.copy_from_slice(key);
+                Cipher::ChaCha(k)
+            },
+        };
+        Some(Self { cipher })
+    }
+
+    /// Encrypts `plaintext`, returning the ciphertext followed by the tag.
+    pub fn seal(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut out = plaintext.to_vec();
+        let tag = match &self.cipher {
+            Cipher::Gcm { aes, h } => {
+                gcm_ctr(aes, nonce, &mut out);
+                gcm_tag(aes, *h, nonce, aad, &out)
+            },
+            Cipher::ChaCha(key) => {
+                chacha20_xor(key, 1, nonce, &mut out);
+                poly1305_tag(key, nonce, aad, &out)
+            },
+        };
+        out.extend_from_slice(&tag);
+        out
+    }
+
+    /// Verifies and decrypts ciphertext-plus-tag; `None` if authentication
+    /// fails.
+    pub fn open(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        let split = sealed.len().checked_sub(TAG_LEN)?;
+        let (ciphertext, tag) = sealed.split_at(split);
+        let mut out = ciphertext.to_vec();
+        match &self.cipher {
+            Cipher::Gcm { aes, h } => {
+                if !ct_eq(&gcm_tag(aes, *h, nonce, aad, ciphertext), tag) {
+                    return None;
+                }
+                gcm_ctr(aes, nonce, &mut out);
+            },
+            Cipher::ChaCha(key) => {
+                if !ct_eq(&poly1305_tag(key, nonce, aad, ciphertext), tag) {
+                    return None;
+                }
+                chacha20_xor(key, 1, nonce, &mut out);
+            },
+        }
+        Some(out)
+    }
+}
+
+const SBOX: [u8; 256] = sbox();
+
+/// Builds the AES S-box from the multiplicative inverse in GF(2^8) and the
+/// affine transform, walking the field by generator 3.
+const fn sbox() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut p: u8 = 1;
+    let mut q: u8 = 1;
+    loop {
+        // p *= 3, q /= 3
+        p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        let x = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        table[p as usize] = x ^ 0x63;
+        if p == 1 {
+            break;
+        }
+    }
+    table[0] = 0x63;
+    table
+}
+
+const fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// AES-128 or AES-256 encryption (decryption is never needed by GCM).
+#[derive(Clone)]
+struct Aes {
+    round_keys: Vec<[u8; 16]>,
+}
+
+impl Aes {
+    fn new(key: &[u8]) -> Self {
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+        let mut words: Vec<[u8; 4]> =
+            key.chunks_exact(4).map(|w| [w[0], w[1], w[2], w[3]]).collect();
+        let mut rcon = 1u8;
+        for i in nk..4 * (rounds + 1) {
+            let mut word = words[i - 1];
+            if i % nk == 0 {
+                word = [
+                    SBOX[word[1] as usize] ^ rcon,
+                    SBOX[word[2] as usize],
+                    SBOX[word[3] as usize],
+                    SBOX[word[0] as usize],
+                ];
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                word = word.map(|b| SBOX[b as usize]);
+            }
+            let previous = words[i - nk];
+            words.push([
+                word[0] ^ previous[0],
+                word[1] ^ previous[1],
+                word[2] ^ previous[2],
+                word[3] ^ previous[3],
+            ]);
+        }
+        let round_keys = words
+            .chunks_exact(4)
+            .map(|chunk| {
+                let mut round_key = [0u8; 16];
+                for (dst, word) in round_key.chunks_exact_mut(4).zip(chunk) {
+                    dst.copy_from_slice(word);
+                }
+                round_key
+            })
+            .collect();
+        Self { round_keys }
+    }
+
+    fn encrypt(&self, mut state: [u8; 16]) -> [u8; 16] {
+        let last = self.round_keys.len() - 1;
+        xor_in(&mut state, &self.round_keys[0]);
+        for (round, round_key) in self.round_keys.iter().enumerate().skip(1) {
+            for b in &mut state {
+                *b = SBOX[*b as usize];
+            }
+            // ShiftRows: row r rotates left by r; bytes are column-major
+            let shifted = state;
+            for c in 0..4 {
+                for r in 1..4 {
+                    state[c * 4 + r] = shifted[((c + r) % 4) * 4 + r];
+                }
+            }
+            if round != last {
+                for column in state.chunks_exact_mut(4) {
+                    let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+                    let all = a0 ^ a1 ^ a2 ^ a3;
+                    column[0] ^= all ^ xtime(a0 ^ a1);
+                    column[1] ^= all ^ xtime(a1 ^ a2);
+                    column[2] ^= all ^ xtime(a2 ^ a3);
+                    column[3] ^= all ^ xtime(a3 ^ a0);
+                }
+            }
+            xor_in(&mut state, round_key);
+        }
+        state
+    }
+}
+
+fn xor_in(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+/// First counter block for a 96-bit nonce.
+fn gcm_j0(nonce: &[u8; NONCE_LEN]) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[..NONCE_LEN].copy_from_slice(nonce);
+    block[15] = 1;
+    block
+}
+
+/// CTR-mode keystream starting at counter 2 (counter 1 masks the tag).
+fn gcm_ctr(aes: &Aes, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    let mut counter = gcm_j0(nonce);
+    for chunk in data.chunks_mut(16) {
+        let next = u32::from_be_bytes([counter[12], counter[13], counter[14], counter[15]]);
+        counter[12..].copy_from_slice(&next.wrapping_add(1).to_be_bytes());
+        xor_in(chunk, &aes.encrypt(counter));
+    }
+}
+
+fn gcm_tag(aes: &Aes, h: u128, nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let mut y = 0u128;
+    for data in [aad, ciphertext] {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            y = gf_mul(y ^ u128::from_be_bytes(block), h);
+        }
+    }
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    y = gf_mul(y ^ lengths, h);
+    (y ^ u128::from_be_bytes(aes.encrypt(gcm_j0(nonce)))).to_be_bytes()
+}
+
+/// Multiplication in GCM's bit-reflected GF(2^128).
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        // Branch-free: mask is all ones when the bit is set
+        let bit = (x >> (127 - i)) & 1;
+        z ^= v & bit.wrapping_neg();
+        let carry = v & 1;
+        v = (v >> 1) ^ ((0xe1u128 << 120) & carry.wrapping_neg());
+    }
+    z
+}
+
+fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let le = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (i, word) in key.chunks_exact(4).enumerate() {
+        state[4 + i] = le(word);
+    }
+    state[12] = counter;
+    for (i, word) in nonce.chunks_exact(4).enumerate() {
+        state[13 + i] = le(word);
+    }
+
+    let mut x = state;
+    let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(16);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(12);
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(8);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(7);
+    };
+    for _ in 0..10 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 1, 5, 9, 13);
+        quarter(&mut x, 2, 6, 10, 14);
+        quarter(&mut x, 3, 7, 11, 15);
+        quarter(&mut x, 0, 5, 10, 15);
+        quarter(&mut x, 1, 6, 11, 12);
+        quarter(&mut x, 2, 7, 8, 13);
+        quarter(&mut x, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, chunk) in out.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&x[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    out
+}
+
+fn chacha20_xor(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        xor_in(
+            chunk,
+            &chacha20_block(key, counter.wrapping_add(i as u32), nonce),
+        );
+    }
+}
+
+fn poly1305_tag(
+    key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8],
+) -> [u8; 16] {
+    let block = chacha20_block(key, 0, nonce);
+    let mut mac = Poly1305::new(&block[..32]);
+    for data in [aad, ciphertext] {
+        mac.update_padded(data);
+    }
+    let mut lengths = [0u8; 16];
+    lengths[..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lengths[8..].copy_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    mac.block(&lengths);
+    mac.finish()
+}
+
+/// Poly1305 over 26-bit limbs.
+struct Poly1305 {
+    r:   [u32; 5],
+    h:   [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8]) -> Self {
+        let le = |i: usize| u32::from_le_bytes([key[i], key[i + 1], key[i + 2], key[i + 3]]);
+        Self {
+            r:   [
+                le(0) & 0x3ffffff,
+                (le(3) >> 2) & 0x3ffff03,
+                (le(6) >> 4) & 0x3ffc0ff,
+                (le(9) >> 6) & 0x3f03fff,
+                (le(12) >> 8) & 0x00fffff,
+            ],
+            h:   [0; 5],
+            pad: [le(16), le(20), le(24), le(28)],
+        }
+    }
+
+    /// Absorbs `data` zero-padded to a multiple of 16 bytes, as the AEAD
+    /// construction does.
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.block(&block);
+        }
+    }
+
+    /// Absorbs one full 16-byte block.
+    fn block(&mut self, m: &[u8; 16]) {
+        let le = |i: usize| u32::from_le_bytes([m[i], m[i + 1], m[i + 2], m[i + 3]]);
+        let hibit = 1 << 24;
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+
+        let h = &mut self.h;
+        h[0] += le(0) & 0x3ffffff;
+        h[1] += (le(3) >> 2) & 0x3ffffff;
+        h[2] += (le(6) >> 4) & 0x3ffffff;
+        h[3] += (le(9) >> 6) & 0x3ffffff;
+        h[4] += (le(12) >> 8) | hibit;
+        let [h0, h1, h2, h3, h4] = h.map(u64::from);
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        h[0] = (d0 & 0x3ffffff) as u32;
+        h[1] = (d1 & 0x3ffffff) as u32;
+        h[2] = (d2 & 0x3ffffff) as u32;
+        h[3] = (d3 & 0x3ffffff) as u32;
+        h[4] = (d4 & 0x3ffffff) as u32;
+        h[0] += ((d4 >> 26) * 5) as u32;
+        h[1] += h[0] >> 26;
+        h[0] &= 0x3ffffff;
+    }
+
+    fn finish(self) -> [u8; 16] {
+        let mut h = self.h;
+        // Full carry
+        for i in 1..5 {
+            h[i] += h[i - 1] >> 26;
+            h[i - 1] &= 0x3ffffff;
+        }
+        h[0] += (h[4] >> 26) * 5;
+        h[4] &= 0x3ffffff;
+        h[1] += h[0] >> 26;
+        h[0] &= 0x3ffffff;
+
+        // g = h + 5 - 2^130; keep h if that went negative
+        let mut g = [0u32; 5];
+        let mut carry = 5;
+        for i in 0..5 {
+            g[i] = h[i] + carry;
+            carry = g[i] >> 26;
+            g[i] &= 0x3ffffff;
+        }
+        g[4] = g[4].wrapping_add(carry << 26).wrapping_sub(1 << 26);
+        let use_g = (g[4] >> 31).wrapping_sub(1);
+        g[4] &= 0x3ffffff;
+        for i in 0..5 {
+            h[i] = (h[i] & !use_g) | (g[i] & use_g);
+        }
+
+        let words = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+        let mut out = [0u8; 16];
+        let mut carry = 0u64;
+        for (i, chunk) in out.chunks_exact_mut(4).enumerate() {
+            let sum = u64::from(words[i]) + u64::from(self.pad[i]) + carry;
+            chunk.copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        out
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::crypto::{from_hex, to_hex};
+
+    fn nonce(hex: &str) -> [u8; NONCE_LEN] {
+        from_hex(hex).try_into().expect("nonce")
+    }
+
+    #[test]
+    fn test_aes_gcm() {
+        // FIPS-197 appendix C.1 / C.3 block vectors
+        let block: [u8; 16] =
+            from_hex("00112233445566778899aabbccddeeff").try_into().expect("block");
+        let aes = Aes::new(&from_hex("000102030405060708090a0b0c0d0e0f"));
+        assert_eq!(
+            to_hex(&aes.encrypt(block)),
+            "69c4e0d86a7b0430d8cdb78070b4c55a"
+        );
+        let aes = Aes::new(&from_hex(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        ));
+        assert_eq!(
+            to_hex(&aes.encrypt(block)),
+            "8ea2b7ca516745bfeafc49904b496089"
+        );
+
+        // GCM spec test case 4 (AES-128, 60-byte plaintext, 20-byte AAD)
+        let key = from_hex("feffe9928665731c6d6a8f9467308308");
+        let plaintext = from_hex(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        );
+        let aad = from_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let iv = nonce("cafebabefacedbaddecaf888");
+        let aead = Aead::new(AeadAlgorithm::Aes128Gcm, &key).expect("key");
+        let sealed = aead.seal(&iv, &aad, &plaintext);
+        assert_eq!(
+            to_hex(&sealed),
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091\
+             5bc94fbc3221a5db94fae95ae7121a47"
+        );
+        assert_eq!(aead.open(&iv, &aad, &sealed), Some(plaintext));
+
+        let mut tampered = sealed;
+        tampered[0] ^= 1;
+        assert_eq!(aead.open(&iv, &aad, &tampered), None);
+        assert!(Aead::new(AeadAlgorithm::Aes256Gcm, &key).is_none());
+    }
+
+    #[test]
+    fn test_chacha20_poly1305() {
+        // RFC 8439 section 2.8.2
+        let key = from_hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let iv = nonce("070000004041424344454647");
+        let aad = from_hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let aead = Aead::new(AeadAlgorithm::ChaCha20Poly1305, &key).expect("key");
+        let sealed = aead.seal(&iv, &aad, plaintext);
+        assert_eq!(
+            to_hex(&sealed[plaintext.len()..]),
+            "1ae10b594f09e26a7e902ecbd0600691"
+        );
+        assert_eq!(to_hex(&sealed[..16]), "d31a8d34648e60db7b86afbc53ef7ec2");
+        assert_eq!(
+            aead.open(&iv, &aad, &sealed).as_deref(),
+            Some(&plaintext[..])
+        );
+        assert_eq!(aead.open(&iv, b"other", &sealed), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! Modular arithmetic on big unsigned integers, for RSA and elliptic curves.
+//!
+//! Numbers are little-endian `u32` limbs and multiplication uses Montgomery
+//! reduction. None of this is constant time.
+
+use std::cmp::Ordering;
+
+/// An odd modulus with its Montgomery constants.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Modulus {
+    n:  Vec<u32>,
+    /// `-n^-1 mod 2^32`
+    n0: u32,
+    /// `R^2 mod n`, where `R = 2^(32 * limbs)`
+    r2: Vec<u32>,
+}
+
+/// A value modulo some [`Modulus`], kept in Montgomery form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Residue(Vec<u32>);
+
+/// Big-endian bytes as exactly `limbs` limbs; `None` if they do not fit.
+fn from_be(bytes: &[u8], limbs: usize) -> Option<Vec<u32>> {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    if bytes.len() > limbs * 4 {
+        return None;
+    }
+    let mut out = vec![0u32; limbs];
+    for (i, &b) in bytes.iter().rev().enumerate() {
+        out[i / 4] |= (b as u32) << (8 * (i % 4));
+    }
+    Some(out)
+}
+
+fn compare(a: &[u32], b: &[u32]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+/// `a -= b`, returning the borrow; `b` may be shorter than `a`.
+fn sub_in_place(a: &mut [u32], b: &[u32]) -> bool {
+    let mut borrow = 0u64;
+    for (i, x) in a.iter_mut().enumerate() {
+        let y = b.get(i).copied().unwrap_or(0);
+        let d = (*x as u64).wrapping_sub(y as u64).wrapping_sub(borrow);
+        *x = d as u32;
+        borrow = (d >> 63) & 1;
+    }
+    borrow != 0
+}
+
+/// `a += b`, returning the carry.
+fn add_in_place(a: &mut [u32], b: &[u32]) -> bool {
+    let mut carry = 0u64;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let s = *x as u64 + y as u64 + carry;
+        *x = s as u32;
+        carry = s >> 32;
+    }
+    carry != 0
+}
+
+impl Modulus {
+    /// `None` unless the big-endian `modulus` is odd and greater than one.
+    pub(crate) fn new(modulus: &[u8]) -> Option<Self> {
+        let start = modulus.iter().position(|&b| b != 0)?;
+        let n = from_be(modulus, (modulus.len() - start).div_ceil(4))?;
+        if n[0] & 1 == 0 || (n.len() == 1 && n[0] == 1) {
+            return None;
+        }
+        // Newton's iteration doubles the correct low bits each round
+        let mut inv = 1u32;
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u32.wrapping_sub(n[0].wrapping_mul(inv)));
+        }
+        // R^2 mod n by doubling 1 for 2 * 32 * limbs bits
+        let mut r2 = vec![0u32; n.len()];
+        r2[0] = 1;
+        for _ in 0..64 * n.len() {
+            let doubled = r2.clone();
+            let carry = add_in_place(&mut r2, &doubled);
+            if carry || compare(&r2, &n) != Ordering::Less {
+                sub_in_place(&mut r2, &n);
+            }
+        }
+        Some(Self { n, n0: inv.wrapping_neg(), r2 })
+    }
+
+    pub(crate) fn bits(&self) -> usize {
+        let top = self.n.len() - 1;
+        top * 32 + (32 - self.n[top].leading_zeros() as usize)
+    }
+
+    /// Length of the modulus in bytes.
+    pub(crate) fn byte_len(&self) -> usize {
+        self.bits().div_ceil(8)
+    }
+
+    /// The big-endian `value` as a residue; `None` unless it is below the
+    /// modulus.
+    pub(crate) fn residue(&self, value: &[u8]) -> Option<Residue> {
+        let value = from_be(value, self.n.len())?;
+        (compare(&value, &self.n) == Ordering::Less).then(|| self.to_montgomery(value))
+    }
+
+    /// `value mod n` for a `value` below `2n`, such as a hash or a
+    /// coordinate from a slightly larger field.
+    pub(crate) fn reduce_once(&self, value: &[u8]) -> Option<Residue> {
+        let mut value = from_be(value, self.n.len())?;
+        if compare(&value, &self.n) != Ordering::Less {
+            sub_in_place(&mut value, &self.n);
+        }
+        (compare(&value, &self.n) == Ordering::Less).then(|| self.to_montgomery(value))
+    }
+
+    fn to_montgomery(&self, value: Vec<u32>) -> Residue {
+        self.mul(&Residue(value), &Residue(self.r2.clone()))
+    }
+
+    /// Big-endian bytes of `a`, [`byte_len`](Self::byte_len) long.
+    pub(crate) fn to_bytes(&self, a: &Residue) -> Vec<u8> {
+        let mut one = vec![0u32; self.n.len()];
+        one[0] = 1;
+        let plain = self.mul(a, &Residue(one));
+        let mut out: Vec<u8> = plain.0.iter().rev().flat_map(|limb| limb.to_be_bytes()).collect();
+        out.drain(..out.len() - self.byte_len());
+        out
+    }
+
+    pub(crate) fn zero(&self) -> Residue {
+        Residue(vec![0; self.n.len()])
+    }
+
+    pub(crate) fn one(&self) -> Residue {
+        let mut one = vec![0u32; self.n.len()];
+        one[0] = 1;
+        self.to_montgomery(one)
+    }
+
+    pub(crate) fn is_zero(&self, a: &Residue) -> bool {
+        a.0.iter().all(|&limb| limb == 0)
+    }
+
+    pub(crate) fn add(&self, a: &Residue, b: &Residue) -> Residue {
+        let mut sum = a.0.clone();
+        let carry = add_in_place(&mut sum, &b.0);
+        if carry || compare(&sum, &self.n) != Ordering::Less {
+            sub_in_place(&mut sum, &self.n);
+        }
+        Residue(sum)
+    }
+
+    pub(crate) fn sub(&self, a: &Residue, b: &Residue) -> Residue {
+        let mut difference = a.0.clone();
+        if sub_in_place(&mut difference, &b.0) {
+            add_in_place(&mut difference, &self.n);
+        }
+        Residue(difference)
+    }
+
+    /// Montgomery product `a * b / R mod n` (CIOS).
+    pub(crate) fn mul(&self, a: &Residue, b: &Residue) -> Residue {
+        let n = &self.n;
+        let s = n.len();
+        let mut t = vec![0u32; s + 2];
+        for &bi in &b.0 {
+            let mut carry = 0u64;
+            for (tj, &aj) in t[..s].iter_mut().zip(&a.0) {
+                let x = *tj as u64 + aj as u64 * bi as u64 + carry;
+                *tj = x as u32;
+                carry = x >> 32;
+            }
+            let x = t[s] as u64 + carry;
+            t[s] = x as u32;
+            t[s + 1] = (x >> 32) as u32;
+
+            let m = t[0].wrapping_mul(self.n0) as u64;
+            let mut carry = (t[0] as u64 + m * n[0] as u64) >> 32;
+            for j in 1..s {
+                let x = t[j] as u64 + m * n[j] as u64 + carry;
+                t[j - 1] = x as u32;
+                carry = x >> 32;
+            }
+            let x = t[s] as u64 + carry;
+            t[s - 1] = x as u32;
+            t[s] = t[s + 1] + (x >> 32) as u32;
+            t[s + 1] = 0;
+        }
+        if t[s] != 0 || compare(&t[..s], n) != Ordering::Less {
+            sub_in_place(&mut t[..s], n);
+        }
+        t.truncate(s);
+        Residue(t)
+    }
+
+    /// `base^exponent` for a big-endian `exponent`.
+    pub(crate) fn pow(&self, base: &Residue, exponent: &[u8]) -> Residue {
+        let mut result = self.one();
+        for byte in exponent {
+            for bit in (0..8).rev() {
+                result = self.mul(&result, &result);
+                if byte >> bit & 1 == 1 {
+                    result = self.mul(&result, base);
+                }
+            }
+        }
+        result
+    }
+
+    /// Inverse of `a` by Fermat's little theorem; only valid for a prime
+    /// modulus.
+    pub(crate) fn inv_prime(&self, a: &Residue) -> Residue {
+        let mut exponent = self.n.clone();
+        sub_in_place(&mut exponent, &[2]);
+        let exponent: Vec<u8> = exponent.iter().rev().flat_map(|limb| limb.to_be_bytes()).collect();
+        self.pow(a, &exponent)
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modular_arithmetic() {
+        // 2^127 - 1 is prime
+        let mut p = vec![0xff; 16];
+        p[0] = 0x7f;
+        let modulus = Modulus::new(&p).expect("odd modulus");
+        assert_eq!(modulus.bits(), 127);
+        assert!(Modulus::new(&[0x10]).is_none());
+        assert!(modulus.residue(&p).is_none());
+
+        let a = modulus.residue(&[0x12, 0x34, 0x56, 0x78, 0x9a]).expect("a");
+        let b = modulus.residue(&[0xff; 15]).expect("b");
+        let product = modulus.mul(&a, &b);
+        assert_eq!(modulus.mul(&product, &modulus.inv_prime(&b)), a);
+        assert_eq!(modulus.add(&modulus.sub(&a, &b), &b), a);
+        assert!(modulus.is_zero(&modulus.sub(&a, &a)));
+
+        // 3^5 = 243; a^(p-1) = 1
+        let three = modulus.residue(&[3]).expect("3");
+        assert_eq!(modulus.to_bytes(&modulus.pow(&three, &[5]))[15], 243);
+        let mut p_minus_one = p.clone();
+        p_minus_one[15] -= 1;
+        assert_eq!(modulus.pow(&a, &p_minus_one), modulus.one());
+    }
+}
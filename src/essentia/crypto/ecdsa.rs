@@ -0,0 +1,355 @@
+//! ECDSA over the NIST P-256 and P-384 curves (FIPS 186-4, SEC 1).
+//!
+//! Points use Jacobian coordinates over [`Modulus`] arithmetic. Signing is
+//! not constant time.
+
+use std::sync::OnceLock;
+
+use super::{
+    bigint::{Modulus, Residue},
+    from_hex, random_bytes,
+    sha2::HashAlgorithm,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Curve {
+    P256,
+    P384,
+}
+
+impl Curve {
+    /// Size of a coordinate or scalar in bytes.
+    pub const fn field_len(self) -> usize {
+        match self {
+            Self::P256 => 32,
+            Self::P384 => 48,
+        }
+    }
+
+    fn group(self) -> Option<&'static Group> {
+        static P256: OnceLock<Option<Group>> = OnceLock::new();
+        static P384: OnceLock<Option<Group>> = OnceLock::new();
+        match self {
+            Self::P256 => P256
+                .get_or_init(|| {
+                    Group::new(
+                        "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+                        "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+                        "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296",
+                        "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5",
+                        "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+                    )
+                })
+                .as_ref(),
+            Self::P384 => P384
+                .get_or_init(|| {
+                    Group::new(
+                        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe\
+                         ffffffff0000000000000000ffffffff",
+                        "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875a\
+                         c656398d8a2ed19d2a85c8edd3ec2aef",
+                        "aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a38\
+                         5502f25dbf55296c3a545e3872760ab7",
+                        "3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c0\
+                         0a60b1ce1d7e819d7a431d7c90ea0e5f",
+                        "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf\
+                         581a0db248b0a77aecec196accc52973",
+                    )
+                })
+                .as_ref(),
+        }
+    }
+}
+
+/// Point in Jacobian coordinates; `z == 0` is the point at infinity.
+#[derive(Clone)]
+struct Point {
+    x: Residue,
+    y: Residue,
+    z: Residue,
+}
+
+/// Curve `y^2 = x^3 - 3x + b` over `p`, with base point `g` of order `n`.
+struct Group {
+    p: Modulus,
+    n: Modulus,
+    b: Residue,
+    g: Point,
+}
+
+impl Group {
+    fn new(p: &str, b: &str, gx: &str, gy: &str, n: &str) -> Option<Self> {
+        let p = Modulus::new(&from_hex(p))?;
+        let n = Modulus::new(&from_hex(n))?;
+        let b = p.residue(&from_hex(b))?;
+        let g = Point { x: p.residue(&from_hex(gx))?, y: p.residue(&from_hex(gy))?, z: p.one() };
+        Some(Self { p, n, b, g })
+    }
+
+    fn infinity(&self) -> Point {
+        Point { x: self.p.one(), y: self.p.one(), z: self.p.zero() }
+    }
+
+    fn double(&self, point: &Point) -> Point {
+        // dbl-2001-b, for a = -3
+        let p = &self.p;
+        let delta = p.mul(&point.z, &point.z);
+        let gamma = p.mul(&point.y, &point.y);
+        let beta = p.mul(&point.x, &gamma);
+        let t = p.mul(&p.sub(&point.x, &delta), &p.add(&point.x, &delta));
+        let alpha = p.add(&p.add(&t, &t), &t);
+        let beta2 = p.add(&beta, &beta);
+        let beta4 = p.add(&beta2, &beta2);
+        let beta8 = p.add(&beta4, &beta4);
+        let x = p.sub(&p.mul(&alpha, &alpha), &beta8);
+        let yz = p.add(&point.y, &point.z);
+        let z = p.sub(&p.sub(&p.mul(&yz, &yz), &gamma), &delta);
+        let gamma2 = p.mul(&gamma, &gamma);
+        let gamma2x2 = p.add(&gamma2, &gamma2);
+        let gamma2x4 = p.add(&gamma2x2, &gamma2x2);
+        let gamma2x8 = p.add(&gamma2x4, &gamma2x4);
+        let y = p.sub(&p.mul(&alpha, &p.sub(&beta4, &x)), &gamma2x8);
+        Point { x, y, z }
+    }
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        // add-2007-bl
+        let p = &self.p;
+        if p.is_zero(&a.z) {
+            return b.clone();
+        }
+        if p.is_zero(&b.z) {
+            return a.clone();
+        }
+        let z1z1 = p.mul(&a.z, &a.z);
+        let z2z2 = p.mul(&b.z, &b.z);
+        let u1 = p.mul(&a.x, &z2z2);
+        let u2 = p.mul(&b.x, &z1z1);
+        let s1 = p.mul(&p.mul(&a.y, &b.z), &z2z2);
+        let s2 = p.mul(&p.mul(&b.y, &a.z), &z1z1);
+        let h = p.sub(&u2, &u1);
+        let r = p.sub(&s2, &s1);
+        if p.is_zero(&h) {
+            return if p.is_zero(&r) {
+                self.double(a)
+            } else {
+                self.infinity()
+            };
+        }
+        let r = p.add(&r, &r);
+        let h2 = p.add(&h, &h);
+        let i = p.mul(&h2, &h2);
+        let j = p.mul(&h, &i);
+        let v = p.mul(&u1, &i);
+        let x = p.sub(&p.sub(&p.mul(&r, &r), &j), &p.add(&v, &v));
+        let s1j = p.mul(&s1, &j);
+        let y = p.sub(&p.mul(&r, &p.sub(&v, &x)), &p.add(&s1j, &s1j));
+        let zz = p.add(&a.z, &b.z);
+        let z = p.mul(&p.sub(&p.sub(&p.mul(&zz, &zz), &z1z1), &z2z2), &h);
+        Point { x, y, z }
+    }
+
+    /// `scalar * point` for a big-endian scalar.
+    fn mul(&self, point: &Point, scalar: &[u8]) -> Point {
+        let mut result = self.infinity();
+        for byte in scalar {
+            for bit in (0..8).rev() {
+                result = self.double(&result);
+                if byte >> bit & 1 == 1 {
+                    result = self.add(&result, point);
+                }
+            }
+        }
+        result
+    }
+
+    /// Affine `(x, y)`, or `None` at infinity.
+    fn affine(&self, point: &Point) -> Option<(Residue, Residue)> {
+        let p = &self.p;
+        if p.is_zero(&point.z) {
+            return None;
+        }
+        let z_inv = p.inv_prime(&point.z);
+        let z_inv2 = p.mul(&z_inv, &z_inv);
+        Some((
+            p.mul(&point.x, &z_inv2),
+            p.mul(&p.mul(&point.y, &z_inv2), &z_inv),
+        ))
+    }
+
+    /// Uncompressed SEC 1 encoding `04 || x || y`; `None` for points off the
+    /// curve.
+    fn decode(&self, bytes: &[u8]) -> Option<Point> {
+        let len = self.p.byte_len();
+        if bytes.len() != 1 + 2 * len || bytes[0] != 4 {
+            return None;
+        }
+        let p = &self.p;
+        let x = p.residue(&bytes[1..1 + len])?;
+        let y = p.residue(&bytes[1 + len..])?;
+        let x3 = p.mul(&p.mul(&x, &x), &x);
+        let x3_minus_3x = p.sub(&p.sub(&p.sub(&x3, &x), &x), &x);
+        (p.mul(&y, &y) == p.add(&x3_minus_3x, &self.b)).then(|| Point { x, y, z: p.one() })
+    }
+
+    /// Hash of the message truncated to the order's length, modulo `n`.
+    fn message_scalar(&self, hash: HashAlgorithm, message: &[u8]) -> Option<Residue> {
+        let digest = hash.digest(message);
+        self.n.reduce_once(&digest[..digest.len().min(self.n.byte_len())])
+    }
+
+    /// A scalar in `[1, n-1]`; `None` if `bytes` is out of that range.
+    fn scalar(&self, bytes: &[u8]) -> Option<Residue> {
+        self.n.residue(bytes).filter(|s| !self.n.is_zero(s))
+    }
+}
+
+/// Verifies the signature `(r, s)` over `message` with the uncompressed
+/// public key point.
+pub fn verify(
+    curve: Curve, public_key: &[u8], hash: HashAlgorithm, message: &[u8], r: &[u8], s: &[u8],
+) -> bool {
+    (|| {
+        let group = curve.group()?;
+        let n = &group.n;
+        let q = group.decode(public_key)?;
+        let (r, s) = (group.scalar(r)?, group.scalar(s)?);
+        let e = group.message_scalar(hash, message)?;
+        let w = n.inv_prime(&s);
+        let u1 = n.to_bytes(&n.mul(&e, &w));
+        let u2 = n.to_bytes(&n.mul(&r, &w));
+        let point = group.add(&group.mul(&group.g, &u1), &group.mul(&q, &u2));
+        let (x, _) = group.affine(&point)?;
+        Some(n.reduce_once(&group.p.to_bytes(&x))? == r)
+    })()
+    .unwrap_or(false)
+}
+
+/// Signs `message` with the big-endian private scalar, returning `(r, s)`,
+/// each [`field_len`](Curve::field_len) bytes.
+pub fn sign(
+    curve: Curve, private_key: &[u8], hash: HashAlgorithm, message: &[u8],
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    let group = curve.group()?;
+    let n = &group.n;
+    let d = group.scalar(private_key)?;
+    let e = group.message_scalar(hash, message)?;
+    loop {
+        let mut k = vec![0u8; n.byte_len()];
+        random_bytes(&mut k);
+        let Some(k) = group.scalar(&k) else { continue };
+        let (x, _) = group.affine(&group.mul(&group.g, &n.to_bytes(&k)))?;
+        let r = n.reduce_once(&group.p.to_bytes(&x))?;
+        if n.is_zero(&r) {
+            continue;
+        }
+        let s = n.mul(&n.inv_prime(&k), &n.add(&e, &n.mul(&r, &d)));
+        if !n.is_zero(&s) {
+            return Some((n.to_bytes(&r), n.to_bytes(&s)));
+        }
+    }
+}
+
+/// Uncompressed public key point for a big-endian private scalar.
+pub fn public_key(curve: Curve, private_key: &[u8]) -> Option<Vec<u8>> {
+    let group = curve.group()?;
+    let d = group.n.to_bytes(&group.scalar(private_key)?);
+    let (x, y) = group.affine(&group.mul(&group.g, &d))?;
+    let mut out = vec![4];
+    out.extend_from_slice(&group.p.to_bytes(&x));
+    out.extend_from_slice(&group.p.to_bytes(&y));
+    Some(out)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p256_public_key_and_signatures() {
+        // RFC 6979 A.2.5 key pair
+        let private = from_hex("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721");
+        let public = public_key(Curve::P256, &private).expect("public key");
+        assert_eq!(
+            public[1..33],
+            from_hex("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6")
+        );
+        assert_eq!(
+            public[33..],
+            from_hex("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299")
+        );
+
+        // RFC 6979 A.2.5, SHA-256, message "sample"
+        let r = from_hex("efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716");
+        let s = from_hex("f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8");
+        assert!(verify(
+            Curve::P256,
+            &public,
+            HashAlgorithm::Sha256,
+            b"sample",
+            &r,
+            &s
+        ));
+        assert!(!verify(
+            Curve::P256,
+            &public,
+            HashAlgorithm::Sha256,
+            b"sample!",
+            &r,
+            &s
+        ));
+        assert!(!verify(
+            Curve::P256,
+            &public,
+            HashAlgorithm::Sha256,
+            b"sample",
+            &s,
+            &r
+        ));
+
+        let (r, s) = sign(Curve::P256, &private, HashAlgorithm::Sha384, b"test").expect("sign");
+        assert!(verify(
+            Curve::P256,
+            &public,
+            HashAlgorithm::Sha384,
+            b"test",
+            &r,
+            &s
+        ));
+    }
+
+    #[test]
+    fn test_p384_signatures() {
+        // RFC 6979 A.2.6 key pair, SHA-384, message "sample"
+        let private = from_hex(
+            "6b9d3dad2e1b8c1c05b19875b6659f4de23c3b667bf297ba9aa47740787137d8\
+             96d5724e4c70a825f872c9ea60d2edf5",
+        );
+        let public = public_key(Curve::P384, &private).expect("public key");
+        let r = from_hex(
+            "94edbb92a5ecb8aad4736e56c691916b3f88140666ce9fa73d64c4ea95ad133c\
+             81a648152e44acf96e36dd1e80fabe46",
+        );
+        let s = from_hex(
+            "99ef4aeb15f178cea1fe40db2603138f130e740a19624526203b6351d0a3a94f\
+             a329c145786e679e7b82c71a38628ac8",
+        );
+        assert!(verify(
+            Curve::P384,
+            &public,
+            HashAlgorithm::Sha384,
+            b"sample",
+            &r,
+            &s
+        ));
+        assert!(!verify(
+            Curve::P384,
+            &public,
+            HashAlgorithm::Sha256,
+            b"sample",
+            &r,
+            &s
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+//! Cryptographic primitives backing `essentia::tls`.
+//!
+//! Implemented from the specifications on the standard library alone, like
+//! the rest of `essentia`. Known-answer tests from the RFCs and NIST documents
+//! live next to each primitive.
+
+pub mod aead;
+pub(crate) mod bigint;
+pub mod ecdsa;
+pub mod rsa;
+pub mod sha2;
+pub mod x25519;
+
+use std::{
+    collections::hash_map::RandomState,
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::Read,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fills `buffer` with unpredictable bytes from the OS.
+///
+/// Reads `/dev/urandom` where it exists. Elsewhere the bytes are derived with
+/// SHA-256 from the per-process random keys the standard library seeds from
+/// the OS for `HashMap`, the clock and a counter.
+pub fn random_bytes(buffer: &mut [u8]) {
+    if File::open("/dev/urandom").and_then(|mut f| f.read_exact(buffer)).is_ok() {
+        return;
+    }
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let mut seed = Vec::with_capacity(48);
+        for _ in 0..2 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            seed.extend_from_slice(&hasher.finish().to_le_bytes());
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        seed.extend_from_slice(&now.as_nanos().to_le_bytes());
+        seed.extend_from_slice(&std::process::id().to_le_bytes());
+        let block = sha2::Sha256::digest(&seed);
+        let take = (buffer.len() - offset).min(block.len());
+        buffer[offset..offset + take].copy_from_slice(&block[..take]);
+        offset += take;
+    }
+}
+
+/// Compares two byte strings in time independent of where they differ.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Lowercase hex encoding.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes hex digits, skipping any other characters.
+pub(crate) fn from_hex(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex
+        .bytes()
+        .filter(u8::is_ascii_hexdigit)
+        .map(|c| (c as char).to_digit(16).unwrap_or(0) as u8)
+        .collect();
+    digits.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]).collect()
+}
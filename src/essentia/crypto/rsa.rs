@@ -0,0 +1,353 @@
+//! RSA signatures (RFC 8017): PKCS #1 v1.5 and PSS verification, and PSS
+//! signing.
+
+use super::{bigint::Modulus, ct_eq, random_bytes, sha2::HashAlgorithm};
+
+/// Smallest modulus accepted, in bits.
+pub const MIN_BITS: usize = 2048;
+
+/// An RSA public key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RsaPublicKey {
+    modulus:  Modulus,
+    exponent: Vec<u8>,
+}
+
+impl std::fmt::Debug for RsaPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RsaPublicKey").field("bits", &self.bits()).finish()
+    }
+}
+
+impl RsaPublicKey {
+    /// Key from big-endian modulus and exponent; `None` for even or
+    /// undersized moduli and exponents below 3.
+    pub fn new(modulus: &[u8], exponent: &[u8]) -> Option<Self> {
+        let modulus = Modulus::new(modulus)?;
+        let exponent = exponent[exponent.iter().position(|&b| b != 0)?..].to_vec();
+        if modulus.bits() < MIN_BITS || exponent == [1] || exponent.last()? & 1 == 0 {
+            return None;
+        }
+        Some(Self { modulus, exponent })
+    }
+
+    pub fn bits(&self) -> usize {
+        self.modulus.bits()
+    }
+
+    /// `signature^e mod n`, or `None` if the signature is out of range.
+    fn encoded_message(&self, signature: &[u8]) -> Option<Vec<u8>> {
+        if signature.len() != self.modulus.byte_len() {
+            return None;
+        }
+        let s = self.modulus.residue(signature)?;
+        Some(self.modulus.to_bytes(&self.modulus.pow(&s, &self.exponent)))
+    }
+
+    /// RSASSA-PKCS1-v1_5 with `hash`.
+    pub fn verify_pkcs1(&self, hash: HashAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+        let Some(em) = self.encoded_message(signature) else {
+            return false;
+        };
+        let prefix: &[u8] = match hash {
+            HashAlgorithm::Sha256 => &DIGEST_INFO_SHA256,
+            HashAlgorithm::Sha384 => &DIGEST_INFO_SHA384,
+            HashAlgorithm::Sha512 => &DIGEST_INFO_SHA512,
+        };
+        let t_len = prefix.len() + hash.output_len();
+        if em.len() < t_len + 11 {
+            return false;
+        }
+        let mut expected = vec![0x00, 0x01];
+        expected.resize(em.len() - t_len - 1, 0xff);
+        expected.push(0x00);
+        expected.extend_from_slice(prefix);
+        expected.extend_from_slice(&hash.digest(message));
+        ct_eq(&em, &expected)
+    }
+
+    /// RSASSA-PSS with `hash` for both the message and MGF1, and the given
+    /// salt length.
+    pub fn verify_pss(
+        &self, hash: HashAlgorithm, message: &[u8], signature: &[u8], salt_len: usize,
+    ) -> bool {
+        let Some(m) = self.encoded_message(signature) else {
+            return false;
+        };
+        let h_len = hash.output_len();
+        let em_bits = self.modulus.bits() - 1;
+        let em_len = em_bits.div_ceil(8);
+        // The encoded message is one byte shorter when emBits is a multiple of 8
+        let (leading, em) = m.split_at(m.len() - em_len);
+        if leading.iter().any(|&b| b != 0)
+            || em_len < h_len + salt_len + 2
+            || em.last() != Some(&0xbc)
+        {
+            return false;
+        }
+        let (masked_db, rest) = em.split_at(em_len - h_len - 1);
+        let h = &rest[..h_len];
+        let top_bits = 8 * em_len - em_bits;
+        if top_bits > 0 && masked_db[0] >> (8 - top_bits) != 0 {
+            return false;
+        }
+
+        let mut db = mgf1(hash, h, masked_db.len());
+        for (d, m) in db.iter_mut().zip(masked_db) {
+            *d ^= m;
+        }
+        db[0] &= 0xff >> top_bits;
+        let padding = em_len - h_len - salt_len - 2;
+        if db[..padding].iter().any(|&b| b != 0) || db[padding] != 0x01 {
+            return false;
+        }
+        let salt = &db[padding + 1..];
+
+        let mut prime = vec![0u8; 8];
+        prime.extend_from_slice(&hash.digest(message));
+        prime.extend_from_slice(salt);
+        ct_eq(&hash.digest(&prime), h)
+    }
+}
+
+/// An RSA private key, for signing.
+///
+/// Signing is a plain modular exponentiation without CRT or blinding, and
+/// like the rest of this module it is not constant time.
+#[derive(Clone)]
+pub struct RsaPrivateKey {
+    public:   RsaPublicKey,
+    exponent: Vec<u8>,
+}
+
+impl std::fmt::Debug for RsaPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RsaPrivateKey").field("bits", &self.public.bits()).finish()
+    }
+}
+
+impl RsaPrivateKey {
+    /// Key from big-endian modulus, public and private exponents; `None`
+    /// where [`RsaPublicKey::new`] would be.
+    pub fn new(modulus: &[u8], public_exponent: &[u8], private_exponent: &[u8]) -> Option<Self> {
+        Some(Self {
+            public:   RsaPublicKey::new(modulus, public_exponent)?,
+            exponent: private_exponent.to_vec(),
+        })
+    }
+
+    pub fn public_key(&self) -> &RsaPublicKey {
+        &self.public
+    }
+
+    /// RSASSA-PSS with `hash` for both the message and MGF1, and a salt as
+    /// long as the hash. `None` if the key does not produce a signature
+    /// that verifies.
+    pub fn sign_pss(&self, hash: HashAlgorithm, message: &[u8]) -> Option<Vec<u8>> {
+        let mut salt = vec![0u8; hash.output_len()];
+        random_bytes(&mut salt);
+        self.sign_pss_with_salt(hash, message, &salt)
+    }
+
+    /// [`sign_pss`](Self::sign_pss) with a given salt, which makes the
+    /// signature deterministic.
+    fn sign_pss_with_salt(
+        &self, hash: HashAlgorithm, message: &[u8], salt: &[u8],
+    ) -> Option<Vec<u8>> {
+        let modulus = &self.public.modulus;
+        let h_len = hash.output_len();
+        let em_bits = modulus.bits() - 1;
+        let em_len = em_bits.div_ceil(8);
+
+        let mut prime = vec![0u8; 8];
+        prime.extend_from_slice(&hash.digest(message));
+        prime.extend_from_slice(salt);
+        let h = hash.digest(&prime);
+
+        let mut db = vec![0u8; em_len.checked_sub(h_len + salt.len() + 2)?];
+        db.push(0x01);
+        db.extend_from_slice(salt);
+        for (d, m) in db.iter_mut().zip(mgf1(hash, &h, em_len - h_len - 1)) {
+            *d ^= m;
+        }
+        db[0] &= 0xff >> (8 * em_len - em_bits);
+        let mut em = db;
+        em.extend_from_slice(&h);
+        em.push(0xbc);
+
+        let m = modulus.residue(&em)?;
+        let signature = modulus.to_bytes(&modulus.pow(&m, &self.exponent));
+        // Catches a private exponent that does not match the public key
+        self.public
+            .verify_pss(hash, message, &signature, salt.len())
+            .then_some(signature)
+    }
+}
+
+/// Mask generation function MGF1.
+fn mgf1(hash: HashAlgorithm, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + hash.output_len());
+    let mut counter = 0u32;
+    while out.len() < len {
+        let mut hasher = hash.hasher();
+        hasher.update(seed);
+        hasher.update(&counter.to_be_bytes());
+        out.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+// DER DigestInfo headers preceding the hash in a PKCS #1 v1.5 signature
+const DIGEST_INFO_SHA256: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+const DIGEST_INFO_SHA384: [u8; 19] = [
+    0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05,
+    0x00, 0x04, 0x30,
+];
+const DIGEST_INFO_SHA512: [u8; 19] = [
+    0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05,
+    0x00, 0x04, 0x40,
+];
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::crypto::from_hex;
+
+    // 2048-bit key from `tls/testdata/client-rsa.key`, e = 65537. Signatures
+    // over "sample" were made and cross-checked with OpenSSL 3 (`openssl
+    // dgst -sign`, and `-verify` for the fixed-salt PSS signature built here)
+    const MODULUS: &str = "bdd8f344dcee6c508d787aae4036d467f0d67a665394853c5291be0807517383\
+        9bacaa5f7d9b2ffe2117443f389e17a1475dde79396f253b00fcce4686475fa5\
+        21ceb60d400c73173b841e73c0071304dab5c14ff79e026710bbfd6ad2d7df33\
+        fcfcc1d05ec9ca8e81e9651dedfc9ac0f69c196cab57b178933f7431f485f451\
+        03366568c40f68d8c6cc5a945e186974dcaa058351dbd83c523151fb99b8fb72\
+        0c11abb18b30721c1ec59463a9d3f4eb267f3a098be1614316ad972385afda48\
+        97dab3006e5a33e1023170896353c104c17136673e453f115db73b9696938615\
+        7bf8ec02e185d490af9a2c41fd98034b5a79ef4bc6c39d2240d693be6aa226cb";
+    const PRIVATE_EXPONENT: &str = "487700b876da3d16ec412216d424f9a3a91ebcfec5056a6bf19a76de4aa8d0fe\
+        59d428fe250c30e0005288b6d70e2537e6ed5227948033c65764a7f9ec745ef2\
+        41baccebb6d869afe30455b95a0179457f9423f339b1a2fa233d9688096de3e1\
+        4cbe4fde90e7d67cd9e2b4399de72f4cdb750f11573fb45ab15de5a4d1d0b5c5\
+        f4325c3193eace18da483a947d541e85e54f978826e22b050313a7be59ade377\
+        76be8791fef042bfae97c32a7743ef933b65b1fa9edd8f8d46d58909c20a7a35\
+        254fe2f8f441e933b4fa528f2c93a3d92b39e169cb73ef4751bcdfbac960846a\
+        2c70d4a6ef5af05067fb82c3c31bbc73d23f5693866e5474ba3bd83683f4a0f1";
+    const PKCS1_SHA256: &str = "72e27be14c61ffe113dc2c5aa9300f135c910bf350f7a0b3ff4133ece50c071e\
+        214cd54cb5ba55784f2b34348b11427f6c743ea3279e18d80df357944640dfc9\
+        dfa10500ba8912163cff89d9be3fa14f6e9a796452559c03089e445338880036\
+        c604f43b5f1e46e5735c5ded59e4811f4530b160558dbfbfce4b0741c48b6909\
+        96740411c202ce5a8e91a5b3f196978bf7c44a40a0721863b83e8b3ade0e4cb3\
+        9dfc3836d8cdba2f2d9610686c2b66b795f7a81aa385f69db4f882a2741491f6\
+        85b67a61235b740cc3c9b8e78095f33beabb23d04838452c759b52a145d0ac9c\
+        cc6651a3e92139c4ffd8ddffe031a0de3386b7362a20c6e04acc6a20fe57f92e";
+    const PKCS1_SHA512: &str = "2d8f512c9b5026c74ce3747990853fc03b88486fdc58b03550cd198bad46fa62\
+        a58d89aa2899906f246bd4d8df711daeb650b0ad6dc66331000f071b334f4e04\
+        9b28e5b31c7e64b93c3ba387a86a250c54a1c9baaa29a5b46d8f080c93071fc2\
+        8b6ab9f117185f85c6a80f513986e6e018abc0d6904dc4a82ee1154a3a01fcc5\
+        2e44b9ddf2dccbcf70c4019132a7fb9293938e93967e67205087a17b961f1166\
+        d30c136249d069ba2e0eafe57a624407fffbaac4c9c9988cd32903fd01e01caa\
+        f7b973c6e462a95af7a7f25c467981a6910db75f1d1c4da2986e7d81bc30ed87\
+        082afc7890ea9fc434e77312a9bca3a2a766c202fbc7dd28c27af1faf10735b0";
+    const PKCS1_BLOCK_TYPE_2: &str = "33ae8173377910ca2c31510610e80c75cd2fc8fc767f4b6d4fceff37d692f327\
+        277d52dfcd6e592ca2b2babeac00df55a8193e3f471478a4ba3dbbfc377bdf48\
+        772909b7e8c744dd0a95dab882efe6cd2d7e93eac35ae7c90dc04d834eb2d206\
+        4ee5e88ef88c1fa4cde4845756f55d957526ee830c36bd80d1948706f6c206f0\
+        d2f74b43fe6612cdbca9d6f5be833fbfaa219fb17c6cc166a5ef693e41e49386\
+        7a724848f457112f2d8c0e88c8971d23e18609ab4a44027084bb256cfbfdbf30\
+        7f45bdba11ece49304308fa1329de1884189333b258cfc3526b5043606267844\
+        cf1ceec2603776690d2d23702ac240bd74b21753e2a3de56f878d92c557d9d62";
+    const PSS_SHA256_FIXED_SALT: &str = "2ab8ef157767bfedcc45c4b70d926858f9d0507cdcf9f1c1fdbb2e8a560762f6\
+        f65c9617c79f76f843f6ca33b35d023dc99472f14c2a8de4e43932322731cb6c\
+        615c28b1135cc3b0e6f83157dad9c3796b42c24e32afb2d0ac9b933503e73d0d\
+        3402bc3e40335727785d13f599d7f0383e8d7f4fa6de9105d35642b31cfc7249\
+        c4115160a8a7b29501314ad1c0cbe3c964030326df02ed9e8c410e270d85c78d\
+        9d3cb6895f50e3f525c68a11060ee9df21f34ac0b59b51246a8ba94ea046ea4f\
+        da4ee40003ae49642355e3383b9318ea890d3196b17cb6d2fbb83f787c717bbe\
+        6bfe4ea1549c8630ba6d70dcab816ddbad5cadebc39a21f511f37d286c2e43c0";
+    const PSS_SHA256_SALT_20: &str = "169b6d9c8071109a5d9f975103a160e08704ca7d163ce6f28b9faac64f93274a\
+        ffaa8ed8eb4ea0dd058baed496b6bad4b9f80a6b02f1bde5cb3ea9570915eae1\
+        e80fb49b1fca39649281f16bab5361a0a2be082c4e0def268c575420f0c0f7e5\
+        fd24eae10e035cdc07dfa44cd890f6eeb0d6a30b93bfaef11f910d187342ae03\
+        58290ba4b362366d96f86bd1cb01e3a18abbc6b764d5da095c05c1a4e7c4b657\
+        f3265e4490af309afaa9fd0e1468aded3efdfbfa4a128436cccb0d044c78c4fb\
+        6a803be734fb7dcf2c58d9d73bcc658ac5f7eb606794f4f2f8d8267068288d44\
+        1d2039388d7ec44cc1c7ed8786a65ea0b6446345a64de261cde2064f6e693960";
+
+    fn key() -> RsaPrivateKey {
+        RsaPrivateKey::new(&from_hex(MODULUS), &[1, 0, 1], &from_hex(PRIVATE_EXPONENT))
+            .expect("key")
+    }
+
+    #[test]
+    fn test_pkcs1_known_answers() {
+        let key = key();
+        let public = key.public_key();
+        assert_eq!(public.bits(), 2048);
+        let sha256 = from_hex(PKCS1_SHA256);
+        assert!(public.verify_pkcs1(HashAlgorithm::Sha256, b"sample", &sha256));
+        assert!(public.verify_pkcs1(HashAlgorithm::Sha512, b"sample", &from_hex(PKCS1_SHA512)));
+
+        // Wrong message, wrong hash, bad padding, truncated
+        assert!(!public.verify_pkcs1(HashAlgorithm::Sha256, b"sampled", &sha256));
+        assert!(!public.verify_pkcs1(HashAlgorithm::Sha384, b"sample", &sha256));
+        let block_type_2 = from_hex(PKCS1_BLOCK_TYPE_2);
+        assert!(!public.verify_pkcs1(HashAlgorithm::Sha256, b"sample", &block_type_2));
+        assert!(!public.verify_pkcs1(HashAlgorithm::Sha256, b"sample", &sha256[1..]));
+        let mut flipped = sha256.clone();
+        flipped[100] ^= 1;
+        assert!(!public.verify_pkcs1(HashAlgorithm::Sha256, b"sample", &flipped));
+    }
+
+    #[test]
+    fn test_pss_known_answers() {
+        let key = key();
+        let public = key.public_key();
+        let salt: Vec<u8> = (0..32).collect();
+        let expected = from_hex(PSS_SHA256_FIXED_SALT);
+        assert_eq!(
+            key.sign_pss_with_salt(HashAlgorithm::Sha256, b"sample", &salt),
+            Some(expected.clone())
+        );
+        assert!(public.verify_pss(HashAlgorithm::Sha256, b"sample", &expected, 32));
+
+        // Salt lengths must match exactly
+        let salt_20 = from_hex(PSS_SHA256_SALT_20);
+        assert!(public.verify_pss(HashAlgorithm::Sha256, b"sample", &salt_20, 20));
+        assert!(!public.verify_pss(HashAlgorithm::Sha256, b"sample", &salt_20, 32));
+        assert!(!public.verify_pss(HashAlgorithm::Sha256, b"sample", &expected, 20));
+        assert!(!public.verify_pss(HashAlgorithm::Sha256, b"sampled", &expected, 32));
+        // A PKCS #1 v1.5 signature is not a valid PSS encoding
+        let pkcs1 = from_hex(PKCS1_SHA256);
+        assert!(!public.verify_pss(HashAlgorithm::Sha256, b"sample", &pkcs1, 32));
+
+        let signature = key.sign_pss(HashAlgorithm::Sha384, b"sample").expect("sign");
+        assert!(public.verify_pss(HashAlgorithm::Sha384, b"sample", &signature, 48));
+    }
+
+    #[test]
+    fn test_signatures_out_of_range() {
+        let key = key();
+        let public = key.public_key();
+        let modulus = from_hex(MODULUS);
+        let mut above = modulus.clone();
+        above[255] += 2;
+        for signature in [modulus, above, vec![0xff; 256]] {
+            assert!(!public.verify_pkcs1(HashAlgorithm::Sha256, b"sample", &signature));
+            assert!(!public.verify_pss(HashAlgorithm::Sha256, b"sample", &signature, 32));
+        }
+
+        // Undersized, even and degenerate keys are refused
+        assert!(RsaPublicKey::new(&from_hex(MODULUS)[..128], &[1, 0, 1]).is_none());
+        let mut even = from_hex(MODULUS);
+        even[255] &= 0xfe;
+        assert!(RsaPublicKey::new(&even, &[1, 0, 1]).is_none());
+        assert!(RsaPublicKey::new(&from_hex(MODULUS), &[1]).is_none());
+        assert!(RsaPublicKey::new(&from_hex(MODULUS), &[2]).is_none());
+    }
+}
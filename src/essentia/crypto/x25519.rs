@@ -0,0 +1,238 @@
+//! X25519 Diffie-Hellman (RFC 7748) over radix-2^51 field elements.
+
+const MASK: u64 = (1 << 51) - 1;
+
+/// Element of GF(2^255 - 19) as five 51-bit limbs, not necessarily reduced.
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+impl Fe {
+    const ONE: Self = Self([1, 0, 0, 0, 0]);
+    const ZERO: Self = Self([0; 5]);
+
+    fn from_bytes(bytes: &[u8; 32]) -> Self {
+        let load = |i: usize| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&bytes[i..i + 8]);
+            u64::from_le_bytes(word)
+        };
+        // The top bit is ignored, per RFC 7748 section 5
+        Self([
+            load(0) & MASK,
+            (load(6) >> 3) & MASK,
+            (load(12) >> 6) & MASK,
+            (load(19) >> 1) & MASK,
+            (load(24) >> 12) & MASK,
+        ])
+    }
+
+    fn to_bytes(self) -> [u8; 32] {
+        let mut h = self.carry().carry().0;
+        // h is now below 2^255 + 19; subtract p once if h >= p
+        let mut q = (h[0] + 19) >> 51;
+        for limb in &h[1..] {
+            q = (limb + q) >> 51;
+        }
+        h[0] += 19 * q;
+        for i in 1..5 {
+            h[i] += h[i - 1] >> 51;
+            h[i - 1] &= MASK;
+        }
+        h[4] &= MASK;
+
+        let words = [
+            h[0] | (h[1] << 51),
+            (h[1] >> 13) | (h[2] << 38),
+            (h[2] >> 26) | (h[3] << 25),
+            (h[3] >> 39) | (h[4] << 12),
+        ];
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    /// Propagates carries so every limb fits in 51 bits (plus a small excess
+    /// in limb 0).
+    fn carry(self) -> Self {
+        let mut h = self.0;
+        for i in 1..5 {
+            h[i] += h[i - 1] >> 51;
+            h[i - 1] &= MASK;
+        }
+        h[0] += 19 * (h[4] >> 51);
+        h[4] &= MASK;
+        Self(h)
+    }
+
+    fn add(self, other: Self) -> Self {
+        let mut h = self.0;
+        for (a, b) in h.iter_mut().zip(other.0) {
+            *a += b;
+        }
+        Self(h)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        // Add 2p first so limbs cannot underflow
+        const TWO_P: [u64; 5] = [
+            0xfffffffffffda,
+            0xffffffffffffe,
+            0xffffffffffffe,
+            0xffffffffffffe,
+            0xffffffffffffe,
+        ];
+        let (a, b) = (self.carry().0, other.carry().0);
+        let mut h = [0u64; 5];
+        for i in 0..5 {
+            h[i] = a[i] + TWO_P[i] - b[i];
+        }
+        Self(h).carry()
+    }
+
+    fn mul(self, other: Self) -> Self {
+        let [a0, a1, a2, a3, a4] = self.0.map(u128::from);
+        let [b0, b1, b2, b3, b4] = other.0.map(u128::from);
+        let [b1_19, b2_19, b3_19, b4_19] = [b1 * 19, b2 * 19, b3 * 19, b4 * 19];
+
+        let c0 = a0 * b0 + a1 * b4_19 + a2 * b3_19 + a3 * b2_19 + a4 * b1_19;
+        let mut c1 = a0 * b1 + a1 * b0 + a2 * b4_19 + a3 * b3_19 + a4 * b2_19;
+        let mut c2 = a0 * b2 + a1 * b1 + a2 * b0 + a3 * b4_19 + a4 * b3_19;
+        let mut c3 = a0 * b3 + a1 * b2 + a2 * b1 + a3 * b0 + a4 * b4_19;
+        let mut c4 = a0 * b4 + a1 * b3 + a2 * b2 + a3 * b1 + a4 * b0;
+
+        c1 += c0 >> 51;
+        c2 += c1 >> 51;
+        c3 += c2 >> 51;
+        c4 += c3 >> 51;
+        let mask = u128::from(MASK);
+        let mut h = [
+            (c0 & mask) as u64,
+            (c1 & mask) as u64,
+            (c2 & mask) as u64,
+            (c3 & mask) as u64,
+            (c4 & mask) as u64,
+        ];
+        h[0] += ((c4 >> 51) * 19) as u64;
+        h[1] += h[0] >> 51;
+        h[0] &= MASK;
+        Self(h)
+    }
+
+    fn square(self) -> Self {
+        self.mul(self)
+    }
+
+    fn mul_small(self, n: u64) -> Self {
+        self.mul(Self([n, 0, 0, 0, 0]))
+    }
+
+    /// Inverse via Fermat: self^(p - 2).
+    fn invert(self) -> Self {
+        // p - 2 = 2^255 - 21: bits 254..=8 set, low byte 0xeb
+        let mut result = Self::ONE;
+        for bit in (0..255).rev() {
+            result = result.square();
+            if bit >= 8 || (0xebu8 >> bit) & 1 == 1 {
+                result = result.mul(self);
+            }
+        }
+        result
+    }
+
+    /// Swaps `a` and `b` when `swap` is 1, without branching on it.
+    fn cswap(swap: u64, a: &mut Self, b: &mut Self) {
+        let mask = swap.wrapping_neg();
+        for (x, y) in a.0.iter_mut().zip(b.0.iter_mut()) {
+            let t = mask & (*x ^ *y);
+            *x ^= t;
+            *y ^= t;
+        }
+    }
+}
+
+/// Scalar multiplication of the u-coordinate `u` by `scalar` (clamped).
+pub fn x25519(scalar: &[u8; 32], u: &[u8; 32]) -> [u8; 32] {
+    let mut k = *scalar;
+    k[0] &= 248;
+    k[31] &= 127;
+    k[31] |= 64;
+
+    let x1 = Fe::from_bytes(u);
+    let (mut x2, mut z2, mut x3, mut z3) = (Fe::ONE, Fe::ZERO, x1, Fe::ONE);
+    let mut swap = 0u64;
+    for t in (0..255).rev() {
+        let bit = u64::from((k[t / 8] >> (t % 8)) & 1);
+        swap ^= bit;
+        Fe::cswap(swap, &mut x2, &mut x3);
+        Fe::cswap(swap, &mut z2, &mut z3);
+        swap = bit;
+
+        let a = x2.add(z2);
+        let aa = a.square();
+        let b = x2.sub(z2);
+        let bb = b.square();
+        let e = aa.sub(bb);
+        let c = x3.add(z3);
+        let d = x3.sub(z3);
+        let da = d.mul(a);
+        let cb = c.mul(b);
+        x3 = da.add(cb).square();
+        z3 = x1.mul(da.sub(cb).square());
+        x2 = aa.mul(bb);
+        z2 = e.mul(aa.add(e.mul_small(121665)));
+    }
+    Fe::cswap(swap, &mut x2, &mut x3);
+    Fe::cswap(swap, &mut z2, &mut z3);
+    x2.mul(z2.invert()).to_bytes()
+}
+
+/// Public key for `scalar`: its product with the base point u = 9.
+pub fn x25519_base(scalar: &[u8; 32]) -> [u8; 32] {
+    let mut base = [0u8; 32];
+    base[0] = 9;
+    x25519(scalar, &base)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::crypto::{from_hex, to_hex};
+
+    fn bytes(hex: &str) -> [u8; 32] {
+        from_hex(hex).try_into().expect("32 bytes")
+    }
+
+    #[test]
+    fn test_rfc7748_vectors() {
+        // Section 5.2
+        let scalar = bytes("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let u = bytes("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        assert_eq!(
+            to_hex(&x25519(&scalar, &u)),
+            "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552"
+        );
+
+        // Section 6.1
+        let alice = bytes("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = bytes("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = x25519_base(&alice);
+        let bob_public = x25519_base(&bob);
+        assert_eq!(
+            to_hex(&alice_public),
+            "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+        );
+        assert_eq!(
+            to_hex(&bob_public),
+            "de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"
+        );
+        let shared = x25519(&alice, &bob_public);
+        assert_eq!(shared, x25519(&bob, &alice_public));
+        assert_eq!(
+            to_hex(&shared),
+            "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"
+        );
+    }
+}
@@ -0,0 +1,986 @@
+//! DEFLATE, zlib and gzip.
+//!
+//! [`Decoder`] inflates `Content-Encoding: gzip` / `deflate` bodies as they
+//! are read, so a compressed SSE stream still yields each event as soon as
+//! the server flushes it. [`gzip`] compresses request bodies with LZ77 and the
+//! fixed Huffman code, which is compact to implement and does well on JSON.
+
+use std::io::{self, Read};
+
+/// `Accept-Encoding` value advertising what [`Decoder`] handles.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// Maximum back-reference distance.
+const WINDOW: usize = 32 * 1024;
+
+/// Most input a single literal/length/distance symbol can take, in bytes.
+const MAX_SYMBOL_BYTES: usize = 8;
+
+/// Most a body may decode to before [`Decoder`] fails, in bytes.
+pub const DEFAULT_MAX_OUTPUT: u64 = 64 * 1024 * 1024;
+
+/// Largest ratio of decoded to compressed size [`Decoder`] accepts, once
+/// the output passes 1 MiB.
+pub const DEFAULT_MAX_RATIO: u64 = 100;
+
+/// Output below which the ratio is not checked, since short repetitive
+/// bodies legitimately compress very well.
+const RATIO_FLOOR: u64 = 1024 * 1024;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order of the code length code lengths in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// A `Content-Encoding` that [`Decoder`] understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    /// zlib-wrapped DEFLATE, or raw DEFLATE from servers that get it wrong.
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Parses a `Content-Encoding` value; `None` for `identity` and codings
+    /// we cannot decode.
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+}
+
+/// Decompresses a complete body.
+pub fn decompress(data: &[u8], encoding: ContentEncoding) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 4);
+    Decoder::new(data, encoding).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// Compresses `data` into a gzip member.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // No name, no mtime, unknown OS
+    let mut out = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Compresses `data` into a raw DEFLATE stream (a single fixed-Huffman
+/// block).
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    const HASH_SIZE: usize = 1 << 15;
+    const MAX_CHAIN: usize = 128;
+    const NONE: usize = usize::MAX;
+
+    let hash = |i: usize| {
+        let key = u32::from(data[i]) << 16 | u32::from(data[i + 1]) << 8 | u32::from(data[i + 2]);
+        (key.wrapping_mul(2_654_435_761) >> 17) as usize & (HASH_SIZE - 1)
+    };
+    let mut head = vec![NONE; HASH_SIZE];
+    let mut prev = vec![NONE; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut out = BitWriter::default();
+    // BFINAL, BTYPE = 01 (fixed Huffman)
+    out.bits(0b011, 3);
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + 3 <= data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != NONE && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(258)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, i - candidate);
+                    if len == 258 {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= 3 {
+            out.length_distance(best_len, best_dist);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            out.literal(u16::from(data[i]));
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    out.literal(256);
+    out.finish()
+}
+
+/// CRC-32 (IEEE) as used by gzip.
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    for &byte in data {
+        crc = TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn adler32_update(adler: u32, data: &[u8]) -> u32 {
+    let (mut a, mut b) = (adler & 0xFFFF, adler >> 16);
+    // 5552 bytes is the most that can be summed before the u32s overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// LSB-first bit sink for the compressor.
+#[derive(Default)]
+struct BitWriter {
+    out:   Vec<u8>,
+    acc:   u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.acc |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Writes a literal/length symbol with the fixed code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn length_distance(&mut self, len: usize, dist: usize) {
+        let l = (0..LENGTH_BASE.len()).rev().find(|&i| usize::from(LENGTH_BASE[i]) <= len);
+        let d = (0..DIST_BASE.len()).rev().find(|&i| usize::from(DIST_BASE[i]) <= dist);
+        let (Some(l), Some(d)) = (l, d) else {
+            return;
+        };
+        self.literal(257 + l as u16);
+        self.bits(
+            (len - usize::from(LENGTH_BASE[l])) as u32,
+            u32::from(LENGTH_EXTRA[l]),
+        );
+        self.code(d as u32, 5);
+        self.bits(
+            (dist - usize::from(DIST_BASE[d])) as u32,
+            u32::from(DIST_EXTRA[d]),
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Buffered LSB-first bit source for the decoder.
+struct Bits<R> {
+    inner: R,
+    buf:   Box<[u8]>,
+    pos:   usize,
+    len:   usize,
+    acc:   u64,
+    count: u32,
+    /// Bytes read from `inner` so far.
+    total: u64,
+}
+
+impl<R: Read> Bits<R> {
+    /// Whole bytes buffered and not yet consumed.
+    fn available(&self) -> usize {
+        self.len - self.pos
+    }
+
+    /// Reads more input; `false` at end of stream.
+    fn refill(&mut self) -> io::Result<bool> {
+        if self.pos == self.len {
+            self.pos = 0;
+            self.len = 0;
+        } else if self.len == self.buf.len() {
+            self.buf.copy_within(self.pos..self.len, 0);
+            self.len -= self.pos;
+            self.pos = 0;
+        }
+        loop {
+            match self.inner.read(&mut self.buf[self.len..]) {
+                Ok(n) => {
+                    self.len += n;
+                    self.total += n as u64;
+                    return Ok(n > 0);
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Whether the input is exhausted; only blocks if nothing is buffered.
+    fn at_end(&mut self) -> io::Result<bool> {
+        Ok(self.available() == 0 && self.count == 0 && !self.refill()?)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        if self.pos == self.len && !self.refill()? {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Truncated compressed stream",
+            ));
+        }
+        self.pos += 1;
+        Ok(self.buf[self.pos - 1])
+    }
+
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.count < count {
+            self.acc |= u64::from(self.byte()?) << self.count;
+            self.count += 8;
+        }
+        let value = (self.acc & ((1 << count) - 1)) as u32;
+        self.acc >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        let partial = self.count % 8;
+        self.acc >>= partial;
+        self.count -= partial;
+    }
+
+    fn u16_le(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+
+    fn u32_le(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes([
+            self.byte()?,
+            self.byte()?,
+            self.byte()?,
+            self.byte()?,
+        ]))
+    }
+
+    /// Skips a zero-terminated gzip header field.
+    fn skip_cstr(&mut self) -> io::Result<()> {
+        while self.byte()? != 0 {}
+        Ok(())
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time so that no input past
+/// the current symbol is ever needed.
+struct Huffman {
+    counts:  [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(invalid("Over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                let slot = &mut offsets[usize::from(len)];
+                symbols[usize::from(*slot)] = symbol as u16;
+                *slot += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn fixed() -> io::Result<(Self, Self)> {
+        let mut lengths = [8u8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        Ok((Self::new(&lengths)?, Self::new(&[5; 30])?))
+    }
+
+    fn decode<R: Read>(&self, bits: &mut Bits<R>) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("Invalid Huffman code"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    Gzip,
+    Zlib,
+    Raw,
+    /// `Content-Encoding: deflate`: zlib, or raw if the header does not fit.
+    Detect,
+}
+
+enum State {
+    /// Before a gzip member or zlib header.
+    Header,
+    /// At a block header.
+    Block,
+    /// Bytes left in a stored block.
+    Stored(usize),
+    /// Inside a Huffman-coded block.
+    Codes,
+    /// After the final block.
+    Trailer,
+    Done,
+}
+
+/// Streaming decompressor: reads compressed bytes from `inner` and yields the
+/// decoded body.
+///
+/// Output is returned as soon as it is decoded; the decoder only waits for
+/// more input when it has nothing to hand out. Reads fail with
+/// [`io::ErrorKind::InvalidData`] once the output passes the size or
+/// expansion limits, so a small compressed body cannot exhaust memory.
+pub struct Decoder<R> {
+    bits:       Bits<R>,
+    wrapper:    Wrapper,
+    state:      State,
+    last:       bool,
+    literals:   Huffman,
+    distance:   Huffman,
+    /// Recent output: the back-reference window plus anything not yet read.
+    window:     Vec<u8>,
+    /// Bytes of `window` already returned to the caller.
+    emitted:    usize,
+    /// Bytes of `window` already added to the checksum.
+    checked:    usize,
+    checksum:   u32,
+    size:       u32,
+    members:    usize,
+    /// Bytes returned to the caller so far.
+    output:     u64,
+    max_output: u64,
+    max_ratio:  u64,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(inner: R, encoding: ContentEncoding) -> Self {
+        let wrapper = match encoding {
+            ContentEncoding::Gzip => Wrapper::Gzip,
+            ContentEncoding::Deflate => Wrapper::Detect,
+        };
+        Self::with_wrapper(inner, wrapper)
+    }
+
+    /// Decodes a raw DEFLATE stream without zlib or gzip framing.
+    pub fn raw(inner: R) -> Self {
+        Self::with_wrapper(inner, Wrapper::Raw)
+    }
+
+    /// Caps the decoded size at `max_output` bytes and, past the first MiB,
+    /// its ratio to the compressed size at `max_ratio`. Defaults to
+    /// [`DEFAULT_MAX_OUTPUT`] and [`DEFAULT_MAX_RATIO`].
+    pub fn with_limits(mut self, max_output: u64, max_ratio: u64) -> Self {
+        self.max_output = max_output;
+        self.max_ratio = max_ratio;
+        self
+    }
+
+    fn with_wrapper(inner: R, wrapper: Wrapper) -> Self {
+        Self {
+            bits: Bits {
+                inner,
+                buf: vec![0; 8192].into_boxed_slice(),
+                pos: 0,
+                len: 0,
+                acc: 0,
+                count: 0,
+                total: 0,
+            },
+            wrapper,
+            state: State::Header,
+            last: false,
+            literals: Huffman { counts: [0; 16], symbols: Vec::new() },
+            distance: Huffman { counts: [0; 16], symbols: Vec::new() },
+            window: Vec::new(),
+            emitted: 0,
+            checked: 0,
+            checksum: 0,
+            size: 0,
+            members: 0,
+            output: 0,
+            max_output: DEFAULT_MAX_OUTPUT,
+            max_ratio: DEFAULT_MAX_RATIO,
+        }
+    }
+
+    fn pending(&self) -> usize {
+        self.window.len() - self.emitted
+    }
+
+    fn check_limits(&self) -> io::Result<()> {
+        let limit = |message: String| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        if self.output > self.max_output {
+            return limit(format!(
+                "Decompressed body exceeds {} bytes",
+                self.max_output
+            ));
+        }
+        if self.output > RATIO_FLOOR && self.output / self.bits.total.max(1) > self.max_ratio {
+            return limit(format!(
+                "Decompressed body expands more than {} times",
+                self.max_ratio
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decodes until `want` bytes are pending, the stream ends, or going on
+    /// would mean waiting for input while output is ready.
+    fn decode(&mut self, want: usize) -> io::Result<()> {
+        loop {
+            let pending = self.pending();
+            if pending >= want || (pending > 0 && self.bits.available() < MAX_SYMBOL_BYTES) {
+                return Ok(());
+            }
+            match self.state {
+                State::Header => {
+                    // An empty body decodes to nothing
+                    if self.members == 0 && self.bits.at_end()? {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    self.read_header()?;
+                    self.state = State::Block;
+                },
+                State::Block if self.last => self.state = State::Trailer,
+                State::Block => {
+                    if pending > 0 {
+                        return Ok(());
+                    }
+                    self.read_block_header()?;
+                },
+                State::Stored(0) => self.state = State::Block,
+                State::Stored(left) => {
+                    if self.bits.available() == 0 {
+                        if pending > 0 {
+                            return Ok(());
+                        }
+                        if !self.bits.refill()? {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "Truncated stored block",
+                            ));
+                        }
+                    }
+                    let n = left.min(self.bits.available());
+                    let start = self.bits.pos;
+                    self.window.extend_from_slice(&self.bits.buf[start..start + n]);
+                    self.bits.pos += n;
+                    self.state = State::Stored(left - n);
+                },
+                State::Codes => self.read_symbol()?,
+                State::Trailer => {
+                    if pending > 0 {
+                        return Ok(());
+                    }
+                    self.read_trailer()?;
+                },
+                State::Done => return Ok(()),
+            }
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        if self.wrapper == Wrapper::Detect {
+            let (cmf, flg) = (self.peek(0)?, self.peek(1)?);
+            let zlib = cmf & 0x0F == 8 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0;
+            self.wrapper = if zlib { Wrapper::Zlib } else { Wrapper::Raw };
+        }
+        match self.wrapper {
+            Wrapper::Gzip => {
+                let bits = &mut self.bits;
+                if bits.byte()? != 0x1F || bits.byte()? != 0x8B || bits.byte()? != 8 {
+                    return Err(invalid("Not a gzip stream"));
+                }
+                let flags = bits.byte()?;
+                // MTIME, XFL, OS
+                for _ in 0..6 {
+                    bits.byte()?;
+                }
+                if flags & 0x04 != 0 {
+                    for _ in 0..bits.u16_le()? {
+                        bits.byte()?;
+                    }
+                }
+                if flags & 0x08 != 0 {
+                    bits.skip_cstr()?;
+                }
+                if flags & 0x10 != 0 {
+                    bits.skip_cstr()?;
+                }
+                if flags & 0x02 != 0 {
+                    bits.u16_le()?;
+                }
+                self.checksum = 0;
+            },
+            Wrapper::Zlib => {
+                let (cmf, flg) = (self.bits.byte()?, self.bits.byte()?);
+                if cmf & 0x0F != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+                    return Err(invalid("Not a zlib stream"));
+                }
+                if flg & 0x20 != 0 {
+                    return Err(invalid("zlib preset dictionaries are not supported"));
+                }
+                self.checksum = 1;
+            },
+            Wrapper::Raw | Wrapper::Detect => {},
+        }
+        self.size = 0;
+        self.last = false;
+        self.members += 1;
+        Ok(())
+    }
+
+    /// Looks at the `offset`th unread byte without consuming it.
+    fn peek(&mut self, offset: usize) -> io::Result<u8> {
+        while self.bits.available() <= offset {
+            if !self.bits.refill()? {
+                return Err(invalid("Truncated deflate header"));
+            }
+        }
+        Ok(self.bits.buf[self.bits.pos + offset])
+    }
+
+    fn read_block_header(&mut self) -> io::Result<()> {
+        self.last = self.bits.bits(1)? == 1;
+        match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.u16_le()?;
+                if len != !self.bits.u16_le()? {
+                    return Err(invalid("Corrupt stored block length"));
+                }
+                self.state = State::Stored(usize::from(len));
+            },
+            1 => {
+                (self.literals, self.distance) = Huffman::fixed()?;
+                self.state = State::Codes;
+            },
+            2 => {
+                self.read_dynamic_tables()?;
+                self.state = State::Codes;
+            },
+            _ => return Err(invalid("Invalid block type")),
+        }
+        Ok(())
+    }
+
+    fn read_dynamic_tables(&mut self) -> io::Result<()> {
+        let bits = &mut self.bits;
+        let literal_count = bits.bits(5)? as usize + 257;
+        let distance_count = bits.bits(5)? as usize + 1;
+        let code_count = bits.bits(4)? as usize + 4;
+        if literal_count > 286 || distance_count > 30 {
+            return Err(invalid("Too many Huffman codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..code_count] {
+            code_lengths[index] = bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths)?;
+
+        let total = literal_count + distance_count;
+        let mut lengths = vec![0u8; total];
+        let mut index = 0;
+        while index < total {
+            let symbol = code_lengths.decode(bits)?;
+            let (len, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let previous = *index
+                        .checked_sub(1)
+                        .and_then(|i| lengths.get(i))
+                        .ok_or_else(|| invalid("Repeat with no previous length"))?;
+                    (previous, 3 + bits.bits(2)? as usize)
+                },
+                17 => (0, 3 + bits.bits(3)? as usize),
+                _ => (0, 11 + bits.bits(7)? as usize),
+            };
+            if index + repeat > total {
+                return Err(invalid("Too many code lengths"));
+            }
+            lengths[index..index + repeat].fill(len);
+            index += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("Missing end-of-block code"));
+        }
+
+        self.literals = Huffman::new(&lengths[..literal_count])?;
+        self.distance = Huffman::new(&lengths[literal_count..])?;
+        Ok(())
+    }
+
+    fn read_symbol(&mut self) -> io::Result<()> {
+        let symbol = self.literals.decode(&mut self.bits)?;
+        if symbol < 256 {
+            self.window.push(symbol as u8);
+            return Ok(());
+        }
+        if symbol == 256 {
+            self.state = State::Block;
+            return Ok(());
+        }
+
+        let index = usize::from(symbol - 257);
+        let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(index), LENGTH_EXTRA.get(index)) else {
+            return Err(invalid("Invalid length code"));
+        };
+        let len = usize::from(base) + self.bits.bits(u32::from(extra))? as usize;
+
+        let index = usize::from(self.distance.decode(&mut self.bits)?);
+        let (Some(&base), Some(&extra)) = (DIST_BASE.get(index), DIST_EXTRA.get(index)) else {
+            return Err(invalid("Invalid distance code"));
+        };
+        let dist = usize::from(base) + self.bits.bits(u32::from(extra))? as usize;
+        if dist > self.window.len() {
+            return Err(invalid("Distance too far back"));
+        }
+        // Byte by byte: the source may overlap what is being written
+        let start = self.window.len() - dist;
+        for i in 0..len {
+            let byte = self.window[start + i];
+            self.window.push(byte);
+        }
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        self.update_checksum();
+        self.bits.align();
+        match self.wrapper {
+            Wrapper::Gzip => {
+                let crc = self.bits.u32_le()?;
+                let size = self.bits.u32_le()?;
+                if crc != self.checksum || size != self.size {
+                    return Err(invalid("gzip checksum mismatch"));
+                }
+                // Concatenated members decode as one body
+                self.state = if self.bits.at_end()? {
+                    State::Done
+                } else {
+                    State::Header
+                };
+            },
+            Wrapper::Zlib => {
+                let adler = u32::from_be_bytes([
+                    self.bits.byte()?,
+                    self.bits.byte()?,
+                    self.bits.byte()?,
+                    self.bits.byte()?,
+                ]);
+                if adler != self.checksum {
+                    return Err(invalid("zlib checksum mismatch"));
+                }
+                self.state = State::Done;
+            },
+            Wrapper::Raw | Wrapper::Detect => self.state = State::Done,
+        }
+        Ok(())
+    }
+
+    fn update_checksum(&mut self) {
+        let fresh = &self.window[self.checked..];
+        match self.wrapper {
+            Wrapper::Gzip => self.checksum = !crc32_update(!self.checksum, fresh),
+            Wrapper::Zlib => self.checksum = adler32_update(self.checksum, fresh),
+            Wrapper::Raw | Wrapper::Detect => {},
+        }
+        self.size = self.size.wrapping_add(fresh.len() as u32);
+        self.checked = self.window.len();
+    }
+
+    /// Drops output that has been read and is out of back-reference range.
+    fn trim(&mut self) {
+        if self.window.len() < 2 * WINDOW {
+            return;
+        }
+        self.update_checksum();
+        let cut = (self.window.len() - WINDOW).min(self.emitted);
+        self.window.drain(..cut);
+        self.emitted -= cut;
+        self.checked -= cut;
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.pending() == 0 {
+            self.decode(buf.len())?;
+        }
+        let n = self.pending().min(buf.len());
+        self.output += n as u64;
+        self.check_limits()?;
+        buf[..n].copy_from_slice(&self.window[self.emitted..self.emitted + n]);
+        self.emitted += n;
+        self.trim();
+        Ok(n)
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("hex"))
+            .collect()
+    }
+
+    #[test]
+    fn test_decodes_reference_streams() {
+        // gzip -9 output using a dynamic Huffman block
+        let gzipped = hex(concat!(
+            "1f8b080000000000020355894112c2201004bf32ee399507e0077c8037cb03923541091b5962548abf",
+            "cb514fdd5d5dc84de21d2b9953a19955edc8640a25098d6455bd661b3375e424666e66e8c021c80ebf",
+            "e8b0490a438fe3c478acdedd7149b2455ce585db3a2f0a7972426e3bd8cf1b838cfbbf8206e6457baa",
+            "f55cbf7c5b935e98000000",
+        ));
+        let text = decompress(&gzipped, ContentEncoding::Gzip).expect("gzip");
+        assert_eq!(text.len(), 152);
+        assert!(text.ends_with(b"the lazy dog sleeps.\"}}]}"));
+
+        // zlib level 0: a single stored block
+        let stored = hex("7801010c00f3ff73746f72656420626c6f636b1f8004bd");
+        assert_eq!(
+            decompress(&stored, ContentEncoding::Deflate).expect("zlib"),
+            b"stored block"
+        );
+
+        // Raw DEFLATE sent as "deflate" is detected
+        assert_eq!(
+            decompress(&stored[2..stored.len() - 4], ContentEncoding::Deflate).expect("raw"),
+            b"stored block"
+        );
+        assert!(decompress(b"", ContentEncoding::Gzip).expect("empty").is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_and_corruption() {
+        let mut body = Vec::new();
+        for i in 0..4000 {
+            body.extend_from_slice(
+                format!("{{\"index\":{},\"text\":\"chunk {}\"}},", i, i % 7).as_bytes(),
+            );
+        }
+        let compressed = gzip(&body);
+        assert!(compressed.len() < body.len() / 4);
+        assert_eq!(
+            decompress(&compressed, ContentEncoding::Gzip).expect("round trip"),
+            body
+        );
+
+        // Two members decode as one body
+        let mut members = gzip(b"Hello, ");
+        members.extend(gzip(b"world"));
+        assert_eq!(
+            decompress(&members, ContentEncoding::Gzip).expect("members"),
+            b"Hello, world"
+        );
+
+        let mut corrupt = compressed.clone();
+        let at = corrupt.len() - 6;
+        corrupt[at] ^= 0xFF;
+        let err = decompress(&corrupt, ContentEncoding::Gzip).expect_err("crc");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = decompress(&compressed[..compressed.len() / 2], ContentEncoding::Gzip)
+            .expect_err("truncated");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_decompression_limits() {
+        // 2 MiB of zeros in a few KiB trips the default ratio limit
+        let bomb = gzip(&vec![0; 2 * 1024 * 1024]);
+        assert!(bomb.len() < 16 * 1024);
+        let err = decompress(&bomb, ContentEncoding::Gzip).expect_err("ratio");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().contains("expands more than 100 times"),
+            "{}",
+            err
+        );
+
+        let mut out = Vec::new();
+        let err = Decoder::new(bomb.as_slice(), ContentEncoding::Gzip)
+            .with_limits(64 * 1024, u64::MAX)
+            .read_to_end(&mut out)
+            .expect_err("size");
+        assert_eq!(err.to_string(), "Decompressed body exceeds 65536 bytes");
+        assert!(out.len() <= 64 * 1024);
+
+        out.clear();
+        Decoder::new(bomb.as_slice(), ContentEncoding::Gzip)
+            .with_limits(2 * 1024 * 1024, u64::MAX)
+            .read_to_end(&mut out)
+            .expect("within limits");
+        assert_eq!(out.len(), 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_streams_each_flushed_event() {
+        /// Hands out one chunk per read and records how many were taken.
+        struct Chunks(Vec<Vec<u8>>, usize);
+
+        impl Read for Chunks {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let Some(chunk) = self.0.get(self.1) else {
+                    return Ok(0);
+                };
+                buf[..chunk.len()].copy_from_slice(chunk);
+                self.1 += 1;
+                Ok(chunk.len())
+            }
+        }
+
+        // Two SSE events, the first ending in a sync flush
+        let chunks = vec![
+            hex("1f8b08000000000000034a492c49b452c8cf4be5e202000000ffff"),
+            hex("4b01334bcaf3b9b800a20d4a3f16000000"),
+        ];
+        let mut decoder = Decoder::new(Chunks(chunks, 0), ContentEncoding::Gzip);
+        let mut first = Vec::new();
+        let mut buf = [0u8; 64];
+        while first.len() < 11 {
+            let n = decoder.read(&mut buf).expect("first event");
+            assert!(n > 0);
+            first.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(first, b"data: one\n\n");
+        // ...without waiting for the second chunk
+        assert_eq!(decoder.bits.inner.1, 1);
+
+        let mut rest = Vec::new();
+        decoder.read_to_end(&mut rest).expect("rest");
+        assert_eq!(rest, b"data: two\n\n");
+    }
+}
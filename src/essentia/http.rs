@@ -1,195 +1,1774 @@
 use std::{
-    collections::HashMap,
-    io::{Read, Write},
-    net::TcpStream,
+    borrow::Cow,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use crate::{
+    errors::LlmError,
+    essentia::{
+        flate::{self, ContentEncoding, Decoder},
+        pool::{Pool, PoolKey},
+        proxy::{Proxy, ProxySetting},
+        tls::{ALPN_HTTP1, TlsConfig, TlsStream},
+        trace::HttpTrace,
+    },
+};
+
+/// Header fields in the order received. Names keep their original case but
+/// lookups ignore it, and repeated names are kept as separate entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field, keeping any existing fields with the same name.
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_string(), value.to_string()));
+    }
+
+    /// First value for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value for `name`, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Drops every field named `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the comma-separated list in `name` contains `token`, ignoring
+    /// case (e.g. `Transfer-Encoding: gzip, chunked`).
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name)
+            .flat_map(|v| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Response {
-    pub status:  u16,
-    pub headers: HashMap<String, String>,
-    pub body:    Vec<u8>,
-}
-
-pub fn get(url: &str) -> Result<Response, &'static str> {
-    let url = crate::essentia::url::Url::parse(url)?;
-    match url.scheme.as_str() {
-        "http" => get_http(&url),
-        "https" => get_https(&url),
-        _ => Err("Unsupported scheme"),
-    }
-}
-
-fn get_http(url: &crate::essentia::url::Url) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(80);
-    let mut stream = TcpStream::connect((host.as_str(), port)).map_err(|_| "Connect failed")?;
-    let request = format!(
-        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
-        url.path, host
-    );
-    stream.write_all(request.as_bytes()).map_err(|_| "Write failed")?;
-    let mut response = Vec::new();
-    stream.read_to_end(&mut response).map_err(|_| "Read failed")?;
-    parse_response(&response)
-}
-
-fn get_https(url: &crate::essentia::url::Url) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(443);
-    let mut tls_stream = crate::essentia::tls::tls_connect(&host, port)?;
-    let request = format!(
-        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
-        url.path, host
-    );
-    tls_stream.write(request.as_bytes())?;
-    let mut response = Vec::new();
-    let mut buffer = [0u8; 1024];
-    loop {
-        let n = tls_stream.read(&mut buffer)?;
+    pub status:    u16,
+    pub headers:   Headers,
+    pub body:      Vec<u8>,
+    /// Trailer fields of a chunked body.
+    pub trailers:  Headers,
+    /// Redirects followed to reach this response, oldest first.
+    pub redirects: Vec<Redirect>,
+}
+
+/// One followed redirect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// Status of the 3xx response.
+    pub status: u16,
+    /// Absolute URL the `Location` header resolved to.
+    pub url:    String,
+}
+
+/// A response whose body is read incrementally from the open connection.
+pub struct StreamingResponse {
+    pub status:    u16,
+    pub headers:   Headers,
+    /// Redirects followed to reach this response, oldest first.
+    pub redirects: Vec<Redirect>,
+    body:          Box<dyn Read + Send>,
+}
+
+impl StreamingResponse {
+    /// Reads the rest of the body into memory.
+    pub fn into_response(mut self) -> Result<Response, LlmError> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body).map_err(read_error)?;
+        Ok(Response {
+            status: self.status,
+            headers: self.headers,
+            body,
+            trailers: Headers::new(),
+            redirects: self.redirects,
+        })
+    }
+}
+
+impl Read for StreamingResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+/// Network deadlines for a request. `None` waits indefinitely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Limit on establishing the TCP connection (per resolved address).
+    pub connect: Option<Duration>,
+    /// Limit on any single read or write once connected.
+    pub read:    Option<Duration>,
+    /// Limit on the whole exchange, from connect until the response has been
+    /// read. Streaming responses only apply it until the head arrives.
+    pub total:   Option<Duration>,
+}
+
+impl Timeouts {
+    /// No deadlines at all.
+    pub const NONE: Self = Self { connect: None, read: None, total: None };
+
+    /// Uses `secs` for every deadline, as configured by
+    /// `LlmPluginConfig.timeout_secs`. Zero disables them.
+    pub fn from_secs(secs: u32) -> Self {
+        let limit = (secs > 0).then(|| Duration::from_secs(u64::from(secs)));
+        Self { connect: limit, read: limit, total: limit }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(30)),
+            read:    Some(Duration::from_secs(120)),
+            total:   None,
+        }
+    }
+}
+
+/// Plain or TLS transport, so every request has one read/write path.
+///
+/// Each read and write is bounded by the per-operation timeout and by what is
+/// left of the request deadline, whichever is sooner.
+pub(crate) struct Connection {
+    transport:    Transport,
+    read_timeout: Option<Duration>,
+    deadline:     Option<Instant>,
+}
+
+enum Transport {
+    Plain(TcpStream),
+    Tls(Box<TlsStream>),
+}
+
+impl Connection {
+    #[cfg(all(test, feature = "full-tests"))]
+    pub(crate) fn plain(stream: TcpStream) -> Self {
+        Self { transport: Transport::Plain(stream), read_timeout: None, deadline: None }
+    }
+
+    /// Connects to the origin of `url`, directly or through `proxy`, using
+    /// `tls_config` for HTTPS.
+    pub(crate) fn open(
+        url: &crate::essentia::url::Url, proxy: Option<&Proxy>, tls_config: &TlsConfig,
+        timeouts: &Timeouts, deadline: Option<Instant>,
+    ) -> Result<Self, LlmError> {
+        let host = url
+            .hostname
+            .clone()
+            .ok_or_else(|| LlmError::InvalidUrl("No hostname".to_string()))?;
+        let tls = match url.scheme.as_str() {
+            "http" => false,
+            "https" => true,
+            _ => {
+                return Err(LlmError::InvalidUrl(format!(
+                    "Unsupported scheme: {}",
+                    url.scheme
+                )));
+            },
+        };
+        let port = url.port.unwrap_or(if tls { 443 } else { 80 });
+
+        let stream = match proxy {
+            Some(proxy) => connect_tcp(&proxy.host, proxy.port, timeouts, deadline)?,
+            None => connect_tcp(&host, port, timeouts, deadline)?,
+        };
+        // Bound the proxy and TLS handshakes by the same limits as the request
+        let handshake_timeout =
+            remaining(timeouts.read, deadline).map_err(|e| LlmError::io("Handshake", e))?;
+        stream
+            .set_read_timeout(handshake_timeout)
+            .and_then(|()| stream.set_write_timeout(handshake_timeout))
+            .map_err(|e| LlmError::io("Handshake", e))?;
+        if let Some(proxy) = proxy.filter(|proxy| !proxy.forwards(url)) {
+            proxy.tunnel(&stream, &host, port)?;
+        }
+
+        let transport = if tls {
+            let stream = TlsStream::handshake_with(stream, &host, tls_config)?;
+            // Only HTTP/1.1 is spoken, whatever else the configuration offers
+            if let Some(protocol) = stream.alpn_protocol().filter(|p| *p != ALPN_HTTP1) {
+                return Err(LlmError::tls(&format!(
+                    "Server selected unsupported protocol {}",
+                    protocol
+                )));
+            }
+            Transport::Tls(Box::new(stream))
+        } else {
+            Transport::Plain(stream)
+        };
+        Ok(Self { transport, read_timeout: timeouts.read, deadline })
+    }
+
+    /// Applies a new per-operation timeout and overall deadline, e.g. when a
+    /// pooled connection is handed to another request.
+    pub(crate) fn set_limits(&mut self, read_timeout: Option<Duration>, deadline: Option<Instant>) {
+        self.read_timeout = read_timeout;
+        self.deadline = deadline;
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match &self.transport {
+            Transport::Plain(stream) => stream,
+            Transport::Tls(stream) => stream.tcp_stream(),
+        }
+    }
+
+    /// Sets the socket timeouts for the next operation.
+    fn arm(&self) -> io::Result<()> {
+        let timeout = remaining(self.read_timeout, self.deadline)?;
+        let socket = self.socket();
+        socket.set_read_timeout(timeout)?;
+        socket.set_write_timeout(timeout)
+    }
+
+    /// Whether an idle connection can still carry a request: the peer has not
+    /// closed it and, for plain HTTP, has not sent anything unsolicited. TLS
+    /// peers may legitimately send records (session tickets) while idle.
+    pub(crate) fn is_alive(&self) -> bool {
+        let socket = self.socket();
+        let tls = matches!(self.transport, Transport::Tls(_));
+        if socket.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut byte = [0u8; 1];
+        let alive = match socket.peek(&mut byte) {
+            Ok(0) => false,
+            Ok(_) => tls,
+            Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+        };
+        socket.set_nonblocking(false).is_ok() && alive
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.arm()?;
+        match &mut self.transport {
+            Transport::Plain(stream) => stream.read(buf),
+            Transport::Tls(stream) => stream.read(buf).map_err(tls_io_error),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.arm()?;
+        match &mut self.transport {
+            Transport::Plain(stream) => stream.write(buf),
+            Transport::Tls(stream) => stream.write(buf).map(|()| buf.len()).map_err(tls_io_error),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Plain(stream) => stream.flush(),
+            Transport::Tls(_) => Ok(()),
+        }
+    }
+}
+
+/// Wraps a TLS error for the `io` traits, keeping timeouts recognizable.
+fn tls_io_error(e: LlmError) -> io::Error {
+    match e {
+        LlmError::Timeout { .. } => io::Error::new(io::ErrorKind::TimedOut, e),
+        e => io::Error::other(e),
+    }
+}
+
+/// The tighter of `limit` and the time left until `deadline`; fails with
+/// `TimedOut` once the deadline has passed.
+fn remaining(limit: Option<Duration>, deadline: Option<Instant>) -> io::Result<Option<Duration>> {
+    let Some(deadline) = deadline else {
+        return Ok(limit);
+    };
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Request deadline exceeded",
+        ));
+    }
+    Ok(Some(limit.map_or(left, |limit| limit.min(left))))
+}
+
+/// Connects to the first reachable address of `host`, bounding each attempt
+/// by the connect timeout and the request deadline.
+fn connect_tcp(
+    host: &str, port: u16, timeouts: &Timeouts, deadline: Option<Instant>,
+) -> Result<TcpStream, LlmError> {
+    let address = format!("{}:{}", host, port);
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|source| LlmError::Connect { address: address.clone(), source })?;
+
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "No addresses resolved");
+    for addr in addrs {
+        let attempt = match remaining(timeouts.connect, deadline) {
+            Ok(Some(limit)) => TcpStream::connect_timeout(&addr, limit),
+            Ok(None) => TcpStream::connect(addr),
+            Err(e) => Err(e),
+        };
+        match attempt {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    if matches!(
+        last_error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    ) {
+        return Err(LlmError::Timeout { operation: "Connect" });
+    }
+    Err(LlmError::Connect { address, source: last_error })
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body as it is read.
+pub struct ChunkedReader<R> {
+    inner:     R,
+    remaining: usize,
+    done:      bool,
+    trailers:  Headers,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, remaining: 0, done: false, trailers: Headers::new() }
+    }
+
+    /// Trailer fields sent after the last chunk; empty until the body has
+    /// been read to the end.
+    pub fn trailers(&self) -> &Headers {
+        &self.trailers
+    }
+
+    pub fn into_trailers(self) -> Headers {
+        self.trailers
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        if self.inner.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Truncated chunked body",
+            ));
+        }
+        while matches!(line.last(), Some(b'\n' | b'\r')) {
+            line.pop();
+        }
+        String::from_utf8(line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid chunk framing"))
+    }
+
+    /// Reads the next chunk-size line, or the trailer section after the
+    /// zero-size chunk.
+    fn start_chunk(&mut self) -> io::Result<()> {
+        let line = self.read_line()?;
+        // Chunk extensions after ';' are ignored
+        let size = line.split(';').next().unwrap_or("").trim();
+        self.remaining = usize::from_str_radix(size, 16).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid chunk size: {}", size),
+            )
+        })?;
+        if self.remaining == 0 {
+            loop {
+                let trailer = self.read_line()?;
+                if trailer.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = trailer.split_once(':') {
+                    self.trailers.append(name.trim(), value.trim());
+                }
+            }
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.start_chunk()?;
+            if self.done {
+                return Ok(0);
+            }
+        }
+        let limit = buf.len().min(self.remaining);
+        let n = self.inner.read(&mut buf[..limit])?;
         if n == 0 {
-            break;
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Truncated chunk",
+            ));
         }
-        response.extend_from_slice(&buffer[..n]);
-    }
-    parse_response(&response)
-}
-
-pub fn post(url: &str, body: &str) -> Result<Response, &'static str> {
-    let url = crate::essentia::url::Url::parse(url)?;
-    match url.scheme.as_str() {
-        "http" => post_http(&url, body),
-        "https" => post_https(&url, body),
-        _ => Err("Unsupported scheme"),
-    }
-}
-
-fn post_http(url: &crate::essentia::url::Url, body: &str) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(80);
-    let mut stream = TcpStream::connect((host.as_str(), port)).map_err(|_| "Connect failed")?;
-    let request = format!(
-        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nContent-Type: \
-         application/json\r\nConnection: close\r\n\r\n{}",
-        url.path,
-        host,
-        body.len(),
-        body
-    );
-    stream.write_all(request.as_bytes()).map_err(|_| "Write failed")?;
-    let mut response = Vec::new();
-    stream.read_to_end(&mut response).map_err(|_| "Read failed")?;
-    parse_response(&response)
-}
-
-fn post_https(url: &crate::essentia::url::Url, body: &str) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(443);
-    let mut tls_stream = crate::essentia::tls::tls_connect(&host, port)?;
-    let request = format!(
-        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nContent-Type: \
-         application/json\r\nConnection: close\r\n\r\n{}",
-        url.path,
-        host,
-        body.len(),
-        body
-    );
-    tls_stream.write(request.as_bytes())?;
-    let mut response = Vec::new();
-    let mut buffer = [0u8; 1024];
-    loop {
-        let n = tls_stream.read(&mut buffer)?;
+        self.remaining -= n;
+        if self.remaining == 0 {
+            // CRLF that closes the chunk data
+            self.read_line()?;
+        }
+        Ok(n)
+    }
+}
+
+/// Reads a `Content-Length` body, failing if the peer closes early.
+struct LengthReader<R> {
+    inner:     R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for LengthReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let limit = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..limit])?;
         if n == 0 {
-            break;
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Truncated body: {} bytes missing", self.remaining),
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// HTTP request method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Patch => "PATCH",
+            Self::Delete => "DELETE",
+            Self::Options => "OPTIONS",
+        }
+    }
+
+    /// Whether repeating the request has the same effect as sending it once
+    /// (RFC 9110, section 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Self::Post | Self::Patch)
+    }
+}
+
+/// Redirects a [`Request`] follows unless told otherwise.
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Whether a header named `name` carries a credential: `Authorization`,
+/// `Proxy-Authorization`, `Cookie`, `api-key` or any `*-api-key`. These are
+/// dropped when a redirect changes origin and redacted from traces.
+pub(crate) fn is_credential_header(name: &str) -> bool {
+    let name = name.trim().to_ascii_lowercase();
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy-authorization" | "cookie" | "api-key"
+    ) || name.ends_with("-api-key")
+}
+
+/// An HTTP request, sent over plain TCP or TLS depending on the URL scheme.
+///
+/// Headers are sent exactly as given, so providers that authenticate with
+/// their own fields (`x-api-key`, `anthropic-version`, ...) need nothing
+/// special. `Host` and `Content-Length` are filled in unless already set.
+///
+/// [`send`](Self::send) reuses keep-alive connections from the global
+/// [`Pool`]; [`send_streaming`](Self::send_streaming) always uses a dedicated
+/// connection, closed when the body is dropped. Either may go through a proxy,
+/// see [`proxy`](Self::proxy).
+///
+/// Responses are requested with `Accept-Encoding: gzip, deflate` and decoded
+/// transparently, so callers always see the plain body.
+///
+/// Redirects are followed up to [`max_redirects`](Self::max_redirects) hops.
+/// 303 (and 301/302 after a POST) continue as GET without the body; 307 and
+/// 308 repeat the request as-is. Credentials are dropped once a redirect
+/// leaves the original scheme, host and port.
+///
+/// Exchanges are written to an [`HttpTrace`] when one is set with
+/// [`trace`](Self::trace) or named by `ESSENTIA_HTTP_TRACE`.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method:        Method,
+    url:           String,
+    headers:       Headers,
+    query:         Vec<(String, String)>,
+    body:          Vec<u8>,
+    keep_alive:    bool,
+    timeouts:      Timeouts,
+    proxy:         ProxySetting,
+    decompress:    bool,
+    compress_min:  Option<usize>,
+    max_redirects: usize,
+    tls:           TlsConfig,
+    trace:         Option<Arc<HttpTrace>>,
+}
+
+impl Request {
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_string(),
+            headers: Headers::new(),
+            query: Vec::new(),
+            body: Vec::new(),
+            keep_alive: true,
+            timeouts: Timeouts::default(),
+            proxy: ProxySetting::default(),
+            decompress: true,
+            compress_min: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            tls: TlsConfig::default(),
+            trace: HttpTrace::from_env(),
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Self::new(Method::Get, url)
+    }
+
+    pub fn post(url: &str) -> Self {
+        Self::new(Method::Post, url)
+    }
+
+    pub fn put(url: &str) -> Self {
+        Self::new(Method::Put, url)
+    }
+
+    pub fn patch(url: &str) -> Self {
+        Self::new(Method::Patch, url)
+    }
+
+    pub fn delete(url: &str) -> Self {
+        Self::new(Method::Delete, url)
+    }
+
+    /// Adds a header. Repeated names are sent as separate fields.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Sets `Authorization: Bearer <token>`.
+    pub fn bearer_auth(self, token: &str) -> Self {
+        self.header("Authorization", &format!("Bearer {}", token))
+    }
+
+    /// Appends a query parameter; names and values are percent-encoded.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the raw request body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Whether [`send`](Self::send) may take a pooled connection and return
+    /// it afterwards. Defaults to `true`; with `false` the request goes out on
+    /// a fresh connection with `Connection: close`.
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Replaces the connect, read and total deadlines.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets every deadline to `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeouts(Timeouts {
+            connect: Some(timeout),
+            read:    Some(timeout),
+            total:   Some(timeout),
+        })
+    }
+
+    /// Chooses the proxy. Defaults to [`ProxySetting::Environment`].
+    pub fn proxy(mut self, proxy: ProxySetting) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Whether to advertise `Accept-Encoding: gzip, deflate` and decode
+    /// compressed responses. Defaults to `true`; with `false` the body is
+    /// returned exactly as sent.
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Gzips bodies of at least `min_len` bytes and sends them with
+    /// `Content-Encoding: gzip`. Only for servers known to accept compressed
+    /// requests.
+    pub fn compress_body(mut self, min_len: usize) -> Self {
+        self.compress_min = Some(min_len);
+        self
+    }
+
+    /// Redirects to follow before giving up. Defaults to
+    /// [`DEFAULT_MAX_REDIRECTS`]; 0 returns 3xx responses as they are.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// TLS settings for HTTPS, such as the trusted roots and pins. Defaults
+    /// to [`TrustStore::default_roots`](crate::essentia::tls::TrustStore::default_roots)
+    /// without pins.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Traces exchanges to `trace`, or nowhere for `None`, instead of the
+    /// file named by `ESSENTIA_HTTP_TRACE`.
+    pub fn trace(mut self, trace: Option<Arc<HttpTrace>>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Sets a JSON body and its `Content-Type`.
+    pub fn json(self, body: &str) -> Self {
+        self.header("Content-Type", "application/json").body(body)
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Sends the request and reads the whole response.
+    pub fn send(&self) -> Result<Response, LlmError> {
+        let deadline = self.timeouts.total.map(|total| Instant::now() + total);
+        let mut request = Cow::Borrowed(self);
+        let mut redirects = Vec::new();
+        loop {
+            let mut response = request.send_once(deadline)?;
+            match request.redirect(response.status, &response.headers, redirects.len())? {
+                Some(next) => {
+                    redirects.push(Redirect { status: response.status, url: next.url.clone() });
+                    request = Cow::Owned(next);
+                },
+                None => {
+                    response.redirects = redirects;
+                    return Ok(response);
+                },
+            }
+        }
+    }
+
+    /// Sends the request and returns once the response head arrives, leaving
+    /// the body to be read incrementally.
+    pub fn send_streaming(&self) -> Result<StreamingResponse, LlmError> {
+        let deadline = self.timeouts.total.map(|total| Instant::now() + total);
+        let mut request = Cow::Borrowed(self);
+        let mut redirects = Vec::new();
+        loop {
+            let mut response = request.stream_once(deadline)?;
+            match request.redirect(response.status, &response.headers, redirects.len())? {
+                // Dropping the response closes its connection
+                Some(next) => {
+                    redirects.push(Redirect { status: response.status, url: next.url.clone() });
+                    request = Cow::Owned(next);
+                },
+                None => {
+                    response.redirects = redirects;
+                    return Ok(response);
+                },
+            }
+        }
+    }
+
+    /// One request/response exchange, without following redirects.
+    fn send_once(&self, deadline: Option<Instant>) -> Result<Response, LlmError> {
+        let url = self.parse_url()?;
+        let proxy = self.proxy.resolve(&url)?;
+        let keep_alive = self.keep_alive && !self.headers.has_token("Connection", "close");
+        if !keep_alive {
+            let mut connection =
+                Connection::open(&url, proxy.as_ref(), &self.tls, &self.timeouts, deadline)?;
+            return self
+                .exchange(&mut connection, &url, proxy.as_ref(), false)
+                .map(|(r, _)| r)
+                .map_err(|f| *f.error);
+        }
+
+        let key = PoolKey::from_url(&url, proxy.as_ref(), &self.tls)?;
+        loop {
+            let mut pooled = Pool::global().checkout(&key, deadline, || {
+                Connection::open(&url, proxy.as_ref(), &self.tls, &self.timeouts, deadline)
+            })?;
+            let Some(connection) = pooled.connection() else {
+                return Err(LlmError::Protocol(
+                    "Pooled connection unavailable".to_string(),
+                ));
+            };
+            connection.set_limits(self.timeouts.read, deadline);
+            match self.exchange(connection, &url, proxy.as_ref(), true) {
+                Ok((response, reusable)) => {
+                    if reusable {
+                        pooled.recycle();
+                    }
+                    return Ok(response);
+                },
+                // The server may close an idle connection just as we reuse it;
+                // nothing was processed, so try again on another connection
+                Err(failure) if pooled.is_reused() && failure.replayable => {},
+                Err(failure) => return Err(*failure.error),
+            }
+        }
+    }
+
+    /// Streaming counterpart of [`send_once`](Self::send_once).
+    fn stream_once(&self, deadline: Option<Instant>) -> Result<StreamingResponse, LlmError> {
+        let url = self.parse_url()?;
+        let proxy = self.proxy.resolve(&url)?;
+        let mut connection =
+            Connection::open(&url, proxy.as_ref(), &self.tls, &self.timeouts, deadline)?;
+        let message = self.message(&url, proxy.as_ref(), false);
+        let traced = self.trace.as_ref().map(|trace| (trace, trace.request(&message, &self.body)));
+        let head = write_message(&mut connection, &message).and_then(|()| {
+            let mut reader = BufReader::new(connection);
+            read_final_head(&mut reader).map(|head| (head, reader))
+        });
+        let (head, mut reader) = match head {
+            Ok(head) => head,
+            Err(e) => {
+                if let Some((trace, exchange)) = traced {
+                    trace.error(exchange, &e);
+                }
+                return Err(e);
+            },
+        };
+        // A stream may legitimately outlast the total deadline; from here on
+        // only the per-read timeout applies
+        reader.get_mut().set_limits(self.timeouts.read, None);
+        let mut body: Box<dyn Read + Send> = match self.framing(&head)? {
+            BodyFraming::Empty => Box::new(io::empty()),
+            BodyFraming::Chunked => Box::new(ChunkedReader::new(reader)),
+            BodyFraming::Length(length) => {
+                Box::new(LengthReader { inner: reader, remaining: length })
+            },
+            BodyFraming::UntilClose => Box::new(reader),
+        };
+        let mut headers = head.headers;
+        if let Some(encoding) = self.content_encoding(&headers) {
+            strip_encoding(&mut headers);
+            body = Box::new(Decoder::new(body, encoding));
+        }
+        if let Some((trace, exchange)) = traced {
+            trace.response(exchange, head.status, &headers, None);
+            body = Box::new(TracedBody { inner: body, trace: Arc::clone(trace), exchange });
+        }
+        Ok(StreamingResponse { status: head.status, headers, redirects: Vec::new(), body })
+    }
+
+    /// The request to send next if `status` is a redirect to follow, after
+    /// `hops` redirects so far.
+    fn redirect(
+        &self, status: u16, headers: &Headers, hops: usize,
+    ) -> Result<Option<Request>, LlmError> {
+        if self.max_redirects == 0 || !matches!(status, 301 | 302 | 303 | 307 | 308) {
+            return Ok(None);
+        }
+        let Some(location) = headers.get("Location") else {
+            return Ok(None);
+        };
+        if hops >= self.max_redirects {
+            return Err(LlmError::Protocol(format!(
+                "Too many redirects (limit {})",
+                self.max_redirects
+            )));
+        }
+
+        let from = self.full_url();
+        let to = crate::essentia::url::resolve(&from, location);
+        let mut next = self.clone();
+        next.url = to;
+        next.query.clear();
+        let target = next.parse_url()?;
+        if !matches!(target.scheme.as_str(), "http" | "https") {
+            return Err(LlmError::InvalidUrl(format!(
+                "Redirect to unsupported scheme: {}",
+                next.url
+            )));
+        }
+
+        let to_get = status == 303 || (matches!(status, 301 | 302) && self.method == Method::Post);
+        if to_get && self.method != Method::Head {
+            next.method = Method::Get;
+            next.body.clear();
+            for name in ["Content-Type", "Content-Length", "Content-Encoding"] {
+                next.headers.remove(name);
+            }
+        }
+        if !crate::essentia::url::same_origin(&self.parse_url()?, &target) {
+            next.headers.entries.retain(|(name, _)| !is_credential_header(name));
+        }
+        Ok(Some(next))
+    }
+
+    /// The URL with query parameters appended.
+    fn full_url(&self) -> String {
+        let mut url = self.url.clone();
+        append_query(&mut url, &self.query);
+        url
+    }
+
+    fn parse_url(&self) -> Result<crate::essentia::url::Url, LlmError> {
+        crate::essentia::url::Url::parse(&self.url).map_err(|e| LlmError::InvalidUrl(e.to_string()))
+    }
+
+    /// Writes the request and reads the response, tracing both. On success,
+    /// also reports whether the connection can carry another request.
+    fn exchange(
+        &self, connection: &mut Connection, url: &crate::essentia::url::Url, proxy: Option<&Proxy>,
+        keep_alive: bool,
+    ) -> Result<(Response, bool), ExchangeFailure> {
+        let message = self.message(url, proxy, keep_alive);
+        let Some(trace) = &self.trace else {
+            return self.transfer(connection, &message, keep_alive);
+        };
+        let exchange = trace.request(&message, &self.body);
+        let result = self.transfer(connection, &message, keep_alive);
+        match &result {
+            Ok((response, _)) => trace.response(
+                exchange,
+                response.status,
+                &response.headers,
+                Some(&response.body),
+            ),
+            Err(failure) => trace.error(exchange, &failure.error),
+        }
+        result
+    }
+
+    /// The untraced part of [`exchange`](Self::exchange).
+    fn transfer(
+        &self, connection: &mut Connection, message: &[u8], keep_alive: bool,
+    ) -> Result<(Response, bool), ExchangeFailure> {
+        let after_response =
+            |error| ExchangeFailure { error: Box::new(error), replayable: false };
+
+        write_message(connection, message).map_err(|error| {
+            let replayable = !matches!(error, LlmError::Timeout { .. });
+            ExchangeFailure { error: Box::new(error), replayable }
+        })?;
+        let mut reader = BufReader::new(connection);
+        // A closed idle connection fails before any of the response arrives.
+        // The request was still delivered, so only idempotent methods may be
+        // sent again; a timeout may mean the server is still working on it.
+        let unanswered = |source: io::Error| {
+            let replayable = self.method.is_idempotent()
+                && matches!(
+                    source.kind(),
+                    io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::BrokenPipe
+                );
+            ExchangeFailure { error: Box::new(LlmError::io("HTTP read", source)), replayable }
+        };
+        match reader.fill_buf() {
+            Ok([]) => {
+                return Err(unanswered(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed before response head",
+                )));
+            },
+            Ok(_) => {},
+            Err(e) => return Err(unanswered(e)),
+        }
+        let head = read_final_head(&mut reader).map_err(after_response)?;
+        let framing = self.framing(&head).map_err(after_response)?;
+        let (mut body, trailers) = read_body(&mut reader, framing).map_err(after_response)?;
+
+        let reusable = keep_alive
+            && head.keeps_alive()
+            && framing != BodyFraming::UntilClose
+            && reader.buffer().is_empty();
+        let mut headers = head.headers;
+        if let Some(encoding) = self.content_encoding(&headers)
+            && !body.is_empty()
+        {
+            body = flate::decompress(&body, encoding).map_err(|e| {
+                after_response(LlmError::Protocol(format!(
+                    "Invalid {} body: {}",
+                    encoding.as_str(),
+                    e
+                )))
+            })?;
+            strip_encoding(&mut headers);
+        }
+        let response =
+            Response { status: head.status, headers, body, trailers, redirects: Vec::new() };
+        Ok((response, reusable))
+    }
+
+    /// The coding to undo on a response, if decompression is enabled.
+    fn content_encoding(&self, headers: &Headers) -> Option<ContentEncoding> {
+        if !self.decompress {
+            return None;
+        }
+        headers.get("Content-Encoding").and_then(ContentEncoding::from_header)
+    }
+
+    /// The body as sent: gzipped when it reaches the
+    /// [`compress_body`](Self::compress_body) threshold.
+    fn encoded_body(&self) -> Cow<'_, [u8]> {
+        match self.compress_min {
+            Some(min_len)
+                if self.body.len() >= min_len && !self.headers.contains("Content-Encoding") =>
+            {
+                Cow::Owned(flate::gzip(&self.body))
+            },
+            _ => Cow::Borrowed(&self.body),
+        }
+    }
+
+    fn framing(&self, head: &Head) -> Result<BodyFraming, LlmError> {
+        if self.method == Method::Head {
+            Ok(BodyFraming::Empty)
+        } else {
+            body_framing(head.status, &head.headers)
+        }
+    }
+
+    /// Target as sent on the request line: path plus encoded query, prefixed
+    /// with the origin when `absolute` (requests forwarded by a proxy).
+    fn target(&self, url: &crate::essentia::url::Url, absolute: bool) -> String {
+        let mut target = url.path.clone();
+        if absolute {
+            let host = url.hostname.as_deref().unwrap_or("");
+            target = match url.port {
+                Some(port) => format!("{}://{}:{}{}", url.scheme, host, port, target),
+                None => format!("{}://{}{}", url.scheme, host, target),
+            };
+        }
+        append_query(&mut target, &self.query);
+        target
+    }
+
+    /// Serializes the request head and body.
+    fn message(
+        &self, url: &crate::essentia::url::Url, proxy: Option<&Proxy>, keep_alive: bool,
+    ) -> Vec<u8> {
+        let host = url.hostname.clone().unwrap_or_default();
+        let forward = proxy.filter(|proxy| proxy.forwards(url));
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\n",
+            self.method.as_str(),
+            self.target(url, forward.is_some())
+        );
+        if !self.headers.contains("Host") {
+            match url.port {
+                Some(port) => head.push_str(&format!("Host: {}:{}\r\n", host, port)),
+                None => head.push_str(&format!("Host: {}\r\n", host)),
+            }
+        }
+        for (name, value) in self.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if let Some(authorization) = forward.and_then(Proxy::authorization) {
+            head.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        }
+        if self.decompress && !self.headers.contains("Accept-Encoding") {
+            head.push_str(&format!("Accept-Encoding: {}\r\n", flate::ACCEPT_ENCODING));
+        }
+        let body = self.encoded_body();
+        if matches!(body, Cow::Owned(_)) {
+            head.push_str("Content-Encoding: gzip\r\n");
         }
-        response.extend_from_slice(&buffer[..n]);
-    }
-    parse_response(&response)
-}
-
-#[allow(unused)]
-pub fn post_with_auth(url: &str, auth: &str, body: &str) -> Result<Response, &'static str> {
-    let url = crate::essentia::url::Url::parse(url)?;
-    match url.scheme.as_str() {
-        "http" => post_with_auth_http(&url, auth, body),
-        "https" => post_with_auth_https(&url, auth, body),
-        _ => Err("Unsupported scheme"),
-    }
-}
-
-#[allow(unused)]
-fn post_with_auth_http(
-    url: &crate::essentia::url::Url, auth: &str, body: &str,
-) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(80);
-    let mut stream = TcpStream::connect((host.as_str(), port)).map_err(|_| "Connect failed")?;
-    let request = format!(
-        "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: {}\r\nContent-Length: {}\r\nContent-Type: \
-         application/json\r\nConnection: close\r\n\r\n{}",
-        url.path,
-        host,
-        auth,
-        body.len(),
-        body
-    );
-    stream.write_all(request.as_bytes()).map_err(|_| "Write failed")?;
-    let mut response = Vec::new();
-    stream.read_to_end(&mut response).map_err(|_| "Read failed")?;
-    parse_response(&response)
-}
-
-#[allow(unused)]
-fn post_with_auth_https(
-    url: &crate::essentia::url::Url, auth: &str, body: &str,
-) -> Result<Response, &'static str> {
-    let host = url.hostname.clone().ok_or("No hostname")?;
-    let port = url.port.unwrap_or(443);
-    let mut tls_stream = crate::essentia::tls::tls_connect(&host, port)?;
-    let request = format!(
-        "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: {}\r\nContent-Length: {}\r\nContent-Type: \
-         application/json\r\nConnection: close\r\n\r\n{}",
-        url.path,
-        host,
-        auth,
-        body.len(),
-        body
-    );
-    tls_stream.write(request.as_bytes())?;
-    let mut response = Vec::new();
-    let mut buffer = [0u8; 1024];
+        let sends_body =
+            !body.is_empty() || matches!(self.method, Method::Post | Method::Put | Method::Patch);
+        if sends_body && !self.headers.contains("Content-Length") {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        // Keep-alive is the HTTP/1.1 default
+        if !keep_alive && !self.headers.contains("Connection") {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str("\r\n");
+
+        let mut message = head.into_bytes();
+        message.extend_from_slice(&body);
+        message
+    }
+}
+
+/// Appends percent-encoded `name=value` pairs to a URL or request target.
+fn append_query(target: &mut String, query: &[(String, String)]) {
+    for (name, value) in query {
+        target.push(if target.contains('?') { '&' } else { '?' });
+        target.push_str(&crate::essentia::url::encode_component(name));
+        target.push('=');
+        target.push_str(&crate::essentia::url::encode_component(value));
+    }
+}
+
+/// Drops the fields that described a body's encoded form once it has been
+/// decoded.
+fn strip_encoding(headers: &mut Headers) {
+    headers.remove("Content-Encoding");
+    headers.remove("Content-Length");
+}
+
+/// A failed [`Request::exchange`], noting whether the request may be sent
+/// again on another connection without risking it being processed twice.
+struct ExchangeFailure {
+    error:      Box<LlmError>,
+    replayable: bool,
+}
+
+/// A streamed body that copies what is read to the trace.
+struct TracedBody {
+    inner:    Box<dyn Read + Send>,
+    trace:    Arc<HttpTrace>,
+    exchange: u64,
+}
+
+impl Read for TracedBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.trace.body_chunk(self.exchange, &buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+fn write_message(connection: &mut Connection, message: &[u8]) -> Result<(), LlmError> {
+    connection
+        .write_all(message)
+        .and_then(|()| connection.flush())
+        .map_err(|e| LlmError::io("HTTP write", e))
+}
+
+pub fn get(url: &str) -> Result<Response, LlmError> {
+    Request::get(url).send()
+}
+
+pub fn post(url: &str, body: &str) -> Result<Response, LlmError> {
+    Request::post(url).json(body).send()
+}
+
+pub fn post_with_auth(url: &str, auth: &str, body: &str) -> Result<Response, LlmError> {
+    Request::post(url).header("Authorization", auth).json(body).send()
+}
+
+pub fn parse_response(data: &[u8]) -> Result<Response, LlmError> {
+    read_response(&mut &data[..])
+}
+
+/// Reads one response from `reader`, framing the body by `Transfer-Encoding`
+/// or `Content-Length` and falling back to reading until close. The body is
+/// kept as raw bytes.
+pub fn read_response<R: BufRead>(reader: &mut R) -> Result<Response, LlmError> {
+    let head = read_final_head(reader)?;
+    let (body, trailers) = read_body(reader, body_framing(head.status, &head.headers)?)?;
+    Ok(Response {
+        status: head.status,
+        headers: head.headers,
+        body,
+        trailers,
+        redirects: Vec::new(),
+    })
+}
+
+/// Reads a body framed by `framing`, returning it with any chunked trailers.
+fn read_body<R: BufRead>(
+    reader: &mut R, framing: BodyFraming,
+) -> Result<(Vec<u8>, Headers), LlmError> {
+    let mut body = Vec::new();
+    let trailers = match framing {
+        BodyFraming::Empty => Headers::new(),
+        BodyFraming::Chunked => {
+            let mut chunked = ChunkedReader::new(reader);
+            chunked.read_to_end(&mut body).map_err(read_error)?;
+            chunked.into_trailers()
+        },
+        BodyFraming::Length(length) => {
+            reader.take(length).read_to_end(&mut body).map_err(read_error)?;
+            if (body.len() as u64) < length {
+                return Err(LlmError::Protocol(format!(
+                    "Truncated body: expected {} bytes, got {}",
+                    length,
+                    body.len()
+                )));
+            }
+            Headers::new()
+        },
+        BodyFraming::UntilClose => {
+            reader.read_to_end(&mut body).map_err(read_error)?;
+            Headers::new()
+        },
+    };
+    Ok((body, trailers))
+}
+
+/// How the length of a response body is determined (RFC 9112 section 6.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFraming {
+    Empty,
+    Chunked,
+    Length(u64),
+    UntilClose,
+}
+
+fn body_framing(status: u16, headers: &Headers) -> Result<BodyFraming, LlmError> {
+    if matches!(status, 100..=199 | 204 | 304) {
+        return Ok(BodyFraming::Empty);
+    }
+    // Transfer-Encoding overrides Content-Length
+    if headers.contains("Transfer-Encoding") {
+        return Ok(if headers.has_token("Transfer-Encoding", "chunked") {
+            BodyFraming::Chunked
+        } else {
+            BodyFraming::UntilClose
+        });
+    }
+
+    let mut length = None;
+    for value in headers.get_all("Content-Length").flat_map(|v| v.split(',')) {
+        let value: u64 = value
+            .trim()
+            .parse()
+            .map_err(|_| LlmError::Protocol(format!("Invalid Content-Length: {}", value)))?;
+        if length.is_some_and(|l| l != value) {
+            return Err(LlmError::Protocol(
+                "Conflicting Content-Length headers".to_string(),
+            ));
+        }
+        length = Some(value);
+    }
+    Ok(length.map_or(BodyFraming::UntilClose, BodyFraming::Length))
+}
+
+/// Maps a body read failure; framing violations are protocol errors rather
+/// than transport ones.
+fn read_error(e: io::Error) -> LlmError {
+    if e.kind() == io::ErrorKind::InvalidData {
+        LlmError::Protocol(e.to_string())
+    } else {
+        LlmError::io("HTTP read", e)
+    }
+}
+
+/// POSTs a JSON body and returns as soon as the response head arrives, leaving
+/// the body to be read incrementally (e.g. by an SSE reader).
+pub fn post_stream(
+    url: &str, auth: Option<&str>, body: &str, timeouts: &Timeouts,
+) -> Result<StreamingResponse, LlmError> {
+    let mut request = Request::post(url).header("Accept", "text/event-stream").timeouts(*timeouts);
+    if let Some(auth) = auth {
+        request = request.header("Authorization", auth);
+    }
+    request.json(body).send_streaming()
+}
+
+/// Status line and header fields of a response.
+pub(crate) struct Head {
+    pub(crate) status:  u16,
+    pub(crate) headers: Headers,
+    /// Sent as HTTP/1.0, where connections close unless asked otherwise.
+    http10:             bool,
+}
+
+impl Head {
+    fn keeps_alive(&self) -> bool {
+        if self.http10 {
+            self.headers.has_token("Connection", "keep-alive")
+        } else {
+            !self.headers.has_token("Connection", "close")
+        }
+    }
+}
+
+/// Reads the head of the final response, skipping interim 1xx responses such
+/// as `100 Continue`.
+pub(crate) fn read_final_head<R: BufRead>(reader: &mut R) -> Result<Head, LlmError> {
     loop {
-        let n = tls_stream.read(&mut buffer)?;
-        if n == 0 {
-            break;
+        let head = read_head(reader)?;
+        // 101 Switching Protocols is final
+        if !(100..200).contains(&head.status) || head.status == 101 {
+            return Ok(head);
         }
-        response.extend_from_slice(&buffer[..n]);
     }
-    parse_response(&response)
 }
 
-fn parse_response(data: &[u8]) -> Result<Response, &'static str> {
-    let text = std::str::from_utf8(data).map_err(|_| "Invalid UTF8")?;
-    let mut lines = text.lines();
-    let status_line = lines.next().ok_or("No status line")?;
-    let status: u16 = status_line
-        .split_whitespace()
-        .nth(1)
-        .ok_or("No status")?
-        .parse()
-        .map_err(|_| "Invalid status")?;
-    let mut headers = HashMap::new();
-    let mut body_start = 0;
-    for (i, line) in lines.enumerate() {
+/// Reads the status line and headers, leaving the reader at the body.
+fn read_head<R: BufRead>(reader: &mut R) -> Result<Head, LlmError> {
+    let mut read_line = || -> Result<String, LlmError> {
+        let mut line = Vec::new();
+        let n = reader.read_until(b'\n', &mut line).map_err(|e| LlmError::io("HTTP read", e))?;
+        if n == 0 {
+            // Typically a keep-alive connection the server has since closed
+            return Err(LlmError::io(
+                "HTTP read",
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed before response head",
+                ),
+            ));
+        }
+        while matches!(line.last(), Some(b'\n' | b'\r')) {
+            line.pop();
+        }
+        String::from_utf8(line)
+            .map_err(|_| LlmError::Protocol("Response head is not valid UTF-8".to_string()))
+    };
+
+    let status_line = read_line()?;
+    let mut parts = status_line.split_whitespace();
+    let version = parts.next().unwrap_or("");
+    let status = match parts.next() {
+        Some(code) if version.starts_with("HTTP/1.") && code.len() == 3 => code.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| LlmError::Protocol(format!("Invalid status line: {}", status_line)))?;
+
+    let mut headers = Headers::new();
+    loop {
+        let line = read_line()?;
         if line.is_empty() {
-            body_start = i + 1;
             break;
         }
-        if let Some(colon) = line.find(':') {
-            let key = line[..colon].trim().to_string();
-            let value = line[colon + 1..].trim().to_string();
-            headers.insert(key, value);
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| LlmError::Protocol(format!("Malformed header line: {}", line)))?;
+        headers.append(name.trim(), value.trim());
+    }
+    Ok(Head { status, headers, http10: version == "HTTP/1.0" })
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use super::*;
+
+    /// Accepts one connection, returns the raw request and replies with
+    /// `response`.
+    fn serve_once(response: &'static [u8]) -> (String, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let request = read_request(&stream);
+            stream.write_all(response).expect("write");
+            request
+        });
+        (format!("http://{}", addr), handle)
+    }
+
+    /// Replies to one connection per entry of `responses`, in order, and
+    /// returns the raw requests.
+    fn serve_each(responses: Vec<String>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let handle = thread::spawn(move || {
+            responses
+                .iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().expect("accept");
+                    let request = read_request(&stream);
+                    stream.write_all(response.as_bytes()).expect("write");
+                    String::from_utf8(request).expect("utf8")
+                })
+                .collect()
+        });
+        (format!("http://{}", addr), handle)
+    }
+
+    /// Reads one request head and its `Content-Length` body.
+    fn read_request(stream: &TcpStream) -> Vec<u8> {
+        let mut request = Vec::new();
+        let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("read");
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                length = value.trim().parse().expect("length");
+            }
+            request.extend_from_slice(line.as_bytes());
+            if line == "\r\n" {
+                break;
+            }
         }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).expect("body");
+        request.extend_from_slice(&body);
+        request
+    }
+
+    #[test]
+    fn test_content_length_binary_body() {
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\ncontent-length: 6\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0x89, b'P', 0xFF, b'\r', b'\n', 0x00]);
+        raw.extend_from_slice(b"ignored");
+
+        let response = parse_response(&raw).expect("response");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers.get("CONTENT-TYPE"), Some("image/png"));
+        assert_eq!(response.body, [0x89, b'P', 0xFF, b'\r', b'\n', 0x00]);
+    }
+
+    #[test]
+    fn test_chunked_body_with_trailers_and_repeated_headers() {
+        let raw = concat!(
+            "HTTP/1.1 100 Continue\r\n\r\n",
+            "HTTP/1.1 200 OK\r\n",
+            "Set-Cookie: a=1\r\n",
+            "Transfer-Encoding: gzip\r\n",
+            "set-cookie: b=2\r\n",
+            "Transfer-Encoding: Chunked\r\n",
+            "\r\n",
+            "5;ext=1\r\nline1\r\n",
+            "7\r\n\r\nline2\r\n",
+            "0\r\n",
+            "X-Checksum: abc\r\n",
+            "\r\n",
+        );
+
+        let response = parse_response(raw.as_bytes()).expect("response");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"line1\r\nline2");
+        assert_eq!(
+            response.headers.get_all("SET-COOKIE").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert!(response.headers.has_token("transfer-encoding", "chunked"));
+        assert_eq!(response.trailers.get("x-checksum"), Some("abc"));
+    }
+
+    #[test]
+    fn test_malformed_framing_is_rejected() {
+        let truncated = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort";
+        let err = parse_response(truncated).expect_err("truncated");
+        assert_eq!(err.kind(), crate::errors::ErrorKind::Protocol);
+
+        let conflicting = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab";
+        assert!(parse_response(conflicting).is_err());
+
+        let bad_chunk = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        let err = parse_response(bad_chunk).expect_err("bad chunk");
+        assert_eq!(err.kind(), crate::errors::ErrorKind::Protocol);
+
+        assert!(parse_response(b"garbage\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_no_body_statuses() {
+        let response =
+            parse_response(b"HTTP/1.1 204 No Content\r\nContent-Length: 3\r\n\r\n").expect("204");
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_request_builder() {
+        let (base, server) = serve_once(b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok");
+        let response = Request::put(&format!("{}/v1/items", base))
+            .header("x-api-key", "secret")
+            .header("anthropic-version", "2023-06-01")
+            .query("q", "a b&c")
+            .query("page", "2")
+            .body(vec![0u8, 1, 2])
+            .send()
+            .expect("response");
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, b"ok");
+
+        let request = server.join().expect("server");
+        let text = String::from_utf8_lossy(&request);
+        assert!(text.starts_with("PUT /v1/items?q=a%20b%26c&page=2 HTTP/1.1\r\n"));
+        assert!(text.contains("\r\nx-api-key: secret\r\n"));
+        assert!(text.contains("\r\nanthropic-version: 2023-06-01\r\n"));
+        assert!(text.contains("\r\nContent-Length: 3\r\n"));
+        assert!(request.ends_with(&[b'\n', 0, 1, 2]));
+    }
+
+    #[test]
+    fn test_delete_without_body() {
+        let (base, server) = serve_once(b"HTTP/1.1 204 No Content\r\n\r\n");
+        let response = Request::delete(&format!("{}/v1/items/7", base)).send().expect("response");
+        assert_eq!(response.status, 204);
+
+        let request = server.join().expect("server");
+        let text = String::from_utf8_lossy(&request);
+        assert!(text.starts_with("DELETE /v1/items/7 HTTP/1.1\r\n"));
+        assert!(!text.contains("Content-Length"));
+    }
+
+    #[test]
+    fn test_trace_redacts_credentials() {
+        let path = std::env::temp_dir().join(format!("essentia-http-{}.trace", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let trace = HttpTrace::open(&path).expect("trace");
+        let body = "{\"ok\":true}";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (base, server) = serve_each(vec![response.clone(), response]);
+        let request = Request::post(&format!("{}/v1/chat", base))
+            .bearer_auth("sk-secret")
+            .header("x-api-key", "sk-secret")
+            .query("key", "sk-secret")
+            .json("{\"api_key\":\"sk-secret\",\"model\":\"grok\"}")
+            .keep_alive(false)
+            .trace(Some(trace));
+        request.send().expect("response");
+        let mut streamed = String::new();
+        request
+            .send_streaming()
+            .expect("stream")
+            .read_to_string(&mut streamed)
+            .expect("read");
+        server.join().expect("server");
+
+        let written = std::fs::read_to_string(&path).expect("read trace");
+        let _ = std::fs::remove_file(&path);
+        assert!(!written.contains("sk-secret"), "{}", written);
+        assert!(written.contains("POST /v1/chat?key=[redacted] HTTP/1.1\n"));
+        assert!(written.contains("Authorization: [redacted]\nx-api-key: [redacted]\n"));
+        assert!(written.contains("{\"api_key\":\"[redacted]\",\"model\":\"grok\"}"));
+        assert_eq!(written.matches("\n\n{\"ok\":true}\n\n").count(), 2);
+        assert!(written.contains("[body streamed]"));
+    }
+
+    /// `data: one\n\ndata: two\n\n`, gzipped with a flush between events.
+    const GZIP_RESPONSE: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 44\r\n\r\n\
+        \x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\x4a\x49\x2c\x49\xb4\x52\
+        \xc8\xcf\x4b\xe5\xe2\x02\x00\x00\x00\xff\xff\x4b\x01\x33\x4b\xca\
+        \xf3\xb9\xb8\x00\xa2\x0d\x4a\x3f\x16\x00\x00\x00";
+
+    #[test]
+    fn test_gzip_response_and_request_body() {
+        let (base, server) = serve_once(GZIP_RESPONSE);
+        let body = "{\"input\":\"batch\"}".repeat(100);
+        let response = Request::post(&format!("{}/v1/batch", base))
+            .compress_body(512)
+            .json(&body)
+            .send()
+            .expect("response");
+        assert_eq!(response.body, b"data: one\n\ndata: two\n\n");
+        assert!(!response.headers.contains("Content-Encoding"));
+
+        let request = server.join().expect("server");
+        let text = String::from_utf8_lossy(&request);
+        assert!(text.contains("\r\nAccept-Encoding: gzip, deflate\r\n"));
+        assert!(text.contains("\r\nContent-Encoding: gzip\r\n"));
+        let split = request.windows(4).position(|w| w == b"\r\n\r\n").expect("head") + 4;
+        assert!(request.len() - split < body.len() / 4);
+        assert_eq!(
+            flate::decompress(&request[split..], ContentEncoding::Gzip).expect("gzip"),
+            body.as_bytes()
+        );
+
+        // Streaming bodies are decoded as they arrive
+        let (base, _server) = serve_once(GZIP_RESPONSE);
+        let mut stream = Request::get(&base).send_streaming().expect("stream");
+        let mut text = String::new();
+        stream.read_to_string(&mut text).expect("read");
+        assert_eq!(text, "data: one\n\ndata: two\n\n");
+
+        // Opting out returns the encoded bytes untouched
+        let (base, _server) = serve_once(GZIP_RESPONSE);
+        let raw = Request::get(&base).decompress(false).send().expect("raw");
+        assert_eq!(raw.headers.get("Content-Encoding"), Some("gzip"));
+        assert_eq!(raw.body.len(), 44);
+    }
+
+    #[test]
+    fn test_redirects() {
+        let redirect = |status: &str, location: &str| {
+            format!(
+                "HTTP/1.1 {}\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status, location
+            )
+        };
+        let (other, other_server) = serve_each(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string(),
+        ]);
+        let (base, server) = serve_each(vec![
+            redirect("307 Temporary Redirect", "next?step=2"),
+            redirect("303 See Other", &format!("{}/done", other)),
+        ]);
+
+        let response = Request::post(&format!("{}/v1/start", base))
+            .query("step", "1")
+            .header("Authorization", "Bearer secret")
+            .header("api-key", "azure-secret")
+            .header("X-Goog-Api-Key", "google-secret")
+            .json("{}")
+            .send()
+            .expect("response");
+        assert_eq!(response.body, b"ok");
+        assert_eq!(response.redirects, vec![
+            Redirect { status: 307, url: format!("{}/v1/next?step=2", base) },
+            Redirect { status: 303, url: format!("{}/done", other) },
+        ]);
+
+        // 307 repeats the POST with its body and credentials
+        let requests = server.join().expect("server");
+        assert!(requests[0].starts_with("POST /v1/start?step=1 HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with("POST /v1/next?step=2 HTTP/1.1\r\n"));
+        assert!(requests[1].contains("\r\nAuthorization: Bearer secret\r\n"));
+        assert!(requests[1].ends_with("\r\n\r\n{}"));
+
+        // 303 to another origin continues as a bare GET
+        let requests = other_server.join().expect("server");
+        assert!(requests[0].starts_with("GET /done HTTP/1.1\r\n"));
+        assert!(!requests[0].contains("Authorization"));
+        assert!(!requests[0].contains("secret"));
+        assert!(!requests[0].contains("Content-Type"));
+        assert!(requests[0].ends_with("\r\n\r\n"));
+
+        let (base, _server) = serve_each(vec![
+            redirect("302 Found", "/a"),
+            redirect("302 Found", "/b"),
+        ]);
+        let err = Request::get(&base).max_redirects(1).send().expect_err("limit");
+        assert_eq!(
+            err.to_string(),
+            "HTTP protocol error: Too many redirects (limit 1)"
+        );
+
+        let (base, _server) = serve_each(vec![redirect("301 Moved Permanently", "/a")]);
+        let response = Request::get(&base).max_redirects(0).send().expect("response");
+        assert_eq!(response.status, 301);
+        assert!(response.redirects.is_empty());
+    }
+
+    #[test]
+    fn test_keep_alive_reuses_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        // A single accepted connection serves both requests
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept");
+            let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+            let mut writer = stream;
+            for reply in ["one", "two"] {
+                let mut line = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).expect("read");
+                    assert!(!line.starts_with("Connection: close"));
+                }
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n{}", reply);
+                writer.write_all(response.as_bytes()).expect("write");
+            }
+        });
+
+        let url = format!("http://{}/ping", addr);
+        let first = Request::get(&url).send().expect("first");
+        let second = Request::get(&url).send().expect("second");
+        assert_eq!(first.body, b"one");
+        assert_eq!(second.body, b"two");
+        server.join().expect("server");
+
+        let url = crate::essentia::url::Url::parse(&url).expect("url");
+        let key = PoolKey::from_url(&url, None, &TlsConfig::default()).expect("key");
+        assert_eq!(Pool::global().idle_count(&key), 1);
+    }
+
+    /// Serves keep-alive connections until dropped. Each connection answers
+    /// its first request and then, if `hang`, reads the next without
+    /// answering; otherwise it closes. Returns the base URL and the number of
+    /// requests received.
+    fn serve_first_request_only(hang: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("addr"));
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&received);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { return };
+                let counter = Arc::clone(&counter);
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+                    let mut writer = stream;
+                    for served in 0.. {
+                        let mut length = 0;
+                        loop {
+                            let mut line = String::new();
+                            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                            if let Some(value) = line.strip_prefix("Content-Length:") {
+                                length = value.trim().parse().expect("length");
+                            }
+                        }
+                        let mut body = vec![0; length];
+                        reader.read_exact(&mut body).expect("body");
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        if served > 0 {
+                            thread::sleep(Duration::from_millis(500));
+                            return;
+                        }
+                        writer
+                            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                            .expect("write");
+                        if !hang {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (base, received)
+    }
+
+    #[test]
+    fn test_reused_connection_replays() {
+        let timeouts = Timeouts { read: Some(Duration::from_millis(200)), ..Timeouts::NONE };
+
+        // A POST that times out on a reused connection is not sent again
+        let (base, received) = serve_first_request_only(true);
+        let post = || Request::post(&base).timeouts(timeouts).json("{}").send();
+        assert_eq!(post().expect("first").body, b"ok");
+        let err = post().expect_err("timeout");
+        assert!(matches!(err, LlmError::Timeout { .. }), "{}", err);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+
+        // Nor is a GET, since the server may still be working on it
+        let (base, received) = serve_first_request_only(true);
+        let get = || Request::get(&base).timeouts(timeouts).send();
+        assert_eq!(get().expect("first").body, b"ok");
+        assert!(get().is_err());
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+
+        // A connection the server closed while idle is skipped at checkout,
+        // so the POST goes out once on a new one
+        let (base, received) = serve_first_request_only(false);
+        let post = || Request::post(&base).json("{}").send();
+        assert_eq!(post().expect("first").body, b"ok");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(post().expect("second").body, b"ok");
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_read_and_total_timeouts() {
+        use crate::errors::ErrorKind;
+
+        // Accepts but never answers
+        let silent = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/", silent.local_addr().expect("addr"));
+        let started = Instant::now();
+        let err = Request::get(&url)
+            .timeouts(Timeouts { read: Some(Duration::from_millis(100)), ..Timeouts::NONE })
+            .send()
+            .expect_err("read timeout");
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Answers one byte at a time, each within the read timeout
+        let slow = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/", slow.local_addr().expect("addr"));
+        let server = thread::spawn(move || {
+            let (mut stream, _) = slow.accept().expect("accept");
+            for byte in b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n" {
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let err = Request::get(&url)
+            .timeouts(Timeouts {
+                read: Some(Duration::from_secs(1)),
+                total: Some(Duration::from_millis(200)),
+                ..Timeouts::NONE
+            })
+            .send()
+            .expect_err("total timeout");
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        server.join().expect("server");
     }
-    let body = text.lines().skip(body_start).collect::<Vec<_>>().join("\n").into_bytes();
-    Ok(Response { status, headers, body })
 }
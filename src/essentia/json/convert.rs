@@ -0,0 +1,499 @@
+//! Conversions between Rust types and [`Value`], and [`impl_json!`] to
+//! generate them for structs.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use super::{Object, Value, parse};
+use crate::errors::LlmError;
+
+/// Types that can be written as JSON.
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+
+    /// Whether a struct field holding this value is left out of the object;
+    /// true for `None`.
+    fn is_absent(&self) -> bool {
+        false
+    }
+}
+
+/// Types that can be read from JSON.
+pub trait FromJson: Sized {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError>;
+
+    /// The value of a struct field missing from the object, if that is
+    /// allowed; `Some(None)` for `Option`.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Why a value could not be converted, and where in the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromJsonError {
+    /// Location of the offending value, such as `messages[0].role`; empty
+    /// for the document itself.
+    pub path:    String,
+    pub message: String,
+}
+
+impl FromJsonError {
+    pub fn new(message: &str) -> Self {
+        Self { path: String::new(), message: message.to_string() }
+    }
+
+    /// The same error, inside the field `key` of an object.
+    pub fn at(mut self, key: &str) -> Self {
+        self.path = if self.path.is_empty() || self.path.starts_with('[') {
+            format!("{}{}", key, self.path)
+        } else {
+            format!("{}.{}", key, self.path)
+        };
+        self
+    }
+
+    /// The same error, inside element `index` of an array.
+    pub fn at_index(mut self, index: usize) -> Self {
+        self.path = if self.path.is_empty() || self.path.starts_with('[') {
+            format!("[{}]{}", index, self.path)
+        } else {
+            format!("[{}].{}", index, self.path)
+        };
+        self
+    }
+}
+
+impl fmt::Display for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for FromJsonError {}
+
+impl From<FromJsonError> for LlmError {
+    fn from(error: FromJsonError) -> Self {
+        LlmError::Json(error.to_string())
+    }
+}
+
+/// Parses `text` and converts the document to `T`.
+pub fn from_json_str<T: FromJson>(text: &str) -> Result<T, LlmError> {
+    Ok(T::from_json(&parse(text)?)?)
+}
+
+/// Implements [`ToJson`] and [`FromJson`] for a struct, field by field, in
+/// place of a derive.
+///
+/// Every field is listed, in the order it is written. `field as "name"` uses
+/// a different JSON key, and `field = expr` supplies a default when the key
+/// is missing. `Option` fields may be missing and are left out when `None`.
+///
+/// ```
+/// use essentia_llm_plugin::{essentia::json::{ToJson, from_json_str}, impl_json};
+///
+/// struct Options {
+///     max_tokens:  Option<u32>,
+///     temperature: f32,
+///     user_id:     String,
+/// }
+///
+/// impl_json!(Options { max_tokens, temperature = 0.7, user_id as "userId" });
+///
+/// let options: Options = from_json_str(r#"{"userId": "u1"}"#).unwrap();
+/// assert_eq!(options.temperature, 0.7);
+/// assert_eq!(options.to_json().to_string(), r#"{"temperature":0.7,"userId":"u1"}"#);
+/// ```
+#[macro_export]
+macro_rules! impl_json {
+    ($ty:ty { $($field:ident $(as $name:literal)? $(= $default:expr)?),* $(,)? }) => {
+        impl $crate::essentia::json::ToJson for $ty {
+            fn to_json(&self) -> $crate::essentia::json::Value {
+                let mut object = $crate::essentia::json::Object::new();
+                $(
+                    if !$crate::essentia::json::ToJson::is_absent(&self.$field) {
+                        object.insert(
+                            $crate::__json_key!($field $(, $name)?),
+                            $crate::essentia::json::ToJson::to_json(&self.$field),
+                        );
+                    }
+                )*
+                $crate::essentia::json::Value::Object(object)
+            }
+        }
+
+        impl $crate::essentia::json::FromJson for $ty {
+            fn from_json(
+                value: &$crate::essentia::json::Value,
+            ) -> ::std::result::Result<Self, $crate::essentia::json::FromJsonError> {
+                let object = value.as_object().ok_or_else(|| {
+                    $crate::essentia::json::FromJsonError::new("expected an object")
+                })?;
+                Ok(Self {
+                    $(
+                        $field: {
+                            let key = $crate::__json_key!($field $(, $name)?);
+                            match object.get(key) {
+                                Some(value) => $crate::essentia::json::FromJson::from_json(value)
+                                    .map_err(|e| e.at(key))?,
+                                None => $crate::__json_missing!(key $(, $default)?),
+                            }
+                        },
+                    )*
+                })
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_key {
+    ($field:ident) => {
+        stringify!($field)
+    };
+    ($field:ident, $name:literal) => {
+        $name
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_missing {
+    ($key:ident) => {
+        $crate::essentia::json::FromJson::from_missing()
+            .ok_or_else(|| $crate::essentia::json::FromJsonError::new("missing field").at($key))?
+    };
+    ($key:ident, $default:expr) => {
+        $default
+    };
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> Value {
+        (**self).to_json()
+    }
+
+    fn is_absent(&self) -> bool {
+        (**self).is_absent()
+    }
+}
+
+impl ToJson for Value {
+    fn to_json(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromJson for Value {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToJson for Object {
+    fn to_json(&self) -> Value {
+        Value::Object(self.clone())
+    }
+}
+
+impl FromJson for Object {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        value
+            .as_object()
+            .cloned()
+            .ok_or_else(|| FromJsonError::new("expected an object"))
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        value.as_bool().ok_or_else(|| FromJsonError::new("expected a boolean"))
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| FromJsonError::new("expected a string"))
+    }
+}
+
+macro_rules! json_integer {
+    ($($ty:ty => $as:ident),*) => {$(
+        impl ToJson for $ty {
+            fn to_json(&self) -> Value {
+                Value::from(*self)
+            }
+        }
+
+        impl FromJson for $ty {
+            fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+                let n = value.$as().ok_or_else(|| match value {
+                    Value::Number(n) if n.is_integer() => FromJsonError::new("integer out of range"),
+                    _ => FromJsonError::new("expected an integer"),
+                })?;
+                <$ty>::try_from(n).map_err(|_| FromJsonError::new("integer out of range"))
+            }
+        }
+    )*};
+}
+
+json_integer!(
+    i8 => as_i64, i16 => as_i64, i32 => as_i64, i64 => as_i64, isize => as_i64,
+    u8 => as_u64, u16 => as_u64, u32 => as_u64, u64 => as_u64, usize => as_u64
+);
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Value {
+        Value::from(*self)
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        value.as_f64().ok_or_else(|| FromJsonError::new("expected a number"))
+    }
+}
+
+impl ToJson for f32 {
+    fn to_json(&self) -> Value {
+        Value::from(*self)
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        f64::from_json(value).map(|n| n as f32)
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToJson::to_json)
+    }
+
+    fn is_absent(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json(value).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn to_json(&self) -> Value {
+        (**self).to_json()
+    }
+}
+
+impl<T: FromJson> FromJson for Box<T> {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        T::from_json(value).map(Box::new)
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Value {
+        self.as_slice().to_json()
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        value
+            .as_array()
+            .ok_or_else(|| FromJsonError::new("expected an array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_json(item).map_err(|e| e.at_index(i)))
+            .collect()
+    }
+}
+
+/// Keys are sorted, so the output does not depend on hash order.
+impl<T: ToJson, S> ToJson for HashMap<String, T, S> {
+    fn to_json(&self) -> Value {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        Value::Object(
+            entries.into_iter().map(|(key, value)| (key.clone(), value.to_json())).collect(),
+        )
+    }
+}
+
+impl<T: FromJson, S: std::hash::BuildHasher + Default> FromJson for HashMap<String, T, S> {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        object_entries(value)
+    }
+}
+
+impl<T: ToJson> ToJson for BTreeMap<String, T> {
+    fn to_json(&self) -> Value {
+        Value::Object(self.iter().map(|(key, value)| (key.clone(), value.to_json())).collect())
+    }
+}
+
+impl<T: FromJson> FromJson for BTreeMap<String, T> {
+    fn from_json(value: &Value) -> Result<Self, FromJsonError> {
+        object_entries(value)
+    }
+}
+
+fn object_entries<T: FromJson, C: FromIterator<(String, T)>>(
+    value: &Value,
+) -> Result<C, FromJsonError> {
+    value
+        .as_object()
+        .ok_or_else(|| FromJsonError::new("expected an object"))?
+        .iter()
+        .map(|(key, item)| Ok((key.clone(), T::from_json(item).map_err(|e| e.at(key))?)))
+        .collect()
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Message {
+        role:    String,
+        content: Option<String>,
+    }
+
+    impl_json!(Message { role, content });
+
+    #[derive(Debug, PartialEq)]
+    struct Payload {
+        model:       String,
+        messages:    Vec<Message>,
+        max_tokens:  Option<u32>,
+        temperature: f32,
+        stream:      bool,
+        metadata:    HashMap<String, i64>,
+    }
+
+    impl_json!(Payload {
+        model,
+        messages,
+        max_tokens as "maxTokens",
+        temperature = 0.7,
+        stream = false,
+        metadata = HashMap::new(),
+    });
+
+    #[test]
+    fn test_struct_round_trip() {
+        let payload = Payload {
+            model:       "grok".to_string(),
+            messages:    vec![Message {
+                role:    "user".to_string(),
+                content: Some("hi \"x\"".to_string()),
+            }],
+            max_tokens:  None,
+            temperature: 0.2,
+            stream:      true,
+            metadata:    [("b".to_string(), 2), ("a".to_string(), -1)].into_iter().collect(),
+        };
+        let text = payload.to_json().to_string();
+        assert_eq!(
+            text,
+            r#"{"model":"grok","messages":[{"role":"user","content":"hi \"x\""}],"temperature":0.2,"stream":true,"metadata":{"a":-1,"b":2}}"#
+        );
+        assert_eq!(
+            from_json_str::<Payload>(&text).expect("round trip"),
+            payload
+        );
+
+        let minimal: Payload =
+            from_json_str(r#"{"model": "m", "messages": [], "maxTokens": 5}"#).expect("defaults");
+        assert_eq!(minimal.max_tokens, Some(5));
+        assert_eq!((minimal.temperature, minimal.stream), (0.7, false));
+        assert!(minimal.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_errors_carry_paths() {
+        let error =
+            |text: &str| Payload::from_json(&parse(text).expect("valid")).expect_err("invalid");
+        assert_eq!(
+            error(r#"{"messages": []}"#).to_string(),
+            "model: missing field"
+        );
+        assert_eq!(
+            error(r#"{"model": "m", "messages": [{"role": "user"}, {"role": 1}]}"#).to_string(),
+            "messages[1].role: expected a string"
+        );
+        assert_eq!(
+            error(r#"{"model": "m", "messages": [], "maxTokens": -1}"#).to_string(),
+            "maxTokens: integer out of range"
+        );
+        assert_eq!(
+            error(r#"{"model": "m", "messages": [], "metadata": {"k": 1.5}}"#).to_string(),
+            "metadata.k: expected an integer"
+        );
+        assert_eq!(error("[]").to_string(), "expected an object");
+        assert!(matches!(
+            from_json_str::<Vec<u8>>("[256]"),
+            Err(LlmError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(u64::MAX.to_json().to_string(), "18446744073709551615");
+        assert_eq!(i8::from_json(&Value::from(-128)), Ok(-128));
+        assert!(u8::from_json(&Value::from(1.0)).is_err());
+        assert_eq!(f64::from_json(&Value::from(3)), Ok(3.0));
+        assert_eq!(Option::<bool>::from_json(&Value::Null), Ok(None));
+        assert_eq!("é".to_json(), Value::from("é"));
+        assert_eq!(vec![Some(1), None].to_json().to_string(), "[1,null]");
+        let map: BTreeMap<String, Vec<String>> =
+            from_json_str(r#"{"z": ["a"], "y": []}"#).expect("map");
+        assert_eq!(map.to_json().to_string(), r#"{"y":[],"z":["a"]}"#);
+    }
+}
@@ -0,0 +1,195 @@
+//! JSON values, an RFC 8259 parser and a writer.
+//!
+//! Parse errors carry the line and column of the offending character.
+//! Nesting is limited to [`DEFAULT_MAX_DEPTH`] arrays and objects, and
+//! repeated object keys resolve per [`DuplicateKeys`], last one winning by
+//! default.
+//!
+//! Objects keep their keys in insertion order, and integers stay exact, so
+//! a parsed document is written back the way it was read apart from
+//! whitespace and escapes. `{}` formats a [`Value`] compactly and `{:#}`
+//! pretty-prints it; [`WriteOptions`] configures indentation, key order and
+//! ASCII-only output.
+//!
+//! [`StreamParser`] parses input that arrives in chunks, and can show what an
+//! unfinished document holds so far.
+//!
+//! [`ToJson`] and [`FromJson`] convert typed values; [`impl_json!`] implements
+//! both for a struct.
+//!
+//! [`Value::pointer`] and friends address a single value by RFC 6901 JSON
+//! Pointer, [`Path`] queries many with wildcards and slices, and
+//! [`Value::merge_patch`] applies an RFC 7386 merge patch.
+
+mod convert;
+mod number;
+mod object;
+mod parser;
+mod patch;
+mod path;
+mod pointer;
+mod stream;
+mod writer;
+
+use std::{fmt, str::FromStr};
+
+pub use self::{
+    convert::{FromJson, FromJsonError, ToJson, from_json_str},
+    number::Number,
+    object::Object,
+    parser::{DEFAULT_MAX_DEPTH, DuplicateKeys, ParseError, ParseOptions, parse, parse_with},
+    path::Path,
+    stream::{Event, StreamParser},
+    writer::{WriteOptions, to_json_pretty, to_json_string, to_json_string_with},
+};
+use crate::errors::LlmError;
+pub use crate::impl_json;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Object),
+}
+
+impl FromStr for Value {
+    type Err = LlmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = if f.alternate() {
+            WriteOptions::pretty()
+        } else {
+            WriteOptions::default()
+        };
+        writer::write_value(f, self, &options, 0)
+    }
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(obj) => obj.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        match self {
+            Value::Array(arr) => arr.get(index),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(n.as_f64()),
+            _ => None,
+        }
+    }
+
+    /// The number as an `i64`, if it is an integer in range.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// The number as a `u64`, if it is a non-negative integer in range.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) => n.as_u64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Value::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut Object> {
+        match self {
+            Value::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+impl From<Object> for Value {
+    fn from(value: Object) -> Self {
+        Value::Object(value)
+    }
+}
+
+macro_rules! value_from_number {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Value::Number(value.into())
+            }
+        }
+    )*};
+}
+
+value_from_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
@@ -0,0 +1,104 @@
+//! JSON numbers that keep integers exact.
+
+use std::fmt;
+
+/// An integer that fits `i64` or `u64`, or a float.
+///
+/// Integers are stored as `i64` whenever they fit, so equal integers compare
+/// equal however they were built. A float never equals an integer: `1.0` and
+/// `1` are different documents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number(N);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum N {
+    Int(i64),
+    /// Only above `i64::MAX`.
+    UInt(u64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn as_f64(&self) -> f64 {
+        match self.0 {
+            N::Int(n) => n as f64,
+            N::UInt(n) => n as f64,
+            N::Float(n) => n,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            N::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            N::Int(n) => u64::try_from(n).ok(),
+            N::UInt(n) => Some(n),
+            N::Float(_) => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self.0, N::Float(_))
+    }
+}
+
+/// Integers as written; floats in their shortest round-tripping form, always
+/// with a fraction or exponent. JSON has no NaN or infinity, so those are
+/// written as `null`.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            N::Int(n) => write!(f, "{}", n),
+            N::UInt(n) => write!(f, "{}", n),
+            N::Float(n) if n.is_finite() => write!(f, "{:?}", n),
+            N::Float(_) => f.write_str("null"),
+        }
+    }
+}
+
+macro_rules! number_from_signed {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Number {
+            fn from(value: $ty) -> Self {
+                Number(N::Int(value as i64))
+            }
+        }
+    )*};
+}
+
+macro_rules! number_from_unsigned {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Number {
+            fn from(value: $ty) -> Self {
+                match i64::try_from(value) {
+                    Ok(n) => Number(N::Int(n)),
+                    Err(_) => Number(N::UInt(value as u64)),
+                }
+            }
+        }
+    )*};
+}
+
+number_from_signed!(i8, i16, i32, i64, isize);
+number_from_unsigned!(u8, u16, u32, u64, usize);
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number(N::Float(value))
+    }
+}
+
+/// Through the shortest decimal form, so `0.7f32` becomes `0.7` rather than
+/// `0.699999988079071`.
+impl From<f32> for Number {
+    fn from(value: f32) -> Self {
+        Number(N::Float(
+            value.to_string().parse().unwrap_or(f64::from(value)),
+        ))
+    }
+}
@@ -0,0 +1,143 @@
+//! JSON objects that keep their keys in insertion order.
+
+use std::{collections::HashMap, fmt};
+
+use super::Value;
+
+/// A JSON object whose keys iterate in insertion order.
+///
+/// Replacing a key keeps its position; removing one shifts later keys down.
+/// Equality ignores order, as it does for JSON objects.
+#[derive(Clone, Default)]
+pub struct Object {
+    entries: Vec<(String, Value)>,
+    /// Position of each key in `entries`.
+    index:   HashMap<String, usize>,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.index.get(key).map(|&i| &mut self.entries[i].1)
+    }
+
+    /// Sets `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: impl Into<String>, value: Value) -> Option<Value> {
+        let key = key.into();
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            },
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            if let Some(position) = self.index.get_mut(key) {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut Value)> {
+        self.entries.iter_mut().map(|(key, value)| (&*key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, Value)> for Object {
+    fn from_iter<I: IntoIterator<Item = (K, Value)>>(iter: I) -> Self {
+        let mut object = Object::new();
+        object.extend(iter);
+        object
+    }
+}
+
+impl<K: Into<String>> Extend<(K, Value)> for Object {
+    fn extend<I: IntoIterator<Item = (K, Value)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for Object {
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
+    type Item = (String, Value);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut object: Object =
+            [("b", Value::from(1)), ("a", Value::from(2))].into_iter().collect();
+        object.insert("c", Value::from(3));
+        assert_eq!(object.insert("b", Value::from(4)), Some(Value::from(1)));
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["b", "a", "c"]);
+
+        assert_eq!(object.remove("b"), Some(Value::from(4)));
+        assert_eq!(object.remove("b"), None);
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["a", "c"]);
+        assert_eq!(object.get("c"), Some(&Value::from(3)));
+        object.insert("b", Value::Null);
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["a", "c", "b"]);
+
+        let reversed: Object = object.clone().into_iter().rev().collect();
+        assert_eq!(reversed, object);
+    }
+}
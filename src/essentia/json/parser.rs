@@ -0,0 +1,661 @@
+//! RFC 8259 parsing with positioned errors, a depth limit and a duplicate
+//! key policy.
+
+use std::fmt;
+
+use super::{Number, Object, Value};
+use crate::errors::LlmError;
+
+/// Nesting depth accepted by [`parse`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// How an object that repeats a key is parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// The last value replaces earlier ones, as in most JSON parsers.
+    #[default]
+    LastWins,
+    /// The first value is kept and later ones are ignored.
+    FirstWins,
+    /// The document is rejected.
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Arrays and objects that may be nested inside each other.
+    pub max_depth:      usize,
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self { max_depth: DEFAULT_MAX_DEPTH, duplicate_keys: DuplicateKeys::default() }
+    }
+}
+
+impl ParseOptions {
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+}
+
+/// Why and where a document failed to parse. Lines and columns count from
+/// one; columns count characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: &'static str,
+    pub line:    usize,
+    pub column:  usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for LlmError {
+    fn from(error: ParseError) -> Self {
+        LlmError::Json(error.to_string())
+    }
+}
+
+pub fn parse(json_str: &str) -> Result<Value, LlmError> {
+    parse_with(json_str, &ParseOptions::default()).map_err(LlmError::from)
+}
+
+/// Parses one JSON document, optionally surrounded by whitespace.
+pub fn parse_with(text: &str, options: &ParseOptions) -> Result<Value, ParseError> {
+    let mut parser = Parser { text, pos: 0, depth: 0, options };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("Trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text:    &'a str,
+    pos:     usize,
+    depth:   usize,
+    options: &'a ParseOptions,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    /// An error at the current position.
+    fn error(&self, message: &'static str) -> ParseError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &'static str) -> ParseError {
+        let before = &self.text[..pos];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        ParseError {
+            message,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> Result<(), ParseError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(_) => Err(self.error("Expected a value")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, ParseError> {
+        if !self.text[self.pos..].starts_with(word) {
+            return Err(self.error("Invalid literal"));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        if self.depth == self.options.max_depth {
+            return Err(self.error("Nesting too deep"));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        self.enter()?;
+        let mut object = Object::new();
+        if self.peek() != Some(b'}') {
+            loop {
+                let key_pos = self.pos;
+                if self.peek() != Some(b'"') {
+                    return Err(self.error("Expected a string key"));
+                }
+                let key = self.string()?;
+                self.skip_whitespace();
+                self.expect(b':', "Expected ':' after object key")?;
+                self.skip_whitespace();
+                let value = self.value()?;
+                match self.options.duplicate_keys {
+                    DuplicateKeys::LastWins => {
+                        object.insert(key, value);
+                    },
+                    DuplicateKeys::FirstWins => {
+                        if !object.contains_key(&key) {
+                            object.insert(key, value);
+                        }
+                    },
+                    DuplicateKeys::Reject => {
+                        if object.insert(key, value).is_some() {
+                            return Err(self.error_at(key_pos, "Duplicate object key"));
+                        }
+                    },
+                }
+                self.skip_whitespace();
+                if self.peek() != Some(b',') {
+                    break;
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+        }
+        self.expect(b'}', "Expected ',' or '}' in object")?;
+        self.depth -= 1;
+        Ok(Value::Object(object))
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.enter()?;
+        let mut array = Vec::new();
+        if self.peek() != Some(b']') {
+            loop {
+                array.push(self.value()?);
+                self.skip_whitespace();
+                if self.peek() != Some(b',') {
+                    break;
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+        }
+        self.expect(b']', "Expected ',' or ']' in array")?;
+        self.depth -= 1;
+        Ok(Value::Array(array))
+    }
+
+    /// A string literal, starting at its opening quote. Unpaired surrogate
+    /// escapes decode to U+FFFD, as Rust strings cannot hold them.
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let run = self.text[self.pos..]
+                .find(|c: char| c == '"' || c == '\\' || c < ' ')
+                .ok_or_else(|| self.error_at(self.text.len(), "Unterminated string"))?;
+            out.push_str(&self.text[self.pos..self.pos + run]);
+            self.pos += run;
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                },
+                Some(b'\\') => self.escape(&mut out)?,
+                _ => return Err(self.error("Unescaped control character in string")),
+            }
+        }
+    }
+
+    fn escape(&mut self, out: &mut String) -> Result<(), ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let decoded = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let unit = self.hex4()?;
+                let decoded = match unit {
+                    0xD800..=0xDBFF if self.text[self.pos..].starts_with("\\u") => {
+                        let low_start = self.pos;
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        if (0xDC00..=0xDFFF).contains(&low) {
+                            let code = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        } else {
+                            // Not a pair: the second escape is decoded on its own
+                            self.pos = low_start;
+                            char::REPLACEMENT_CHARACTER
+                        }
+                    },
+                    _ => char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER),
+                };
+                out.push(decoded);
+                return Ok(());
+            },
+            _ => return Err(self.error_at(start, "Invalid escape sequence")),
+        };
+        self.pos += 1;
+        out.push(decoded);
+        Ok(())
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("Invalid \\u escape"))?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| self.error("Invalid \\u escape"))
+    }
+
+    /// A number per the RFC grammar: no leading zeros, `+` signs, or bare
+    /// decimal points. Integers that fit stay exact as `i64` or `u64`.
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(self.error("Invalid number")),
+        }
+        let mut is_integer = true;
+        if self.peek() == Some(b'.') {
+            is_integer = false;
+            self.pos += 1;
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error("Expected digits after decimal point"));
+            }
+            self.digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_integer = false;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error("Expected digits in exponent"));
+            }
+            self.digits();
+        }
+        let text = &self.text[start..self.pos];
+        if is_integer {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Value::Number(n.into()));
+            }
+            if let Ok(n) = text.parse::<u64>() {
+                return Ok(Value::Number(n.into()));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::Number(Number::from(n))),
+            _ => Err(self.error_at(start, "Number out of range")),
+        }
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn parse_default(text: &str) -> Result<Value, ParseError> {
+        parse_with(text, &ParseOptions::default())
+    }
+
+    /// Cases from the JSONTestSuite corpus: `y_` documents must parse and
+    /// `n_` documents must not.
+    #[test]
+    fn test_json_test_suite() {
+        // Named after the JSONTestSuite files, covering every y_ category
+        let accepted = [
+            ("y_array_arraysWithSpaces", r#"[[]   ]"#),
+            ("y_array_empty-string", r#"[""]"#),
+            ("y_array_empty", r#"[]"#),
+            ("y_array_ending_with_newline", r#"["a"]"#),
+            ("y_array_false", r#"[false]"#),
+            ("y_array_heterogeneous", r#"[null, 1, "1", {}]"#),
+            ("y_array_null", r#"[null]"#),
+            ("y_array_with_1_and_newline", "[1\n]"),
+            ("y_array_with_leading_space", " [1]"),
+            ("y_array_with_several_null", "[1,null,null,null,2]"),
+            ("y_array_with_trailing_space", "[2] "),
+            ("y_number", "[123e65]"),
+            ("y_number_0e+1", "[0e+1]"),
+            ("y_number_0e1", "[0e1]"),
+            ("y_number_after_space", "[ 4]"),
+            (
+                "y_number_double_close_to_zero",
+                "[-0.000000000000000000000000000000000000000001]",
+            ),
+            ("y_number_int_with_exp", "[20e1]"),
+            ("y_number_minus_zero", "[-0]"),
+            ("y_number_negative_int", "[-123]"),
+            ("y_number_negative_one", "[-1]"),
+            ("y_number_real_capital_e", "[1E22]"),
+            ("y_number_real_capital_e_neg_exp", "[1E-2]"),
+            ("y_number_real_capital_e_pos_exp", "[1E+2]"),
+            ("y_number_real_exponent", "[123e45]"),
+            ("y_number_real_fraction_exponent", "[123.456e78]"),
+            ("y_number_real_neg_exp", "[1e-2]"),
+            ("y_number_real_pos_exponent", "[1e+2]"),
+            ("y_number_simple_int", "[123]"),
+            ("y_number_simple_real", "[123.456789]"),
+            ("y_object", r#"{"asd":"sdf", "dfg":"fgh"}"#),
+            ("y_object_basic", r#"{"asd":"sdf"}"#),
+            ("y_object_duplicated_key", r#"{"a":"b","a":"c"}"#),
+            ("y_object_duplicated_key_and_value", r#"{"a":"b","a":"b"}"#),
+            ("y_object_empty", "{}"),
+            ("y_object_empty_key", r#"{"":0}"#),
+            ("y_object_escaped_null_in_key", r#"{"foo\u0000bar": 42}"#),
+            (
+                "y_object_extreme_numbers",
+                r#"{ "min": -1.0e+28, "max": 1.0e+28 }"#,
+            ),
+            (
+                "y_object_long_strings",
+                r#"{"x":[{"id": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"}], "id": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"}"#,
+            ),
+            ("y_object_simple", r#"{"a":[]}"#),
+            (
+                "y_object_string_unicode",
+                r#"{"title":"\u041f\u043e\u043b\u0442\u043e\u0440\u0430" }"#,
+            ),
+            ("y_object_with_newlines", "{\n\"a\": \"b\"\n}"),
+            (
+                "y_string_1_2_3_bytes_UTF-8_sequences",
+                r#"["\u0060\u012a\u12AB"]"#,
+            ),
+            ("y_string_accepted_surrogate_pair", r#"["\uD801\udc37"]"#),
+            ("y_string_allowed_escapes", r#"["\"\\\/\b\f\n\r\t"]"#),
+            ("y_string_backslash_and_u_escaped_zero", r#"["\\u0000"]"#),
+            ("y_string_backslash_doublequotes", r#"["\""]"#),
+            ("y_string_comments", r#"["a/*b*/c/*d//e"]"#),
+            ("y_string_double_escape_a", r#"["\\a"]"#),
+            ("y_string_escaped_control_character", r#"["\u0012"]"#),
+            ("y_string_escaped_noncharacter", r#"["\uFFFF"]"#),
+            ("y_string_in_array_with_leading_space", r#"[ "asd"]"#),
+            ("y_string_last_surrogates_1_and_2", r#"["\uDBFF\uDFFF"]"#),
+            ("y_string_nbsp_uescaped", r#"["new\u00A0line"]"#),
+            ("y_string_nonCharacterInUTF-8_U+FFFF", "[\"\u{ffff}\"]"),
+            ("y_string_null_escape", r#"["\u0000"]"#),
+            ("y_string_pi", r#"["π"]"#),
+            ("y_string_simple_ascii", r#"["asd "]"#),
+            ("y_string_space", r#"" ""#),
+            ("y_string_u+2028_line_sep", "[\"\u{2028}\"]"),
+            ("y_string_unicode_U+10FFFE_nonchar", r#"["\uDBFF\uDFFE"]"#),
+            ("y_string_unescaped_char_delete", "[\"\u{7f}\"]"),
+            ("y_string_unicode_escaped_double_quote", r#"["\u0022"]"#),
+            ("y_string_uescaped_newline", r#"["new\u000Aline"]"#),
+            ("y_string_utf8", r#"["€𝄞"]"#),
+            ("y_structure_lonely_false", "false"),
+            ("y_structure_lonely_int", "42"),
+            ("y_structure_lonely_negative_real", "-0.1"),
+            ("y_structure_lonely_null", "null"),
+            ("y_structure_lonely_string", r#""asd""#),
+            ("y_structure_lonely_true", "true"),
+            ("y_structure_string_empty", r#""""#),
+            ("y_structure_trailing_newline", "[\"a\"]\n"),
+            ("y_structure_true_in_array", "[true]"),
+            ("y_structure_whitespace_array", " [] "),
+        ];
+        for (name, text) in accepted {
+            assert!(parse_default(text).is_ok(), "{} should be accepted", name);
+        }
+
+        // Every n_ category; the invalid UTF-8 cases cannot be expressed as &str
+        let rejected = [
+            ("n_array_1_true_without_comma", "[1 true]"),
+            ("n_array_colon_instead_of_comma", r#"["": 1]"#),
+            ("n_array_comma_after_close", r#"[""],"#),
+            ("n_array_comma_and_number", "[,1]"),
+            ("n_array_double_comma", "[1,,2]"),
+            ("n_array_double_extra_comma", r#"["x",,]"#),
+            ("n_array_extra_close", r#"["x"]]"#),
+            ("n_array_extra_comma", r#"["",]"#),
+            ("n_array_incomplete", r#"["x""#),
+            ("n_array_incomplete_invalid_value", "[x"),
+            ("n_array_inner_array_no_comma", "[3[4]]"),
+            ("n_array_items_separated_by_semicolon", "[1:2]"),
+            ("n_array_just_comma", "[,]"),
+            ("n_array_just_minus", "[-]"),
+            ("n_array_missing_value", r#"[   , ""]"#),
+            ("n_array_newlines_unclosed", "[\"a\",\n4\n,1,"),
+            ("n_array_number_and_comma", "[1,]"),
+            ("n_array_number_and_several_commas", "[1,,]"),
+            ("n_array_star_inside", "[*]"),
+            ("n_array_unclosed", r#"[""#),
+            ("n_array_unclosed_trailing_comma", "[1,"),
+            ("n_array_unclosed_with_object_inside", "[{}"),
+            ("n_incomplete_false", "[fals]"),
+            ("n_incomplete_null", "[nul]"),
+            ("n_incomplete_true", "[tru]"),
+            ("n_multidigit_number_then_00", "123\u{0}"),
+            ("n_number_++", "[++1234]"),
+            ("n_number_+1", "[+1]"),
+            ("n_number_+Inf", "[+Inf]"),
+            ("n_number_-01", "[-01]"),
+            ("n_number_-1.0.", "[-1.0.]"),
+            ("n_number_-2.", "[-2.]"),
+            ("n_number_-NaN", "[-NaN]"),
+            ("n_number_.-1", "[.-1]"),
+            ("n_number_.2e-3", "[.2e-3]"),
+            ("n_number_0.1.2", "[0.1.2]"),
+            ("n_number_0.3e+", "[0.3e+]"),
+            ("n_number_0.e1", "[0.e1]"),
+            ("n_number_0e", "[0e]"),
+            ("n_number_1.0e", "[1.0e]"),
+            ("n_number_1_000", "[1 000.0]"),
+            ("n_number_2.e3", "[2.e3]"),
+            ("n_number_9.e+", "[9.e+]"),
+            ("n_number_Inf", "[Inf]"),
+            ("n_number_NaN", "[NaN]"),
+            ("n_number_expression", "[1+2]"),
+            ("n_number_hex_1_digit", "[0x1]"),
+            ("n_number_hex_2_digits", "[0x42]"),
+            ("n_number_infinity", "[Infinity]"),
+            ("n_number_minus_space_1", "[- 1]"),
+            ("n_number_neg_int_starting_with_zero", "[-012]"),
+            ("n_number_neg_real_without_int_part", "[-.123]"),
+            ("n_number_real_without_fractional_part", "[1.]"),
+            ("n_number_starting_with_dot", "[.123]"),
+            ("n_number_with_alpha", "[1.2a-3]"),
+            ("n_number_with_leading_zero", "[012]"),
+            ("n_object_bad_value", r#"["x", truth]"#),
+            ("n_object_comma_instead_of_colon", r#"{"x", null}"#),
+            ("n_object_double_colon", r#"{"x"::"b"}"#),
+            ("n_object_garbage_at_end", r#"{"a":"a" 123}"#),
+            ("n_object_key_with_single_quotes", "{key: 'value'}"),
+            ("n_object_missing_colon", r#"{"a" b}"#),
+            ("n_object_missing_key", r#"{:"b"}"#),
+            ("n_object_missing_semicolon", r#"{"a" "b"}"#),
+            ("n_object_missing_value", r#"{"a":"#),
+            ("n_object_no-colon", r#"{"a""#),
+            ("n_object_non_string_key", "{1:1}"),
+            ("n_object_repeated_null_null", "{null:null,null:null}"),
+            ("n_object_several_trailing_commas", r#"{"id":0,,,,,}"#),
+            ("n_object_single_quote", "{'a':0}"),
+            ("n_object_trailing_comma", r#"{"id":0,}"#),
+            ("n_object_trailing_comment", r#"{"a":"b"}/**/"#),
+            ("n_object_two_commas_in_a_row", r#"{"a":"b",,"c":"d"}"#),
+            ("n_object_unquoted_key", r#"{a: "b"}"#),
+            ("n_object_unterminated-value", r#"{"a":"a"#),
+            ("n_object_with_trailing_garbage", r#"{"a": true} "x""#),
+            ("n_single_space", " "),
+            ("n_string_1_surrogate_then_escape", r#"["\uD800\"]"#),
+            ("n_string_accentuated_char_no_quotes", "[é]"),
+            ("n_string_backslash_00", "[\"\\\u{0}\"]"),
+            ("n_string_escape_x", r#"["\x00"]"#),
+            ("n_string_escaped_backslash_bad", r#"["\\\"]"#),
+            ("n_string_escaped_ctrl_char_tab", "[\"\\\t\"]"),
+            ("n_string_escaped_emoji", "[\"\\🌀\"]"),
+            ("n_string_incomplete_escape", r#"["\"]"#),
+            ("n_string_incomplete_escaped_character", r#"["\u00A"]"#),
+            ("n_string_incomplete_surrogate", r#"["\uD834\uDd"]"#),
+            ("n_string_invalid_backslash_esc", r#"["\a"]"#),
+            ("n_string_invalid_unicode_escape", r#"["\uqqqq"]"#),
+            ("n_string_leading_uescaped_thinspace", r#"[\u0020"asd"]"#),
+            ("n_string_no_quotes_with_bad_escape", r"[\n]"),
+            ("n_string_single_doublequote", "\""),
+            ("n_string_single_quote", "['single quote']"),
+            ("n_string_single_string_no_double_quotes", "abc"),
+            ("n_string_start_escape_unclosed", r#"["\"#),
+            ("n_string_unescaped_ctrl_char", "[\"a\u{0}a\"]"),
+            ("n_string_unescaped_newline", "[\"new\nline\"]"),
+            ("n_string_unescaped_tab", "[\"\t\"]"),
+            ("n_string_unicode_CapitalU", r#""\UA66D""#),
+            ("n_string_with_trailing_garbage", r#"""x"#),
+            ("n_structure_angle_bracket_.", "<.>"),
+            ("n_structure_array_trailing_garbage", "[1]x"),
+            ("n_structure_array_with_extra_array_close", "[1]]"),
+            ("n_structure_capitalized_True", "[True]"),
+            ("n_structure_close_unopened_array", "1]"),
+            (
+                "n_structure_comma_instead_of_closing_brace",
+                r#"{"x": true,"#,
+            ),
+            ("n_structure_double_array", "[][]"),
+            ("n_structure_end_array", "]"),
+            ("n_structure_lone-open-bracket", "["),
+            ("n_structure_no_data", ""),
+            ("n_structure_null-byte-outside-string", "[\u{0}]"),
+            ("n_structure_number_with_trailing_garbage", "2@"),
+            ("n_structure_object_followed_by_closing_object", "{}}"),
+            ("n_structure_object_unclosed_no_value", r#"{"":"#),
+            ("n_structure_object_with_comment", r#"{"a":/*comment*/"b"}"#),
+            ("n_structure_open_array_apostrophe", "['"),
+            ("n_structure_open_object", "{"),
+            ("n_structure_single_star", "*"),
+            ("n_structure_trailing_#", r##"{"a":"b"}#{}"##),
+            ("n_structure_uescaped_LF_before_string", r#"[\u000A""]"#),
+            ("n_structure_unclosed_array", "[1"),
+            ("n_structure_unicode-identifier", "å"),
+            ("n_structure_UTF8_BOM_no_data", "\u{feff}"),
+            ("n_structure_whitespace_formfeed", "[\u{c}]"),
+            ("i_number_real_pos_overflow", "[123123e100000]"),
+            ("i_structure_UTF-8_BOM_empty_object", "\u{feff}{}"),
+        ];
+        for (name, text) in rejected {
+            assert!(parse_default(text).is_err(), "{} should be rejected", name);
+        }
+        let opening_arrays = "[".repeat(100_000);
+        assert!(
+            parse_default(&opening_arrays).is_err(),
+            "n_structure_100000_opening_arrays"
+        );
+    }
+
+    #[test]
+    fn test_strings_and_numbers() {
+        let value = parse_default(r#"{"a\"b": "x,}y", "e": "\u00e9\ud83d\ude00\n", "n": -1.5e2}"#)
+            .expect("parse");
+        assert_eq!(value.get("a\"b").and_then(Value::as_str), Some("x,}y"));
+        assert_eq!(value.get("e").and_then(Value::as_str), Some("é😀\n"));
+        assert_eq!(value.get("n").and_then(Value::as_f64), Some(-150.0));
+
+        // Unpaired surrogates become U+FFFD; the escape after one still decodes
+        let value = parse_default(r#"["\ud800", "\udc00x", "\ud800\u0041"]"#).expect("parse");
+        let strings: Vec<_> =
+            value.as_array().expect("array").iter().filter_map(Value::as_str).collect();
+        assert_eq!(strings, ["\u{fffd}", "\u{fffd}x", "\u{fffd}A"]);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let error = parse_default("{\n  \"a\": 1,\n  \"b\": tru\n}").expect_err("invalid");
+        assert_eq!(
+            (error.message, error.line, error.column),
+            ("Invalid literal", 3, 8)
+        );
+        let error = parse_default("[\"é\", 01]").expect_err("invalid");
+        assert_eq!((error.line, error.column), (1, 8));
+        let error = parse_default("[\"abc").expect_err("invalid");
+        assert_eq!(error.to_string(), "Unterminated string at line 1, column 6");
+        assert!(matches!(parse("[1,]"), Err(LlmError::Json(_))));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse_default(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+        let error = parse_default(&nested(DEFAULT_MAX_DEPTH + 1)).expect_err("too deep");
+        assert_eq!(error.message, "Nesting too deep");
+        // Far deeper input fails the same way instead of exhausting the stack
+        assert!(parse_default(&nested(100_000)).is_err());
+        let shallow = ParseOptions::default().with_max_depth(1);
+        assert!(parse_with("[{}]", &shallow).is_err());
+        assert!(parse_with("[1]", &shallow).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let text = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let options = ParseOptions::default();
+        let first = |options: ParseOptions| {
+            parse_with(text, &options).map(|value| value.get("a").and_then(Value::as_f64))
+        };
+        assert_eq!(first(options), Ok(Some(3.0)));
+        assert_eq!(
+            first(options.with_duplicate_keys(DuplicateKeys::FirstWins)),
+            Ok(Some(1.0))
+        );
+        let error = first(options.with_duplicate_keys(DuplicateKeys::Reject)).expect_err("dup");
+        assert_eq!((error.message, error.column), ("Duplicate object key", 18));
+    }
+}
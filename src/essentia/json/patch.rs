@@ -0,0 +1,74 @@
+//! RFC 7386 JSON Merge Patch.
+
+use super::{Object, Value};
+
+impl Value {
+    /// Applies `patch` as an RFC 7386 merge patch: an object patch merges
+    /// into an object member by member, removing members set to `null`; any
+    /// other patch replaces the value.
+    pub fn merge_patch(&mut self, patch: &Value) {
+        let Value::Object(patch) = patch else {
+            *self = patch.clone();
+            return;
+        };
+        if !matches!(self, Value::Object(_)) {
+            *self = Value::Object(Object::new());
+        }
+        let Value::Object(target) = self else {
+            return;
+        };
+        for (key, value) in patch.iter() {
+            if value.is_null() {
+                target.remove(key);
+            } else if let Some(existing) = target.get_mut(key) {
+                existing.merge_patch(value);
+            } else {
+                // Merged into nothing, so nested nulls are dropped too
+                let mut added = Value::Null;
+                added.merge_patch(value);
+                target.insert(key.clone(), added);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use crate::essentia::json::parse;
+
+    /// The examples of RFC 7386 appendix A.
+    #[test]
+    fn test_rfc_7386_examples() {
+        let cases = [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"a":null}"#, r#"{}"#),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a":{"b":"d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+            (
+                r#"{}"#,
+                r#"{"a":{"bb":{"ccc":null}}}"#,
+                r#"{"a":{"bb":{}}}"#,
+            ),
+        ];
+        for (target, patch, expected) in cases {
+            let mut value = parse(target).expect("target");
+            value.merge_patch(&parse(patch).expect("patch"));
+            assert_eq!(value.to_string(), expected, "{} + {}", target, patch);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! A small JSONPath-like query language.
+//!
+//! A path is an optional `$` followed by steps: `.name` or `['name']` for an
+//! object member, `[2]` or `[-1]` for an array element counted from the start
+//! or end, `.*` or `[*]` for every member or element, and `[start:end:step]`
+//! for a slice with any part left out. A leading name needs no dot, as in
+//! `choices[0].message.content`. Steps that do not match are skipped rather
+//! than failing the query.
+
+use std::{fmt, str::FromStr};
+
+use super::Value;
+use crate::errors::LlmError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Slice {
+        start: Option<i64>,
+        end:   Option<i64>,
+        step:  usize,
+    },
+}
+
+/// A parsed query; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    source: String,
+    steps:  Vec<Step>,
+}
+
+impl Path {
+    pub fn parse(path: &str) -> Result<Self, LlmError> {
+        let error = |reason: &str, at: usize| {
+            LlmError::Json(format!(
+                "Invalid path {:?}: {} at offset {}",
+                path, reason, at
+            ))
+        };
+        let bytes = path.as_bytes();
+        let mut pos = usize::from(path.starts_with('$'));
+        let mut steps = Vec::new();
+        while pos < bytes.len() {
+            let start = pos;
+            match bytes[pos] {
+                b'.' => {
+                    pos += 1;
+                    if bytes.get(pos) == Some(&b'*') {
+                        pos += 1;
+                        steps.push(Step::Wildcard);
+                    } else {
+                        let name = name(path, &mut pos);
+                        if name.is_empty() {
+                            return Err(error("expected a member name", pos));
+                        }
+                        steps.push(Step::Key(name.to_string()));
+                    }
+                },
+                b'[' => {
+                    // A quoted name may itself contain ']'
+                    let rest = path[pos + 1..].trim_start();
+                    let mut from = pos;
+                    if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '\'' | '"')) {
+                        let start = path.len() - rest.len() + 1;
+                        from = path[start..]
+                            .find(quote)
+                            .map(|i| start + i + 1)
+                            .ok_or_else(|| error("unclosed quote", pos))?;
+                    }
+                    let close = path[from..]
+                        .find(']')
+                        .map(|i| from + i)
+                        .ok_or_else(|| error("unclosed '['", pos))?;
+                    let inner = path[pos + 1..close].trim();
+                    steps.push(selector(inner).ok_or_else(|| error("invalid selector", pos))?);
+                    pos = close + 1;
+                },
+                _ if start == 0 => {
+                    let name = name(path, &mut pos);
+                    if name.is_empty() {
+                        return Err(error("expected a member name", pos));
+                    }
+                    steps.push(Step::Key(name.to_string()));
+                },
+                _ => return Err(error("expected '.' or '['", pos)),
+            }
+        }
+        Ok(Self { source: path.to_string(), steps })
+    }
+
+    /// Every value the path matches, in document order.
+    pub fn query<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut matches = vec![value];
+        for step in &self.steps {
+            matches = matches.into_iter().flat_map(|value| select(step, value)).collect();
+        }
+        matches
+    }
+}
+
+impl FromStr for Path {
+    type Err = LlmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Value {
+    /// Every value `path` matches; see [`Path`].
+    pub fn query(&self, path: &str) -> Result<Vec<&Value>, LlmError> {
+        Ok(Path::parse(path)?.query(self))
+    }
+}
+
+/// A bare member name, which runs until the next `.` or `[`.
+fn name<'a>(path: &'a str, pos: &mut usize) -> &'a str {
+    let start = *pos;
+    let len = path[start..].find(['.', '[']).unwrap_or(path.len() - start);
+    *pos = start + len;
+    &path[start..*pos]
+}
+
+fn selector(inner: &str) -> Option<Step> {
+    if inner == "*" {
+        return Some(Step::Wildcard);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(key) = inner.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+            return Some(Step::Key(key.to_string()));
+        }
+    }
+    if !inner.contains(':') {
+        return inner.parse().ok().map(Step::Index);
+    }
+    let bound = |part: Option<&str>| -> Option<Option<i64>> {
+        match part.map(str::trim) {
+            None | Some("") => Some(None),
+            Some(part) => part.parse().ok().map(Some),
+        }
+    };
+    let mut parts = inner.split(':');
+    let start = bound(parts.next())?;
+    let end = bound(parts.next())?;
+    let step = match bound(parts.next())? {
+        None => 1,
+        Some(step) => usize::try_from(step).ok().filter(|&step| step > 0)?,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Step::Slice { start, end, step })
+}
+
+fn select<'a>(step: &Step, value: &'a Value) -> Vec<&'a Value> {
+    match (step, value) {
+        (Step::Key(key), Value::Object(object)) => object.get(key).into_iter().collect(),
+        (Step::Index(index), Value::Array(array)) => {
+            resolve(*index, array.len()).and_then(|i| array.get(i)).into_iter().collect()
+        },
+        (Step::Wildcard, Value::Object(object)) => object.values().collect(),
+        (Step::Wildcard, Value::Array(array)) => array.iter().collect(),
+        (Step::Slice { start, end, step }, Value::Array(array)) => {
+            let len = array.len();
+            let clamp = |bound: i64| resolve(bound, len).unwrap_or(if bound < 0 { 0 } else { len });
+            let start = start.map_or(0, clamp);
+            let end = end.map_or(len, clamp);
+            array
+                .get(start..end.max(start))
+                .unwrap_or_default()
+                .iter()
+                .step_by(*step)
+                .collect()
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// An index counted from the end when negative, if it is in bounds.
+fn resolve(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)?
+    } else {
+        usize::try_from(index).ok()?
+    };
+    (index < len).then_some(index)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::json::parse;
+
+    fn query(document: &Value, path: &str) -> String {
+        let matches = document.query(path).expect("valid path");
+        let values: Vec<String> = matches.iter().map(|value| value.to_string()).collect();
+        values.join(" ")
+    }
+
+    #[test]
+    fn test_queries() {
+        let document = parse(
+            r#"{"choices": [
+                {"message": {"content": "a"}, "index": 0},
+                {"message": {"content": "b"}, "index": 1},
+                {"delta": {"content": "c"}, "index": 2}
+            ], "n": [0, 1, 2, 3, 4, 5], "odd key": {"x.y": true, "a]b": 1}}"#,
+        )
+        .expect("valid");
+        let cases = [
+            ("choices[0].message.content", r#""a""#),
+            ("$.choices[-1].delta.content", r#""c""#),
+            ("$.choices[*].message.content", r#""a" "b""#),
+            ("$['choices'][1][\"index\"]", "1"),
+            ("$['odd key']['x.y']", "true"),
+            ("$['odd key']['a]b']", "1"),
+            ("$[ \"odd key\" ][\"a]b\"]", "1"),
+            ("$.n[1:3]", "1 2"),
+            ("$.n[::2]", "0 2 4"),
+            ("$.n[-2:]", "4 5"),
+            ("$.n[:-4]", "0 1"),
+            ("$.n[4:2]", ""),
+            ("$.n[-10:1]", "0"),
+            ("$.n[6]", ""),
+            ("$.n[-7]", ""),
+            ("$.choices.*.index", "0 1 2"),
+            ("$.missing[0].x", ""),
+            ("$", &document.to_string()),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(query(&document, path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        for path in [
+            "$.",
+            "$[",
+            "$[0",
+            "$['a]",
+            "$['a']x",
+            "$[a]",
+            "$[1:2:0]",
+            "$[1:2:3:4]",
+            "$x",
+            "a..b",
+        ] {
+            assert!(
+                matches!(Path::parse(path), Err(LlmError::Json(_))),
+                "{}",
+                path
+            );
+        }
+        let path: Path = "choices[0]".parse().expect("valid");
+        assert_eq!(path.to_string(), "choices[0]");
+    }
+}
@@ -0,0 +1,198 @@
+//! RFC 6901 JSON Pointers, such as `/choices/0/message/content`.
+
+use super::Value;
+use crate::errors::LlmError;
+
+impl Value {
+    /// The value `pointer` refers to; `""` is the whole document. `None` if
+    /// it does not exist or the pointer is malformed.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        tokens(pointer)?.iter().try_fold(self, |value, token| match value {
+            Value::Object(object) => object.get(token),
+            Value::Array(array) => array.get(array_index(token)?),
+            _ => None,
+        })
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        tokens(pointer)?.iter().try_fold(self, |value, token| match value {
+            Value::Object(object) => object.get_mut(token),
+            Value::Array(array) => array.get_mut(array_index(token)?),
+            _ => None,
+        })
+    }
+
+    /// Stores `value` at `pointer`, returning the value it replaced.
+    ///
+    /// The parent must exist. A new key is added to an object, and `-` or
+    /// the length of an array appends to it.
+    pub fn set_pointer(&mut self, pointer: &str, value: Value) -> Result<Option<Value>, LlmError> {
+        let error = |reason: &str| LlmError::Json(format!("Cannot set {}: {}", pointer, reason));
+        let mut tokens = tokens(pointer).ok_or_else(|| error("malformed pointer"))?;
+        let Some(last) = tokens.pop() else {
+            return Ok(Some(std::mem::replace(self, value)));
+        };
+        let parent =
+            self.pointer_mut(&join(&tokens)).ok_or_else(|| error("parent does not exist"))?;
+        match parent {
+            Value::Object(object) => Ok(object.insert(last, value)),
+            Value::Array(array) => {
+                let index = if last == "-" {
+                    array.len()
+                } else {
+                    array_index(&last).ok_or_else(|| error("invalid array index"))?
+                };
+                match index.cmp(&array.len()) {
+                    std::cmp::Ordering::Less => {
+                        Ok(Some(std::mem::replace(&mut array[index], value)))
+                    },
+                    std::cmp::Ordering::Equal => {
+                        array.push(value);
+                        Ok(None)
+                    },
+                    std::cmp::Ordering::Greater => Err(error("array index out of range")),
+                }
+            },
+            _ => Err(error("parent is not an object or array")),
+        }
+    }
+
+    /// Removes and returns the value at `pointer`; later array elements
+    /// shift down. The whole document cannot be removed.
+    pub fn remove_pointer(&mut self, pointer: &str) -> Option<Value> {
+        let mut tokens = tokens(pointer)?;
+        let last = tokens.pop()?;
+        match self.pointer_mut(&join(&tokens))? {
+            Value::Object(object) => object.remove(&last),
+            Value::Array(array) => {
+                let index = array_index(&last).filter(|&index| index < array.len())?;
+                Some(array.remove(index))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Unescaped reference tokens, or `None` if `pointer` is malformed.
+fn tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| {
+            let mut out = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => match chars.next()? {
+                        '0' => out.push('~'),
+                        '1' => out.push('/'),
+                        _ => return None,
+                    },
+                    c => out.push(c),
+                }
+            }
+            Some(out)
+        })
+        .collect()
+}
+
+fn join(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// An array index token: digits without leading zeros.
+fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::json::parse;
+
+    #[test]
+    fn test_rfc_6901_examples() {
+        let document = parse(
+            r#"{"foo": ["bar", "baz"], "": 0, "a/b": 1, "c%d": 2, "e^f": 3, "g|h": 4,
+                "i\\j": 5, "k\"l": 6, " ": 7, "m~n": 8}"#,
+        )
+        .expect("valid");
+        let cases = [
+            ("/foo", r#"["bar","baz"]"#),
+            ("/foo/0", r#""bar""#),
+            ("/", "0"),
+            ("/a~1b", "1"),
+            ("/c%d", "2"),
+            ("/e^f", "3"),
+            ("/g|h", "4"),
+            ("/i\\j", "5"),
+            ("/k\"l", "6"),
+            ("/ ", "7"),
+            ("/m~0n", "8"),
+        ];
+        for (pointer, expected) in cases {
+            assert_eq!(
+                document.pointer(pointer).map(Value::to_string).as_deref(),
+                Some(expected),
+                "{}",
+                pointer
+            );
+        }
+        assert_eq!(document.pointer(""), Some(&document));
+        for missing in [
+            "foo", "/foo/2", "/foo/01", "/foo/-", "/x", "/m~2n", "/foo/0/x",
+        ] {
+            assert_eq!(document.pointer(missing), None, "{}", missing);
+        }
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut document = parse(r#"{"a": {"b": [1, 2]}}"#).expect("valid");
+        assert_eq!(
+            document.set_pointer("/a/b/0", Value::from(9)).expect("replace"),
+            Some(Value::from(1))
+        );
+        assert_eq!(
+            document.set_pointer("/a/b/-", Value::from(3)).expect("append"),
+            None
+        );
+        assert_eq!(
+            document.set_pointer("/a/b/3", Value::from(4)).expect("append"),
+            None
+        );
+        assert_eq!(
+            document.set_pointer("/a/c~1d", Value::Null).expect("add"),
+            None
+        );
+        assert_eq!(document.to_string(), r#"{"a":{"b":[9,2,3,4],"c/d":null}}"#);
+
+        assert!(document.set_pointer("/a/b/9", Value::Null).is_err());
+        assert!(document.set_pointer("/x/y", Value::Null).is_err());
+        assert!(document.set_pointer("/a/b/0/z", Value::Null).is_err());
+        assert!(document.set_pointer("a", Value::Null).is_err());
+
+        assert_eq!(document.remove_pointer("/a/b/1"), Some(Value::from(2)));
+        assert_eq!(document.remove_pointer("/a/c~1d"), Some(Value::Null));
+        assert_eq!(document.remove_pointer("/a/b/3"), None);
+        assert_eq!(document.remove_pointer(""), None);
+        *document.pointer_mut("/a/b/0").expect("exists") = Value::from("x");
+        assert_eq!(document.to_string(), r#"{"a":{"b":["x",3,4]}}"#);
+
+        let old = document.set_pointer("", Value::from(true)).expect("root");
+        assert_eq!((old.is_some(), document), (true, Value::from(true)));
+    }
+}
@@ -0,0 +1,617 @@
+//! Push-based parsing of JSON that arrives in pieces, such as tool-call
+//! arguments spread over streamed deltas.
+
+use super::{DuplicateKeys, Object, ParseError, ParseOptions, Value, parse_with};
+
+/// What a chunk of input completed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// An object key; its value follows.
+    Key(String),
+    /// A string, number, boolean or null.
+    Scalar(Value),
+    /// A top-level document finished.
+    Document(Value),
+}
+
+/// Parses JSON fed in arbitrary byte chunks.
+///
+/// Input may hold several documents separated by whitespace; each one ends
+/// with an [`Event::Document`]. Chunks may split tokens anywhere, including
+/// inside escapes and UTF-8 sequences. Once the input fails to parse, every
+/// later call returns the same error.
+#[derive(Debug, Default)]
+pub struct StreamParser {
+    options: ParseOptions,
+    /// Containers being built, outermost first.
+    stack:   Vec<Frame>,
+    expect:  Expect,
+    token:   Token,
+    /// Position of the next byte.
+    at:      Position,
+    /// Where the token in progress started.
+    start:   Position,
+    failed:  Option<ParseError>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    line:   usize,
+    column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+impl Position {
+    fn error(self, message: &'static str) -> ParseError {
+        ParseError { message, line: self.line, column: self.column }
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    Array(Vec<Value>),
+    /// `key` holds the key whose value is being parsed, and where it started.
+    Object {
+        object: Object,
+        key:    Option<(String, Position)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Expect {
+    /// A value: at the top level, after `:`, or after `,` in an array.
+    #[default]
+    Value,
+    /// A value or `]`, just after `[`.
+    FirstItem,
+    /// A key or `}`, just after `{`.
+    FirstKey,
+    /// A key, after `,` in an object.
+    Key,
+    Colon,
+    /// `,` or the end of the enclosing container.
+    CommaOrEnd,
+}
+
+#[derive(Debug, Default)]
+enum Token {
+    #[default]
+    None,
+    String {
+        /// Decoded contents so far, as UTF-8.
+        bytes:          Vec<u8>,
+        escape:         Escape,
+        /// A high surrogate waiting for its low half.
+        high_surrogate: Option<u32>,
+        is_key:         bool,
+    },
+    Number(String),
+    Literal {
+        word:    &'static str,
+        matched: usize,
+        value:   Value,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Escape {
+    None,
+    Backslash,
+    Unicode { digits: u8, unit: u32 },
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ParseOptions) -> Self {
+        Self { options, ..Self::default() }
+    }
+
+    /// Feeds the next chunk, returning the events it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<Event>, ParseError> {
+        if let Some(error) = &self.failed {
+            return Err(error.clone());
+        }
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if let Err(error) = self.byte(byte, &mut events) {
+                self.failed = Some(error.clone());
+                return Err(error);
+            }
+            if byte == b'\n' {
+                self.at = Position { line: self.at.line + 1, column: 1 };
+            } else if byte & 0xC0 != 0x80 {
+                self.at.column += 1;
+            }
+        }
+        Ok(events)
+    }
+
+    /// Ends the input. A trailing top-level number completes here; a document
+    /// still open is an error.
+    pub fn finish(&mut self) -> Result<Vec<Event>, ParseError> {
+        if let Some(error) = &self.failed {
+            return Err(error.clone());
+        }
+        let mut events = Vec::new();
+        let mut result = Ok(());
+        if matches!(self.token, Token::Number(_)) {
+            result = self.finish_number(&mut events);
+        }
+        if result.is_ok() && (!matches!(self.token, Token::None) || !self.stack.is_empty()) {
+            result = Err(self.at.error("Unexpected end of input"));
+        }
+        match result {
+            Ok(()) => Ok(events),
+            Err(error) => {
+                self.failed = Some(error.clone());
+                Err(error)
+            },
+        }
+    }
+
+    /// Whether a document has started and not yet finished.
+    pub fn in_document(&self) -> bool {
+        !self.stack.is_empty() || !matches!(self.token, Token::None)
+    }
+
+    /// Best-effort value of the unfinished document: containers are closed
+    /// where the input stops, a partial string keeps the text so far, and a
+    /// partial number is kept if it is valid as it stands. Keys still being
+    /// read or waiting for a value are left out.
+    ///
+    /// `None` between documents and once the input has failed to parse.
+    pub fn partial(&self) -> Option<Value> {
+        if self.failed.is_some() {
+            return None;
+        }
+        let mut child = match &self.token {
+            Token::String { bytes, is_key: false, .. } => {
+                let valid = match std::str::from_utf8(bytes) {
+                    Ok(text) => text,
+                    // Cut inside a character
+                    Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+                };
+                Some(Value::String(valid.to_string()))
+            },
+            Token::Number(text) => parse_with(text, &ParseOptions::default()).ok(),
+            _ => None,
+        };
+        for frame in self.stack.iter().rev() {
+            child = Some(match frame {
+                Frame::Array(items) => {
+                    let mut items = items.clone();
+                    items.extend(child);
+                    Value::Array(items)
+                },
+                Frame::Object { object, key } => {
+                    let mut object = object.clone();
+                    if let (Some((key, _)), Some(child)) = (key, child) {
+                        object.insert(key.clone(), child);
+                    }
+                    Value::Object(object)
+                },
+            });
+        }
+        child
+    }
+
+    fn byte(&mut self, byte: u8, events: &mut Vec<Event>) -> Result<(), ParseError> {
+        match &mut self.token {
+            Token::None => {},
+            Token::String { .. } => return self.string_byte(byte, events),
+            Token::Number(text) => {
+                if let b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' = byte {
+                    text.push(char::from(byte));
+                    return Ok(());
+                }
+                // The byte after a number is handled as usual
+                self.finish_number(events)?;
+            },
+            Token::Literal { word, matched, value } => {
+                if word.as_bytes()[*matched] != byte {
+                    return Err(self.start.error("Invalid literal"));
+                }
+                *matched += 1;
+                if *matched == word.len() {
+                    let value = std::mem::replace(value, Value::Null);
+                    self.token = Token::None;
+                    self.complete(value, events)?;
+                }
+                return Ok(());
+            },
+        }
+
+        let expects_value = matches!(self.expect, Expect::Value | Expect::FirstItem);
+        let expects_key = matches!(self.expect, Expect::Key | Expect::FirstKey);
+        self.start = self.at;
+        match byte {
+            b' ' | b'\t' | b'\n' | b'\r' => {},
+            b'{' | b'[' if expects_value => {
+                if self.stack.len() == self.options.max_depth {
+                    return Err(self.at.error("Nesting too deep"));
+                }
+                if byte == b'{' {
+                    self.stack.push(Frame::Object { object: Object::new(), key: None });
+                    self.expect = Expect::FirstKey;
+                    events.push(Event::StartObject);
+                } else {
+                    self.stack.push(Frame::Array(Vec::new()));
+                    self.expect = Expect::FirstItem;
+                    events.push(Event::StartArray);
+                }
+            },
+            b'"' if expects_value || expects_key => {
+                self.token = Token::String {
+                    bytes:          Vec::new(),
+                    escape:         Escape::None,
+                    high_surrogate: None,
+                    is_key:         expects_key,
+                };
+            },
+            b'-' | b'0'..=b'9' if expects_value => {
+                self.token = Token::Number(char::from(byte).to_string());
+            },
+            b't' | b'f' | b'n' if expects_value => {
+                let (word, value) = match byte {
+                    b't' => ("true", Value::Bool(true)),
+                    b'f' => ("false", Value::Bool(false)),
+                    _ => ("null", Value::Null),
+                };
+                self.token = Token::Literal { word, matched: 1, value };
+            },
+            b':' if self.expect == Expect::Colon => self.expect = Expect::Value,
+            b',' if self.expect == Expect::CommaOrEnd => {
+                self.expect = match self.stack.last() {
+                    Some(Frame::Object { .. }) => Expect::Key,
+                    _ => Expect::Value,
+                };
+            },
+            b']' if self.expect == Expect::FirstItem
+                || (self.expect == Expect::CommaOrEnd
+                    && matches!(self.stack.last(), Some(Frame::Array(_)))) =>
+            {
+                if let Some(Frame::Array(items)) = self.stack.pop() {
+                    events.push(Event::EndArray);
+                    self.complete(Value::Array(items), events)?;
+                }
+            },
+            b'}' if self.expect == Expect::FirstKey
+                || (self.expect == Expect::CommaOrEnd
+                    && matches!(self.stack.last(), Some(Frame::Object { .. }))) =>
+            {
+                if let Some(Frame::Object { object, .. }) = self.stack.pop() {
+                    events.push(Event::EndObject);
+                    self.complete(Value::Object(object), events)?;
+                }
+            },
+            _ => {
+                return Err(self.at.error(match self.expect {
+                    Expect::Value | Expect::FirstItem => "Expected a value",
+                    Expect::Key | Expect::FirstKey => "Expected a string key",
+                    Expect::Colon => "Expected ':' after object key",
+                    Expect::CommaOrEnd => match self.stack.last() {
+                        Some(Frame::Object { .. }) => "Expected ',' or '}' in object",
+                        _ => "Expected ',' or ']' in array",
+                    },
+                }));
+            },
+        }
+        Ok(())
+    }
+
+    fn string_byte(&mut self, byte: u8, events: &mut Vec<Event>) -> Result<(), ParseError> {
+        let at = self.at;
+        let Token::String { bytes, escape, high_surrogate, is_key } = &mut self.token else {
+            return Ok(());
+        };
+        match *escape {
+            Escape::None => match byte {
+                b'"' => {
+                    flush_surrogate(bytes, high_surrogate);
+                    let is_key = *is_key;
+                    let bytes = std::mem::take(bytes);
+                    self.token = Token::None;
+                    let text = String::from_utf8(bytes)
+                        .map_err(|_| self.start.error("Invalid UTF-8 in string"))?;
+                    if is_key {
+                        if let Some(Frame::Object { key, .. }) = self.stack.last_mut() {
+                            *key = Some((text.clone(), self.start));
+                        }
+                        self.expect = Expect::Colon;
+                        events.push(Event::Key(text));
+                        return Ok(());
+                    }
+                    return self.complete(Value::String(text), events);
+                },
+                b'\\' => *escape = Escape::Backslash,
+                0..0x20 => return Err(at.error("Unescaped control character in string")),
+                _ => {
+                    flush_surrogate(bytes, high_surrogate);
+                    bytes.push(byte);
+                },
+            },
+            Escape::Backslash => {
+                let decoded = match byte {
+                    b'u' => {
+                        *escape = Escape::Unicode { digits: 0, unit: 0 };
+                        return Ok(());
+                    },
+                    b'"' => '"',
+                    b'\\' => '\\',
+                    b'/' => '/',
+                    b'b' => '\u{8}',
+                    b'f' => '\u{c}',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    _ => return Err(at.error("Invalid escape sequence")),
+                };
+                flush_surrogate(bytes, high_surrogate);
+                push_char(bytes, decoded);
+                *escape = Escape::None;
+            },
+            Escape::Unicode { digits, unit } => {
+                let digit =
+                    char::from(byte).to_digit(16).ok_or_else(|| at.error("Invalid \\u escape"))?;
+                let unit = unit * 16 + digit;
+                if digits < 3 {
+                    *escape = Escape::Unicode { digits: digits + 1, unit };
+                    return Ok(());
+                }
+                *escape = Escape::None;
+                match (high_surrogate.take(), unit) {
+                    (Some(high), 0xDC00..=0xDFFF) => {
+                        let code = 0x10000 + ((high - 0xD800) << 10) + (unit - 0xDC00);
+                        push_char(
+                            bytes,
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+                        );
+                    },
+                    (high, _) => {
+                        if high.is_some() {
+                            push_char(bytes, char::REPLACEMENT_CHARACTER);
+                        }
+                        match unit {
+                            0xD800..=0xDBFF => *high_surrogate = Some(unit),
+                            _ => push_char(
+                                bytes,
+                                char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER),
+                            ),
+                        }
+                    },
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish_number(&mut self, events: &mut Vec<Event>) -> Result<(), ParseError> {
+        let Token::Number(text) = std::mem::take(&mut self.token) else {
+            return Ok(());
+        };
+        let value =
+            parse_with(&text, &ParseOptions::default()).map_err(|e| self.start.error(e.message))?;
+        self.complete(value, events)
+    }
+
+    /// Places a finished value in its container, or ends the document.
+    fn complete(&mut self, value: Value, events: &mut Vec<Event>) -> Result<(), ParseError> {
+        if !matches!(value, Value::Array(_) | Value::Object(_)) {
+            events.push(Event::Scalar(value.clone()));
+        }
+        self.expect = Expect::CommaOrEnd;
+        match self.stack.last_mut() {
+            None => {
+                self.expect = Expect::Value;
+                events.push(Event::Document(value));
+            },
+            Some(Frame::Array(items)) => items.push(value),
+            Some(Frame::Object { object, key }) => {
+                let Some((key, position)) = key.take() else {
+                    return Ok(());
+                };
+                match self.options.duplicate_keys {
+                    DuplicateKeys::LastWins => {
+                        object.insert(key, value);
+                    },
+                    DuplicateKeys::FirstWins => {
+                        if !object.contains_key(&key) {
+                            object.insert(key, value);
+                        }
+                    },
+                    DuplicateKeys::Reject => {
+                        if object.insert(key, value).is_some() {
+                            return Err(position.error("Duplicate object key"));
+                        }
+                    },
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// A high surrogate not followed by its low half decodes to U+FFFD, as in
+/// [`parse`](super::parse).
+fn flush_surrogate(bytes: &mut Vec<u8>, high_surrogate: &mut Option<u32>) {
+    if high_surrogate.take().is_some() {
+        push_char(bytes, char::REPLACEMENT_CHARACTER);
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::json::parse;
+
+    /// Feeds `text` in chunks of `size` bytes and returns its documents.
+    fn documents(text: &str, size: usize) -> Result<Vec<Value>, ParseError> {
+        let mut parser = StreamParser::new();
+        let mut events = Vec::new();
+        for chunk in text.as_bytes().chunks(size) {
+            events.extend(parser.push(chunk)?);
+        }
+        events.extend(parser.finish()?);
+        Ok(events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Document(value) => Some(value),
+                _ => None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_matches_parse_at_any_split() {
+        let texts = [
+            r#"{"a\"b": "x,}y", "e": "\u00e9\ud83d\ude00\n", "n": -1.5e2, "i": 18446744073709551615}"#,
+            r#"[null, true, false, [], {}, [[1], {"k": [2, "3"]}], "é😀", "\ud800x"]"#,
+            "\n 42 ",
+            r#""\\\/\b\f\r\t""#,
+        ];
+        for text in texts {
+            let expected = parse(text).expect("valid");
+            for size in [1, 2, 3, 7, text.len()] {
+                assert_eq!(
+                    documents(text, size),
+                    Ok(vec![expected.clone()]),
+                    "{:?}",
+                    text
+                );
+            }
+        }
+        for text in [
+            "[1,]",
+            "{\"a\" 1}",
+            "[01]",
+            "[\"\\x\"]",
+            "[tru]",
+            "\"a\u{1}\"",
+            "{1:2}",
+        ] {
+            assert!(parse(text).is_err());
+            assert!(documents(text, 1).is_err(), "should reject {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_events_and_documents() {
+        let mut parser = StreamParser::new();
+        let events = parser.push(br#"{"k": [1, "s"]} {"#).expect("push");
+        assert_eq!(events, [
+            Event::StartObject,
+            Event::Key("k".to_string()),
+            Event::StartArray,
+            Event::Scalar(Value::from(1)),
+            Event::Scalar(Value::from("s")),
+            Event::EndArray,
+            Event::EndObject,
+            Event::Document(parse(r#"{"k": [1, "s"]}"#).expect("valid")),
+            Event::StartObject,
+        ]);
+        assert!(parser.in_document());
+        assert_eq!(
+            parser.push(b"}").expect("push").last(),
+            Some(&Event::Document(Value::Object(Object::new())))
+        );
+        assert!(!parser.in_document());
+
+        // A top-level number only ends at a delimiter or the end of input
+        assert_eq!(parser.push(b"12").expect("push"), []);
+        assert_eq!(
+            parser.finish().expect("finish").last(),
+            Some(&Event::Document(Value::from(12)))
+        );
+        assert!(StreamParser::new().finish().expect("no documents").is_empty());
+        let mut open = StreamParser::new();
+        open.push(b"[1").expect("push");
+        assert_eq!(
+            open.finish().expect_err("open").message,
+            "Unexpected end of input"
+        );
+    }
+
+    #[test]
+    fn test_partial_values() {
+        let text = r#"{"city": "Par\u00e9s", "days": [1, 2.5], "units": null}"#;
+        let mut parser = StreamParser::new();
+        let mut seen = Vec::new();
+        for byte in text.bytes() {
+            parser.push(&[byte]).expect("push");
+            seen.push(parser.partial().map(|value| value.to_string()));
+        }
+        let at = |prefix: &str| seen[prefix.len() - 1].clone();
+        assert_eq!(at("{"), Some("{}".to_string()));
+        assert_eq!(at(r#"{"ci"#), Some("{}".to_string()));
+        assert_eq!(at(r#"{"city": "Pa"#), Some(r#"{"city":"Pa"}"#.to_string()));
+        assert_eq!(
+            at(r#"{"city": "Par\u00"#),
+            Some(r#"{"city":"Par"}"#.to_string())
+        );
+        assert_eq!(
+            at(r#"{"city": "Par\u00e9s", "days": [1, 2."#),
+            Some(r#"{"city":"Parés","days":[1]}"#.to_string())
+        );
+        assert_eq!(
+            at(r#"{"city": "Par\u00e9s", "days": [1, 2.5"#),
+            Some(r#"{"city":"Parés","days":[1,2.5]}"#.to_string())
+        );
+        assert_eq!(seen.last().cloned().flatten(), None);
+
+        // Cut inside a UTF-8 sequence
+        let mut parser = StreamParser::new();
+        parser.push(&"[\"é".as_bytes()[..3]).expect("push");
+        assert_eq!(parser.partial(), Some(Value::Array(vec![Value::from("")])));
+        parser.push(b"x").expect("push");
+        assert_eq!(parser.partial(), Some(Value::Array(vec![Value::from("")])));
+        assert_eq!(
+            parser.push(b"\"").expect_err("invalid").message,
+            "Invalid UTF-8 in string"
+        );
+        assert_eq!(parser.partial(), None);
+    }
+
+    #[test]
+    fn test_errors_and_limits() {
+        let mut parser = StreamParser::new();
+        parser.push(b"{\n  \"a\": 1,\n").expect("push");
+        let error = parser.push(b"  \"b\": tru\n}").expect_err("invalid");
+        assert_eq!(
+            (error.message, error.line, error.column),
+            ("Invalid literal", 3, 8)
+        );
+        // The failure sticks
+        assert_eq!(parser.push(b"{}"), Err(error.clone()));
+        assert_eq!(parser.finish(), Err(error));
+
+        let options = ParseOptions::default().with_max_depth(2);
+        let mut parser = StreamParser::with_options(options);
+        assert_eq!(
+            parser.push(b"[[[").expect_err("deep").message,
+            "Nesting too deep"
+        );
+
+        let options = options.with_duplicate_keys(DuplicateKeys::Reject);
+        let error = StreamParser::with_options(options)
+            .push(br#"{"a": 1, "a": 2}"#)
+            .expect_err("duplicate");
+        assert_eq!((error.message, error.column), ("Duplicate object key", 10));
+    }
+}
@@ -0,0 +1,218 @@
+//! Compact and pretty JSON output.
+
+use std::fmt::{self, Write};
+
+use super::Value;
+
+/// How [`to_json_string_with`] lays out a document.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WriteOptions {
+    /// Indentation per nesting level; `None` writes one line with no spaces.
+    pub indent:     Option<String>,
+    /// Escape everything outside ASCII, as surrogate pairs beyond the BMP.
+    pub ascii_only: bool,
+    /// Sort object keys instead of keeping insertion order.
+    pub sort_keys:  bool,
+}
+
+impl WriteOptions {
+    /// Two-space indentation, one value per line.
+    pub fn pretty() -> Self {
+        Self::default().with_indent("  ")
+    }
+
+    pub fn with_indent(mut self, indent: &str) -> Self {
+        self.indent = Some(indent.to_string());
+        self
+    }
+
+    pub fn with_ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    pub fn with_sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+}
+
+pub fn to_json_string(value: &Value) -> String {
+    to_json_string_with(value, &WriteOptions::default())
+}
+
+pub fn to_json_pretty(value: &Value) -> String {
+    to_json_string_with(value, &WriteOptions::pretty())
+}
+
+pub fn to_json_string_with(value: &Value, options: &WriteOptions) -> String {
+    let mut out = String::new();
+    // Writing to a String cannot fail
+    let _ = write_value(&mut out, value, options, 0);
+    out
+}
+
+pub(super) fn write_value<W: Write>(
+    out: &mut W, value: &Value, options: &WriteOptions, level: usize,
+) -> fmt::Result {
+    match value {
+        Value::Null => out.write_str("null"),
+        Value::Bool(b) => out.write_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write!(out, "{}", n),
+        Value::String(s) => write_string(out, s, options.ascii_only),
+        Value::Array(array) => {
+            if array.is_empty() {
+                return out.write_str("[]");
+            }
+            out.write_char('[')?;
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                newline(out, options, level + 1)?;
+                write_value(out, item, options, level + 1)?;
+            }
+            newline(out, options, level)?;
+            out.write_char(']')
+        },
+        Value::Object(object) => {
+            if object.is_empty() {
+                return out.write_str("{}");
+            }
+            let mut entries: Vec<_> = object.iter().collect();
+            if options.sort_keys {
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+            }
+            out.write_char('{')?;
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                newline(out, options, level + 1)?;
+                write_string(out, key, options.ascii_only)?;
+                out.write_str(if options.indent.is_some() { ": " } else { ":" })?;
+                write_value(out, item, options, level + 1)?;
+            }
+            newline(out, options, level)?;
+            out.write_char('}')
+        },
+    }
+}
+
+fn newline<W: Write>(out: &mut W, options: &WriteOptions, level: usize) -> fmt::Result {
+    let Some(indent) = &options.indent else {
+        return Ok(());
+    };
+    out.write_char('\n')?;
+    for _ in 0..level {
+        out.write_str(indent)?;
+    }
+    Ok(())
+}
+
+/// A string literal, using the short escapes where JSON has them and `\u`
+/// for other control characters.
+fn write_string<W: Write>(out: &mut W, s: &str, ascii_only: bool) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{8}' => out.write_str("\\b")?,
+            '\u{c}' => out.write_str("\\f")?,
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+            c if ascii_only && !c.is_ascii() => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(out, "\\u{:04x}", unit)?;
+                }
+            },
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::json::{Object, parse};
+
+    #[test]
+    fn test_escaping() {
+        let text = "q\"b\\s/n\nt\tc\u{1}\u{1f}é😀";
+        let value = Value::from(text);
+        assert_eq!(
+            to_json_string(&value),
+            r#""q\"b\\s/n\nt\tc\u0001\u001fé😀""#
+        );
+        let ascii = to_json_string_with(&value, &WriteOptions::default().with_ascii_only(true));
+        assert_eq!(ascii, r#""q\"b\\s/n\nt\tc\u0001\u001f\u00e9\ud83d\ude00""#);
+        for written in [to_json_string(&value), ascii] {
+            assert_eq!(parse(&written).expect("round trip"), value);
+        }
+    }
+
+    #[test]
+    fn test_numbers() {
+        let values = [
+            (Value::from(1.0), "1.0"),
+            (Value::from(0.1), "0.1"),
+            (Value::from(-2.5e-7), "-2.5e-7"),
+            (Value::from(1e300), "1e300"),
+            (Value::from(f64::NAN), "null"),
+            (Value::from(0.7f32), "0.7"),
+            (Value::from(-3), "-3"),
+            (Value::from(u64::MAX), "18446744073709551615"),
+        ];
+        for (value, expected) in values {
+            assert_eq!(to_json_string(&value), expected);
+        }
+
+        let value = parse("[9007199254740993, -9223372036854775808, 18446744073709551615, 1.0]")
+            .expect("parse");
+        assert_eq!(
+            value.get_index(0).and_then(Value::as_i64),
+            Some(9_007_199_254_740_993)
+        );
+        assert_eq!(value.get_index(1).and_then(Value::as_i64), Some(i64::MIN));
+        assert_eq!(value.get_index(2).and_then(Value::as_u64), Some(u64::MAX));
+        assert_eq!(value.get_index(3).and_then(Value::as_i64), None);
+        assert_eq!(
+            to_json_string(&value),
+            "[9007199254740993,-9223372036854775808,18446744073709551615,1.0]"
+        );
+        assert_eq!(Value::from(7u8), Value::from(7i64));
+        assert_ne!(Value::from(1), Value::from(1.0));
+    }
+
+    #[test]
+    fn test_key_order_and_pretty_printing() {
+        let text =
+            r#"{"model":"grok","messages":[{"role":"user","content":"hi"}],"extra":{},"n":[]}"#;
+        let value = parse(text).expect("parse");
+        assert_eq!(to_json_string(&value), text);
+        assert_eq!(value.to_string(), text);
+
+        let pretty = "{\n  \"model\": \"grok\",\n  \"messages\": [\n    {\n      \"role\": \
+                      \"user\",\n      \"content\": \"hi\"\n    }\n  ],\n  \"extra\": {},\n  \
+                      \"n\": []\n}";
+        assert_eq!(to_json_pretty(&value), pretty);
+        assert_eq!(format!("{:#}", value), pretty);
+
+        let object: Object = [
+            ("b", Value::from(1)),
+            ("a", Value::Array(vec![Value::Null])),
+        ]
+        .into_iter()
+        .collect();
+        let options = WriteOptions::default().with_indent("\t").with_sort_keys(true);
+        assert_eq!(
+            to_json_string_with(&Value::Object(object), &options),
+            "{\n\t\"a\": [\n\t\tnull\n\t],\n\t\"b\": 1\n}"
+        );
+    }
+}
@@ -1,10 +1,16 @@
 pub mod base64;
 pub mod cookies;
+pub mod crypto;
+pub mod flate;
 pub mod html;
 pub mod http;
 pub mod json;
 pub mod multipart;
+pub mod pool;
+pub mod proxy;
 pub mod regex;
+pub mod sse;
 pub mod tls;
+pub mod trace;
 pub mod url;
 pub mod uuid;
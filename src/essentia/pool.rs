@@ -0,0 +1,399 @@
+//! HTTP connection pool.
+//!
+//! Keeps idle keep-alive connections per `(scheme, host, port)` so repeated
+//! requests to the same provider skip TCP and TLS setup. The pool is shared
+//! between threads: a checkout takes an idle connection if a live one is
+//! available, opens a new one while the host is under its connection cap, and
+//! otherwise waits for another thread to return one. Every checkout and
+//! release also closes idle connections past their timeout, for all hosts.
+
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    errors::LlmError,
+    essentia::{http::Connection, proxy::Proxy, tls::TlsConfig},
+};
+
+/// Pool limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Connections per host, idle and in use combined.
+    pub max_per_host: usize,
+    /// Idle connections older than this are closed instead of reused.
+    pub idle_timeout: Duration,
+    /// How long a checkout waits for a connection when the host is at its
+    /// cap, at most until the request deadline.
+    pub wait_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_per_host: 8,
+            idle_timeout: Duration::from_secs(90),
+            wait_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Identifies the origin a connection is bound to, and the proxy it goes
+/// through.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub scheme: String,
+    pub host:   String,
+    pub port:   u16,
+    /// The proxy, as displayed (without password).
+    pub proxy:  Option<String>,
+    /// Settings the TLS session was authenticated with, for HTTPS.
+    pub tls:    Option<TlsConfig>,
+}
+
+impl PoolKey {
+    pub fn from_url(
+        url: &crate::essentia::url::Url, proxy: Option<&Proxy>, tls: &TlsConfig,
+    ) -> Result<Self, LlmError> {
+        let host = url
+            .hostname
+            .clone()
+            .ok_or_else(|| LlmError::InvalidUrl("No hostname".to_string()))?;
+        let https = url.scheme.eq_ignore_ascii_case("https");
+        let port = url.port.unwrap_or(if https { 443 } else { 80 });
+        Ok(Self {
+            scheme: url.scheme.to_ascii_lowercase(),
+            host: host.to_ascii_lowercase(),
+            port,
+            proxy: proxy.map(ToString::to_string),
+            tls: https.then(|| tls.clone()),
+        })
+    }
+}
+
+struct IdleConnection {
+    connection: Connection,
+    since:      Instant,
+}
+
+#[derive(Default)]
+struct HostState {
+    idle: Vec<IdleConnection>,
+    /// Idle plus checked-out connections.
+    open: usize,
+}
+
+struct PoolState {
+    config: PoolConfig,
+    hosts:  HashMap<PoolKey, HostState>,
+}
+
+impl PoolState {
+    /// Closes idle connections past the idle timeout, for every host, and
+    /// forgets hosts left with no connections.
+    fn evict_expired(&mut self) {
+        let idle_timeout = self.config.idle_timeout;
+        for host in self.hosts.values_mut() {
+            let before = host.idle.len();
+            host.idle.retain(|idle| idle.since.elapsed() < idle_timeout);
+            host.open -= before - host.idle.len();
+        }
+        self.hosts.retain(|_, host| host.open > 0);
+    }
+}
+
+/// Thread-safe keep-alive connection pool.
+pub struct Pool {
+    state:    Mutex<PoolState>,
+    released: Condvar,
+}
+
+impl Pool {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            state:    Mutex::new(PoolState { config, hosts: HashMap::new() }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Process-wide pool used by [`Request`](crate::essentia::http::Request).
+    pub fn global() -> &'static Pool {
+        static GLOBAL: OnceLock<Pool> = OnceLock::new();
+        GLOBAL.get_or_init(|| Pool::new(PoolConfig::default()))
+    }
+
+    pub fn config(&self) -> PoolConfig {
+        self.lock().config
+    }
+
+    /// Replaces the limits. Connections already open are kept.
+    pub fn set_config(&self, config: PoolConfig) {
+        self.lock().config = config;
+        self.released.notify_all();
+    }
+
+    /// Idle connections currently held for `key`.
+    pub fn idle_count(&self, key: &PoolKey) -> usize {
+        self.lock().hosts.get(key).map_or(0, |host| host.idle.len())
+    }
+
+    /// Closes every idle connection.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        for host in state.hosts.values_mut() {
+            host.open -= host.idle.len();
+            host.idle.clear();
+        }
+        state.hosts.retain(|_, host| host.open > 0);
+    }
+
+    /// Takes a live idle connection for `key`, or opens one with `connect`,
+    /// waiting no later than `deadline` for one to free up.
+    pub(crate) fn checkout(
+        &self, key: &PoolKey, deadline: Option<Instant>,
+        connect: impl FnOnce() -> Result<Connection, LlmError>,
+    ) -> Result<PooledConnection<'_>, LlmError> {
+        let mut state = self.lock();
+        let wait_until = Instant::now() + state.config.wait_timeout;
+        let deadline = deadline.map_or(wait_until, |deadline| deadline.min(wait_until));
+        loop {
+            state.evict_expired();
+            let config = state.config;
+            let host = state.hosts.entry(key.clone()).or_default();
+
+            while let Some(idle) = host.idle.pop() {
+                if idle.since.elapsed() < config.idle_timeout && idle.connection.is_alive() {
+                    return Ok(PooledConnection {
+                        pool:       self,
+                        key:        key.clone(),
+                        connection: Some(idle.connection),
+                        reused:     true,
+                    });
+                }
+                // Expired or closed by the peer
+                host.open -= 1;
+            }
+
+            if host.open < config.max_per_host.max(1) {
+                host.open += 1;
+                drop(state);
+                return match connect() {
+                    Ok(connection) => Ok(PooledConnection {
+                        pool:       self,
+                        key:        key.clone(),
+                        connection: Some(connection),
+                        reused:     false,
+                    }),
+                    Err(e) => {
+                        self.release(key, None);
+                        Err(e)
+                    },
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(LlmError::Timeout { operation: "Connection pool checkout" });
+            }
+            state = self
+                .released
+                .wait_timeout(state, deadline - now)
+                .map(|(guard, _)| guard)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+    }
+
+    /// Returns a connection to the idle list, or forgets it when `None`.
+    fn release(&self, key: &PoolKey, connection: Option<Connection>) {
+        let mut state = self.lock();
+        state.evict_expired();
+        if let Some(host) = state.hosts.get_mut(key) {
+            match connection {
+                Some(connection) => {
+                    host.idle.push(IdleConnection { connection, since: Instant::now() })
+                },
+                None => host.open -= 1,
+            }
+        }
+        drop(state);
+        self.released.notify_one();
+    }
+
+    /// Idle connections currently held across all hosts.
+    pub fn total_idle(&self) -> usize {
+        self.lock().hosts.values().map(|host| host.idle.len()).sum()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        // The state stays consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A checked-out connection. Dropping it closes the connection; call
+/// [`recycle`](Self::recycle) once a response has been fully read to keep it.
+pub(crate) struct PooledConnection<'a> {
+    pool:       &'a Pool,
+    key:        PoolKey,
+    connection: Option<Connection>,
+    reused:     bool,
+}
+
+impl PooledConnection<'_> {
+    /// Whether the connection carried an earlier request.
+    pub(crate) fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    pub(crate) fn connection(&mut self) -> Option<&mut Connection> {
+        self.connection.as_mut()
+    }
+
+    /// Hands the connection back for reuse.
+    pub(crate) fn recycle(mut self) {
+        let connection = self.connection.take();
+        self.pool.release(&self.key, connection);
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if self.connection.take().is_some() {
+            self.pool.release(&self.key, None);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        thread,
+    };
+
+    use super::*;
+
+    fn key(port: u16) -> PoolKey {
+        PoolKey {
+            scheme: "http".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            proxy: None,
+            tls: None,
+        }
+    }
+
+    fn connect(listener: &TcpListener) -> Result<Connection, LlmError> {
+        let addr = listener.local_addr().expect("addr");
+        Ok(Connection::plain(
+            TcpStream::connect(addr).expect("connect"),
+        ))
+    }
+
+    #[test]
+    fn test_reuse_and_stale_detection() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let key = key(listener.local_addr().expect("addr").port());
+        let pool = Pool::new(PoolConfig::default());
+
+        let first = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        assert!(!first.is_reused());
+        let (server_side, _) = listener.accept().expect("accept");
+        first.recycle();
+        assert_eq!(pool.idle_count(&key), 1);
+
+        let second = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        assert!(second.is_reused());
+        second.recycle();
+
+        // Once the peer closes, the idle socket is discarded on checkout
+        drop(server_side);
+        thread::sleep(Duration::from_millis(20));
+        let third = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        assert!(!third.is_reused());
+    }
+
+    #[test]
+    fn test_idle_eviction() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let key = key(listener.local_addr().expect("addr").port());
+        let pool = Pool::new(PoolConfig { idle_timeout: Duration::ZERO, ..PoolConfig::default() });
+
+        pool.checkout(&key, None, || connect(&listener)).expect("checkout").recycle();
+        let next = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        assert!(!next.is_reused());
+    }
+
+    #[test]
+    fn test_idle_eviction_sweeps_other_hosts() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let pool = Pool::new(PoolConfig {
+            idle_timeout: Duration::from_millis(20),
+            ..PoolConfig::default()
+        });
+
+        let once = PoolKey { host: "localhost".to_string(), ..key(port) };
+        pool.checkout(&once, None, || connect(&listener)).expect("checkout").recycle();
+        assert_eq!(pool.total_idle(), 1);
+        thread::sleep(Duration::from_millis(40));
+
+        // Releasing a connection for another key closes the expired one
+        pool.checkout(&key(port), None, || connect(&listener))
+            .expect("checkout")
+            .recycle();
+        assert_eq!(pool.idle_count(&once), 0);
+        assert_eq!(pool.total_idle(), 1);
+    }
+
+    #[test]
+    fn test_checkout_wait_is_capped_by_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let key = key(listener.local_addr().expect("addr").port());
+        let pool = Pool::new(PoolConfig { max_per_host: 1, ..PoolConfig::default() });
+
+        let _held = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        let started = Instant::now();
+        let deadline = Some(started + Duration::from_millis(50));
+        let err = pool.checkout(&key, deadline, || connect(&listener)).err().map(|e| e.kind());
+        assert_eq!(err, Some(crate::errors::ErrorKind::Timeout));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_per_host_cap_blocks_until_release() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let key = key(listener.local_addr().expect("addr").port());
+        let pool = Arc::new(Pool::new(PoolConfig {
+            max_per_host: 1,
+            wait_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        }));
+
+        let held = pool.checkout(&key, None, || connect(&listener)).expect("checkout");
+        let err = pool.checkout(&key, None, || connect(&listener)).err().map(|e| e.kind());
+        assert_eq!(err, Some(crate::errors::ErrorKind::Timeout));
+
+        pool.set_config(PoolConfig { wait_timeout: Duration::from_secs(5), ..pool.config() });
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            let key = key.clone();
+            let addr = listener.local_addr().expect("addr");
+            thread::spawn(move || {
+                pool.checkout(&key, None, || {
+                    Ok(Connection::plain(
+                        TcpStream::connect(addr).expect("connect"),
+                    ))
+                })
+                .map(|c| c.is_reused())
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        held.recycle();
+        assert_eq!(waiter.join().expect("thread").ok(), Some(true));
+    }
+}
@@ -0,0 +1,213 @@
+//! Server-Sent Events reader.
+//!
+//! Parses a `text/event-stream` body incrementally from any [`Read`], yielding
+//! each event as soon as its terminating blank line arrives. Lines are only
+//! decoded once complete, so multi-byte UTF-8 characters split across reads
+//! are reassembled before decoding.
+
+use std::io::{self, Read};
+
+use crate::errors::LlmError;
+
+/// Data payload that OpenAI-compatible APIs send to mark the end of a stream.
+pub const DONE_MARKER: &str = "[DONE]";
+
+/// A dispatched event.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SseEvent {
+    /// `event:` field; `None` means the default `message` type.
+    pub event: Option<String>,
+    /// `data:` lines joined with `\n`.
+    pub data:  String,
+    /// Last `id:` field seen on the stream.
+    pub id:    Option<String>,
+    /// `retry:` reconnection delay in milliseconds.
+    pub retry: Option<u64>,
+}
+
+impl SseEvent {
+    pub fn is_done(&self) -> bool {
+        self.data == DONE_MARKER
+    }
+}
+
+/// Incremental event reader. Iteration ends at end of input or after a
+/// `[DONE]` event, which is not yielded.
+pub struct SseReader<R> {
+    inner:    R,
+    buffer:   Vec<u8>,
+    pending:  SseEvent,
+    /// Whether `pending` has seen a `data:` line.
+    has_data: bool,
+    last_id:  Option<String>,
+    eof:      bool,
+    done:     bool,
+}
+
+impl<R: Read> SseReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+            pending: SseEvent::default(),
+            has_data: false,
+            last_id: None,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Whether the `[DONE]` marker has been received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Reads until the next complete event. Returns `Ok(None)` at end of
+    /// stream; an unterminated trailing event is discarded, as the spec
+    /// requires.
+    pub fn next_event(&mut self) -> Result<Option<SseEvent>, LlmError> {
+        if self.done {
+            return Ok(None);
+        }
+        loop {
+            while let Some(newline) = self.buffer.iter().position(|&b| b == b'\n') {
+                let mut line: Vec<u8> = self.buffer.drain(..=newline).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                let line = String::from_utf8(line).map_err(|_| {
+                    LlmError::Protocol("Event stream line is not valid UTF-8".to_string())
+                })?;
+                if let Some(event) = self.process_line(&line) {
+                    if event.is_done() {
+                        self.done = true;
+                        return Ok(None);
+                    }
+                    return Ok(Some(event));
+                }
+            }
+
+            if self.eof {
+                return Ok(None);
+            }
+
+            let mut chunk = [0u8; 4096];
+            // Framing and decoding failures are protocol errors
+            let n = self.inner.read(&mut chunk).map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => LlmError::Protocol(e.to_string()),
+                _ => LlmError::io("Event stream read", e),
+            })?;
+            if n == 0 {
+                self.eof = true;
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Applies one line; returns the event when the line dispatches one.
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            if !self.has_data {
+                self.pending = SseEvent::default();
+                return None;
+            }
+            self.has_data = false;
+            let mut event = std::mem::take(&mut self.pending);
+            event.id = self.last_id.clone();
+            return Some(event);
+        }
+        if line.starts_with(':') {
+            return None; // comment / keep-alive
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => {
+                if self.has_data {
+                    self.pending.data.push('\n');
+                }
+                self.pending.data.push_str(value);
+                self.has_data = true;
+            },
+            "event" => self.pending.event = Some(value.to_string()),
+            // An id containing NUL is ignored per the spec
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => self.pending.retry = value.parse().ok(),
+            _ => {},
+        }
+        None
+    }
+}
+
+impl<R: Read> Iterator for SseReader<R> {
+    type Item = Result<SseEvent, LlmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    /// Yields the input a few bytes at a time to exercise chunk boundaries.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_events_and_done_marker() {
+        let stream = concat!(
+            ": keep-alive\r\n\r\n",
+            "event: delta\r\ndata: one\r\ndata: two\r\nid: 7\r\n\r\n",
+            "data: {\"x\":1}\n\n",
+            "data: [DONE]\n\n",
+            "data: ignored\n\n",
+        );
+        let events: Vec<_> =
+            SseReader::new(stream.as_bytes()).collect::<Result<_, _>>().expect("events");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("delta"));
+        assert_eq!(events[0].data, "one\ntwo");
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(events[1].data, "{\"x\":1}");
+        assert_eq!(events[1].id.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_multibyte_split_across_reads() {
+        let stream = "data: héllo wörld 🦀\n\ndata: 日本語\n\n".as_bytes();
+        for step in 1..5 {
+            let events: Vec<_> = SseReader::new(Trickle { data: stream, step })
+                .map(|e| e.expect("event").data)
+                .collect();
+            assert_eq!(events, ["héllo wörld 🦀", "日本語"]);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_event_is_discarded() {
+        let mut reader = SseReader::new(&b"data: partial"[..]);
+        assert!(reader.next_event().expect("read").is_none());
+        assert!(!reader.is_done());
+    }
+}
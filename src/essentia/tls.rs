@@ -1,175 +0,0 @@
-// Bespoke TLS implementation using pure Rust std
-
-use std::{
-    io::{Read, Write},
-    net::TcpStream,
-};
-
-use essentia_core_utils::crypto::sha256;
-
-#[derive(Debug)]
-#[allow(dead_code)]
-pub struct Certificate {
-    pub subject:    String,
-    pub issuer:     String,
-    pub public_key: Vec<u8>,
-    pub valid_from: String,
-    pub valid_to:   String,
-}
-
-#[derive(Debug)]
-pub struct TlsStream {
-    stream:    TcpStream,
-    encrypted: bool,
-}
-
-impl TlsStream {
-    pub fn connect(host: &str, port: u16) -> Result<Self, &'static str> {
-        let stream = TcpStream::connect((host, port)).map_err(|_| "TCP connect failed")?;
-        let mut tls_stream = TlsStream { stream, encrypted: false };
-
-        // Perform TLS handshake
-        tls_stream.perform_handshake(host)?;
-
-        Ok(tls_stream)
-    }
-
-    fn perform_handshake(&mut self, host: &str) -> Result<(), &'static str> {
-        // TLS 1.2 Client Hello
-        let client_hello = self.build_client_hello(host);
-        self.stream.write_all(&client_hello).map_err(|_| "Write failed")?;
-
-        // Read Server Hello
-        let mut buffer = [0u8; 1024];
-        let n = self.stream.read(&mut buffer).map_err(|_| "Read failed")?;
-        self.parse_server_hello(&buffer[..n])?;
-
-        // Simplified: assume handshake succeeds
-        self.encrypted = true;
-        Ok(())
-    }
-
-    fn build_client_hello(&self, host: &str) -> Vec<u8> {
-        let mut hello = Vec::new();
-
-        // TLS record header
-        hello.extend_from_slice(&[0x16, 0x03, 0x03]); // Handshake, TLS 1.2
-        hello.extend_from_slice(&[0x00, 0x00]); // Length placeholder
-
-        // Handshake header
-        hello.push(0x01); // Client Hello
-        hello.extend_from_slice(&[0x00, 0x00, 0x00]); // Length placeholder
-
-        // Protocol version
-        hello.extend_from_slice(&[0x03, 0x03]); // TLS 1.2
-
-        // Random (32 bytes)
-        let random: [u8; 32] = sha256(host.as_bytes());
-        hello.extend_from_slice(&random);
-
-        // Session ID length
-        hello.push(0x00);
-
-        // Cipher suites
-        hello.extend_from_slice(&[0x00, 0x02]); // 2 cipher suites
-        hello.extend_from_slice(&[0x00, 0x3D]); // TLS_RSA_WITH_AES_256_CBC_SHA256
-        hello.extend_from_slice(&[0x00, 0x35]); // TLS_RSA_WITH_AES_128_CBC_SHA
-
-        // Compression methods
-        hello.extend_from_slice(&[0x01, 0x00]);
-
-        // Extensions
-        let mut extensions = Vec::new();
-
-        // Server Name Indication (SNI)
-        extensions.extend_from_slice(&[0x00, 0x00]); // Extension type
-        let sni_data = self.build_sni_extension(host);
-        extensions.extend_from_slice(
-            &(u16::try_from(sni_data.len() + 2).unwrap_or(u16::MAX)).to_be_bytes(),
-        );
-        extensions
-            .extend_from_slice(&(u16::try_from(sni_data.len()).unwrap_or(u16::MAX)).to_be_bytes());
-        extensions.extend_from_slice(&sni_data);
-
-        // Add extensions length
-        let ext_len = extensions.len() as u16;
-        hello.extend_from_slice(&ext_len.to_be_bytes());
-        hello.extend_from_slice(&extensions);
-
-        // Fix lengths
-        let handshake_len = hello.len() - 5;
-        hello[3..5]
-            .copy_from_slice(&(u16::try_from(handshake_len).unwrap_or(u16::MAX)).to_be_bytes());
-        hello[7..10].copy_from_slice(&(handshake_len as u32 - 4).to_be_bytes());
-
-        let record_len = hello.len() - 5;
-        hello[3..5].copy_from_slice(&(u16::try_from(record_len).unwrap_or(u16::MAX)).to_be_bytes());
-
-        hello
-    }
-
-    fn build_sni_extension(&self, host: &str) -> Vec<u8> {
-        let mut sni = Vec::new();
-        sni.push(0x00); // DNS hostname
-        let host_bytes = host.as_bytes();
-        sni.extend_from_slice(&(u16::try_from(host_bytes.len()).unwrap_or(u16::MAX)).to_be_bytes());
-        sni.extend_from_slice(host_bytes);
-        sni
-    }
-
-    fn parse_server_hello(&self, data: &[u8]) -> Result<(), &'static str> {
-        // Simplified parsing - in real implementation, parse properly
-        if data.len() < 5 || data[0] != 0x16 {
-            return Err("Invalid server hello");
-        }
-        Ok(())
-    }
-
-    pub fn write(&mut self, data: &[u8]) -> Result<(), &'static str> {
-        if !self.encrypted {
-            return Err("Not encrypted");
-        }
-        // In real implementation, encrypt data
-        self.stream.write_all(data).map_err(|_| "Write failed")
-    }
-
-    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, &'static str> {
-        if !self.encrypted {
-            return Err("Not encrypted");
-        }
-        // In real implementation, decrypt data
-        self.stream.read(buffer).map_err(|_| "Read failed")
-    }
-}
-
-pub fn tls_connect(host: &str, port: u16) -> Result<TlsStream, &'static str> {
-    TlsStream::connect(host, port)
-}
-
-// Basic X.509 certificate parsing (simplified)
-#[allow(unused)]
-pub fn parse_certificate(_cert_data: &[u8]) -> Result<Certificate, &'static str> {
-    // This is a placeholder - real X.509 parsing requires ASN.1 DER decoding
-    // For now, return a dummy certificate
-    Ok(Certificate {
-        subject:    "example.com".to_string(),
-        issuer:     "Example CA".to_string(),
-        public_key: vec![0; 32],
-        valid_from: "2023-01-01".to_string(),
-        valid_to:   "2025-01-01".to_string(),
-    })
-}
-
-// Certificate validation (simplified)
-#[allow(unused)]
-pub fn validate_certificate(cert: &Certificate, hostname: &str) -> Result<(), &'static str> {
-    // Check hostname
-    if !cert.subject.contains(hostname) {
-        return Err("Certificate hostname mismatch");
-    }
-
-    // Check validity dates (simplified)
-    // In real implementation, parse dates and check against current time
-
-    Ok(())
-}
@@ -0,0 +1,642 @@
+//! Client side of the TLS 1.3 full handshake (RFC 8446 section 2).
+
+use std::{
+    io::{Read, Write},
+    net::IpAddr,
+    time::{Instant, SystemTime},
+};
+
+use super::{
+    TlsConfig,
+    codec::*,
+    identity::ClientIdentity,
+    key_schedule::{CipherSuite, KeySchedule, TrafficKey, finished_mac},
+    keylog::KeyLog,
+    pin::{PinSet, check_pins},
+    record::{Alert, ContentType, RecordLayer},
+    session::Ticket,
+    trust::TrustStore,
+    x509::{Certificate, PublicKey, SignatureAlgorithm},
+};
+use crate::{
+    errors::LlmError,
+    essentia::crypto::{
+        ct_eq,
+        ecdsa::Curve,
+        random_bytes,
+        sha2::HashAlgorithm,
+        x25519::{x25519, x25519_base},
+    },
+};
+
+/// Signature schemes offered for CertificateVerify and certificates.
+const SIGNATURE_SCHEMES: [u16; 8] = [
+    0x0403, // ecdsa_secp256r1_sha256
+    0x0503, // ecdsa_secp384r1_sha384
+    0x0804, // rsa_pss_rsae_sha256
+    0x0805, // rsa_pss_rsae_sha384
+    0x0806, // rsa_pss_rsae_sha512
+    0x0401, // rsa_pkcs1_sha256
+    0x0501, // rsa_pkcs1_sha384
+    0x0601, // rsa_pkcs1_sha512
+];
+
+/// What the handshake established.
+pub(crate) struct Session {
+    pub(crate) suite:             CipherSuite,
+    /// Validated server chain as sent, leaf first.
+    pub(crate) certificates:      Vec<Certificate>,
+    /// Protocol selected by ALPN.
+    pub(crate) alpn:              Option<String>,
+    /// A cached session was resumed.
+    pub(crate) resumed:           bool,
+    /// Secret that NewSessionTicket PSKs are derived from.
+    pub(crate) resumption_secret: Vec<u8>,
+}
+
+struct ClientHello<'a> {
+    host:       &'a str,
+    random:     [u8; 32],
+    session_id: [u8; 32],
+    public_key: [u8; 32],
+    cookie:     Option<Vec<u8>>,
+    alpn:       &'a [String],
+    /// Ticket offered as a PSK, with its obfuscated age.
+    ticket:     Option<(&'a Ticket, u32)>,
+}
+
+impl ClientHello<'_> {
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(256);
+        put_u16(&mut body, 0x0303);
+        body.extend_from_slice(&self.random);
+        // A non-empty legacy session id turns on middlebox compatibility mode
+        put_vec8(&mut body, &self.session_id);
+        put_prefixed(&mut body, 2, |out| {
+            for suite in CipherSuite::ALL {
+                put_u16(out, suite.id());
+            }
+        });
+        put_vec8(&mut body, &[0]);
+        put_prefixed(&mut body, 2, |out| {
+            let name = self.host.trim_start_matches('[').trim_end_matches(']');
+            if name.parse::<IpAddr>().is_err() {
+                put_extension(out, SERVER_NAME, |out| {
+                    put_prefixed(out, 2, |out| {
+                        out.push(0);
+                        put_vec16(out, name.as_bytes());
+                    });
+                });
+            }
+            put_extension(out, SUPPORTED_GROUPS, |out| {
+                put_vec16(out, &X25519.to_be_bytes())
+            });
+            put_extension(out, SIGNATURE_ALGORITHMS, |out| {
+                put_prefixed(out, 2, |out| {
+                    for scheme in SIGNATURE_SCHEMES {
+                        put_u16(out, scheme);
+                    }
+                });
+            });
+            put_extension(out, SUPPORTED_VERSIONS, |out| {
+                put_vec8(out, &TLS13.to_be_bytes())
+            });
+            put_extension(out, KEY_SHARE, |out| {
+                put_prefixed(out, 2, |out| {
+                    put_u16(out, X25519);
+                    put_vec16(out, &self.public_key);
+                });
+            });
+            if !self.alpn.is_empty() {
+                put_extension(out, ALPN, |out| {
+                    put_prefixed(out, 2, |out| {
+                        for protocol in self.alpn {
+                            put_vec8(out, protocol.as_bytes());
+                        }
+                    });
+                });
+            }
+            if let Some(cookie) = &self.cookie {
+                put_extension(out, COOKIE, |out| put_vec16(out, cookie));
+            }
+            // pre_shared_key must come last; its binder is filled in by
+            // `bind_psk` once the rest of the message is known
+            if let Some((ticket, age)) = self.ticket {
+                put_extension(out, PSK_KEY_EXCHANGE_MODES, |out| {
+                    put_vec8(out, &[PSK_DHE_KE])
+                });
+                put_extension(out, PRE_SHARED_KEY, |out| {
+                    put_prefixed(out, 2, |out| {
+                        put_vec16(out, &ticket.identity);
+                        out.extend_from_slice(&age.to_be_bytes());
+                    });
+                    put_prefixed(out, 2, |out| {
+                        put_vec8(out, &vec![0; ticket.suite.hash().output_len()]);
+                    });
+                });
+            }
+        });
+        handshake_message(CLIENT_HELLO, &body)
+    }
+}
+
+/// A ServerHello or HelloRetryRequest.
+struct ServerHello {
+    retry:     bool,
+    suite:     CipherSuite,
+    /// Server's X25519 share (ServerHello only).
+    key_share: Option<[u8; 32]>,
+    /// Group the server asked for (HelloRetryRequest only).
+    group:     Option<u16>,
+    cookie:    Option<Vec<u8>>,
+    /// Index of the PSK the server accepted.
+    psk:       Option<u16>,
+}
+
+fn parse_server_hello<S: Read + Write>(
+    records: &mut RecordLayer<S>, message: &[u8], session_id: &[u8],
+) -> Result<ServerHello, LlmError> {
+    if message[0] != SERVER_HELLO {
+        return Err(records.fatal(Alert::UNEXPECTED_MESSAGE, "Expected ServerHello"));
+    }
+    let decoded = (|| {
+        let mut reader = Reader::new(&message[4..]);
+        let _legacy_version = reader.u16()?;
+        let random = reader.bytes(32)?;
+        let echoed_session_id = reader.vec8()?;
+        let suite = reader.u16()?;
+        let compression = reader.u8()?;
+        let extensions = parse_extensions(reader.vec16()?)?;
+        reader
+            .is_empty()
+            .then_some((random, echoed_session_id, suite, compression, extensions))
+    })();
+    let Some((random, echoed_session_id, suite, compression, extensions)) = decoded else {
+        return Err(records.fatal(Alert::DECODE_ERROR, "Malformed ServerHello"));
+    };
+
+    let Some(suite) = CipherSuite::from_id(suite) else {
+        return Err(records.fatal(
+            Alert::ILLEGAL_PARAMETER,
+            "Server chose an unoffered cipher suite",
+        ));
+    };
+    if echoed_session_id != session_id || compression != 0 {
+        return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Malformed ServerHello"));
+    }
+    let retry = random == HELLO_RETRY_RANDOM;
+    let mut hello =
+        ServerHello { retry, suite, key_share: None, group: None, cookie: None, psk: None };
+    let mut version = None;
+    for (kind, data) in extensions {
+        let mut reader = Reader::new(data);
+        let parsed = match kind {
+            SUPPORTED_VERSIONS => reader.u16().map(|v| version = Some(v)),
+            KEY_SHARE if retry => reader.u16().map(|group| hello.group = Some(group)),
+            KEY_SHARE => (|| {
+                let group = reader.u16()?;
+                let key = reader.vec16()?;
+                let key: [u8; 32] = key.try_into().ok().filter(|_| group == X25519)?;
+                hello.key_share = Some(key);
+                Some(())
+            })(),
+            COOKIE if retry => reader.vec16().map(|cookie| hello.cookie = Some(cookie.to_vec())),
+            PRE_SHARED_KEY if !retry => reader.u16().map(|index| hello.psk = Some(index)),
+            _ => {
+                return Err(records.fatal(
+                    Alert::UNSUPPORTED_EXTENSION,
+                    "Unexpected ServerHello extension",
+                ));
+            },
+        };
+        if parsed.is_none() || !reader.is_empty() {
+            return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Malformed ServerHello extension"));
+        }
+    }
+    if version != Some(TLS13) {
+        return Err(records.fatal(Alert::PROTOCOL_VERSION, "Server does not support TLS 1.3"));
+    }
+    Ok(hello)
+}
+
+/// Checks that `message` is a `kind` message and returns its body.
+fn expect<'m, S: Read + Write>(
+    records: &mut RecordLayer<S>, message: &'m [u8], kind: u8, name: &str,
+) -> Result<&'m [u8], LlmError> {
+    if message[0] != kind {
+        return Err(records.fatal(Alert::UNEXPECTED_MESSAGE, &format!("Expected {}", name)));
+    }
+    Ok(&message[4..])
+}
+
+/// The algorithm for a CertificateVerify signed with `scheme`, if the
+/// scheme was offered, is allowed in TLS 1.3 and suits `key`.
+pub(crate) fn certificate_verify_algorithm(
+    scheme: u16, key: &PublicKey,
+) -> Option<SignatureAlgorithm> {
+    let pss =
+        |hash: HashAlgorithm| SignatureAlgorithm::RsaPss { hash, salt_len: hash.output_len() };
+    match (scheme, key) {
+        (0x0403, PublicKey::Ec { curve: Curve::P256, .. }) => {
+            Some(SignatureAlgorithm::Ecdsa(HashAlgorithm::Sha256))
+        },
+        (0x0503, PublicKey::Ec { curve: Curve::P384, .. }) => {
+            Some(SignatureAlgorithm::Ecdsa(HashAlgorithm::Sha384))
+        },
+        (0x0804, PublicKey::Rsa(_)) => Some(pss(HashAlgorithm::Sha256)),
+        (0x0805, PublicKey::Rsa(_)) => Some(pss(HashAlgorithm::Sha384)),
+        (0x0806, PublicKey::Rsa(_)) => Some(pss(HashAlgorithm::Sha512)),
+        _ => None,
+    }
+}
+
+/// Content covered by a CertificateVerify signature from `side` (`"server"`
+/// or `"client"`).
+pub(crate) fn certificate_verify_content(side: &str, transcript_hash: &[u8]) -> Vec<u8> {
+    let mut content = vec![0x20; 64];
+    content.extend_from_slice(format!("TLS 1.3, {} CertificateVerify\0", side).as_bytes());
+    content.extend_from_slice(transcript_hash);
+    content
+}
+
+/// Client Certificate and, when a certificate is sent, CertificateVerify
+/// messages answering a CertificateRequest. `transcript` must end with the
+/// server Finished; the messages are added to it.
+fn client_authentication(
+    request: &CertificateRequest, identity: Option<&ClientIdentity>, suite: CipherSuite,
+    transcript: &mut Transcript,
+) -> Result<Vec<Vec<u8>>, (Alert, &'static str)> {
+    // Without an identity, or a key the server accepts, send an empty list
+    // and let the server decide whether to continue
+    let identity = identity.and_then(|identity| {
+        identity.key().scheme(&request.schemes).map(|scheme| (identity, scheme))
+    });
+    let mut body = Vec::new();
+    put_vec8(&mut body, &request.context);
+    put_prefixed(&mut body, 3, |out| {
+        for certificate in identity.iter().flat_map(|(identity, _)| identity.certificates()) {
+            put_prefixed(out, 3, |out| out.extend_from_slice(certificate.der()));
+            put_u16(out, 0);
+        }
+    });
+    let certificate = handshake_message(CERTIFICATE, &body);
+    transcript.add(&certificate);
+    let Some((identity, scheme)) = identity else {
+        return Ok(vec![certificate]);
+    };
+
+    let content = certificate_verify_content("client", &transcript.hash(suite.hash()));
+    let signature = identity
+        .key()
+        .sign(scheme, &content)
+        .ok_or((Alert::INTERNAL_ERROR, "Signing failed"))?;
+    let mut body = Vec::new();
+    put_u16(&mut body, scheme);
+    put_vec16(&mut body, &signature);
+    let verify = handshake_message(CERTIFICATE_VERIFY, &body);
+    transcript.add(&verify);
+    Ok(vec![certificate, verify])
+}
+
+/// A server's CertificateRequest.
+pub(crate) struct CertificateRequest {
+    pub(crate) context: Vec<u8>,
+    /// signature_algorithms the server accepts.
+    pub(crate) schemes: Vec<u16>,
+}
+
+fn parse_certificate_request(body: &[u8]) -> Option<CertificateRequest> {
+    let mut reader = Reader::new(body);
+    let context = reader.vec8()?.to_vec();
+    let extensions = parse_extensions(reader.vec16()?)?;
+    if !reader.is_empty() {
+        return None;
+    }
+    let (_, list) = extensions.into_iter().find(|(kind, _)| *kind == SIGNATURE_ALGORITHMS)?;
+    let mut list = Reader::new(list);
+    let mut schemes = Reader::new(list.vec16()?);
+    if !list.is_empty() {
+        return None;
+    }
+    let mut request = CertificateRequest { context, schemes: Vec::new() };
+    while !schemes.is_empty() {
+        request.schemes.push(schemes.u16()?);
+    }
+    Some(request)
+}
+
+/// Parses and validates the server's chain, then checks it against any pins
+/// for `host`.
+fn authenticate(
+    chain: &[Vec<u8>], host: &str, roots: &TrustStore, pins: &[PinSet],
+) -> Result<Vec<Certificate>, LlmError> {
+    let chain = chain
+        .iter()
+        .map(|der| Certificate::from_der(der))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Wall-clock time from std: certificate validity is an absolute UTC
+    // instant, and chain verification takes it as a parameter so callers and
+    // tests can supply their own clock
+    let path = roots.verify(&chain, host, SystemTime::now())?;
+    check_pins(pins, host, &path)?;
+    Ok(chain)
+}
+
+/// Authenticates the server in a full handshake: an optional
+/// CertificateRequest, then Certificate and CertificateVerify, added to
+/// `transcript`. Returns the validated chain and the request.
+fn authenticate_server<S: Read + Write>(
+    records: &mut RecordLayer<S>, transcript: &mut Transcript, suite: CipherSuite, host: &str,
+    roots: &TrustStore, config: &TlsConfig,
+) -> Result<(Vec<Certificate>, Option<CertificateRequest>), LlmError> {
+    let mut message = records.read_handshake()?;
+    let mut certificate_request = None;
+    if message[0] == CERTIFICATE_REQUEST {
+        let Some(request) = parse_certificate_request(&message[4..]) else {
+            return Err(records.fatal(Alert::DECODE_ERROR, "Malformed CertificateRequest"));
+        };
+        certificate_request = Some(request);
+        transcript.add(&message);
+        message = records.read_handshake()?;
+    }
+
+    let body = expect(records, &message, CERTIFICATE, "Certificate")?;
+    let Some(certificates) = parse_certificate_list(body) else {
+        return Err(records.fatal(Alert::DECODE_ERROR, "Malformed Certificate"));
+    };
+    if certificates.is_empty() {
+        return Err(records.fatal(Alert::DECODE_ERROR, "Server sent no certificate"));
+    }
+    let certificates = match authenticate(&certificates, host, roots, &config.pins) {
+        Ok(chain) => chain,
+        Err(e) => {
+            records.send_alert(Alert::BAD_CERTIFICATE);
+            return Err(e);
+        },
+    };
+    transcript.add(&message);
+
+    let message = records.read_handshake()?;
+    let body = expect(records, &message, CERTIFICATE_VERIFY, "CertificateVerify")?;
+    let mut reader = Reader::new(body);
+    let (Some(scheme), Some(signature), true) = (reader.u16(), reader.vec16(), reader.is_empty())
+    else {
+        return Err(records.fatal(Alert::DECODE_ERROR, "Malformed CertificateVerify"));
+    };
+    let key = &certificates[0].public_key;
+    let Some(algorithm) = certificate_verify_algorithm(scheme, key) else {
+        return Err(records.fatal(
+            Alert::ILLEGAL_PARAMETER,
+            "Server used an unoffered signature scheme",
+        ));
+    };
+    let content = certificate_verify_content("server", &transcript.hash(suite.hash()));
+    if !key.verify(algorithm, &content, signature) {
+        return Err(records.fatal(
+            Alert::DECRYPT_ERROR,
+            "CertificateVerify signature is invalid",
+        ));
+    }
+    transcript.add(&message);
+    Ok((certificates, certificate_request))
+}
+
+/// The protocol selected in EncryptedExtensions, which must be one of
+/// `offered`; `Err` if malformed or unoffered.
+fn parse_encrypted_extensions(body: &[u8], offered: &[String]) -> Result<Option<String>, Alert> {
+    let mut reader = Reader::new(body);
+    let extensions = reader.vec16().and_then(parse_extensions).ok_or(Alert::DECODE_ERROR)?;
+    if !reader.is_empty() {
+        return Err(Alert::DECODE_ERROR);
+    }
+    let Some((_, data)) = extensions.into_iter().find(|(kind, _)| *kind == ALPN) else {
+        return Ok(None);
+    };
+    let mut reader = Reader::new(data);
+    let mut list = Reader::new(reader.vec16().ok_or(Alert::DECODE_ERROR)?);
+    let name = list.vec8().ok_or(Alert::DECODE_ERROR)?;
+    if !reader.is_empty() || !list.is_empty() {
+        return Err(Alert::DECODE_ERROR);
+    }
+    offered
+        .iter()
+        .find(|protocol| protocol.as_bytes() == name)
+        .cloned()
+        .map(Some)
+        .ok_or(Alert::ILLEGAL_PARAMETER)
+}
+
+/// Fills in the PSK binder that ends `client_hello`, an HMAC over the
+/// transcript so far and the hello up to its binders list.
+fn bind_psk(client_hello: &mut [u8], ticket: &Ticket, transcript: &Transcript) {
+    let hash = ticket.suite.hash();
+    let binder_key =
+        KeySchedule::new(ticket.suite, Some(&ticket.psk)).derive("res binder", &hash.digest(&[]));
+    let len = hash.output_len();
+    // The binders list holds one length-prefixed binder
+    let truncated = client_hello.len() - 3 - len;
+    let binder = finished_mac(
+        ticket.suite,
+        &binder_key,
+        &transcript.hash_with(hash, &client_hello[..truncated]),
+    );
+    let end = client_hello.len();
+    client_hello[end - len..].copy_from_slice(&binder);
+}
+
+/// Runs an (EC)DHE handshake with `host` over `records`, offering `ticket`
+/// for resumption. A full handshake authenticates the server against `roots`
+/// and `config`'s pins, and presents its client identity if the server asks
+/// for one. Traffic secrets go to `config`'s key log, else `SSLKEYLOGFILE`.
+pub(crate) fn handshake<S: Read + Write>(
+    records: &mut RecordLayer<S>, host: &str, roots: &TrustStore, config: &TlsConfig,
+    ticket: Option<&Ticket>,
+) -> Result<Session, LlmError> {
+    let key_log = match &config.key_log {
+        Some(log) => Some(log.as_ref()),
+        None => KeyLog::from_env(),
+    };
+    let mut secret = [0u8; 32];
+    random_bytes(&mut secret);
+    let mut hello = ClientHello {
+        host,
+        random: [0; 32],
+        session_id: [0; 32],
+        public_key: x25519_base(&secret),
+        cookie: None,
+        alpn: &config.alpn_protocols,
+        ticket: ticket.map(|ticket| (ticket, ticket.obfuscated_age(Instant::now()))),
+    };
+    random_bytes(&mut hello.random);
+    random_bytes(&mut hello.session_id);
+
+    let mut transcript = Transcript::default();
+    let mut client_hello = hello.encode();
+    if let Some((ticket, _)) = hello.ticket {
+        bind_psk(&mut client_hello, ticket, &transcript);
+    }
+    transcript.add(&client_hello);
+    records.write_record(ContentType::Handshake, &client_hello)?;
+
+    let mut message = records.read_handshake()?;
+    let mut server_hello = parse_server_hello(records, &message, &hello.session_id)?;
+    if server_hello.retry {
+        // Only a cookie can be asked for: X25519 is the one group offered
+        if server_hello.group.is_some_and(|group| group != X25519) {
+            return Err(records.fatal(
+                Alert::HANDSHAKE_FAILURE,
+                "Server requires an unsupported group",
+            ));
+        }
+        if server_hello.cookie.is_none() {
+            return Err(records.fatal(
+                Alert::ILLEGAL_PARAMETER,
+                "HelloRetryRequest changes nothing",
+            ));
+        }
+        let retry_suite = server_hello.suite;
+        transcript.restart(retry_suite.hash());
+        transcript.add(&message);
+
+        hello.cookie = server_hello.cookie.take();
+        // The PSK can only be kept if its hash matches the chosen suite's
+        hello.ticket = hello.ticket.filter(|(ticket, _)| ticket.suite.hash() == retry_suite.hash());
+        let mut client_hello = hello.encode();
+        if let Some((ticket, _)) = hello.ticket {
+            bind_psk(&mut client_hello, ticket, &transcript);
+        }
+        transcript.add(&client_hello);
+        records.write_record(ContentType::Handshake, &client_hello)?;
+
+        message = records.read_handshake()?;
+        server_hello = parse_server_hello(records, &message, &hello.session_id)?;
+        if server_hello.retry || server_hello.suite != retry_suite {
+            return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Inconsistent HelloRetryRequest"));
+        }
+    }
+    transcript.add(&message);
+
+    let suite = server_hello.suite;
+    let resumed = match (server_hello.psk, hello.ticket) {
+        (None, _) => None,
+        (Some(0), Some((ticket, _))) if ticket.suite.hash() == suite.hash() => Some(ticket),
+        _ => {
+            return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Server selected an unoffered PSK"));
+        },
+    };
+    let Some(server_share) = server_hello.key_share else {
+        return Err(records.fatal(Alert::MISSING_EXTENSION, "ServerHello without key share"));
+    };
+    let shared = x25519(&secret, &server_share);
+    if shared == [0; 32] {
+        return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Invalid X25519 share"));
+    }
+
+    let mut schedule = KeySchedule::new(suite, resumed.map(|ticket| ticket.psk.as_slice()));
+    schedule.advance(Some(&shared));
+    let hash = transcript.hash(suite.hash());
+    let client_secret = schedule.derive("c hs traffic", &hash);
+    let server_secret = schedule.derive("s hs traffic", &hash);
+    if let Some(log) = key_log {
+        log.write(
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET",
+            &hello.random,
+            &client_secret,
+        );
+        log.write(
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET",
+            &hello.random,
+            &server_secret,
+        );
+    }
+    records.set_read_key(TrafficKey::new(suite, &server_secret)?)?;
+    records.set_write_key(TrafficKey::new(suite, &client_secret)?);
+
+    let message = records.read_handshake()?;
+    let body = expect(
+        records,
+        &message,
+        ENCRYPTED_EXTENSIONS,
+        "EncryptedExtensions",
+    )?;
+    let alpn = match parse_encrypted_extensions(body, &config.alpn_protocols) {
+        Ok(alpn) => alpn,
+        Err(Alert::ILLEGAL_PARAMETER) => {
+            return Err(records.fatal(
+                Alert::ILLEGAL_PARAMETER,
+                "Server selected an unoffered application protocol",
+            ));
+        },
+        Err(alert) => return Err(records.fatal(alert, "Malformed EncryptedExtensions")),
+    };
+    transcript.add(&message);
+
+    // A resumed session was authenticated by the handshake that issued it
+    let (certificates, certificate_request) = match resumed {
+        Some(ticket) => (ticket.certificates.clone(), None),
+        None => authenticate_server(records, &mut transcript, suite, host, roots, config)?,
+    };
+
+    let message = records.read_handshake()?;
+    let body = expect(records, &message, FINISHED, "Finished")?;
+    let expected = finished_mac(suite, &server_secret, &transcript.hash(suite.hash()));
+    if !ct_eq(body, &expected) {
+        return Err(records.fatal(Alert::DECRYPT_ERROR, "Server Finished verification failed"));
+    }
+    transcript.add(&message);
+
+    schedule.advance(None);
+    let hash = transcript.hash(suite.hash());
+    let client_app_secret = schedule.derive("c ap traffic", &hash);
+    let server_app_secret = schedule.derive("s ap traffic", &hash);
+    if let Some(log) = key_log {
+        log.write("CLIENT_TRAFFIC_SECRET_0", &hello.random, &client_app_secret);
+        log.write("SERVER_TRAFFIC_SECRET_0", &hello.random, &server_app_secret);
+    }
+    records.set_read_key(TrafficKey::new(suite, &server_app_secret)?)?;
+
+    records.write_record(ContentType::ChangeCipherSpec, &[1])?;
+    if let Some(request) = certificate_request {
+        let identity = config.client_identity.as_deref();
+        match client_authentication(&request, identity, suite, &mut transcript) {
+            Ok(messages) => records.write_record(ContentType::Handshake, &messages.concat())?,
+            Err((alert, message)) => return Err(records.fatal(alert, message)),
+        }
+    }
+    let finished = handshake_message(
+        FINISHED,
+        &finished_mac(suite, &client_secret, &transcript.hash(suite.hash())),
+    );
+    records.write_record(ContentType::Handshake, &finished)?;
+    records.set_write_key(TrafficKey::new(suite, &client_app_secret)?);
+    transcript.add(&finished);
+
+    Ok(Session {
+        suite,
+        certificates,
+        alpn,
+        resumed: resumed.is_some(),
+        resumption_secret: schedule.derive("res master", &transcript.hash(suite.hash())),
+    })
+}
+
+/// Certificate entries of a Certificate message body, ignoring their
+/// extensions; `None` if malformed.
+pub(crate) fn parse_certificate_list(body: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(body);
+    let _context = reader.vec8()?;
+    let mut list = Reader::new(reader.vec24()?);
+    if !reader.is_empty() {
+        return None;
+    }
+    let mut certificates = Vec::new();
+    while !list.is_empty() {
+        let certificate = list.vec24()?;
+        let _extensions = list.vec16()?;
+        if certificate.is_empty() {
+            return None;
+        }
+        certificates.push(certificate.to_vec());
+    }
+    Some(certificates)
+}
@@ -0,0 +1,176 @@
+//! Big-endian, length-prefixed encoding used by TLS messages.
+
+/// Cursor over a received message. Every accessor returns `None` when the
+/// input is too short, which callers report as a decode error.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn u24(&mut self) -> Option<usize> {
+        self.bytes(3)
+            .map(|b| (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize)
+    }
+
+    /// A vector with a one-byte length prefix.
+    pub(crate) fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    pub(crate) fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    pub(crate) fn vec24(&mut self) -> Option<&'a [u8]> {
+        let len = self.u24()?;
+        self.bytes(len)
+    }
+}
+
+pub(crate) fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn put_u24(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+}
+
+/// Writes whatever `body` appends, prefixed with its length in `width` bytes.
+pub(crate) fn put_prefixed(out: &mut Vec<u8>, width: usize, body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.resize(start + width, 0);
+    body(out);
+    let len = (out.len() - start - width) as u32;
+    out[start..start + width].copy_from_slice(&len.to_be_bytes()[4 - width..]);
+}
+
+pub(crate) fn put_vec8(out: &mut Vec<u8>, data: &[u8]) {
+    put_prefixed(out, 1, |out| out.extend_from_slice(data));
+}
+
+pub(crate) fn put_vec16(out: &mut Vec<u8>, data: &[u8]) {
+    put_prefixed(out, 2, |out| out.extend_from_slice(data));
+}
+
+/// A handshake message: type, 24-bit length, body.
+pub(crate) fn handshake_message(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(4 + body.len());
+    message.push(kind);
+    put_u24(&mut message, body.len());
+    message.extend_from_slice(body);
+    message
+}
+
+// Handshake message types
+pub(crate) const CLIENT_HELLO: u8 = 1;
+pub(crate) const SERVER_HELLO: u8 = 2;
+pub(crate) const NEW_SESSION_TICKET: u8 = 4;
+pub(crate) const ENCRYPTED_EXTENSIONS: u8 = 8;
+pub(crate) const CERTIFICATE: u8 = 11;
+pub(crate) const CERTIFICATE_REQUEST: u8 = 13;
+pub(crate) const CERTIFICATE_VERIFY: u8 = 15;
+pub(crate) const FINISHED: u8 = 20;
+pub(crate) const KEY_UPDATE: u8 = 24;
+pub(crate) const MESSAGE_HASH: u8 = 254;
+
+// Extension types
+pub(crate) const SERVER_NAME: u16 = 0;
+pub(crate) const SUPPORTED_GROUPS: u16 = 10;
+pub(crate) const SIGNATURE_ALGORITHMS: u16 = 13;
+pub(crate) const ALPN: u16 = 16;
+pub(crate) const PRE_SHARED_KEY: u16 = 41;
+pub(crate) const SUPPORTED_VERSIONS: u16 = 43;
+pub(crate) const COOKIE: u16 = 44;
+pub(crate) const PSK_KEY_EXCHANGE_MODES: u16 = 45;
+pub(crate) const KEY_SHARE: u16 = 51;
+
+pub(crate) const TLS13: u16 = 0x0304;
+/// PSK with (EC)DHE key exchange, the only mode offered.
+pub(crate) const PSK_DHE_KE: u8 = 1;
+pub(crate) const X25519: u16 = 0x001d;
+
+/// ServerHello.random of a HelloRetryRequest: SHA-256 of "HelloRetryRequest".
+pub(crate) const HELLO_RETRY_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+/// Writes one extension: type and length-prefixed data.
+pub(crate) fn put_extension(out: &mut Vec<u8>, kind: u16, body: impl FnOnce(&mut Vec<u8>)) {
+    put_u16(out, kind);
+    put_prefixed(out, 2, body);
+}
+
+/// Splits an extensions block into `(type, data)` pairs; `None` if it is
+/// malformed or repeats a type.
+pub(crate) fn parse_extensions(data: &[u8]) -> Option<Vec<(u16, &[u8])>> {
+    let mut reader = Reader::new(data);
+    let mut extensions: Vec<(u16, &[u8])> = Vec::new();
+    while !reader.is_empty() {
+        let kind = reader.u16()?;
+        let body = reader.vec16()?;
+        if extensions.iter().any(|(seen, _)| *seen == kind) {
+            return None;
+        }
+        extensions.push((kind, body));
+    }
+    Some(extensions)
+}
+
+/// Running transcript of handshake messages.
+#[derive(Default)]
+pub(crate) struct Transcript {
+    messages: Vec<u8>,
+}
+
+impl Transcript {
+    pub(crate) fn add(&mut self, message: &[u8]) {
+        self.messages.extend_from_slice(message);
+    }
+
+    pub(crate) fn hash(&self, hash: crate::essentia::crypto::sha2::HashAlgorithm) -> Vec<u8> {
+        hash.digest(&self.messages)
+    }
+
+    /// Hash of the transcript followed by `partial`, which is not added; used
+    /// for PSK binders over a truncated ClientHello.
+    pub(crate) fn hash_with(
+        &self, hash: crate::essentia::crypto::sha2::HashAlgorithm, partial: &[u8],
+    ) -> Vec<u8> {
+        hash.digest(&[self.messages.as_slice(), partial].concat())
+    }
+
+    /// Replaces the first ClientHello with its `message_hash` stand-in, as
+    /// required after a HelloRetryRequest.
+    pub(crate) fn restart(&mut self, hash: crate::essentia::crypto::sha2::HashAlgorithm) {
+        let digest = hash.digest(&self.messages);
+        self.messages = handshake_message(MESSAGE_HASH, &digest);
+    }
+}
@@ -0,0 +1,181 @@
+//! ASN.1 DER decoding (X.690), as much as X.509 and key files need.
+
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const UTF8_STRING: u8 = 0x0c;
+pub(crate) const PRINTABLE_STRING: u8 = 0x13;
+pub(crate) const TELETEX_STRING: u8 = 0x14;
+pub(crate) const IA5_STRING: u8 = 0x16;
+pub(crate) const UTC_TIME: u8 = 0x17;
+pub(crate) const GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const UNIVERSAL_STRING: u8 = 0x1c;
+pub(crate) const BMP_STRING: u8 = 0x1e;
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const SET: u8 = 0x31;
+
+/// Tag of a constructed `[n]` context-specific field.
+pub(crate) const fn explicit(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// Tag of a primitive `[n] IMPLICIT` context-specific field.
+pub(crate) const fn implicit(n: u8) -> u8 {
+    0x80 | n
+}
+
+/// Cursor over a sequence of DER elements. Every accessor returns `None` on
+/// malformed or non-canonical input.
+#[derive(Clone, Copy)]
+pub(crate) struct Der<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Tag of the next element.
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// The next element as `(tag, contents, whole encoding)`.
+    fn element(&mut self) -> Option<(u8, &'a [u8], &'a [u8])> {
+        let tag = *self.data.first()?;
+        // High tag numbers never occur in the structures parsed here
+        if tag & 0x1f == 0x1f {
+            return None;
+        }
+        let first = *self.data.get(1)? as usize;
+        let (length, header) = match first {
+            0..=0x7f => (first, 2),
+            0x81..=0x84 => {
+                let count = first & 0x7f;
+                let bytes = self.data.get(2..2 + count)?;
+                let length = bytes.iter().fold(0usize, |acc, &b| acc << 8 | b as usize);
+                // Long form only when needed, without leading zeros
+                if length < 0x80 || bytes[0] == 0 {
+                    return None;
+                }
+                (length, 2 + count)
+            },
+            _ => return None,
+        };
+        let end = header.checked_add(length)?;
+        let whole = self.data.get(..end)?;
+        self.data = &self.data[end..];
+        Some((tag, &whole[header..], whole))
+    }
+
+    /// Contents of the next element, which must have tag `tag`.
+    pub(crate) fn read(&mut self, tag: u8) -> Option<&'a [u8]> {
+        if self.peek()? != tag {
+            return None;
+        }
+        self.element().map(|(_, contents, _)| contents)
+    }
+
+    /// Tag and contents of the next element, whatever its tag.
+    pub(crate) fn read_any(&mut self) -> Option<(u8, &'a [u8])> {
+        self.element().map(|(tag, contents, _)| (tag, contents))
+    }
+
+    /// The next element's full encoding, header included.
+    pub(crate) fn read_raw(&mut self, tag: u8) -> Option<&'a [u8]> {
+        if self.peek()? != tag {
+            return None;
+        }
+        self.element().map(|(_, _, whole)| whole)
+    }
+
+    /// Contents of the next element if it has tag `tag`; `Some(None)` when
+    /// the element is absent.
+    pub(crate) fn optional(&mut self, tag: u8) -> Option<Option<&'a [u8]>> {
+        if self.peek() == Some(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    /// A SEQUENCE, as a cursor over its elements.
+    pub(crate) fn sequence(&mut self) -> Option<Der<'a>> {
+        self.read(SEQUENCE).map(Der::new)
+    }
+
+    /// Magnitude of a non-negative INTEGER, without sign padding.
+    pub(crate) fn unsigned(&mut self) -> Option<&'a [u8]> {
+        let contents = self.read(INTEGER)?;
+        match contents {
+            [] => None,
+            [first, ..] if first & 0x80 != 0 => None,
+            [0, second, ..] if second & 0x80 == 0 => None,
+            [0, rest @ ..] if !rest.is_empty() => Some(rest),
+            _ => Some(contents),
+        }
+    }
+
+    /// A non-negative INTEGER that fits in a `u64`.
+    pub(crate) fn small_unsigned(&mut self) -> Option<u64> {
+        let bytes = self.unsigned()?;
+        (bytes.len() <= 8).then(|| bytes.iter().fold(0u64, |acc, &b| acc << 8 | b as u64))
+    }
+
+    pub(crate) fn boolean(&mut self) -> Option<bool> {
+        match self.read(BOOLEAN)? {
+            [0x00] => Some(false),
+            [0xff] => Some(true),
+            _ => None,
+        }
+    }
+
+    /// BIT STRING contents, which must be a whole number of bytes.
+    pub(crate) fn bit_string(&mut self) -> Option<&'a [u8]> {
+        match self.read(BIT_STRING)? {
+            [0, bits @ ..] => Some(bits),
+            _ => None,
+        }
+    }
+
+    /// BIT STRING of named flags, as bits numbered from the most significant
+    /// bit of the first byte.
+    pub(crate) fn flags(&mut self) -> Option<u32> {
+        let (unused, bits) = self.read(BIT_STRING)?.split_first()?;
+        if *unused > 7 || bits.len() > 4 || (bits.is_empty() && *unused != 0) {
+            return None;
+        }
+        Some(
+            bits.iter()
+                .enumerate()
+                .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (24 - 8 * i)),
+        )
+    }
+}
+
+/// Dotted-decimal form of an encoded OID, for error messages.
+pub(crate) fn oid_to_string(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut value = 0u64;
+    for &byte in oid {
+        value = value << 7 | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    arcs.iter().map(|arc| arc.to_string()).collect::<Vec<_>>().join(".")
+}
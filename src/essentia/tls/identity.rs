@@ -0,0 +1,300 @@
+//! Client certificates and private keys for mutual TLS.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use super::{
+    der::{self, Der},
+    trust::pem_blocks,
+    x509::{self, Certificate, PublicKey},
+};
+use crate::{
+    errors::LlmError,
+    essentia::crypto::{
+        ecdsa::{self, Curve},
+        rsa::RsaPrivateKey,
+        sha2::HashAlgorithm,
+    },
+};
+
+/// A private key that can sign a CertificateVerify.
+#[derive(Clone)]
+pub enum PrivateKey {
+    /// Big-endian private scalar on `curve`.
+    Ec {
+        curve:  Curve,
+        scalar: Vec<u8>,
+    },
+    Rsa(RsaPrivateKey),
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ec { curve, .. } => f.debug_struct("Ec").field("curve", curve).finish(),
+            Self::Rsa(key) => key.fmt(f),
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Parses an unencrypted PKCS #8 `PrivateKeyInfo` holding an EC (P-256,
+    /// P-384) or RSA key.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, LlmError> {
+        parse_pkcs8(der).ok_or_else(|| LlmError::tls("Invalid or unsupported PKCS#8 private key"))
+    }
+
+    /// Parses the `PRIVATE KEY` block of a PEM file.
+    pub fn from_pem(pem: &str) -> Result<Self, LlmError> {
+        if let Some(der) = pem_blocks(pem, "PRIVATE KEY").first() {
+            return Self::from_pkcs8(der);
+        }
+        let other = ["ENCRYPTED PRIVATE KEY", "EC PRIVATE KEY", "RSA PRIVATE KEY"]
+            .into_iter()
+            .find(|label| pem.contains(&format!("-----BEGIN {}-----", label)));
+        Err(LlmError::tls(&match other {
+            Some(label) => format!(
+                "{} keys are not supported; convert with `openssl pkcs8 -topk8 -nocrypt`",
+                label
+            ),
+            None => "No PRIVATE KEY block found".to_string(),
+        }))
+    }
+
+    /// Whether this is the private half of `public`.
+    fn matches(&self, public: &PublicKey) -> bool {
+        match (self, public) {
+            (Self::Ec { curve, scalar }, PublicKey::Ec { curve: other, point }) => {
+                curve == other && ecdsa::public_key(*curve, scalar).as_ref() == Some(point)
+            },
+            (Self::Rsa(key), PublicKey::Rsa(other)) => key.public_key() == other,
+            _ => false,
+        }
+    }
+
+    /// The first signature scheme in `accepted` this key can sign with.
+    pub(crate) fn scheme(&self, accepted: &[u16]) -> Option<u16> {
+        let own: &[u16] = match self {
+            Self::Ec { curve: Curve::P256, .. } => &[0x0403],
+            Self::Ec { curve: Curve::P384, .. } => &[0x0503],
+            Self::Rsa(_) => &[0x0804, 0x0805, 0x0806],
+        };
+        accepted.iter().copied().find(|scheme| own.contains(scheme))
+    }
+
+    /// Signs `message` with `scheme`, which must come from
+    /// [`scheme`](Self::scheme). ECDSA signatures are DER `Ecdsa-Sig-Value`s.
+    pub(crate) fn sign(&self, scheme: u16, message: &[u8]) -> Option<Vec<u8>> {
+        match (self, scheme) {
+            (Self::Ec { curve, scalar }, 0x0403 | 0x0503) => {
+                let hash = match curve {
+                    Curve::P256 => HashAlgorithm::Sha256,
+                    Curve::P384 => HashAlgorithm::Sha384,
+                };
+                let (r, s) = ecdsa::sign(*curve, scalar, hash, message)?;
+                Some(ecdsa_signature_der(&r, &s))
+            },
+            (Self::Rsa(key), 0x0804) => key.sign_pss(HashAlgorithm::Sha256, message),
+            (Self::Rsa(key), 0x0805) => key.sign_pss(HashAlgorithm::Sha384, message),
+            (Self::Rsa(key), 0x0806) => key.sign_pss(HashAlgorithm::Sha512, message),
+            _ => None,
+        }
+    }
+}
+
+/// Identities loaded by [`ClientIdentity::from_files`], by certificate and
+/// key path.
+type IdentityCache = HashMap<(PathBuf, PathBuf), Arc<ClientIdentity>>;
+
+/// A client certificate chain, leaf first, with the leaf's private key.
+#[derive(Clone)]
+pub struct ClientIdentity {
+    certificates: Vec<Certificate>,
+    key:          PrivateKey,
+}
+
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+            .field("subject", &self.certificates[0].subject)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl ClientIdentity {
+    /// Fails if `certificates` is empty or `key` does not belong to the
+    /// first certificate.
+    pub fn new(certificates: Vec<Certificate>, key: PrivateKey) -> Result<Self, LlmError> {
+        let Some(leaf) = certificates.first() else {
+            return Err(LlmError::tls("Client certificate chain is empty"));
+        };
+        if !key.matches(&leaf.public_key) {
+            return Err(LlmError::tls(&format!(
+                "Private key does not match client certificate '{}'",
+                leaf.subject
+            )));
+        }
+        Ok(Self { certificates, key })
+    }
+
+    /// Reads the chain from the `CERTIFICATE` blocks of `certificates` and
+    /// the key from `key`.
+    pub fn from_pem(certificates: &str, key: &str) -> Result<Self, LlmError> {
+        let chain = pem_blocks(certificates, "CERTIFICATE")
+            .iter()
+            .map(|der| Certificate::from_der(der))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(chain, PrivateKey::from_pem(key)?)
+    }
+
+    /// Loads PEM files, once per pair of paths, so connections made with
+    /// the same identity can be pooled together.
+    pub fn from_files(
+        certificates: impl AsRef<Path>, key: impl AsRef<Path>,
+    ) -> Result<Arc<ClientIdentity>, LlmError> {
+        static LOADED: OnceLock<Mutex<IdentityCache>> = OnceLock::new();
+        let paths = (
+            certificates.as_ref().to_path_buf(),
+            key.as_ref().to_path_buf(),
+        );
+        let mut loaded = LOADED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(identity) = loaded.get(&paths) {
+            return Ok(Arc::clone(identity));
+        }
+        let read = |path: &Path| {
+            fs::read_to_string(path)
+                .map_err(|e| LlmError::Config(format!("Cannot read {}: {}", path.display(), e)))
+        };
+        let identity = Arc::new(Self::from_pem(&read(&paths.0)?, &read(&paths.1)?)?);
+        loaded.insert(paths, Arc::clone(&identity));
+        Ok(identity)
+    }
+
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+
+    pub(crate) fn key(&self) -> &PrivateKey {
+        &self.key
+    }
+}
+
+fn parse_pkcs8(data: &[u8]) -> Option<PrivateKey> {
+    let mut outer = Der::new(data);
+    let mut info = outer.sequence()?;
+    // Version 1 (RFC 5958) may append the public key, which is ignored
+    if info.small_unsigned()? > 1 || !outer.is_empty() {
+        return None;
+    }
+    let (oid, parameters) = x509::parse_algorithm(info.read_raw(der::SEQUENCE)?)?;
+    let mut key = Der::new(info.read(der::OCTET_STRING)?);
+    match oid {
+        x509::RSA_ENCRYPTION => {
+            let mut fields = key.sequence()?;
+            if fields.small_unsigned()? != 0 {
+                return None;
+            }
+            let modulus = fields.unsigned()?;
+            let public_exponent = fields.unsigned()?;
+            let private_exponent = fields.unsigned()?;
+            RsaPrivateKey::new(modulus, public_exponent, private_exponent).map(PrivateKey::Rsa)
+        },
+        x509::EC_PUBLIC_KEY => {
+            let curve = x509::named_curve(Der::new(parameters?).read(der::OID)?)?;
+            let mut fields = key.sequence()?;
+            if fields.small_unsigned()? != 1 {
+                return None;
+            }
+            let scalar = fields.read(der::OCTET_STRING)?.to_vec();
+            ecdsa::public_key(curve, &scalar)?;
+            Some(PrivateKey::Ec { curve, scalar })
+        },
+        _ => None,
+    }
+}
+
+/// DER `Ecdsa-Sig-Value` for big-endian `r` and `s`.
+pub(crate) fn ecdsa_signature_der(r: &[u8], s: &[u8]) -> Vec<u8> {
+    let mut integers = Vec::new();
+    for value in [r, s] {
+        let start = value.iter().position(|&b| b != 0).unwrap_or(value.len().saturating_sub(1));
+        let value = &value[start..];
+        let pad = value.first().is_none_or(|&b| b & 0x80 != 0);
+        integers.push(der::INTEGER);
+        integers.push((value.len() + usize::from(pad)) as u8);
+        if pad {
+            integers.push(0);
+        }
+        integers.extend_from_slice(value);
+    }
+    let mut out = vec![der::SEQUENCE, integers.len() as u8];
+    out.extend_from_slice(&integers);
+    out
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    const CLIENT_PEM: &str = include_str!("testdata/client.pem");
+    const CLIENT_KEY: &str = include_str!("testdata/client.key");
+    const RSA_PEM: &str = include_str!("testdata/client-rsa.pem");
+    const RSA_KEY: &str = include_str!("testdata/client-rsa.key");
+
+    #[test]
+    fn test_keys_sign_verifiably() {
+        for (certificate, key) in [(CLIENT_PEM, CLIENT_KEY), (RSA_PEM, RSA_KEY)] {
+            let identity = ClientIdentity::from_pem(certificate, key).expect("identity");
+            let public = &identity.certificates()[0].public_key;
+            let scheme = identity.key().scheme(&[0x0806, 0x0403]).expect("scheme");
+            let signature = identity.key().sign(scheme, b"message").expect("sign");
+            let algorithm = super::super::client::certificate_verify_algorithm(scheme, public)
+                .expect("algorithm");
+            assert!(public.verify(algorithm, b"message", &signature));
+            assert!(!public.verify(algorithm, b"other", &signature));
+        }
+        let key = PrivateKey::from_pem(CLIENT_KEY).expect("key");
+        assert_eq!(key.scheme(&[0x0804, 0x0503]), None);
+    }
+
+    #[test]
+    fn test_rejects_mismatched_or_unsupported_keys() {
+        let err = ClientIdentity::from_pem(CLIENT_PEM, RSA_KEY).expect_err("mismatch");
+        assert!(
+            err.to_string()
+                .contains("does not match client certificate 'CN=essentia-client'"),
+            "{}",
+            err
+        );
+        let err = ClientIdentity::from_pem("", CLIENT_KEY).expect_err("empty chain");
+        assert!(err.to_string().contains("chain is empty"));
+
+        let legacy = CLIENT_KEY.replace("PRIVATE KEY", "EC PRIVATE KEY");
+        let err = PrivateKey::from_pem(&legacy).expect_err("SEC1");
+        assert!(
+            err.to_string().contains("openssl pkcs8 -topk8 -nocrypt"),
+            "{}",
+            err
+        );
+        assert!(PrivateKey::from_pkcs8(&[0x30, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_ecdsa_signature_der() {
+        assert_eq!(ecdsa_signature_der(&[0, 0, 0x7f], &[0x80, 1]), [
+            0x30, 0x08, 0x02, 0x01, 0x7f, 0x02, 0x03, 0x00, 0x80, 0x01
+        ]);
+        assert_eq!(ecdsa_signature_der(&[0, 0], &[1]), [
+            0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x01
+        ]);
+    }
+}
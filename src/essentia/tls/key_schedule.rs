@@ -0,0 +1,216 @@
+//! TLS 1.3 cipher suites and key schedule (RFC 8446 section 7).
+
+use crate::{
+    errors::LlmError,
+    essentia::crypto::{
+        aead::{Aead, AeadAlgorithm, NONCE_LEN},
+        sha2::HashAlgorithm,
+    },
+};
+
+/// Cipher suites offered by the client, in preference order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipherSuite {
+    Aes128GcmSha256,
+    Aes256GcmSha384,
+    ChaCha20Poly1305Sha256,
+}
+
+impl CipherSuite {
+    pub const ALL: [Self; 3] = [
+        Self::Aes128GcmSha256,
+        Self::Aes256GcmSha384,
+        Self::ChaCha20Poly1305Sha256,
+    ];
+
+    pub const fn id(self) -> u16 {
+        match self {
+            Self::Aes128GcmSha256 => 0x1301,
+            Self::Aes256GcmSha384 => 0x1302,
+            Self::ChaCha20Poly1305Sha256 => 0x1303,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|suite| suite.id() == id)
+    }
+
+    /// IANA name, e.g. `TLS_AES_128_GCM_SHA256`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Aes128GcmSha256 => "TLS_AES_128_GCM_SHA256",
+            Self::Aes256GcmSha384 => "TLS_AES_256_GCM_SHA384",
+            Self::ChaCha20Poly1305Sha256 => "TLS_CHACHA20_POLY1305_SHA256",
+        }
+    }
+
+    pub const fn aead(self) -> AeadAlgorithm {
+        match self {
+            Self::Aes128GcmSha256 => AeadAlgorithm::Aes128Gcm,
+            Self::Aes256GcmSha384 => AeadAlgorithm::Aes256Gcm,
+            Self::ChaCha20Poly1305Sha256 => AeadAlgorithm::ChaCha20Poly1305,
+        }
+    }
+
+    pub const fn hash(self) -> HashAlgorithm {
+        match self {
+            Self::Aes128GcmSha256 | Self::ChaCha20Poly1305Sha256 => HashAlgorithm::Sha256,
+            Self::Aes256GcmSha384 => HashAlgorithm::Sha384,
+        }
+    }
+}
+
+/// HKDF-Expand-Label.
+pub(crate) fn expand_label(
+    hash: HashAlgorithm, secret: &[u8], label: &str, context: &[u8], len: usize,
+) -> Vec<u8> {
+    let label = format!("tls13 {}", label);
+    let mut info = Vec::with_capacity(4 + label.len() + context.len());
+    info.extend_from_slice(&(len as u16).to_be_bytes());
+    info.push(label.len() as u8);
+    info.extend_from_slice(label.as_bytes());
+    info.push(context.len() as u8);
+    info.extend_from_slice(context);
+    hash.hkdf_expand(secret, &info, len)
+}
+
+/// Derive-Secret over an already computed transcript hash.
+pub(crate) fn derive_secret(
+    hash: HashAlgorithm, secret: &[u8], label: &str, transcript_hash: &[u8],
+) -> Vec<u8> {
+    expand_label(hash, secret, label, transcript_hash, hash.output_len())
+}
+
+/// The chain of early, handshake and master secrets.
+pub(crate) struct KeySchedule {
+    suite:  CipherSuite,
+    secret: Vec<u8>,
+}
+
+impl KeySchedule {
+    /// Starts at the early secret, from `psk` or all zeros.
+    pub(crate) fn new(suite: CipherSuite, psk: Option<&[u8]>) -> Self {
+        let hash = suite.hash();
+        let zeros = vec![0u8; hash.output_len()];
+        Self { suite, secret: hash.hkdf_extract(&zeros, psk.unwrap_or(&zeros)) }
+    }
+
+    /// Moves to the next stage, mixing in `ikm` (the ECDHE secret for the
+    /// handshake secret, nothing for the master secret).
+    pub(crate) fn advance(&mut self, ikm: Option<&[u8]>) {
+        let hash = self.suite.hash();
+        let zeros = vec![0u8; hash.output_len()];
+        let empty = hash.digest(&[]);
+        let salt = derive_secret(hash, &self.secret, "derived", &empty);
+        self.secret = hash.hkdf_extract(&salt, ikm.unwrap_or(&zeros));
+    }
+
+    pub(crate) fn derive(&self, label: &str, transcript_hash: &[u8]) -> Vec<u8> {
+        derive_secret(self.suite.hash(), &self.secret, label, transcript_hash)
+    }
+}
+
+/// `verify_data` of a Finished message sent under `traffic_secret`.
+pub(crate) fn finished_mac(
+    suite: CipherSuite, traffic_secret: &[u8], transcript_hash: &[u8],
+) -> Vec<u8> {
+    let hash = suite.hash();
+    let key = expand_label(hash, traffic_secret, "finished", &[], hash.output_len());
+    hash.hmac(&key, transcript_hash)
+}
+
+/// Record protection for one direction, with its sequence number.
+pub(crate) struct TrafficKey {
+    suite:  CipherSuite,
+    secret: Vec<u8>,
+    aead:   Aead,
+    iv:     [u8; NONCE_LEN],
+    seq:    u64,
+}
+
+impl TrafficKey {
+    pub(crate) fn new(suite: CipherSuite, secret: &[u8]) -> Result<Self, LlmError> {
+        let hash = suite.hash();
+        let key = expand_label(hash, secret, "key", &[], suite.aead().key_len());
+        let mut iv = [0u8; NONCE_LEN];
+        iv.copy_from_slice(&expand_label(hash, secret, "iv", &[], NONCE_LEN));
+        let aead =
+            Aead::new(suite.aead(), &key).ok_or_else(|| LlmError::tls("Invalid traffic key"))?;
+        Ok(Self { suite, secret: secret.to_vec(), aead, iv, seq: 0 })
+    }
+
+    /// The key after a KeyUpdate.
+    pub(crate) fn next(&self) -> Result<Self, LlmError> {
+        let hash = self.suite.hash();
+        Self::new(
+            self.suite,
+            &expand_label(hash, &self.secret, "traffic upd", &[], hash.output_len()),
+        )
+    }
+
+    /// Per-record nonce; `None` once the sequence number is exhausted.
+    fn nonce(&mut self) -> Option<[u8; NONCE_LEN]> {
+        let seq = self.seq;
+        self.seq = seq.checked_add(1)?;
+        let mut nonce = self.iv;
+        for (n, s) in nonce[NONCE_LEN - 8..].iter_mut().zip(seq.to_be_bytes()) {
+            *n ^= s;
+        }
+        Some(nonce)
+    }
+
+    pub(crate) fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Option<Vec<u8>> {
+        let nonce = self.nonce()?;
+        Some(self.aead.seal(&nonce, aad, plaintext))
+    }
+
+    pub(crate) fn open(&mut self, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        let nonce = self.nonce()?;
+        self.aead.open(&nonce, aad, sealed)
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::crypto::{from_hex, to_hex};
+
+    #[test]
+    fn test_rfc8448_handshake_keys() {
+        // RFC 8448 section 3, "Simple 1-RTT Handshake"
+        let suite = CipherSuite::Aes128GcmSha256;
+        let shared = from_hex("8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d");
+        let transcript =
+            from_hex("860c06edc07858ee8e78f0e7428c58edd6b43f2ca3e6e95f02ed063cf0e1cad8");
+
+        let mut schedule = KeySchedule::new(suite, None);
+        assert_eq!(
+            to_hex(&schedule.secret),
+            "33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a"
+        );
+        schedule.advance(Some(&shared));
+        assert_eq!(
+            to_hex(&schedule.secret),
+            "1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac"
+        );
+        let server = schedule.derive("s hs traffic", &transcript);
+        assert_eq!(
+            to_hex(&server),
+            "b67b7d690cc16c4e75e54213cb2d37b4e9c912bcded9105d42befd59d391ad38"
+        );
+
+        let key = TrafficKey::new(suite, &server).expect("key");
+        assert_eq!(to_hex(&key.iv), "5d313eb2671276ee13000b30");
+        assert_eq!(
+            to_hex(&expand_label(suite.hash(), &server, "key", &[], 16)),
+            "3fce516009c21727d0f2e4e86ee403bc"
+        );
+
+        schedule.advance(None);
+        assert_eq!(
+            to_hex(&schedule.secret),
+            "18df06843d13a08bf2a449844c5f8a478001bc4d4c627984d5a41da8d0402919"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! NSS key log output, so captured traffic can be decrypted by tools such as
+//! Wireshark.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{core::logger::Log, errors::LlmError, essentia::crypto::to_hex};
+
+/// Environment variable naming the key log file, as read by browsers and
+/// curl.
+pub const KEYLOG_ENV: &str = "SSLKEYLOGFILE";
+
+/// A file that handshake secrets are appended to, one
+/// `<label> <client random> <secret>` line each.
+///
+/// Anyone holding the file can decrypt the logged connections; only enable it
+/// while debugging.
+pub struct KeyLog {
+    file: Mutex<File>,
+}
+
+impl fmt::Debug for KeyLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyLog").finish_non_exhaustive()
+    }
+}
+
+impl KeyLog {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LlmError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+            LlmError::Config(format!("Cannot open key log {}: {}", path.display(), e))
+        })?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    /// The log named by [`KEYLOG_ENV`], opened on first use. A file that
+    /// cannot be opened is reported once and then ignored.
+    pub(crate) fn from_env() -> Option<&'static KeyLog> {
+        static ENV: OnceLock<Option<KeyLog>> = OnceLock::new();
+        ENV.get_or_init(|| {
+            let path = std::env::var_os(KEYLOG_ENV).filter(|path| !path.is_empty())?;
+            KeyLog::open(path).map_err(|e| Log::error(&e.to_string())).ok()
+        })
+        .as_ref()
+    }
+
+    /// Appends one secret; write errors are ignored, as the connection does
+    /// not depend on them.
+    pub(crate) fn write(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format!("{} {} {}\n", label, to_hex(client_random), to_hex(secret));
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = file.write_all(line.as_bytes());
+    }
+}
@@ -0,0 +1,751 @@
+//! TLS 1.3 client over `std::net::TcpStream`.
+//!
+//! Implements the full (EC)DHE handshake with X25519 and the
+//! `TLS_AES_128_GCM_SHA256`, `TLS_AES_256_GCM_SHA384` and
+//! `TLS_CHACHA20_POLY1305_SHA256` suites, record protection, KeyUpdate,
+//! alerts and close_notify. Older protocol versions are refused.
+//!
+//! The server's chain is validated against a [`TrustStore`] (the bundle
+//! named by `ESSENTIA_CA_BUNDLE`, else the system CA bundle, by default), its
+//! names are matched against the host, and the CertificateVerify signature is
+//! checked with RSA-PSS or ECDSA. Hosts may additionally be pinned to
+//! specific keys with [`PinSet`]s.
+//!
+//! When the server sends a CertificateRequest, the configured
+//! [`ClientIdentity`] is presented and proven with a CertificateVerify; without
+//! one, an empty certificate list is sent.
+//!
+//! The ClientHello offers ALPN protocols ([`ALPN_HTTP1`] by default). Session
+//! tickets are cached per host and configuration, and offered as PSKs
+//! (`psk_dhe_ke`) on the next connection until they expire; set
+//! `ESSENTIA_DEBUG` to log cache activity.
+//!
+//! Traffic secrets are appended to the file named by `SSLKEYLOGFILE`, or a
+//! configured [`KeyLog`], in the NSS key log format.
+
+mod client;
+mod codec;
+mod der;
+mod identity;
+mod key_schedule;
+mod keylog;
+mod pin;
+mod record;
+mod session;
+#[cfg(all(test, feature = "full-tests"))]
+mod testing;
+mod trust;
+mod x509;
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    net::TcpStream,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use self::{
+    codec::{KEY_UPDATE, NEW_SESSION_TICKET, handshake_message},
+    record::{Alert, ContentType, RecordLayer, parse_alert},
+    session::{SessionCache, SessionKey, Ticket},
+};
+pub use self::{
+    identity::{ClientIdentity, PrivateKey},
+    key_schedule::CipherSuite,
+    keylog::{KEYLOG_ENV, KeyLog},
+    pin::{PinSet, spki_pin},
+    trust::{CA_BUNDLE_ENV, SYSTEM_BUNDLES, TrustStore},
+    x509::{Certificate, KeyUsage, PublicKey, SignatureAlgorithm, SubjectAltName, verify_chain},
+};
+use crate::{core::logger::Log, errors::LlmError};
+
+/// ALPN identifier for HTTP/1.1.
+pub const ALPN_HTTP1: &str = "http/1.1";
+/// ALPN identifier for HTTP/2.
+pub const ALPN_H2: &str = "h2";
+
+/// Settings for a TLS connection.
+///
+/// Configurations compare equal when they share the same trust store, pins,
+/// client identity and protocol settings, so pooled connections and session
+/// tickets are only reused under the settings they were authenticated with.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// Roots server chains must lead to; `None` uses
+    /// [`TrustStore::default_roots`].
+    pub roots:           Option<Arc<TrustStore>>,
+    /// Keys required for specific hosts.
+    pub pins:            Vec<PinSet>,
+    /// Certificate and key presented when the server asks for one.
+    pub client_identity: Option<Arc<ClientIdentity>>,
+    /// ALPN protocols offered, most preferred first; empty sends none.
+    pub alpn_protocols:  Vec<String>,
+    /// Offer cached session tickets and keep the ones the server sends.
+    pub resumption:      bool,
+    /// Where traffic secrets are logged; `None` uses [`KEYLOG_ENV`].
+    pub key_log:         Option<Arc<KeyLog>>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            roots:           None,
+            pins:            Vec::new(),
+            client_identity: None,
+            alpn_protocols:  vec![ALPN_HTTP1.to_string()],
+            resumption:      true,
+            key_log:         None,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Trusts only the roots in `roots`.
+    pub fn with_roots(roots: TrustStore) -> Self {
+        Self { roots: Some(Arc::new(roots)), ..Self::default() }
+    }
+
+    /// Builds settings from configured values: a PEM bundle file or
+    /// directory (`None` for the default roots) and a
+    /// [`PinSet::parse_list`] pin list.
+    pub fn from_settings(ca_bundle: Option<&str>, pins: Option<&str>) -> Result<Self, LlmError> {
+        Ok(Self {
+            roots: ca_bundle
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(TrustStore::from_path)
+                .transpose()?,
+            pins: pins.map(PinSet::parse_list).transpose()?.unwrap_or_default(),
+            ..Self::default()
+        })
+    }
+
+    pub fn with_pins(mut self, pins: Vec<PinSet>) -> Self {
+        self.pins = pins;
+        self
+    }
+
+    pub fn with_client_identity(mut self, identity: Arc<ClientIdentity>) -> Self {
+        self.client_identity = Some(identity);
+        self
+    }
+
+    pub fn with_alpn_protocols<'a>(mut self, protocols: impl IntoIterator<Item = &'a str>) -> Self {
+        self.alpn_protocols = protocols.into_iter().map(str::to_string).collect();
+        self
+    }
+
+    pub fn with_resumption(mut self, resumption: bool) -> Self {
+        self.resumption = resumption;
+        self
+    }
+
+    pub fn with_key_log(mut self, key_log: Arc<KeyLog>) -> Self {
+        self.key_log = Some(key_log);
+        self
+    }
+}
+
+/// Whether both are unset or share the same allocation.
+fn same_arc<T>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &Self) -> bool {
+        same_arc(&self.roots, &other.roots)
+            && same_arc(&self.client_identity, &other.client_identity)
+            && same_arc(&self.key_log, &other.key_log)
+            && self.pins == other.pins
+            && self.alpn_protocols == other.alpn_protocols
+            && self.resumption == other.resumption
+    }
+}
+
+impl Eq for TlsConfig {}
+
+impl Hash for TlsConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.roots.as_ref().map(Arc::as_ptr).hash(state);
+        self.pins.hash(state);
+        self.client_identity.as_ref().map(Arc::as_ptr).hash(state);
+        self.alpn_protocols.hash(state);
+        self.resumption.hash(state);
+        self.key_log.as_ref().map(Arc::as_ptr).hash(state);
+    }
+}
+
+/// An established TLS 1.3 connection.
+pub struct TlsStream {
+    records:      RecordLayer<TcpStream>,
+    suite:        CipherSuite,
+    certificates: Vec<Certificate>,
+    alpn:         Option<String>,
+    resumed:      bool,
+    /// Where tickets from this server are cached, and the secret they are
+    /// derived from; `None` when resumption is off.
+    resumption:   Option<(SessionKey, Vec<u8>)>,
+    /// Decrypted application data not yet returned by [`read`](Self::read).
+    plaintext:    Vec<u8>,
+    offset:       usize,
+    /// The peer sent close_notify.
+    closed:       bool,
+    /// We sent close_notify.
+    shut_down:    bool,
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("peer", &self.records.stream().peer_addr().ok())
+            .field("suite", &self.suite)
+            .field("alpn", &self.alpn)
+            .field("resumed", &self.resumed)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+impl TlsStream {
+    pub fn connect(host: &str, port: u16) -> Result<Self, LlmError> {
+        Self::connect_with(host, port, &TlsConfig::default())
+    }
+
+    pub fn connect_with(host: &str, port: u16, config: &TlsConfig) -> Result<Self, LlmError> {
+        let stream = TcpStream::connect((host, port)).map_err(|source| LlmError::Connect {
+            address: format!("{}:{}", host, port),
+            source,
+        })?;
+        Self::handshake_with(stream, host, config)
+    }
+
+    /// Runs the handshake over an already connected socket, trusting the
+    /// default roots. Any timeouts set on `stream` apply to the handshake.
+    pub fn handshake(stream: TcpStream, host: &str) -> Result<Self, LlmError> {
+        Self::handshake_with(stream, host, &TlsConfig::default())
+    }
+
+    /// Runs the handshake with `config`; `host` is sent as SNI and must be
+    /// named by the server's certificate, unless a cached session for `host`
+    /// is resumed.
+    pub fn handshake_with(
+        stream: TcpStream, host: &str, config: &TlsConfig,
+    ) -> Result<Self, LlmError> {
+        let roots = match &config.roots {
+            Some(roots) => Arc::clone(roots),
+            None => TrustStore::default_roots()?,
+        };
+        let key = config.resumption.then(|| SessionKey::new(host, config));
+        let ticket = key.as_ref().and_then(|key| SessionCache::global().take(key, Instant::now()));
+        let mut records = RecordLayer::new(stream);
+        let session = client::handshake(&mut records, host, &roots, config, ticket.as_ref())?;
+        if ticket.is_some() {
+            Log::debug(&format!(
+                "TLS: {} session for {}",
+                if session.resumed {
+                    "resumed"
+                } else {
+                    "server declined cached"
+                },
+                host
+            ));
+        }
+        Ok(Self {
+            records,
+            suite: session.suite,
+            certificates: session.certificates,
+            alpn: session.alpn,
+            resumed: session.resumed,
+            resumption: key.map(|key| (key, session.resumption_secret)),
+            plaintext: Vec::new(),
+            offset: 0,
+            closed: false,
+            shut_down: false,
+        })
+    }
+
+    /// Negotiated cipher suite.
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.suite
+    }
+
+    /// Certificates presented by the server, leaf first; for a resumed
+    /// session, those of the handshake that issued the ticket.
+    pub fn peer_certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+
+    /// Protocol the server selected by ALPN, if any.
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn.as_deref()
+    }
+
+    /// Whether the handshake resumed a cached session.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Encrypts and sends `data`.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), LlmError> {
+        if self.shut_down {
+            return Err(LlmError::tls("Connection already closed"));
+        }
+        self.records.write_record(ContentType::ApplicationData, data)
+    }
+
+    /// Underlying socket, for liveness checks and socket options.
+    pub(crate) fn tcp_stream(&self) -> &TcpStream {
+        self.records.stream()
+    }
+
+    /// Reads decrypted application data. Returns 0 once the peer has sent
+    /// close_notify.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, LlmError> {
+        loop {
+            if self.offset < self.plaintext.len() {
+                let n = buffer.len().min(self.plaintext.len() - self.offset);
+                buffer[..n].copy_from_slice(&self.plaintext[self.offset..self.offset + n]);
+                self.offset += n;
+                return Ok(n);
+            }
+            if self.closed || buffer.is_empty() {
+                return Ok(0);
+            }
+
+            let (kind, data) = self.records.read_record()?;
+            match kind {
+                ContentType::ApplicationData => {
+                    self.plaintext = data;
+                    self.offset = 0;
+                },
+                ContentType::Handshake => {
+                    self.records.push_handshake(&data);
+                    while let Some(message) = self.records.next_handshake() {
+                        self.post_handshake(&message)?;
+                    }
+                },
+                ContentType::Alert => match parse_alert(&data) {
+                    Some(Alert::CLOSE_NOTIFY) => self.closed = true,
+                    Some(Alert::USER_CANCELED) => {},
+                    Some(alert) => return Err(alert.received()),
+                    None => return Err(self.records.fatal(Alert::DECODE_ERROR, "Malformed alert")),
+                },
+                ContentType::ChangeCipherSpec => {
+                    return Err(self.records.fatal(Alert::UNEXPECTED_MESSAGE, "Unexpected record"));
+                },
+            }
+        }
+    }
+
+    /// Handles a handshake message received after the handshake.
+    fn post_handshake(&mut self, message: &[u8]) -> Result<(), LlmError> {
+        match (message[0], &message[4..]) {
+            (NEW_SESSION_TICKET, body) => {
+                let Some((key, secret)) = &self.resumption else {
+                    return Ok(());
+                };
+                let now = Instant::now();
+                match Ticket::from_new_session_ticket(
+                    body,
+                    self.suite,
+                    secret,
+                    &self.certificates,
+                    now,
+                ) {
+                    Some(Some(ticket)) => SessionCache::global().insert(key, ticket),
+                    Some(None) => {},
+                    None => {
+                        return Err(self
+                            .records
+                            .fatal(Alert::DECODE_ERROR, "Malformed NewSessionTicket"));
+                    },
+                }
+                Ok(())
+            },
+            (KEY_UPDATE, [request @ (0 | 1)]) => {
+                self.records.update_read_key()?;
+                if *request == 1 && !self.shut_down {
+                    let reply = handshake_message(KEY_UPDATE, &[0]);
+                    self.records.write_record(ContentType::Handshake, &reply)?;
+                    self.records.update_write_key()?;
+                }
+                Ok(())
+            },
+            (KEY_UPDATE, _) => Err(self.records.fatal(Alert::DECODE_ERROR, "Malformed KeyUpdate")),
+            _ => Err(self.records.fatal(Alert::UNEXPECTED_MESSAGE, "Unexpected handshake message")),
+        }
+    }
+
+    /// Sends close_notify. Further writes fail; reads continue until the
+    /// peer closes its side.
+    pub fn close(&mut self) -> Result<(), LlmError> {
+        if self.shut_down {
+            return Ok(());
+        }
+        self.shut_down = true;
+        self.records.write_record(ContentType::Alert, &[1, Alert::CLOSE_NOTIFY.0])
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+pub fn tls_connect(host: &str, port: u16) -> Result<TlsStream, LlmError> {
+    TlsStream::connect(host, port)
+}
+
+/// Parses a DER-encoded X.509 certificate.
+pub fn parse_certificate(cert_data: &[u8]) -> Result<Certificate, LlmError> {
+    Certificate::from_der(cert_data)
+}
+
+/// Checks a certificate's validity period and that it names `hostname`.
+/// Chains are checked with [`verify_chain`] or [`TrustStore::verify`].
+pub fn validate_certificate(cert: &Certificate, hostname: &str) -> Result<(), LlmError> {
+    cert.check_validity(SystemTime::now())?;
+    cert.check_host(hostname)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::{
+        testing::{INTERMEDIATE_PEM, ServerConnection, ServerOptions, accept, test_roots},
+        trust::pem_blocks,
+        *,
+    };
+
+    fn test_config() -> TlsConfig {
+        TlsConfig::with_roots(test_roots())
+    }
+
+    fn connect(host: &str, port: u16) -> Result<TlsStream, LlmError> {
+        TlsStream::connect_with(host, port, &test_config())
+    }
+
+    /// Runs `session` on the server end of one connection; the thread returns
+    /// the client's SNI name.
+    fn serve(
+        options: ServerOptions,
+        session: impl FnOnce(&mut ServerConnection) -> Result<(), LlmError> + Send + 'static,
+    ) -> (u16, thread::JoinHandle<Result<Option<String>, LlmError>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let handle = thread::spawn(move || {
+            let mut connection = accept(&listener, &options)?;
+            session(&mut connection)?;
+            Ok(connection.server_name.take())
+        });
+        (port, handle)
+    }
+
+    fn read_exactly(stream: &mut TlsStream, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            let n = stream.read(&mut data[filled..]).expect("read");
+            assert!(n > 0, "early EOF");
+            filled += n;
+        }
+        data
+    }
+
+    #[test]
+    fn test_handshake_and_records_for_each_suite() {
+        let large: Vec<u8> = (0..40_000u32).map(|i| (i % 251) as u8).collect();
+        for suite in CipherSuite::ALL {
+            let options = ServerOptions {
+                suite,
+                hello_retry: suite == CipherSuite::ChaCha20Poly1305Sha256,
+                ..ServerOptions::default()
+            };
+            let reply = large.clone();
+            let (port, server) = serve(options, move |connection| {
+                assert_eq!(connection.read()?, b"ping");
+                connection.write(&reply)?;
+                connection.key_update()?;
+                connection.write(b"after update")?;
+                assert_eq!(connection.read()?, b"bye");
+                connection.close();
+                Ok(())
+            });
+
+            let mut stream = connect("localhost", port).expect("handshake");
+            assert_eq!(stream.cipher_suite(), suite);
+            let chain: Vec<&[u8]> = stream.peer_certificates().iter().map(|c| c.der()).collect();
+            assert_eq!(chain, ServerOptions::default().certificates);
+            stream.write(b"ping").expect("write");
+            assert_eq!(read_exactly(&mut stream, large.len()), large);
+            assert_eq!(read_exactly(&mut stream, 12), b"after update");
+            // Sent under the key the server asked us to move to
+            stream.write(b"bye").expect("write");
+            assert_eq!(stream.read(&mut [0u8; 16]).expect("close_notify"), 0);
+
+            let server_name = server.join().expect("server").expect("server session");
+            assert_eq!(server_name.as_deref(), Some("localhost"));
+        }
+    }
+
+    #[test]
+    fn test_certificate_spanning_records() {
+        // Enough redundant copies of the intermediate to span several records
+        let mut options = ServerOptions::default();
+        let intermediate = pem_blocks(INTERMEDIATE_PEM, "CERTIFICATE").remove(0);
+        options.certificates.extend(std::iter::repeat_n(intermediate, 30));
+        let length: usize = options.certificates.iter().map(Vec::len).sum();
+        assert!(length > 20_000);
+        let (port, server) = serve(options, |_| Ok(()));
+        let stream = connect("localhost", port).expect("handshake");
+        assert_eq!(stream.peer_certificates().len(), 32);
+        server.join().expect("server").expect("server session");
+    }
+
+    #[test]
+    fn test_untrusted_or_mismatched_certificate_is_rejected() {
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        let err =
+            TlsStream::connect_with("localhost", port, &TlsConfig::with_roots(TrustStore::new()))
+                .expect_err("no roots");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: No trusted root certificates are configured"
+        );
+        let err = server.join().expect("server").expect_err("alert");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Peer sent alert bad_certificate (42)"
+        );
+
+        // A trusted intermediate anchors the chain like a root would
+        let mut roots = TrustStore::new();
+        roots.add_pem(INTERMEDIATE_PEM);
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        assert!(TlsStream::connect_with("localhost", port, &TlsConfig::with_roots(roots)).is_ok());
+        server.join().expect("server").expect("server session");
+
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("connect");
+        let err = TlsStream::handshake_with(stream, "other.example", &test_config())
+            .expect_err("host mismatch");
+        assert!(
+            err.to_string().contains("is not valid for other.example"),
+            "{}",
+            err
+        );
+        server.join().expect("server").expect_err("alert");
+    }
+
+    #[test]
+    fn test_certificate_pins() {
+        let chain = ServerOptions::default().certificates;
+        let intermediate = Certificate::from_der(&chain[1]).expect("intermediate");
+        let pinned = |pin: &str| {
+            let pins = PinSet::parse_list(&format!("LOCALHOST={}", pin)).expect("pins");
+            test_config().with_pins(pins)
+        };
+
+        // Any key on the validated path satisfies the pin
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        TlsStream::connect_with("localhost", port, &pinned(&spki_pin(&intermediate)))
+            .expect("pinned intermediate");
+        server.join().expect("server").expect("server session");
+
+        let wrong = format!("sha256/{}", crate::essentia::base64::encode(&[0; 32]));
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        let err = TlsStream::connect_with("localhost", port, &pinned(&wrong)).expect_err("pin");
+        assert_eq!(err.kind(), crate::errors::ErrorKind::PinMismatch);
+        assert!(
+            err.to_string().contains(&spki_pin(&intermediate)),
+            "{}",
+            err
+        );
+        server.join().expect("server").expect_err("alert");
+
+        // Pins for other hosts do not apply
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("connect");
+        TlsStream::handshake_with(stream, "127.0.0.1", &pinned(&wrong)).expect("unpinned host");
+        server.join().expect("server").expect("server session");
+    }
+
+    #[test]
+    fn test_client_certificate_authentication() {
+        let options =
+            ServerOptions { request_client_certificate: true, ..ServerOptions::default() };
+        let identities = [
+            (
+                include_str!("testdata/client.pem"),
+                include_str!("testdata/client.key"),
+            ),
+            (
+                include_str!("testdata/client-rsa.pem"),
+                include_str!("testdata/client-rsa.key"),
+            ),
+        ];
+        for (certificate, key) in identities {
+            let identity = ClientIdentity::from_pem(certificate, key).expect("identity");
+            let leaf = identity.certificates()[0].der().to_vec();
+            let (port, server) = serve(options.clone(), move |connection| {
+                assert_eq!(connection.client_certificates, vec![leaf]);
+                Ok(())
+            });
+            let config = test_config().with_client_identity(Arc::new(identity));
+            TlsStream::connect_with("localhost", port, &config).expect("mutual TLS");
+            server.join().expect("server").expect("server session");
+        }
+
+        // Without an identity the client answers with an empty list
+        let (port, server) = serve(options, |connection| {
+            assert!(connection.client_certificates.is_empty());
+            Ok(())
+        });
+        connect("localhost", port).expect("no identity");
+        server.join().expect("server").expect("server session");
+    }
+
+    #[test]
+    fn test_alpn_negotiation() {
+        let options = ServerOptions { alpn: Some(ALPN_H2), ..ServerOptions::default() };
+        let (port, server) = serve(options.clone(), |connection| {
+            assert_eq!(connection.offered_protocols, [ALPN_H2, ALPN_HTTP1]);
+            Ok(())
+        });
+        let config = test_config().with_alpn_protocols([ALPN_H2, ALPN_HTTP1]);
+        let stream = TlsStream::connect_with("localhost", port, &config).expect("h2");
+        assert_eq!(stream.alpn_protocol(), Some(ALPN_H2));
+        server.join().expect("server").expect("server session");
+
+        // Only offered protocols may be selected
+        let (port, server) = serve(options, |_| Ok(()));
+        let err = connect("localhost", port).expect_err("unoffered protocol");
+        assert!(
+            err.to_string().contains("unoffered application protocol"),
+            "{}",
+            err
+        );
+        server.join().expect("server").expect_err("alert");
+    }
+
+    #[test]
+    fn test_session_resumption() {
+        let options = ServerOptions {
+            alpn: Some(ALPN_HTTP1),
+            ticket_lifetime: Some(60),
+            ..ServerOptions::default()
+        };
+        let config = test_config();
+        let handshake = |options: ServerOptions, resumed: bool| {
+            let (port, server) = serve(options, move |connection| {
+                assert_eq!(connection.resumed, resumed);
+                connection.write(b"hi")
+            });
+            let mut stream = TlsStream::connect_with("localhost", port, &config).expect("connect");
+            assert_eq!(stream.is_resumed(), resumed);
+            assert_eq!(stream.alpn_protocol(), Some(ALPN_HTTP1));
+            assert_eq!(stream.peer_certificates().len(), 2);
+            // Tickets arrive with the first read
+            assert_eq!(read_exactly(&mut stream, 2), b"hi");
+            server.join().expect("server").expect("server session");
+        };
+
+        handshake(options.clone(), false);
+        // Each connection uses up its ticket and is sent a new one, also
+        // across a HelloRetryRequest
+        handshake(ServerOptions { hello_retry: true, ..options.clone() }, true);
+        handshake(
+            ServerOptions { ticket_lifetime: None, ..options.clone() },
+            true,
+        );
+        handshake(options.clone(), false);
+
+        // Tickets are not shared with other trust stores
+        let (port, server) = serve(options, |_| Ok(()));
+        let stream = connect("localhost", port).expect("full");
+        assert!(!stream.is_resumed());
+        server.join().expect("server").expect("server session");
+    }
+
+    #[test]
+    fn test_key_log() {
+        let path = std::env::temp_dir().join(format!("essentia-keylog-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key_log = Arc::new(KeyLog::open(&path).expect("key log"));
+        let (port, server) = serve(ServerOptions::default(), |_| Ok(()));
+        let config = test_config().with_key_log(key_log);
+        TlsStream::connect_with("localhost", port, &config).expect("connect");
+        server.join().expect("server").expect("server session");
+
+        let written = std::fs::read_to_string(&path).expect("read");
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<Vec<&str>> = written.lines().map(|line| line.split(' ').collect()).collect();
+        let labels: Vec<&str> = lines.iter().map(|fields| fields[0]).collect();
+        assert_eq!(labels, [
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET",
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET",
+            "CLIENT_TRAFFIC_SECRET_0",
+            "SERVER_TRAFFIC_SECRET_0"
+        ]);
+        for fields in &lines {
+            // Client random, then a SHA-256 sized secret
+            assert_eq!(fields[1], lines[0][1]);
+            assert_eq!((fields[1].len(), fields[2].len()), (64, 64));
+        }
+    }
+
+    #[test]
+    fn test_bad_certificate_verify_signature() {
+        // Signing with a key that does not match the leaf
+        let mut options = ServerOptions::default();
+        options.signing_key[31] ^= 1;
+        let (port, server) = serve(options, |_| Ok(()));
+        let err = connect("localhost", port).expect_err("bad signature");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: CertificateVerify signature is invalid"
+        );
+        server.join().expect("server").expect_err("alert");
+    }
+
+    #[test]
+    fn test_bad_finished_is_rejected_with_alert() {
+        let options = ServerOptions { bad_finished: true, ..ServerOptions::default() };
+        let (port, server) = serve(options, |_| Ok(()));
+        let err = connect("127.0.0.1", port).expect_err("bad Finished");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Server Finished verification failed"
+        );
+
+        let err = server.join().expect("server").expect_err("alert");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Peer sent alert decrypt_error (51)"
+        );
+    }
+
+    #[test]
+    fn test_https_request_over_loopback() {
+        let (port, server) = serve(ServerOptions::default(), |connection| {
+            let request = String::from_utf8(connection.read()?).expect("utf8");
+            assert!(request.starts_with("GET /v1/models HTTP/1.1\r\n"));
+            connection.write(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+        });
+        let response =
+            crate::essentia::http::Request::get(&format!("https://localhost:{}/v1/models", port))
+                .tls(test_config())
+                .send()
+                .expect("response");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"ok");
+        server.join().expect("server").expect("server session");
+    }
+}
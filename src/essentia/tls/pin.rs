@@ -0,0 +1,84 @@
+//! Public key pinning: SHA-256 hashes of SubjectPublicKeyInfo, written
+//! `sha256/<base64>` as in RFC 7469 and curl's `--pinnedpubkey`.
+
+use super::x509::Certificate;
+use crate::{errors::LlmError, essentia::base64};
+
+/// Keys accepted for one host. When any set names the host, its validated
+/// chain (leaf, intermediates or root) must contain one of the pinned keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PinSet {
+    /// Lowercase host name, matched exactly.
+    pub host:   String,
+    pub hashes: Vec<[u8; 32]>,
+}
+
+impl PinSet {
+    pub fn new(host: &str) -> Self {
+        Self { host: normalize_host(host), hashes: Vec::new() }
+    }
+
+    /// Adds a `sha256/<base64>` pin.
+    pub fn with_pin(mut self, pin: &str) -> Result<Self, LlmError> {
+        let invalid = || LlmError::Config(format!("Invalid certificate pin: {}", pin));
+        let encoded = pin.trim().strip_prefix("sha256/").ok_or_else(invalid)?;
+        let hash = base64::decode(encoded).map_err(|_| invalid())?;
+        self.hashes.push(<[u8; 32]>::try_from(hash).map_err(|_| invalid())?);
+        Ok(self)
+    }
+
+    /// Parses `host=pin[,pin...]` entries separated by `;` or newlines,
+    /// e.g. `api.x.ai=sha256/AAA...=,sha256/BBB...=`.
+    pub fn parse_list(value: &str) -> Result<Vec<PinSet>, LlmError> {
+        value
+            .split([';', '\n'])
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (host, pins) = entry.split_once('=').ok_or_else(|| {
+                    LlmError::Config(format!("Invalid pin entry (expected host=pins): {}", entry))
+                })?;
+                pins.split(',')
+                    .filter(|pin| !pin.trim().is_empty())
+                    .try_fold(PinSet::new(host), |set, pin| set.with_pin(pin))
+            })
+            .collect()
+    }
+}
+
+/// The `sha256/<base64>` pin for a certificate's key.
+pub fn spki_pin(certificate: &Certificate) -> String {
+    format!(
+        "sha256/{}",
+        base64::encode(&essentia_core_utils::crypto::sha256(certificate.spki()))
+    )
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Checks the validated `path` against every set pinning `host`.
+pub(crate) fn check_pins(
+    pins: &[PinSet], host: &str, path: &[&Certificate],
+) -> Result<(), LlmError> {
+    let host = normalize_host(host);
+    let mut pinned = pins
+        .iter()
+        .filter(|set| set.host == host)
+        .flat_map(|set| &set.hashes)
+        .peekable();
+    if pinned.peek().is_none() {
+        return Ok(());
+    }
+    let hashes: Vec<[u8; 32]> = path
+        .iter()
+        .map(|certificate| essentia_core_utils::crypto::sha256(certificate.spki()))
+        .collect();
+    if pinned.any(|pin| hashes.contains(pin)) {
+        return Ok(());
+    }
+    Err(LlmError::PinMismatch {
+        host,
+        presented: path.iter().map(|certificate| spki_pin(certificate)).collect(),
+    })
+}
@@ -0,0 +1,294 @@
+//! TLS record layer: framing, record protection and handshake message
+//! reassembly (RFC 8446 section 5).
+
+use std::io::{Read, Write};
+
+use super::key_schedule::TrafficKey;
+use crate::errors::LlmError;
+
+/// Largest plaintext fragment per record.
+pub(crate) const MAX_FRAGMENT: usize = 1 << 14;
+
+/// Largest record body accepted from the peer.
+const MAX_CIPHERTEXT: usize = MAX_FRAGMENT + 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentType {
+    ChangeCipherSpec,
+    Alert,
+    Handshake,
+    ApplicationData,
+}
+
+impl ContentType {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            20 => Some(Self::ChangeCipherSpec),
+            21 => Some(Self::Alert),
+            22 => Some(Self::Handshake),
+            23 => Some(Self::ApplicationData),
+            _ => None,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::ChangeCipherSpec => 20,
+            Self::Alert => 21,
+            Self::Handshake => 22,
+            Self::ApplicationData => 23,
+        }
+    }
+}
+
+/// Alert description (RFC 8446 section 6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Alert(pub(crate) u8);
+
+impl Alert {
+    pub(crate) const BAD_CERTIFICATE: Self = Self(42);
+    pub(crate) const BAD_RECORD_MAC: Self = Self(20);
+    pub(crate) const CLOSE_NOTIFY: Self = Self(0);
+    pub(crate) const DECODE_ERROR: Self = Self(50);
+    pub(crate) const DECRYPT_ERROR: Self = Self(51);
+    pub(crate) const HANDSHAKE_FAILURE: Self = Self(40);
+    pub(crate) const ILLEGAL_PARAMETER: Self = Self(47);
+    pub(crate) const INTERNAL_ERROR: Self = Self(80);
+    pub(crate) const MISSING_EXTENSION: Self = Self(109);
+    pub(crate) const PROTOCOL_VERSION: Self = Self(70);
+    pub(crate) const RECORD_OVERFLOW: Self = Self(22);
+    pub(crate) const UNEXPECTED_MESSAGE: Self = Self(10);
+    pub(crate) const UNSUPPORTED_EXTENSION: Self = Self(110);
+    pub(crate) const USER_CANCELED: Self = Self(90);
+
+    pub(crate) fn name(self) -> &'static str {
+        match self.0 {
+            0 => "close_notify",
+            10 => "unexpected_message",
+            20 => "bad_record_mac",
+            22 => "record_overflow",
+            40 => "handshake_failure",
+            42 => "bad_certificate",
+            43 => "unsupported_certificate",
+            44 => "certificate_revoked",
+            45 => "certificate_expired",
+            46 => "certificate_unknown",
+            47 => "illegal_parameter",
+            48 => "unknown_ca",
+            49 => "access_denied",
+            50 => "decode_error",
+            51 => "decrypt_error",
+            70 => "protocol_version",
+            71 => "insufficient_security",
+            80 => "internal_error",
+            86 => "inappropriate_fallback",
+            90 => "user_canceled",
+            109 => "missing_extension",
+            110 => "unsupported_extension",
+            112 => "unrecognized_name",
+            115 => "unknown_psk_identity",
+            116 => "certificate_required",
+            120 => "no_application_protocol",
+            _ => "unknown",
+        }
+    }
+
+    /// Error for an alert received from the peer.
+    pub(crate) fn received(self) -> LlmError {
+        LlmError::tls(&format!("Peer sent alert {} ({})", self.name(), self.0))
+    }
+}
+
+/// Reads and writes records on `stream`, protecting them once keys are set.
+pub(crate) struct RecordLayer<S> {
+    stream:    S,
+    read_key:  Option<TrafficKey>,
+    write_key: Option<TrafficKey>,
+    /// Handshake bytes received but not yet returned as whole messages.
+    handshake: Vec<u8>,
+}
+
+impl<S: Read + Write> RecordLayer<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Self { stream, read_key: None, write_key: None, handshake: Vec::new() }
+    }
+
+    pub(crate) fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Switches inbound protection. A handshake message may not straddle a
+    /// key change, so any partial message left over is an error.
+    pub(crate) fn set_read_key(&mut self, key: TrafficKey) -> Result<(), LlmError> {
+        if !self.handshake.is_empty() {
+            return Err(self.fatal(
+                Alert::UNEXPECTED_MESSAGE,
+                "Handshake message spans a key change",
+            ));
+        }
+        self.read_key = Some(key);
+        Ok(())
+    }
+
+    pub(crate) fn set_write_key(&mut self, key: TrafficKey) {
+        self.write_key = Some(key);
+    }
+
+    /// Applies a KeyUpdate to the inbound key.
+    pub(crate) fn update_read_key(&mut self) -> Result<(), LlmError> {
+        match self.read_key.as_ref().map(TrafficKey::next) {
+            Some(key) => self.set_read_key(key?),
+            None => Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "KeyUpdate before handshake keys")),
+        }
+    }
+
+    /// Applies a KeyUpdate to the outbound key.
+    pub(crate) fn update_write_key(&mut self) -> Result<(), LlmError> {
+        if let Some(key) = &self.write_key {
+            self.write_key = Some(key.next()?);
+        }
+        Ok(())
+    }
+
+    /// Reads one record, returning its real content type and plaintext.
+    pub(crate) fn read_record(&mut self) -> Result<(ContentType, Vec<u8>), LlmError> {
+        let mut header = [0u8; 5];
+        self.stream.read_exact(&mut header).map_err(|e| LlmError::io("TLS read", e))?;
+        let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+        if length > MAX_CIPHERTEXT {
+            return Err(self.fatal(Alert::RECORD_OVERFLOW, "Record too large"));
+        }
+        let Some(outer) = ContentType::from_u8(header[0]) else {
+            return Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Unknown record type"));
+        };
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body).map_err(|e| LlmError::io("TLS read", e))?;
+
+        let Some(key) = self.read_key.as_mut() else {
+            if outer == ContentType::ApplicationData {
+                return Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Data before handshake keys"));
+            }
+            return Ok((outer, body));
+        };
+        match outer {
+            // Middlebox compatibility; never protected
+            ContentType::ChangeCipherSpec => return Ok((outer, body)),
+            ContentType::ApplicationData => {},
+            _ => return Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Unprotected record")),
+        }
+        let Some(mut plaintext) = key.open(&header, &body) else {
+            return Err(self.fatal(Alert::BAD_RECORD_MAC, "Record authentication failed"));
+        };
+        // TLSInnerPlaintext: content, real type, zero padding
+        let Some(end) = plaintext.iter().rposition(|&b| b != 0) else {
+            return Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Record without content type"));
+        };
+        let inner = ContentType::from_u8(plaintext[end]);
+        plaintext.truncate(end);
+        match inner {
+            Some(ContentType::ChangeCipherSpec) | None => {
+                Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Invalid protected record type"))
+            },
+            _ if plaintext.len() > MAX_FRAGMENT => {
+                Err(self.fatal(Alert::RECORD_OVERFLOW, "Record too large"))
+            },
+            Some(inner) => Ok((inner, plaintext)),
+        }
+    }
+
+    /// Writes `data` as one or more records of type `kind`.
+    pub(crate) fn write_record(&mut self, kind: ContentType, data: &[u8]) -> Result<(), LlmError> {
+        let mut out = Vec::with_capacity(data.len() + 32);
+        for fragment in data.chunks(MAX_FRAGMENT) {
+            match self.write_key.as_mut().filter(|_| kind != ContentType::ChangeCipherSpec) {
+                Some(key) => {
+                    let mut inner = fragment.to_vec();
+                    inner.push(kind.as_u8());
+                    let length = (inner.len() + crate::essentia::crypto::aead::TAG_LEN) as u16;
+                    let mut header = [ContentType::ApplicationData.as_u8(), 3, 3, 0, 0];
+                    header[3..].copy_from_slice(&length.to_be_bytes());
+                    let sealed = key
+                        .seal(&header, &inner)
+                        .ok_or_else(|| LlmError::tls("Record sequence number exhausted"))?;
+                    out.extend_from_slice(&header);
+                    out.extend_from_slice(&sealed);
+                },
+                None => {
+                    out.extend_from_slice(&[kind.as_u8(), 3, 3]);
+                    out.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+                    out.extend_from_slice(fragment);
+                },
+            }
+        }
+        self.stream
+            .write_all(&out)
+            .and_then(|()| self.stream.flush())
+            .map_err(|e| LlmError::io("TLS write", e))
+    }
+
+    /// Buffers the body of a handshake record.
+    pub(crate) fn push_handshake(&mut self, data: &[u8]) {
+        self.handshake.extend_from_slice(data);
+    }
+
+    /// The next complete buffered handshake message (header included).
+    pub(crate) fn next_handshake(&mut self) -> Option<Vec<u8>> {
+        if self.handshake.len() < 4 {
+            return None;
+        }
+        let length = (self.handshake[1] as usize) << 16
+            | (self.handshake[2] as usize) << 8
+            | self.handshake[3] as usize;
+        if self.handshake.len() < 4 + length {
+            return None;
+        }
+        Some(self.handshake.drain(..4 + length).collect())
+    }
+
+    /// Reads records until a whole handshake message is available, skipping
+    /// compatibility ChangeCipherSpec records.
+    pub(crate) fn read_handshake(&mut self) -> Result<Vec<u8>, LlmError> {
+        loop {
+            if let Some(message) = self.next_handshake() {
+                return Ok(message);
+            }
+            let (kind, data) = self.read_record()?;
+            match kind {
+                ContentType::Handshake if !data.is_empty() => self.push_handshake(&data),
+                ContentType::ChangeCipherSpec if data == [1] => {},
+                ContentType::Alert => {
+                    let alert = parse_alert(&data);
+                    return Err(match alert {
+                        Some(alert) => alert.received(),
+                        None => self.fatal(Alert::DECODE_ERROR, "Malformed alert"),
+                    });
+                },
+                _ => return Err(self.fatal(Alert::UNEXPECTED_MESSAGE, "Unexpected record")),
+            }
+        }
+    }
+
+    /// Sends `alert` (fatal unless it is close_notify), ignoring failures.
+    pub(crate) fn send_alert(&mut self, alert: Alert) {
+        let level = if alert == Alert::CLOSE_NOTIFY || alert == Alert::USER_CANCELED {
+            1
+        } else {
+            2
+        };
+        let _ = self.write_record(ContentType::Alert, &[level, alert.0]);
+    }
+
+    /// Reports a local failure to the peer and returns it as an error.
+    pub(crate) fn fatal(&mut self, alert: Alert, message: &str) -> LlmError {
+        self.send_alert(alert);
+        LlmError::tls(message)
+    }
+}
+
+/// Description of a two-byte alert record.
+pub(crate) fn parse_alert(data: &[u8]) -> Option<Alert> {
+    match data {
+        [_level, description] => Some(Alert(*description)),
+        _ => None,
+    }
+}
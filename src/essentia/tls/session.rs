@@ -0,0 +1,221 @@
+//! TLS 1.3 session tickets (RFC 8446 section 4.6.1), cached per host so
+//! reconnects can resume with a PSK instead of a full handshake.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use super::{
+    TlsConfig,
+    codec::Reader,
+    key_schedule::{CipherSuite, expand_label},
+    x509::Certificate,
+};
+use crate::core::logger::Log;
+
+/// Longest lifetime a ticket may be used for: seven days.
+const MAX_LIFETIME_SECS: u32 = 604_800;
+
+/// Tickets kept per host; servers usually send two after each handshake.
+const MAX_TICKETS: usize = 4;
+
+/// A ticket and the PSK it stands for.
+#[derive(Clone)]
+pub(crate) struct Ticket {
+    /// Suite of the connection that issued it; a resumption must use one
+    /// with the same hash.
+    pub(crate) suite:        CipherSuite,
+    /// Opaque label sent back to the server.
+    pub(crate) identity:     Vec<u8>,
+    pub(crate) psk:          Vec<u8>,
+    age_add:                 u32,
+    received:                Instant,
+    lifetime:                Duration,
+    /// Server chain authenticated by the original full handshake.
+    pub(crate) certificates: Vec<Certificate>,
+}
+
+impl Ticket {
+    /// Parses a NewSessionTicket body received on a connection with
+    /// `suite` and `resumption_secret`. `Some(None)` for tickets that must not
+    /// be used (zero lifetime); `None` if malformed.
+    pub(crate) fn from_new_session_ticket(
+        body: &[u8], suite: CipherSuite, resumption_secret: &[u8], certificates: &[Certificate],
+        now: Instant,
+    ) -> Option<Option<Self>> {
+        let mut reader = Reader::new(body);
+        let lifetime = u32::from_be_bytes(reader.bytes(4)?.try_into().ok()?);
+        let age_add = u32::from_be_bytes(reader.bytes(4)?.try_into().ok()?);
+        let nonce = reader.vec8()?;
+        let identity = reader.vec16()?;
+        let _extensions = reader.vec16()?;
+        if !reader.is_empty() || identity.is_empty() {
+            return None;
+        }
+        if lifetime == 0 {
+            return Some(None);
+        }
+        let hash = suite.hash();
+        Some(Some(Self {
+            suite,
+            identity: identity.to_vec(),
+            psk: expand_label(
+                hash,
+                resumption_secret,
+                "resumption",
+                nonce,
+                hash.output_len(),
+            ),
+            age_add,
+            received: now,
+            lifetime: Duration::from_secs(lifetime.min(MAX_LIFETIME_SECS).into()),
+            certificates: certificates.to_vec(),
+        }))
+    }
+
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.received) >= self.lifetime
+    }
+
+    /// Ticket age in milliseconds, obfuscated with the server's `age_add`.
+    pub(crate) fn obfuscated_age(&self, now: Instant) -> u32 {
+        let age = now.saturating_duration_since(self.received).as_millis() as u32;
+        age.wrapping_add(self.age_add)
+    }
+}
+
+/// Which cached tickets a connection may use: the host name, and the settings
+/// the original server was authenticated under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SessionKey {
+    host:   String,
+    config: TlsConfig,
+}
+
+impl SessionKey {
+    pub(crate) fn new(host: &str, config: &TlsConfig) -> Self {
+        Self { host: host.trim_end_matches('.').to_ascii_lowercase(), config: config.clone() }
+    }
+}
+
+/// Unexpired tickets by host, newest last.
+#[derive(Default)]
+pub(crate) struct SessionCache {
+    tickets: Mutex<HashMap<SessionKey, VecDeque<Ticket>>>,
+}
+
+impl SessionCache {
+    /// The cache shared by every connection in the process.
+    pub(crate) fn global() -> &'static SessionCache {
+        static CACHE: OnceLock<SessionCache> = OnceLock::new();
+        CACHE.get_or_init(SessionCache::default)
+    }
+
+    pub(crate) fn insert(&self, key: &SessionKey, ticket: Ticket) {
+        Log::debug(&format!(
+            "TLS: cached session ticket for {} (lifetime {} s)",
+            key.host,
+            ticket.lifetime.as_secs()
+        ));
+        let mut tickets = self.tickets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let queue = tickets.entry(key.clone()).or_default();
+        if queue.len() == MAX_TICKETS {
+            queue.pop_front();
+        }
+        queue.push_back(ticket);
+    }
+
+    /// Removes and returns the newest unexpired ticket for `key`. Tickets are
+    /// used once, so a server cannot link connections by them.
+    pub(crate) fn take(&self, key: &SessionKey, now: Instant) -> Option<Ticket> {
+        let mut tickets = self.tickets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let queue = tickets.get_mut(key)?;
+        let before = queue.len();
+        queue.retain(|ticket| !ticket.is_expired(now));
+        if queue.len() < before {
+            Log::debug(&format!(
+                "TLS: dropped {} expired session ticket(s) for {}",
+                before - queue.len(),
+                key.host
+            ));
+        }
+        let ticket = queue.pop_back();
+        if queue.is_empty() {
+            tickets.remove(key);
+        }
+        ticket
+    }
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn new_session_ticket(lifetime: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&lifetime.to_be_bytes());
+        body.extend_from_slice(&1000u32.to_be_bytes());
+        body.extend_from_slice(&[1, 0, 0, 3, b'a', b'b', b'c', 0, 0]);
+        body
+    }
+
+    fn ticket(lifetime: u32, now: Instant) -> Ticket {
+        let suite = CipherSuite::Aes128GcmSha256;
+        Ticket::from_new_session_ticket(&new_session_ticket(lifetime), suite, &[7; 32], &[], now)
+            .expect("well formed")
+            .expect("usable")
+    }
+
+    #[test]
+    fn test_parse_new_session_ticket() {
+        let now = Instant::now();
+        let ticket = ticket(60, now);
+        assert_eq!(ticket.identity, b"abc");
+        assert_eq!(ticket.psk.len(), 32);
+        assert_eq!(
+            ticket.obfuscated_age(now + Duration::from_millis(250)),
+            1250
+        );
+        assert!(!ticket.is_expired(now + Duration::from_secs(59)));
+        assert!(ticket.is_expired(now + Duration::from_secs(60)));
+        // Lifetimes are capped at seven days
+        let week = Duration::from_secs(MAX_LIFETIME_SECS.into());
+        assert!(self::ticket(u32::MAX, now).is_expired(now + week));
+
+        let suite = CipherSuite::Aes128GcmSha256;
+        let zero = new_session_ticket(0);
+        assert!(
+            Ticket::from_new_session_ticket(&zero, suite, &[7; 32], &[], now)
+                .expect("well formed")
+                .is_none()
+        );
+        let truncated = &zero[..zero.len() - 1];
+        assert!(Ticket::from_new_session_ticket(truncated, suite, &[7; 32], &[], now).is_none());
+    }
+
+    #[test]
+    fn test_cache_expires_and_uses_tickets_once() {
+        let cache = SessionCache::default();
+        let key = SessionKey::new("API.example.", &TlsConfig::default());
+        let now = Instant::now();
+        cache.insert(&key, ticket(60, now));
+        cache.insert(&key, ticket(10, now));
+        for _ in 0..MAX_TICKETS {
+            cache.insert(
+                &SessionKey::new("other.example", &TlsConfig::default()),
+                ticket(60, now),
+            );
+        }
+
+        // The newer ticket has expired and is dropped; the older is used once
+        let later = now + Duration::from_secs(30);
+        let same_host = SessionKey::new("api.example", &TlsConfig::default());
+        assert!(cache.take(&same_host, later).is_some());
+        assert!(cache.take(&same_host, later).is_none());
+        let other = TlsConfig::default().with_resumption(false);
+        assert!(cache.take(&SessionKey::new("other.example", &other), now).is_none());
+    }
+}
@@ -0,0 +1,440 @@
+//! Minimal TLS 1.3 server for loopback tests, built from the same record
+//! layer and key schedule as the client.
+
+use std::net::{TcpListener, TcpStream};
+
+use super::{
+    client::{certificate_verify_algorithm, certificate_verify_content, parse_certificate_list},
+    codec::*,
+    identity::ecdsa_signature_der,
+    key_schedule::{CipherSuite, KeySchedule, TrafficKey, expand_label, finished_mac},
+    record::{Alert, ContentType, RecordLayer},
+    trust::{TrustStore, pem_blocks},
+    x509::Certificate,
+};
+use crate::{
+    errors::LlmError,
+    essentia::crypto::{
+        ct_eq,
+        ecdsa::{self, Curve},
+        from_hex, random_bytes,
+        sha2::HashAlgorithm,
+        x25519::{x25519, x25519_base},
+    },
+};
+
+/// Root of the test chain in `testdata`.
+pub(crate) const ROOT_PEM: &str = include_str!("testdata/root.pem");
+pub(crate) const INTERMEDIATE_PEM: &str = include_str!("testdata/intermediate.pem");
+pub(crate) const LEAF_PEM: &str = include_str!("testdata/leaf.pem");
+/// Private scalar of the P-256 key in `leaf.pem`.
+const LEAF_KEY: &str = "0731bd43bed290c7b94c53009ef229be737cee19b2e98739092b7775e7a77358";
+
+/// A store trusting only [`ROOT_PEM`].
+pub(crate) fn test_roots() -> TrustStore {
+    let mut roots = TrustStore::new();
+    roots.add_pem(ROOT_PEM);
+    roots
+}
+
+/// How the test server behaves during the handshake.
+#[derive(Clone)]
+pub(crate) struct ServerOptions {
+    pub(crate) suite: CipherSuite,
+    /// Answer the first ClientHello with a cookie HelloRetryRequest.
+    pub(crate) hello_retry: bool,
+    /// Send a Finished with a wrong MAC.
+    pub(crate) bad_finished: bool,
+    /// DER certificate chain to present, leaf first.
+    pub(crate) certificates: Vec<Vec<u8>>,
+    /// P-256 private key of the leaf, for CertificateVerify.
+    pub(crate) signing_key: Vec<u8>,
+    /// Send a CertificateRequest and verify the client's answer.
+    pub(crate) request_client_certificate: bool,
+    /// Protocol to select by ALPN, whether or not the client offered it.
+    pub(crate) alpn: Option<&'static str>,
+    /// Send a NewSessionTicket with this lifetime in seconds after the
+    /// handshake. Tickets carry their PSK in the clear, so any server
+    /// accepts them.
+    pub(crate) ticket_lifetime: Option<u32>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            suite: CipherSuite::Aes128GcmSha256,
+            hello_retry: false,
+            bad_finished: false,
+            certificates: [LEAF_PEM, INTERMEDIATE_PEM]
+                .iter()
+                .flat_map(|pem| pem_blocks(pem, "CERTIFICATE"))
+                .collect(),
+            signing_key: from_hex(LEAF_KEY),
+            request_client_certificate: false,
+            alpn: None,
+            ticket_lifetime: None,
+        }
+    }
+}
+
+/// Server end of an established connection.
+pub(crate) struct ServerConnection {
+    pub(crate) records:             RecordLayer<TcpStream>,
+    /// SNI host name sent by the client.
+    pub(crate) server_name:         Option<String>,
+    /// DER chain the client authenticated with, leaf first; empty when it
+    /// sent none or was not asked.
+    pub(crate) client_certificates: Vec<Vec<u8>>,
+    /// ALPN protocols offered by the client.
+    pub(crate) offered_protocols:   Vec<String>,
+    /// The client resumed with a ticket.
+    pub(crate) resumed:             bool,
+}
+
+impl ServerConnection {
+    /// Reads the next application data record.
+    pub(crate) fn read(&mut self) -> Result<Vec<u8>, LlmError> {
+        loop {
+            match self.records.read_record()? {
+                (ContentType::ApplicationData, data) => return Ok(data),
+                (ContentType::Alert, data) => {
+                    return Err(super::record::parse_alert(&data)
+                        .map_or_else(|| LlmError::tls("Malformed alert"), Alert::received));
+                },
+                (ContentType::Handshake, data) => {
+                    // KeyUpdate replies from the client
+                    self.records.push_handshake(&data);
+                    while self.records.next_handshake().is_some() {
+                        self.records.update_read_key()?;
+                    }
+                },
+                _ => return Err(LlmError::tls("Unexpected record")),
+            }
+        }
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) -> Result<(), LlmError> {
+        self.records.write_record(ContentType::ApplicationData, data)
+    }
+
+    /// Sends KeyUpdate, asking the client to update too.
+    pub(crate) fn key_update(&mut self) -> Result<(), LlmError> {
+        self.records
+            .write_record(ContentType::Handshake, &handshake_message(KEY_UPDATE, &[1]))?;
+        self.records.update_write_key()
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.records.send_alert(Alert::CLOSE_NOTIFY);
+    }
+}
+
+/// Accepts one connection on `listener` and runs the server handshake.
+pub(crate) fn accept(
+    listener: &TcpListener, options: &ServerOptions,
+) -> Result<ServerConnection, LlmError> {
+    let (stream, _) = listener.accept().map_err(|e| LlmError::io("accept", e))?;
+    let mut records = RecordLayer::new(stream);
+    let suite = options.suite;
+    let mut transcript = Transcript::default();
+
+    let mut message = records.read_handshake()?;
+    let mut hello = parse_client_hello(&message).ok_or_else(|| LlmError::tls("Bad ClientHello"))?;
+    if !hello.suites.contains(&suite.id()) {
+        return Err(records.fatal(Alert::HANDSHAKE_FAILURE, "No common cipher suite"));
+    }
+    if options.hello_retry {
+        transcript.add(&message);
+        transcript.restart(suite.hash());
+        let retry = server_hello(&HELLO_RETRY_RANDOM, &hello.session_id, suite, |out| {
+            put_extension(out, COOKIE, |out| put_vec16(out, b"retry cookie"));
+        });
+        transcript.add(&retry);
+        records.write_record(ContentType::Handshake, &retry)?;
+        message = records.read_handshake()?;
+        hello = parse_client_hello(&message).ok_or_else(|| LlmError::tls("Bad ClientHello"))?;
+        if hello.cookie.as_deref() != Some(b"retry cookie".as_slice()) {
+            return Err(records.fatal(Alert::ILLEGAL_PARAMETER, "Cookie not echoed"));
+        }
+    }
+    // Accept a PSK whose binder, over the hello up to the binders list,
+    // checks out
+    let psk = match &hello.psk {
+        Some((identity, binder)) if identity.get(..2) == Some(&suite.id().to_be_bytes()[..]) => {
+            let psk = &identity[2..];
+            let hash = suite.hash();
+            let binder_key =
+                KeySchedule::new(suite, Some(psk)).derive("res binder", &hash.digest(&[]));
+            let truncated = &message[..message.len() - hello.binders_len];
+            let expected = finished_mac(suite, &binder_key, &transcript.hash_with(hash, truncated));
+            if !ct_eq(binder, &expected) {
+                return Err(records.fatal(Alert::DECRYPT_ERROR, "PSK binder verification failed"));
+            }
+            Some(psk.to_vec())
+        },
+        _ => None,
+    };
+    transcript.add(&message);
+
+    let mut secret = [0u8; 32];
+    random_bytes(&mut secret);
+    let mut random = [0u8; 32];
+    random_bytes(&mut random);
+    let public = x25519_base(&secret);
+    let reply = server_hello(&random, &hello.session_id, suite, |out| {
+        put_extension(out, KEY_SHARE, |out| {
+            put_u16(out, X25519);
+            put_vec16(out, &public);
+        });
+        if psk.is_some() {
+            put_extension(out, PRE_SHARED_KEY, |out| put_u16(out, 0));
+        }
+    });
+    transcript.add(&reply);
+    records.write_record(ContentType::Handshake, &reply)?;
+    records.write_record(ContentType::ChangeCipherSpec, &[1])?;
+
+    let mut schedule = KeySchedule::new(suite, psk.as_deref());
+    schedule.advance(Some(&x25519(&secret, &hello.key_share)));
+    let hash = transcript.hash(suite.hash());
+    let client_secret = schedule.derive("c hs traffic", &hash);
+    let server_secret = schedule.derive("s hs traffic", &hash);
+    records.set_write_key(TrafficKey::new(suite, &server_secret)?);
+    records.set_read_key(TrafficKey::new(suite, &client_secret)?)?;
+
+    // The whole flight goes out in one write, split across records
+    let mut flight = Vec::new();
+    let mut add = |message: Vec<u8>, transcript: &mut Transcript| {
+        transcript.add(&message);
+        flight.extend_from_slice(&message);
+    };
+    let mut body = Vec::new();
+    put_prefixed(&mut body, 2, |out| {
+        if let Some(protocol) = options.alpn {
+            put_extension(out, ALPN, |out| {
+                put_prefixed(out, 2, |out| put_vec8(out, protocol.as_bytes()));
+            });
+        }
+    });
+    add(
+        handshake_message(ENCRYPTED_EXTENSIONS, &body),
+        &mut transcript,
+    );
+    let request_client_certificate = options.request_client_certificate && psk.is_none();
+    if request_client_certificate {
+        let mut body = Vec::new();
+        put_vec8(&mut body, &[]);
+        put_prefixed(&mut body, 2, |out| {
+            put_extension(out, SIGNATURE_ALGORITHMS, |out| {
+                put_prefixed(out, 2, |out| {
+                    for scheme in [0x0403, 0x0503, 0x0804] {
+                        put_u16(out, scheme);
+                    }
+                });
+            });
+        });
+        add(
+            handshake_message(CERTIFICATE_REQUEST, &body),
+            &mut transcript,
+        );
+    }
+    if psk.is_none() {
+        let mut body = Vec::new();
+        put_vec8(&mut body, &[]);
+        put_prefixed(&mut body, 3, |out| {
+            for certificate in &options.certificates {
+                put_prefixed(out, 3, |out| out.extend_from_slice(certificate));
+                put_u16(out, 0);
+            }
+        });
+        add(handshake_message(CERTIFICATE, &body), &mut transcript);
+        let content = certificate_verify_content("server", &transcript.hash(suite.hash()));
+        let (r, s) = ecdsa::sign(
+            Curve::P256,
+            &options.signing_key,
+            HashAlgorithm::Sha256,
+            &content,
+        )
+        .ok_or_else(|| LlmError::tls("Bad signing key"))?;
+        let mut body = Vec::new();
+        put_u16(&mut body, 0x0403);
+        put_vec16(&mut body, &ecdsa_signature_der(&r, &s));
+        add(
+            handshake_message(CERTIFICATE_VERIFY, &body),
+            &mut transcript,
+        );
+    }
+    let mut mac = finished_mac(suite, &server_secret, &transcript.hash(suite.hash()));
+    if options.bad_finished {
+        mac[0] ^= 1;
+    }
+    add(handshake_message(FINISHED, &mac), &mut transcript);
+    records.write_record(ContentType::Handshake, &flight)?;
+
+    schedule.advance(None);
+    let hash = transcript.hash(suite.hash());
+    let client_app_secret = schedule.derive("c ap traffic", &hash);
+    let server_app_secret = schedule.derive("s ap traffic", &hash);
+    records.set_write_key(TrafficKey::new(suite, &server_app_secret)?);
+
+    let mut client_certificates = Vec::new();
+    if request_client_certificate {
+        let message = records.read_handshake()?;
+        client_certificates = (message[0] == CERTIFICATE)
+            .then(|| parse_certificate_list(&message[4..]))
+            .flatten()
+            .ok_or_else(|| records.fatal(Alert::DECODE_ERROR, "Bad client Certificate"))?;
+        transcript.add(&message);
+        if let Some(leaf) = client_certificates.first() {
+            let content = certificate_verify_content("client", &transcript.hash(suite.hash()));
+            let message = records.read_handshake()?;
+            let mut reader = Reader::new(message.get(4..).unwrap_or_default());
+            let key = Certificate::from_der(leaf)?.public_key;
+            let verified = (message[0] == CERTIFICATE_VERIFY)
+                .then(|| Some((reader.u16()?, reader.vec16()?)))
+                .flatten()
+                .and_then(|(scheme, signature)| {
+                    let algorithm = certificate_verify_algorithm(scheme, &key)?;
+                    Some(key.verify(algorithm, &content, signature))
+                });
+            if verified != Some(true) {
+                return Err(records.fatal(
+                    Alert::DECRYPT_ERROR,
+                    "Client CertificateVerify verification failed",
+                ));
+            }
+            transcript.add(&message);
+        }
+    }
+    let message = records.read_handshake()?;
+    let expected = finished_mac(suite, &client_secret, &transcript.hash(suite.hash()));
+    if message[0] != FINISHED || !ct_eq(&message[4..], &expected) {
+        return Err(records.fatal(Alert::DECRYPT_ERROR, "Client Finished verification failed"));
+    }
+    transcript.add(&message);
+    records.set_read_key(TrafficKey::new(suite, &client_app_secret)?)?;
+
+    if let Some(lifetime) = options.ticket_lifetime {
+        let hash = suite.hash();
+        let resumption_secret = schedule.derive("res master", &transcript.hash(hash));
+        let nonce = [0u8];
+        let mut identity = suite.id().to_be_bytes().to_vec();
+        identity.extend(expand_label(
+            hash,
+            &resumption_secret,
+            "resumption",
+            &nonce,
+            hash.output_len(),
+        ));
+        let mut body = lifetime.to_be_bytes().to_vec();
+        body.extend_from_slice(&random_u32().to_be_bytes());
+        put_vec8(&mut body, &nonce);
+        put_vec16(&mut body, &identity);
+        put_vec16(&mut body, &[]);
+        records.write_record(
+            ContentType::Handshake,
+            &handshake_message(NEW_SESSION_TICKET, &body),
+        )?;
+    }
+
+    Ok(ServerConnection {
+        records,
+        server_name: hello.server_name,
+        client_certificates,
+        offered_protocols: hello.protocols,
+        resumed: psk.is_some(),
+    })
+}
+
+struct ClientHelloInfo {
+    session_id:  Vec<u8>,
+    suites:      Vec<u16>,
+    key_share:   [u8; 32],
+    server_name: Option<String>,
+    cookie:      Option<Vec<u8>>,
+    protocols:   Vec<String>,
+    /// First PSK identity and binder offered.
+    psk:         Option<(Vec<u8>, Vec<u8>)>,
+    /// Length of the binders list that ends the message, prefix included.
+    binders_len: usize,
+}
+
+fn parse_client_hello(message: &[u8]) -> Option<ClientHelloInfo> {
+    let mut reader = Reader::new(message.get(4..)?);
+    let _version = reader.u16()?;
+    let _random = reader.bytes(32)?;
+    let session_id = reader.vec8()?.to_vec();
+    let suites = reader
+        .vec16()?
+        .chunks_exact(2)
+        .map(|s| u16::from_be_bytes([s[0], s[1]]))
+        .collect();
+    let _compression = reader.vec8()?;
+    let mut info = ClientHelloInfo {
+        session_id,
+        suites,
+        key_share: [0; 32],
+        server_name: None,
+        cookie: None,
+        protocols: Vec::new(),
+        psk: None,
+        binders_len: 0,
+    };
+    for (kind, data) in parse_extensions(reader.vec16()?)? {
+        let mut reader = Reader::new(data);
+        match kind {
+            SERVER_NAME => {
+                let mut list = Reader::new(reader.vec16()?);
+                let _kind = list.u8()?;
+                info.server_name = Some(String::from_utf8(list.vec16()?.to_vec()).ok()?);
+            },
+            KEY_SHARE => {
+                let mut shares = Reader::new(reader.vec16()?);
+                while !shares.is_empty() {
+                    let group = shares.u16()?;
+                    let key = shares.vec16()?;
+                    if group == X25519 {
+                        info.key_share = key.try_into().ok()?;
+                    }
+                }
+            },
+            COOKIE => info.cookie = Some(reader.vec16()?.to_vec()),
+            ALPN => {
+                let mut list = Reader::new(reader.vec16()?);
+                while !list.is_empty() {
+                    info.protocols.push(String::from_utf8(list.vec8()?.to_vec()).ok()?);
+                }
+            },
+            PRE_SHARED_KEY => {
+                let identity = Reader::new(reader.vec16()?).vec16()?.to_vec();
+                let binders = reader.vec16()?;
+                info.binders_len = 2 + binders.len();
+                info.psk = Some((identity, Reader::new(binders).vec8()?.to_vec()));
+            },
+            _ => {},
+        }
+    }
+    Some(info)
+}
+
+fn server_hello(
+    random: &[u8; 32], session_id: &[u8], suite: CipherSuite, extensions: impl FnOnce(&mut Vec<u8>),
+) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_u16(&mut body, 0x0303);
+    body.extend_from_slice(random);
+    put_vec8(&mut body, session_id);
+    put_u16(&mut body, suite.id());
+    body.push(0);
+    put_prefixed(&mut body, 2, |out| {
+        put_extension(out, SUPPORTED_VERSIONS, |out| put_u16(out, TLS13));
+        extensions(out);
+    });
+    handshake_message(SERVER_HELLO, &body)
+}
+
+fn random_u32() -> u32 {
+    let mut bytes = [0u8; 4];
+    random_bytes(&mut bytes);
+    u32::from_be_bytes(bytes)
+}
@@ -0,0 +1,211 @@
+//! Trust anchors for server certificate validation.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use super::x509::{Certificate, verify_chain};
+use crate::errors::LlmError;
+
+/// CA bundles shipped by common operating systems, in lookup order.
+pub const SYSTEM_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+/// Environment variable naming a PEM bundle file or directory to trust
+/// instead of the system roots.
+pub const CA_BUNDLE_ENV: &str = "ESSENTIA_CA_BUNDLE";
+
+/// Root certificates trusted to issue server certificates.
+#[derive(Clone, Default)]
+pub struct TrustStore {
+    roots: Vec<Certificate>,
+}
+
+impl fmt::Debug for TrustStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrustStore").field("roots", &self.roots.len()).finish()
+    }
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The operating system's CA bundle, loaded on first use from the first
+    /// of [`SYSTEM_BUNDLES`] that exists. Empty when there is none.
+    pub fn system() -> Arc<TrustStore> {
+        static SYSTEM: OnceLock<Arc<TrustStore>> = OnceLock::new();
+        SYSTEM
+            .get_or_init(|| {
+                let mut store = TrustStore::new();
+                if let Some(pem) =
+                    SYSTEM_BUNDLES.iter().find_map(|path| std::fs::read_to_string(path).ok())
+                {
+                    store.add_pem(&pem);
+                }
+                Arc::new(store)
+            })
+            .clone()
+    }
+
+    /// Roots used when none are configured: the bundle named by
+    /// [`CA_BUNDLE_ENV`], else [`system`](Self::system).
+    pub fn default_roots() -> Result<Arc<TrustStore>, LlmError> {
+        match std::env::var(CA_BUNDLE_ENV) {
+            Ok(path) if !path.trim().is_empty() => Self::from_path(path.trim()),
+            _ => Ok(Self::system()),
+        }
+    }
+
+    /// Loads the PEM bundle at `path`, or every PEM file in it if it is a
+    /// directory.
+    ///
+    /// Each path is read once and the store shared, so connections made
+    /// with the same bundle can be pooled together; changes to the files
+    /// take effect on restart.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Arc<TrustStore>, LlmError> {
+        static LOADED: OnceLock<Mutex<HashMap<PathBuf, Arc<TrustStore>>>> = OnceLock::new();
+        let path = path.as_ref();
+        let mut loaded = LOADED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(store) = loaded.get(path) {
+            return Ok(Arc::clone(store));
+        }
+        let store = Arc::new(Self::load(path)?);
+        loaded.insert(path.to_path_buf(), Arc::clone(&store));
+        Ok(store)
+    }
+
+    /// Reads `path` without caching; see [`from_path`](Self::from_path).
+    pub fn load(path: &Path) -> Result<TrustStore, LlmError> {
+        let unreadable = |e: std::io::Error| {
+            LlmError::Config(format!("Cannot read CA bundle {}: {}", path.display(), e))
+        };
+        let mut store = TrustStore::new();
+        if path.is_dir() {
+            let mut files = fs::read_dir(path)
+                .map_err(unreadable)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(unreadable)?;
+            files.sort();
+            // Non-PEM files are skipped
+            for file in files.iter().filter(|file| file.is_file()) {
+                if let Ok(pem) = fs::read_to_string(file) {
+                    store.add_pem(&pem);
+                }
+            }
+        } else {
+            store.add_pem(&fs::read_to_string(path).map_err(unreadable)?);
+        }
+        if store.is_empty() {
+            return Err(LlmError::Config(format!(
+                "No certificates found in CA bundle {}",
+                path.display()
+            )));
+        }
+        Ok(store)
+    }
+
+    pub fn add(&mut self, root: Certificate) {
+        self.roots.push(root);
+    }
+
+    pub fn add_der(&mut self, der: &[u8]) -> Result<(), LlmError> {
+        self.add(Certificate::from_der(der)?);
+        Ok(())
+    }
+
+    /// Adds every `CERTIFICATE` block of a PEM bundle and returns how many
+    /// were added. Blocks that do not parse are skipped, since system
+    /// bundles may hold roots with algorithms this crate does not support.
+    pub fn add_pem(&mut self, pem: &str) -> usize {
+        let before = self.roots.len();
+        for der in pem_blocks(pem, "CERTIFICATE") {
+            let _ = self.add_der(&der);
+        }
+        self.roots.len() - before
+    }
+
+    pub fn roots(&self) -> &[Certificate] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Validates a server chain, leaf first, and returns the path to the
+    /// trust anchor; see [`verify_chain`].
+    pub fn verify<'a>(
+        &'a self, chain: &'a [Certificate], host: &str, now: SystemTime,
+    ) -> Result<Vec<&'a Certificate>, LlmError> {
+        if self.roots.is_empty() {
+            return Err(LlmError::tls("No trusted root certificates are configured"));
+        }
+        verify_chain(chain, &self.roots, host, now)
+    }
+}
+
+/// Decoded contents of each `-----BEGIN {label}-----` block in `pem`.
+/// Blocks with invalid base64 are skipped.
+pub(crate) fn pem_blocks(pem: &str, label: &str) -> Vec<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let mut blocks = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(&begin) {
+        let body = &rest[start + begin.len()..];
+        let Some(stop) = body.find(&end) else { break };
+        let base64: String = body[..stop].chars().filter(|c| !c.is_whitespace()).collect();
+        if let Ok(der) = crate::essentia::base64::decode(&base64) {
+            blocks.push(der);
+        }
+        rest = &body[stop + end.len()..];
+    }
+    blocks
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_file_and_directory() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/essentia/tls/testdata");
+        let store = TrustStore::load(&testdata.join("root.pem")).expect("file");
+        assert_eq!(store.len(), 1);
+        // Every certificate in every file of the directory
+        assert_eq!(TrustStore::load(&testdata).expect("directory").len(), 7);
+
+        let shared = TrustStore::from_path(testdata.join("root.pem")).expect("cached");
+        assert!(Arc::ptr_eq(
+            &shared,
+            &TrustStore::from_path(testdata.join("root.pem")).expect("cached")
+        ));
+
+        let err = TrustStore::load(&testdata.join("missing.pem")).expect_err("missing");
+        assert!(err.to_string().starts_with("Configuration error: Cannot read CA bundle"));
+        let empty = std::env::temp_dir().join(format!("essentia-empty-{}.pem", std::process::id()));
+        fs::write(&empty, "not a certificate").expect("write");
+        let err = TrustStore::load(&empty).expect_err("empty");
+        let _ = fs::remove_file(&empty);
+        assert!(err.to_string().contains("No certificates found"));
+    }
+}
@@ -0,0 +1,842 @@
+//! X.509 v3 certificates (RFC 5280): DER parsing, signature checks, chain
+//! building and host name matching (RFC 6125).
+
+use std::{
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::der::{self, Der};
+use crate::{
+    errors::LlmError,
+    essentia::crypto::{
+        ecdsa::{self, Curve},
+        rsa::RsaPublicKey,
+        sha2::HashAlgorithm,
+    },
+};
+
+// Algorithm identifiers
+pub(super) const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const MGF1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x08];
+const RSASSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+pub(super) const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const ECDSA_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+// Extensions and key purposes
+const KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const ANY_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25, 0x00];
+const SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+
+/// Longest chain accepted between the leaf and a root.
+const MAX_INTERMEDIATES: usize = 8;
+
+/// A public key from a certificate's SubjectPublicKeyInfo.
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    /// Uncompressed point on `curve`.
+    Ec {
+        curve: Curve,
+        point: Vec<u8>,
+    },
+    /// An algorithm this crate cannot verify with, by OID.
+    Unsupported(String),
+}
+
+/// How a signature was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    RsaPkcs1(HashAlgorithm),
+    RsaPss {
+        hash:     HashAlgorithm,
+        salt_len: usize,
+    },
+    Ecdsa(HashAlgorithm),
+}
+
+impl PublicKey {
+    /// Checks `signature` over `message`. ECDSA signatures are DER
+    /// `Ecdsa-Sig-Value`s.
+    pub fn verify(&self, algorithm: SignatureAlgorithm, message: &[u8], signature: &[u8]) -> bool {
+        match (self, algorithm) {
+            (Self::Rsa(key), SignatureAlgorithm::RsaPkcs1(hash)) => {
+                key.verify_pkcs1(hash, message, signature)
+            },
+            (Self::Rsa(key), SignatureAlgorithm::RsaPss { hash, salt_len }) => {
+                key.verify_pss(hash, message, signature, salt_len)
+            },
+            (Self::Ec { curve, point }, SignatureAlgorithm::Ecdsa(hash)) => {
+                let mut outer = Der::new(signature);
+                let parsed = outer.sequence().and_then(|mut sequence| {
+                    let r = sequence.unsigned()?;
+                    let s = sequence.unsigned()?;
+                    sequence.is_empty().then_some((r, s))
+                });
+                match parsed {
+                    Some((r, s)) if outer.is_empty() => {
+                        ecdsa::verify(*curve, point, hash, message, r, s)
+                    },
+                    _ => false,
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Key usage bits (RFC 5280 section 4.2.1.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUsage(u32);
+
+impl KeyUsage {
+    pub const DIGITAL_SIGNATURE: Self = Self(1 << 31);
+    pub const KEY_ENCIPHERMENT: Self = Self(1 << 29);
+    pub const KEY_CERT_SIGN: Self = Self(1 << 26);
+    pub const CRL_SIGN: Self = Self(1 << 25);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// A subjectAltName entry; other name forms are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+}
+
+/// A parsed X.509 v3 certificate.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    /// Subject name, e.g. `O=Example, CN=example.com`.
+    pub subject:            String,
+    pub issuer:             String,
+    /// Serial number as encoded.
+    pub serial:             Vec<u8>,
+    pub not_before:         SystemTime,
+    pub not_after:          SystemTime,
+    pub subject_alt_names:  Vec<SubjectAltName>,
+    /// `None` when the extension is absent, which allows any usage.
+    pub key_usage:          Option<KeyUsage>,
+    /// Extended key usage OIDs in dotted form; `None` when absent.
+    pub extended_key_usage: Option<Vec<String>>,
+    /// basicConstraints `cA`.
+    pub is_ca:              bool,
+    /// basicConstraints `pathLenConstraint`.
+    pub path_len:           Option<u64>,
+    pub public_key:         PublicKey,
+    common_name:            Option<String>,
+    der:                    Vec<u8>,
+    tbs:                    Vec<u8>,
+    subject_der:            Vec<u8>,
+    issuer_der:             Vec<u8>,
+    spki:                   Vec<u8>,
+    /// `Err` carries the OID of an unsupported algorithm.
+    signature_algorithm:    Result<SignatureAlgorithm, String>,
+    signature:              Vec<u8>,
+    server_auth:            bool,
+}
+
+impl Certificate {
+    pub fn from_der(der: &[u8]) -> Result<Self, LlmError> {
+        parse(der).map_err(|message| LlmError::tls(&format!("Invalid certificate: {}", message)))
+    }
+
+    /// The certificate as received.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The encoded SubjectPublicKeyInfo.
+    pub fn spki(&self) -> &[u8] {
+        &self.spki
+    }
+
+    /// The last common name in the subject.
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// Fails unless `now` is within the validity period.
+    pub fn check_validity(&self, now: SystemTime) -> Result<(), LlmError> {
+        if now < self.not_before {
+            return Err(LlmError::tls(&format!(
+                "Certificate '{}' is not valid yet",
+                self.subject
+            )));
+        }
+        if now > self.not_after {
+            return Err(LlmError::tls(&format!(
+                "Certificate '{}' has expired",
+                self.subject
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the certificate names `host`, a DNS name or IP address.
+    ///
+    /// Follows RFC 6125: IP addresses only match iPAddress entries, the
+    /// common name is consulted only without DNS entries, and a wildcard
+    /// must be the whole left-most label and covers exactly one label.
+    pub fn matches_host(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self.subject_alt_names.contains(&SubjectAltName::Ip(ip));
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let mut names = self
+            .subject_alt_names
+            .iter()
+            .filter_map(|name| match name {
+                SubjectAltName::Dns(name) => Some(name.as_str()),
+                SubjectAltName::Ip(_) => None,
+            })
+            .peekable();
+        if names.peek().is_none() {
+            return self.common_name().is_some_and(|name| dns_name_matches(name, &host));
+        }
+        names.any(|name| dns_name_matches(name, &host))
+    }
+
+    /// Like [`matches_host`](Self::matches_host), as a `Result`.
+    pub fn check_host(&self, host: &str) -> Result<(), LlmError> {
+        if self.matches_host(host) {
+            return Ok(());
+        }
+        Err(LlmError::tls(&format!(
+            "Certificate '{}' is not valid for {}",
+            self.subject, host
+        )))
+    }
+
+    /// Whether `issuer`'s key made this certificate's signature.
+    pub fn is_signed_by(&self, issuer: &Certificate) -> bool {
+        self.signature_algorithm
+            .clone()
+            .is_ok_and(|algorithm| issuer.public_key.verify(algorithm, &self.tbs, &self.signature))
+    }
+
+    fn is_self_issued(&self) -> bool {
+        self.subject_der == self.issuer_der
+    }
+}
+
+/// Matches a presented DNS name against a lowercase host name.
+fn dns_name_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        // At least two labels after the wildcard, so `*.com` matches nothing
+        Some(suffix) if suffix.contains('.') && !suffix.contains('*') => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == suffix),
+        Some(_) => false,
+        None => !pattern.contains('*') && pattern == host,
+    }
+}
+
+/// Checks that `chain` (leaf first, as sent by the server) is valid for
+/// `host` at `now` and leads to one of `roots`, returning the path from the
+/// leaf to the trust anchor.
+///
+/// Intermediates may come in any order and unused ones are ignored. Trust
+/// anchors are taken as they are, without checking their own validity.
+pub fn verify_chain<'a>(
+    chain: &'a [Certificate], roots: &'a [Certificate], host: &str, now: SystemTime,
+) -> Result<Vec<&'a Certificate>, LlmError> {
+    let Some(leaf) = chain.first() else {
+        return Err(LlmError::tls("No server certificate"));
+    };
+    leaf.check_validity(now)?;
+    leaf.check_host(host)?;
+    if !leaf.server_auth {
+        return Err(LlmError::tls(
+            "Certificate is not valid for server authentication",
+        ));
+    }
+    if leaf.key_usage.is_some_and(|usage| !usage.contains(KeyUsage::DIGITAL_SIGNATURE)) {
+        return Err(LlmError::tls(
+            "Certificate key may not be used for signatures",
+        ));
+    }
+    let mut used = vec![false; chain.len()];
+    build_path(leaf, &chain[1..], &mut used[1..], roots, 0, now)
+}
+
+/// Depth-first search from `certificate` to a root through unused
+/// intermediates; the path found starts with `certificate`.
+fn build_path<'a>(
+    certificate: &'a Certificate, intermediates: &'a [Certificate], used: &mut [bool],
+    roots: &'a [Certificate], depth: usize, now: SystemTime,
+) -> Result<Vec<&'a Certificate>, LlmError> {
+    if roots.iter().any(|root| root.der == certificate.der) {
+        return Ok(vec![certificate]);
+    }
+    if let Some(root) = roots
+        .iter()
+        .find(|root| root.subject_der == certificate.issuer_der && certificate.is_signed_by(root))
+    {
+        return Ok(vec![certificate, root]);
+    }
+
+    let mut error = None;
+    if depth < MAX_INTERMEDIATES {
+        for i in 0..intermediates.len() {
+            let issuer = &intermediates[i];
+            if used[i] || issuer.subject_der != certificate.issuer_der {
+                continue;
+            }
+            let checked = issuer.check_validity(now).and_then(|()| {
+                // pathLenConstraint does not count self-issued intermediates
+                let below = depth - usize::from(depth > 0 && certificate.is_self_issued());
+                if !issuer.is_ca
+                    || issuer.key_usage.is_some_and(|u| !u.contains(KeyUsage::KEY_CERT_SIGN))
+                    || issuer.path_len.is_some_and(|len| (len as usize) < below)
+                {
+                    return Err(LlmError::tls(&format!(
+                        "Certificate '{}' may not issue certificates",
+                        issuer.subject
+                    )));
+                }
+                if !certificate.is_signed_by(issuer) {
+                    return Err(LlmError::tls(&format!(
+                        "Bad signature on certificate '{}'",
+                        certificate.subject
+                    )));
+                }
+                used[i] = true;
+                let result = build_path(issuer, intermediates, used, roots, depth + 1, now);
+                used[i] = false;
+                result
+            });
+            match checked {
+                Ok(mut path) => {
+                    path.insert(0, certificate);
+                    return Ok(path);
+                },
+                Err(e) => error = Some(e),
+            }
+        }
+    }
+    Err(error.unwrap_or_else(|| {
+        LlmError::tls(&format!(
+            "Certificate '{}' is not issued by a trusted root",
+            certificate.subject
+        ))
+    }))
+}
+
+fn parse(der: &[u8]) -> Result<Certificate, String> {
+    let malformed = |what: &str| format!("malformed {}", what);
+    let mut outer = Der::new(der);
+    let mut certificate = outer.sequence().ok_or_else(|| malformed("certificate"))?;
+    if !outer.is_empty() {
+        return Err("trailing data".to_string());
+    }
+    let tbs = certificate.read_raw(der::SEQUENCE).ok_or_else(|| malformed("certificate"))?;
+    let outer_algorithm =
+        certificate.read_raw(der::SEQUENCE).ok_or_else(|| malformed("algorithm"))?;
+    let signature = certificate.bit_string().ok_or_else(|| malformed("signature"))?;
+    if !certificate.is_empty() {
+        return Err(malformed("certificate"));
+    }
+
+    let mut fields = Der::new(tbs).sequence().ok_or_else(|| malformed("certificate"))?;
+    let version = match fields.optional(der::explicit(0)).ok_or_else(|| malformed("version"))? {
+        Some(version) => Der::new(version).small_unsigned().ok_or_else(|| malformed("version"))?,
+        None => 0,
+    };
+    if version != 2 {
+        return Err(format!("unsupported version {}", version + 1));
+    }
+    let serial = fields.read(der::INTEGER).ok_or_else(|| malformed("serial number"))?;
+    let inner_algorithm = fields.read_raw(der::SEQUENCE).ok_or_else(|| malformed("algorithm"))?;
+    if inner_algorithm != outer_algorithm {
+        return Err("mismatched signature algorithms".to_string());
+    }
+    let issuer_der = fields.read_raw(der::SEQUENCE).ok_or_else(|| malformed("issuer"))?;
+    let mut validity = fields.sequence().ok_or_else(|| malformed("validity"))?;
+    let not_before = parse_time(&mut validity).ok_or_else(|| malformed("validity"))?;
+    let not_after = parse_time(&mut validity).ok_or_else(|| malformed("validity"))?;
+    let subject_der = fields.read_raw(der::SEQUENCE).ok_or_else(|| malformed("subject"))?;
+    let spki = fields.read_raw(der::SEQUENCE).ok_or_else(|| malformed("public key"))?;
+    for unique_id in [der::implicit(1), der::implicit(2)] {
+        fields.optional(unique_id).ok_or_else(|| malformed("unique identifier"))?;
+    }
+    let extensions = fields.optional(der::explicit(3)).ok_or_else(|| malformed("extensions"))?;
+    if !fields.is_empty() {
+        return Err(malformed("certificate"));
+    }
+
+    let (subject, common_name) = parse_name(subject_der).ok_or_else(|| malformed("subject"))?;
+    let (issuer, _) = parse_name(issuer_der).ok_or_else(|| malformed("issuer"))?;
+    let mut parsed = Certificate {
+        subject,
+        issuer,
+        serial: serial.to_vec(),
+        not_before,
+        not_after,
+        subject_alt_names: Vec::new(),
+        key_usage: None,
+        extended_key_usage: None,
+        is_ca: false,
+        path_len: None,
+        public_key: parse_public_key(spki).ok_or_else(|| malformed("public key"))?,
+        common_name,
+        der: der.to_vec(),
+        tbs: tbs.to_vec(),
+        subject_der: subject_der.to_vec(),
+        issuer_der: issuer_der.to_vec(),
+        spki: spki.to_vec(),
+        signature_algorithm: parse_signature_algorithm(outer_algorithm)
+            .ok_or_else(|| malformed("signature algorithm"))?,
+        signature: signature.to_vec(),
+        server_auth: true,
+    };
+    if let Some(extensions) = extensions {
+        parse_extensions(&mut parsed, extensions)?;
+    }
+    Ok(parsed)
+}
+
+fn parse_extensions(certificate: &mut Certificate, data: &[u8]) -> Result<(), String> {
+    let malformed = |what: &str| format!("malformed {} extension", what);
+    let mut outer = Der::new(data);
+    let mut list = outer.sequence().ok_or_else(|| malformed("certificate"))?;
+    let mut seen: Vec<&[u8]> = Vec::new();
+    while !list.is_empty() {
+        let mut extension = list.sequence().ok_or_else(|| malformed("certificate"))?;
+        let id = extension.read(der::OID).ok_or_else(|| malformed("certificate"))?;
+        let critical = match extension.peek() {
+            Some(der::BOOLEAN) => extension.boolean().ok_or_else(|| malformed("certificate"))?,
+            _ => false,
+        };
+        let value = extension.read(der::OCTET_STRING).ok_or_else(|| malformed("certificate"))?;
+        if !extension.is_empty() {
+            return Err(malformed("certificate"));
+        }
+        if seen.contains(&id) {
+            return Err(format!("duplicate extension {}", der::oid_to_string(id)));
+        }
+        seen.push(id);
+
+        let mut value = Der::new(value);
+        match id {
+            SUBJECT_ALT_NAME => {
+                let mut names = value.sequence().ok_or_else(|| malformed("subjectAltName"))?;
+                while !names.is_empty() {
+                    let (tag, name) =
+                        names.read_any().ok_or_else(|| malformed("subjectAltName"))?;
+                    let name = match (tag, name.len()) {
+                        // dNSName [2] IA5String
+                        (0x82, _) => SubjectAltName::Dns(
+                            std::str::from_utf8(name)
+                                .ok()
+                                .filter(|name| name.is_ascii())
+                                .ok_or_else(|| malformed("subjectAltName"))?
+                                .to_string(),
+                        ),
+                        // iPAddress [7] OCTET STRING
+                        (0x87, 4) => SubjectAltName::Ip(IpAddr::from(
+                            <[u8; 4]>::try_from(name).map_err(|_| malformed("subjectAltName"))?,
+                        )),
+                        (0x87, 16) => SubjectAltName::Ip(IpAddr::from(
+                            <[u8; 16]>::try_from(name).map_err(|_| malformed("subjectAltName"))?,
+                        )),
+                        (0x87, _) => return Err(malformed("subjectAltName")),
+                        _ => continue,
+                    };
+                    certificate.subject_alt_names.push(name);
+                }
+            },
+            KEY_USAGE => {
+                let flags = value.flags().ok_or_else(|| malformed("keyUsage"))?;
+                certificate.key_usage = Some(KeyUsage(flags));
+            },
+            BASIC_CONSTRAINTS => {
+                let mut constraints =
+                    value.sequence().ok_or_else(|| malformed("basicConstraints"))?;
+                if constraints.peek() == Some(der::BOOLEAN) {
+                    certificate.is_ca =
+                        constraints.boolean().ok_or_else(|| malformed("basicConstraints"))?;
+                }
+                if !constraints.is_empty() {
+                    certificate.path_len = Some(
+                        constraints
+                            .small_unsigned()
+                            .ok_or_else(|| malformed("basicConstraints"))?,
+                    );
+                }
+            },
+            EXT_KEY_USAGE => {
+                let mut purposes = value.sequence().ok_or_else(|| malformed("extKeyUsage"))?;
+                let mut usages = Vec::new();
+                let mut server_auth = false;
+                while !purposes.is_empty() {
+                    let purpose =
+                        purposes.read(der::OID).ok_or_else(|| malformed("extKeyUsage"))?;
+                    server_auth |= purpose == SERVER_AUTH || purpose == ANY_EXTENDED_KEY_USAGE;
+                    usages.push(der::oid_to_string(purpose));
+                }
+                certificate.server_auth = server_auth;
+                certificate.extended_key_usage = Some(usages);
+            },
+            _ if critical => {
+                return Err(format!(
+                    "unsupported critical extension {}",
+                    der::oid_to_string(id)
+                ));
+            },
+            _ => continue,
+        }
+        if !value.is_empty() {
+            return Err(malformed(&der::oid_to_string(id)));
+        }
+    }
+    if !outer.is_empty() {
+        return Err(malformed("certificate"));
+    }
+    Ok(())
+}
+
+/// Display form of a Name and its last common name.
+fn parse_name(data: &[u8]) -> Option<(String, Option<String>)> {
+    let mut rdns = Der::new(data).sequence()?;
+    let mut parts = Vec::new();
+    let mut common_name = None;
+    while !rdns.is_empty() {
+        let mut set = Der::new(rdns.read(der::SET)?);
+        while !set.is_empty() {
+            let mut attribute = set.sequence()?;
+            let kind = attribute.read(der::OID)?;
+            let (tag, value) = attribute.read_any()?;
+            let value = match tag {
+                der::UTF8_STRING
+                | der::PRINTABLE_STRING
+                | der::IA5_STRING
+                | der::TELETEX_STRING => String::from_utf8_lossy(value).into_owned(),
+                der::BMP_STRING => String::from_utf16_lossy(
+                    &value
+                        .chunks_exact(2)
+                        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                        .collect::<Vec<_>>(),
+                ),
+                der::UNIVERSAL_STRING => value
+                    .chunks(4)
+                    .filter_map(|c| {
+                        char::from_u32(u32::from_be_bytes(<[u8; 4]>::try_from(c).ok()?))
+                    })
+                    .collect(),
+                _ => format!("#{}", crate::essentia::crypto::to_hex(value)),
+            };
+            let name = match kind {
+                [0x55, 0x04, 0x03] => {
+                    common_name = Some(value.clone());
+                    "CN".to_string()
+                },
+                [0x55, 0x04, 0x06] => "C".to_string(),
+                [0x55, 0x04, 0x07] => "L".to_string(),
+                [0x55, 0x04, 0x08] => "ST".to_string(),
+                [0x55, 0x04, 0x0a] => "O".to_string(),
+                [0x55, 0x04, 0x0b] => "OU".to_string(),
+                _ => der::oid_to_string(kind),
+            };
+            parts.push(format!("{}={}", name, value));
+        }
+    }
+    Some((parts.join(", "), common_name))
+}
+
+/// UTCTime or GeneralizedTime in the `Z` forms RFC 5280 requires.
+fn parse_time(der: &mut Der<'_>) -> Option<SystemTime> {
+    let (tag, value) = der.read_any()?;
+    let text = std::str::from_utf8(value).ok()?;
+    let digits = text.strip_suffix('Z').filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+    let number = |range: std::ops::Range<usize>| digits.get(range)?.parse::<u32>().ok();
+    let (year, rest) = match (tag, digits.len()) {
+        (der::UTC_TIME, 12) => {
+            let year = number(0..2)? as i64;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, 2)
+        },
+        (der::GENERALIZED_TIME, 14) => (number(0..4)? as i64, 4),
+        _ => return None,
+    };
+    let month = number(rest..rest + 2)?;
+    let day = number(rest + 2..rest + 4)?;
+    let hour = number(rest + 4..rest + 6)? as i64;
+    let minute = number(rest + 6..rest + 8)? as i64;
+    let second = number(rest + 8..rest + 10)? as i64;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let days = crate::core::retry::days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    })
+}
+
+/// Algorithm OID and raw parameters of an AlgorithmIdentifier.
+pub(super) fn parse_algorithm(data: &[u8]) -> Option<(&[u8], Option<&[u8]>)> {
+    let mut sequence = Der::new(data).sequence()?;
+    let oid = sequence.read(der::OID)?;
+    let parameters = match sequence.peek() {
+        Some(tag) => Some(sequence.read_raw(tag)?),
+        None => None,
+    };
+    sequence.is_empty().then_some((oid, parameters))
+}
+
+/// The curve a namedCurve OID refers to, if supported.
+pub(super) fn named_curve(oid: &[u8]) -> Option<Curve> {
+    match oid {
+        PRIME256V1 => Some(Curve::P256),
+        SECP384R1 => Some(Curve::P384),
+        _ => None,
+    }
+}
+
+fn parse_public_key(spki: &[u8]) -> Option<PublicKey> {
+    let mut sequence = Der::new(spki).sequence()?;
+    let algorithm = sequence.read_raw(der::SEQUENCE)?;
+    let key = sequence.bit_string()?;
+    if !sequence.is_empty() {
+        return None;
+    }
+    let (oid, parameters) = parse_algorithm(algorithm)?;
+    match oid {
+        RSA_ENCRYPTION => {
+            let mut outer = Der::new(key);
+            let mut fields = outer.sequence()?;
+            let modulus = fields.unsigned()?;
+            let exponent = fields.unsigned()?;
+            if !fields.is_empty() || !outer.is_empty() {
+                return None;
+            }
+            Some(match RsaPublicKey::new(modulus, exponent) {
+                Some(key) => PublicKey::Rsa(key),
+                None => PublicKey::Unsupported("RSA key below 2048 bits".to_string()),
+            })
+        },
+        EC_PUBLIC_KEY => {
+            let curve = Der::new(parameters?).read(der::OID)?;
+            let Some(curve) = named_curve(curve) else {
+                return Some(PublicKey::Unsupported(der::oid_to_string(curve)));
+            };
+            Some(PublicKey::Ec { curve, point: key.to_vec() })
+        },
+        _ => Some(PublicKey::Unsupported(der::oid_to_string(oid))),
+    }
+}
+
+/// `Err` names an algorithm that parses but is not supported, such as
+/// SHA-1 signatures on old roots.
+fn parse_signature_algorithm(data: &[u8]) -> Option<Result<SignatureAlgorithm, String>> {
+    let (oid, parameters) = parse_algorithm(data)?;
+    Some(Ok(match oid {
+        SHA256_WITH_RSA => SignatureAlgorithm::RsaPkcs1(HashAlgorithm::Sha256),
+        SHA384_WITH_RSA => SignatureAlgorithm::RsaPkcs1(HashAlgorithm::Sha384),
+        SHA512_WITH_RSA => SignatureAlgorithm::RsaPkcs1(HashAlgorithm::Sha512),
+        ECDSA_WITH_SHA256 => SignatureAlgorithm::Ecdsa(HashAlgorithm::Sha256),
+        ECDSA_WITH_SHA384 => SignatureAlgorithm::Ecdsa(HashAlgorithm::Sha384),
+        ECDSA_WITH_SHA512 => SignatureAlgorithm::Ecdsa(HashAlgorithm::Sha512),
+        RSASSA_PSS => match parameters.and_then(parse_pss_parameters) {
+            Some(algorithm) => algorithm,
+            None => return Some(Err("RSASSA-PSS with unsupported parameters".to_string())),
+        },
+        _ => return Some(Err(der::oid_to_string(oid))),
+    }))
+}
+
+/// RSASSA-PSS-params, supported with SHA-2, matching MGF1 and trailer 1.
+fn parse_pss_parameters(data: &[u8]) -> Option<SignatureAlgorithm> {
+    let hash_of = |oid: &[u8]| match oid {
+        SHA256 => Some(HashAlgorithm::Sha256),
+        SHA384 => Some(HashAlgorithm::Sha384),
+        SHA512 => Some(HashAlgorithm::Sha512),
+        _ => None,
+    };
+    let mut fields = Der::new(data).sequence()?;
+    // The SHA-1 defaults are not supported, so both must be present
+    let hash = hash_of(parse_algorithm(fields.read(der::explicit(0))?)?.0)?;
+    let (mgf, mgf_hash) = parse_algorithm(fields.read(der::explicit(1))?)?;
+    if mgf != MGF1 || hash_of(parse_algorithm(mgf_hash?)?.0)? != hash {
+        return None;
+    }
+    let salt_len = match fields.optional(der::explicit(2))? {
+        Some(salt) => Der::new(salt).small_unsigned()? as usize,
+        None => 20,
+    };
+    if let Some(trailer) = fields.optional(der::explicit(3))?
+        && Der::new(trailer).small_unsigned()? != 1
+    {
+        return None;
+    }
+    fields.is_empty().then_some(SignatureAlgorithm::RsaPss { hash, salt_len })
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::essentia::tls::trust::pem_blocks;
+
+    fn load(pem: &str) -> Certificate {
+        let der = pem_blocks(pem, "CERTIFICATE").remove(0);
+        Certificate::from_der(&der).expect("certificate")
+    }
+
+    fn fixtures() -> (Certificate, Certificate, Certificate) {
+        (
+            load(include_str!("testdata/root.pem")),
+            load(include_str!("testdata/intermediate.pem")),
+            load(include_str!("testdata/leaf.pem")),
+        )
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let (root, intermediate, leaf) = fixtures();
+        assert_eq!(root.subject, "O=Essentia Test, CN=Essentia Test Root");
+        assert_eq!(intermediate.issuer, root.subject);
+        assert_eq!(leaf.subject, "CN=localhost");
+        assert_eq!(leaf.common_name(), Some("localhost"));
+        assert!(matches!(root.public_key, PublicKey::Ec {
+            curve: Curve::P256,
+            ..
+        }));
+        assert!(matches!(&intermediate.public_key, PublicKey::Rsa(key) if key.bits() == 2048));
+
+        assert!(root.is_ca && intermediate.is_ca && !leaf.is_ca);
+        assert_eq!(intermediate.path_len, Some(0));
+        let usage = intermediate.key_usage.expect("key usage");
+        assert!(usage.contains(KeyUsage::KEY_CERT_SIGN));
+        assert!(!usage.contains(KeyUsage::DIGITAL_SIGNATURE));
+        assert_eq!(leaf.subject_alt_names, [
+            SubjectAltName::Dns("localhost".to_string()),
+            SubjectAltName::Dns("*.test.example".to_string()),
+            SubjectAltName::Ip(IpAddr::from([127, 0, 0, 1])),
+        ]);
+        assert_eq!(
+            leaf.extended_key_usage.as_deref(),
+            Some(["1.3.6.1.5.5.7.3.1".to_string()].as_slice())
+        );
+
+        // 2026-10-17T07:07:16Z, and 36500 days later as GeneralizedTime
+        let since_epoch = |t: SystemTime| t.duration_since(UNIX_EPOCH).expect("epoch").as_secs();
+        assert_eq!(since_epoch(leaf.not_before), 1_792_220_836);
+        assert_eq!(since_epoch(leaf.not_after), 1_792_220_836 + 36_500 * 86_400);
+
+        assert!(Certificate::from_der(&leaf.der()[..100]).is_err());
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let (root, intermediate, leaf) = fixtures();
+        let pss = load(include_str!("testdata/leaf-pss.pem"));
+        let now = leaf.not_before + Duration::from_secs(86_400);
+        let roots = [root.clone()];
+        let chain = [leaf.clone(), intermediate.clone()];
+        let path = verify_chain(&chain, &roots, "localhost", now).expect("PKCS#1 chain");
+        let subjects: Vec<&str> = path.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, [
+            &leaf.subject,
+            &intermediate.subject,
+            &root.subject
+        ]);
+        verify_chain(&[pss, intermediate.clone()], &roots, "localhost", now).expect("PSS chain");
+        // Chain order beyond the leaf does not matter, and a sent root is fine
+        verify_chain(
+            &[leaf.clone(), root.clone(), intermediate.clone()],
+            &roots,
+            "localhost",
+            now,
+        )
+        .expect("unordered chain");
+
+        let err = verify_chain(std::slice::from_ref(&leaf), &roots, "localhost", now)
+            .expect_err("no path");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Certificate 'CN=localhost' is not issued by a trusted root"
+        );
+        let other = [load(include_str!("testdata/root-p384.pem"))];
+        assert!(verify_chain(&chain, &other, "localhost", now).is_err());
+
+        let err = verify_chain(
+            &chain,
+            &roots,
+            "localhost",
+            leaf.not_after + Duration::from_secs(1),
+        )
+        .expect_err("expired");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Certificate 'CN=localhost' has expired"
+        );
+        let err = verify_chain(
+            &chain,
+            &roots,
+            "localhost",
+            leaf.not_before - Duration::from_secs(1),
+        )
+        .expect_err("not yet valid");
+        assert_eq!(
+            err.to_string(),
+            "TLS error: Certificate 'CN=localhost' is not valid yet"
+        );
+
+        // The leaf may not sign further certificates
+        assert!(!leaf.is_signed_by(&root));
+        assert!(intermediate.is_signed_by(&root));
+    }
+
+    #[test]
+    fn test_host_matching() {
+        let (_, _, leaf) = fixtures();
+        for host in ["localhost", "LOCALHOST", "a.test.example", "127.0.0.1"] {
+            assert!(leaf.check_host(host).is_ok(), "{}", host);
+        }
+        for host in ["a.b.test.example", "test.example", "127.0.0.2", "::1"] {
+            assert!(leaf.check_host(host).is_err(), "{}", host);
+        }
+
+        assert!(dns_name_matches("*.example.com", "api.example.com"));
+        assert!(!dns_name_matches("*.com", "example.com"));
+        assert!(!dns_name_matches("api*.example.com", "api1.example.com"));
+        assert!(!dns_name_matches("*.example.com", ".example.com"));
+    }
+
+    #[test]
+    fn test_p384_self_signature() {
+        let root = load(include_str!("testdata/root-p384.pem"));
+        assert!(matches!(root.public_key, PublicKey::Ec {
+            curve: Curve::P384,
+            ..
+        }));
+        assert!(root.is_self_issued());
+        assert!(root.is_signed_by(&root));
+    }
+}
@@ -0,0 +1,328 @@
+//! Opt-in wire trace of HTTP exchanges, written to a file with credentials
+//! redacted.
+//!
+//! Each request and response is appended as its head and body, tagged with
+//! an exchange number so concurrent requests can be told apart:
+//!
+//! ```text
+//! --- #1 request @1760000000.123
+//! POST /v1/chat/completions HTTP/1.1
+//! Host: api.x.ai
+//! Authorization: [redacted]
+//!
+//! {"model":"grok-3","messages":[...]}
+//!
+//! --- #1 response @1760000000.456
+//! HTTP/1.1 200
+//! Content-Type: application/json
+//!
+//! {"choices":[...]}
+//! ```
+//!
+//! Credential headers, query parameters and JSON fields whose names look like
+//! keys, tokens or passwords have their values replaced with `[redacted]`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    core::logger::Log,
+    errors::LlmError,
+    essentia::http::{Headers, is_credential_header},
+};
+
+/// Environment variable naming a file to trace every request to.
+pub const TRACE_ENV: &str = "ESSENTIA_HTTP_TRACE";
+
+/// Body bytes written per message; the rest is summarized.
+const MAX_BODY: usize = 64 * 1024;
+
+const REDACTED: &str = "[redacted]";
+
+/// A trace file shared by every request that uses it.
+pub struct HttpTrace {
+    path:          PathBuf,
+    file:          Mutex<File>,
+    next_exchange: AtomicU64,
+}
+
+impl fmt::Debug for HttpTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpTrace").field("path", &self.path).finish()
+    }
+}
+
+impl HttpTrace {
+    /// Opens `path` for appending, once per path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Arc<HttpTrace>, LlmError> {
+        static OPEN: OnceLock<Mutex<HashMap<PathBuf, Arc<HttpTrace>>>> = OnceLock::new();
+        let path = path.as_ref();
+        let mut open = OPEN
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(trace) = open.get(path) {
+            return Ok(Arc::clone(trace));
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+            LlmError::Config(format!("Cannot open HTTP trace {}: {}", path.display(), e))
+        })?;
+        let trace = Arc::new(HttpTrace {
+            path:          path.to_path_buf(),
+            file:          Mutex::new(file),
+            next_exchange: AtomicU64::new(1),
+        });
+        open.insert(path.to_path_buf(), Arc::clone(&trace));
+        Ok(trace)
+    }
+
+    /// The trace named by [`TRACE_ENV`], if set. A file that cannot be
+    /// opened is reported once and then ignored.
+    pub fn from_env() -> Option<Arc<HttpTrace>> {
+        static ENV: OnceLock<Option<Arc<HttpTrace>>> = OnceLock::new();
+        ENV.get_or_init(|| {
+            let path = std::env::var_os(TRACE_ENV).filter(|path| !path.is_empty())?;
+            HttpTrace::open(path).map_err(|e| Log::error(&e.to_string())).ok()
+        })
+        .clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records a serialized request head with its unencoded `body`, and
+    /// returns the exchange number for the matching response.
+    pub(crate) fn request(&self, message: &[u8], body: &[u8]) -> u64 {
+        let exchange = self.next_exchange.fetch_add(1, Ordering::Relaxed);
+        let head = message
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map_or(message, |end| &message[..end]);
+        let head = String::from_utf8_lossy(head);
+        let mut lines = head.split("\r\n");
+        let mut entry = entry_header(exchange, "request");
+        if let Some(request_line) = lines.next() {
+            entry.push_str(&redact_request_line(request_line));
+            entry.push('\n');
+        }
+        for line in lines {
+            match line.split_once(':') {
+                Some((name, value)) => push_header(&mut entry, name, value.trim()),
+                None => entry.push_str(&format!("{}\n", line)),
+            }
+        }
+        push_body(&mut entry, body);
+        self.write(&entry);
+        exchange
+    }
+
+    /// Records a response head and, unless it is streamed, its decoded body.
+    pub(crate) fn response(
+        &self, exchange: u64, status: u16, headers: &Headers, body: Option<&[u8]>,
+    ) {
+        let mut entry = entry_header(exchange, "response");
+        entry.push_str(&format!("HTTP/1.1 {}\n", status));
+        for (name, value) in headers.iter() {
+            push_header(&mut entry, name, value);
+        }
+        match body {
+            Some(body) => push_body(&mut entry, body),
+            None => entry.push_str("\n[body streamed]\n\n"),
+        }
+        self.write(&entry);
+    }
+
+    /// Records part of a streamed response body.
+    pub(crate) fn body_chunk(&self, exchange: u64, data: &[u8]) {
+        let mut entry = entry_header(exchange, "body");
+        push_body(&mut entry, data);
+        self.write(&entry);
+    }
+
+    pub(crate) fn error(&self, exchange: u64, error: &LlmError) {
+        let mut entry = entry_header(exchange, "failed");
+        entry.push_str(&format!("{}\n\n", error));
+        self.write(&entry);
+    }
+
+    /// Appends one entry; write errors are ignored, as the request does not
+    /// depend on them.
+    fn write(&self, entry: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = file.write_all(entry.as_bytes());
+    }
+}
+
+fn entry_header(exchange: u64, kind: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "--- #{} {} @{}.{:03}\n",
+        exchange,
+        kind,
+        now.as_secs(),
+        now.subsec_millis()
+    )
+}
+
+/// Whether a header, query parameter or JSON field named `name` may carry a
+/// credential.
+fn is_sensitive(name: &str) -> bool {
+    if is_credential_header(name) {
+        return true;
+    }
+    let name = name.trim().to_ascii_lowercase();
+    matches!(name.as_str(), "set-cookie" | "key")
+        || name.ends_with("token")
+        || ["api-key", "api_key", "apikey", "secret", "password"]
+            .iter()
+            .any(|part| name.contains(part))
+}
+
+fn push_header(entry: &mut String, name: &str, value: &str) {
+    let value = if is_sensitive(name) { REDACTED } else { value };
+    entry.push_str(&format!("{}: {}\n", name, value));
+}
+
+/// The request line with sensitive query parameter values redacted.
+fn redact_request_line(line: &str) -> String {
+    let mut parts = line.splitn(3, ' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return line.to_string();
+    };
+    let Some((path, query)) = target.split_once('?') else {
+        return line.to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name) => format!("{}={}", name, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{} {}?{} {}", method, path, query.join("&"), version)
+}
+
+fn push_body(entry: &mut String, body: &[u8]) {
+    entry.push('\n');
+    let shown = &body[..body.len().min(MAX_BODY)];
+    match std::str::from_utf8(shown) {
+        Ok(text) => entry.push_str(&redact_json(text)),
+        // Cut inside a character: show what decodes
+        Err(e) if e.error_len().is_none() && body.len() > MAX_BODY => entry.push_str(&redact_json(
+            &String::from_utf8_lossy(&shown[..e.valid_up_to()]),
+        )),
+        Err(_) => {
+            entry.push_str(&format!("[{} bytes of binary data]\n\n", body.len()));
+            return;
+        },
+    }
+    if body.len() > MAX_BODY {
+        entry.push_str(&format!("\n[{} more bytes]", body.len() - MAX_BODY));
+    }
+    entry.push_str("\n\n");
+}
+
+/// Replaces the string values of sensitive fields in JSON text. Anything
+/// else, including text that is not JSON, passes through unchanged.
+fn redact_json(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut redact_next = false;
+    let mut search = 0;
+    while let Some(offset) = text[search..].find('"') {
+        let start = search + offset;
+        let Some(end) = closing_quote(text, start + 1) else {
+            break;
+        };
+        search = end + 1;
+        if redact_next {
+            out.push_str(&text[copied..start]);
+            out.push_str(&format!("\"{}\"", REDACTED));
+            copied = end + 1;
+            redact_next = false;
+            continue;
+        }
+        let after = text[end + 1..].trim_start();
+        redact_next = is_sensitive(&text[start + 1..end])
+            && after.strip_prefix(':').is_some_and(|value| value.trim_start().starts_with('"'));
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Index of the quote ending a string literal whose contents start at `from`.
+fn closing_quote(text: &str, from: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text[from..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(from + i),
+            _ => {},
+        }
+    }
+    None
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redaction() {
+        assert_eq!(
+            redact_json(r#"{"api_key": "sk-1", "model": "grok", "Token":"a\"b", "max_tokens": 5}"#),
+            r#"{"api_key": "[redacted]", "model": "grok", "Token":"[redacted]", "max_tokens": 5}"#
+        );
+        assert_eq!(redact_json("not \"json"), "not \"json");
+        assert_eq!(
+            redact_request_line("GET /v1/models?key=abc&limit=5 HTTP/1.1"),
+            "GET /v1/models?key=[redacted]&limit=5 HTTP/1.1"
+        );
+        assert!(is_sensitive("X-Api-Key") && is_sensitive("Set-Cookie"));
+        assert!(is_credential_header("api-key") && is_credential_header("X-Goog-Api-Key"));
+        assert!(!is_credential_header("Set-Cookie") && !is_credential_header("x-api-key-id"));
+        assert!(is_sensitive("access_token") && is_sensitive("X-Auth-Token"));
+        assert!(!is_sensitive("Content-Type") && !is_sensitive("max_tokens"));
+    }
+
+    #[test]
+    fn test_trace_file() {
+        let path = std::env::temp_dir().join(format!("essentia-trace-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let trace = HttpTrace::open(&path).expect("open");
+        assert!(Arc::ptr_eq(
+            &trace,
+            &HttpTrace::open(&path).expect("cached")
+        ));
+
+        let message = b"POST /v1/chat HTTP/1.1\r\nAuthorization: Bearer sk-1\r\n\r\n\x1f\x8b";
+        let exchange = trace.request(message, br#"{"password":"hunter2"}"#);
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "session=1");
+        trace.response(exchange, 200, &headers, Some(&[0xff, 0xfe]));
+        trace.error(exchange, &LlmError::Timeout { operation: "HTTP read" });
+
+        let written = std::fs::read_to_string(&path).expect("read");
+        let _ = std::fs::remove_file(&path);
+        assert!(written.contains(&format!("--- #{} request @", exchange)));
+        assert!(written.contains("Authorization: [redacted]\n"));
+        assert!(written.contains(r#"{"password":"[redacted]"}"#));
+        assert!(written.contains("HTTP/1.1 200\nSet-Cookie: [redacted]\n"));
+        assert!(written.contains("[2 bytes of binary data]"));
+        assert!(!written.contains("sk-1") && !written.contains("hunter2"));
+    }
+}
@@ -51,3 +51,175 @@ impl Url {
         })
     }
 }
+
+/// Percent-encodes everything except RFC 3986 unreserved characters, for use
+/// in a query name or value.
+pub fn encode_component(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char);
+            },
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Decodes `%XX` escapes, e.g. in URL userinfo. Malformed escapes are kept
+/// as-is.
+pub fn decode_component(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Resolves `reference` (e.g. a `Location` header) against the absolute URL
+/// `base`, per RFC 3986 section 5. Fragments are dropped.
+pub fn resolve(base: &str, reference: &str) -> String {
+    let reference = reference.trim();
+    let reference = reference.split_once('#').map_or(reference, |(r, _)| r);
+    if let Some((scheme, _)) = reference.split_once("://")
+        && !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return reference.to_string();
+    }
+
+    let base = base.split_once('#').map_or(base, |(b, _)| b);
+    let (scheme, rest) = base.split_once("://").unwrap_or(("http", base));
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path_and_query) = rest.split_at(authority_end);
+    let base_path = path_and_query.split_once('?').map_or(path_and_query, |(p, _)| p);
+
+    if let Some(network_path) = reference.strip_prefix("//") {
+        return format!("{}://{}", scheme, network_path);
+    }
+    let origin = format!("{}://{}", scheme, authority);
+    if reference.is_empty() {
+        return format!("{}{}", origin, path_and_query);
+    }
+    if reference.starts_with('?') {
+        return format!("{}{}{}", origin, base_path, reference);
+    }
+
+    let (path, query) = match reference.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (reference, None),
+    };
+    let merged = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        // Relative to the base's directory
+        let directory = base_path.rfind('/').map_or("/", |i| &base_path[..=i]);
+        format!("{}{}", directory, path)
+    };
+    let mut resolved = format!("{}{}", origin, remove_dot_segments(&merged));
+    if let Some(query) = query {
+        resolved.push('?');
+        resolved.push_str(query);
+    }
+    resolved
+}
+
+/// Collapses `.` and `..` segments of an absolute path.
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = path.split('/').skip(1).peekable();
+    while let Some(segment) = parts.next() {
+        let last = parts.peek().is_none();
+        match segment {
+            "." => {
+                if last {
+                    segments.push("");
+                }
+            },
+            ".." => {
+                segments.pop();
+                if last {
+                    segments.push("");
+                }
+            },
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Whether two URLs share scheme, host and port, treating a missing port as
+/// the scheme's default.
+pub fn same_origin(a: &Url, b: &Url) -> bool {
+    let port = |url: &Url| url.port.unwrap_or(if url.scheme == "https" { 443 } else { 80 });
+    let host = |url: &Url| url.hostname.as_deref().map(str::to_ascii_lowercase);
+    a.scheme.eq_ignore_ascii_case(&b.scheme) && host(a) == host(b) && port(a) == port(b)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let base = "https://api.example.com/v1/chat/completions?stream=true";
+        for (reference, expected) in [
+            ("/v2/chat", "https://api.example.com/v2/chat"),
+            ("models", "https://api.example.com/v1/chat/models"),
+            (
+                "../embeddings?x=1",
+                "https://api.example.com/v1/embeddings?x=1",
+            ),
+            ("../../../a", "https://api.example.com/a"),
+            ("./", "https://api.example.com/v1/chat/"),
+            (
+                "?page=2",
+                "https://api.example.com/v1/chat/completions?page=2",
+            ),
+            ("//cdn.example.com/x", "https://cdn.example.com/x"),
+            ("http://other:8080/y#frag", "http://other:8080/y"),
+        ] {
+            assert_eq!(resolve(base, reference), expected, "{}", reference);
+        }
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let url = |s: &str| Url::parse(s).expect("url");
+        assert!(same_origin(
+            &url("https://a.example/x"),
+            &url("https://A.example:443/y")
+        ));
+        assert!(!same_origin(
+            &url("https://a.example/"),
+            &url("http://a.example/")
+        ));
+        assert!(!same_origin(
+            &url("http://a.example/"),
+            &url("http://a.example:8080/")
+        ));
+        assert!(!same_origin(
+            &url("https://a.example/"),
+            &url("https://b.example/")
+        ));
+    }
+}
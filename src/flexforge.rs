@@ -6,44 +6,105 @@
 //!
 //! - Provider selection (External AI, Code Assist, Local)
 //! - API key configuration (secure)
-//! - Streaming token output via ERSP
+//! - Streaming token output via ERSP: a worker thread fills a bounded
+//!   [`TokenFrame`] queue that `render_frame` drains at `target_fps`
 //! - Model parameter tuning
 
+use std::{collections::HashMap, fmt, sync::Arc};
+
 use essentia_traits::plugin_contracts::{
     ConfigField, ConfigSchema, FlexForgeCapability, FlexForgeIntegration, FlexForgePanelCategory,
     FlexForgePanelInfo, StreamingCapable, UiConfigurable,
 };
 
+use crate::{
+    core::{
+        copilot::ExternalCodeAssist,
+        external_llm::ExternalLlm,
+        stream::{DEFAULT_QUEUE_CAPACITY, StreamedToolCall, TokenFrame, TokenStream},
+    },
+    errors::LlmError,
+    essentia::{
+        http::Timeouts,
+        json::{self, Object, Value},
+        proxy::ProxySetting,
+        tls::{ClientIdentity, PrivateKey, TlsConfig, TrustStore},
+        trace::HttpTrace,
+    },
+    traits::ChatProvider,
+    types::{ChatDelta, ChatRequest, ChatResponse},
+};
+
+/// Frames forwarded per `render_frame` call, so a burst of tokens is spread
+/// over several frames instead of stalling one.
+const MAX_FRAMES_PER_RENDER: usize = 64;
+
 /// LLM Plugin `FlexForge` integration.
-#[derive(Debug)]
 pub struct LlmPluginFlexForge {
     config:        LlmPluginConfig,
+    api_key:       String,
+    providers:     HashMap<LlmProvider, Arc<dyn ChatProvider>>,
     stream_active: bool,
     stream_id:     Option<u64>,
     next_id:       u64,
+    /// Request started by the next `start_stream` call.
+    pending:       Option<ChatRequest>,
+    /// Worker for the active stream, if it carries a request.
+    token_stream:  Option<TokenStream>,
+    /// Frames drained by `render_frame`, waiting to be sent over ERSP.
+    outbox:        Vec<TokenFrame>,
+    /// Text streamed so far for the current request.
+    stream_output: String,
+    /// Tool calls streamed so far for the current request, by index.
+    stream_tools:  Vec<StreamedToolCall>,
+    /// Built from `ca_bundle` and `certificate_pins` when they change, so
+    /// requests share pooled connections and session tickets.
+    tls:           TlsConfig,
+    /// The custom provider's client certificate, loaded when
+    /// `client_certificate` or `client_key` changes; `Err` says why the
+    /// pair cannot be used.
+    identity:      Result<Option<Arc<ClientIdentity>>, String>,
 }
 
 /// Configuration for the LLM plugin.
 #[derive(Debug, Clone)]
 pub struct LlmPluginConfig {
     /// Selected LLM provider
-    pub provider:          LlmProvider,
+    pub provider:           LlmProvider,
     /// Model name/identifier
-    pub model:             String,
+    pub model:              String,
     /// Maximum tokens for response
-    pub max_tokens:        u32,
+    pub max_tokens:         u32,
     /// Sampling temperature
-    pub temperature:       f32,
+    pub temperature:        f32,
     /// Enable streaming responses
-    pub streaming_enabled: bool,
+    pub streaming_enabled:  bool,
     /// Request timeout in seconds
-    pub timeout_secs:      u32,
+    pub timeout_secs:       u32,
     /// API endpoint (for custom providers)
-    pub custom_endpoint:   Option<String>,
+    pub custom_endpoint:    Option<String>,
+    /// Proxy URL (`http://`, `socks5://`) or `direct`; `None` follows
+    /// `HTTPS_PROXY` / `HTTP_PROXY` / `NO_PROXY`
+    pub proxy:              Option<String>,
+    /// PEM bundle file or directory of trusted roots; `None` follows
+    /// `ESSENTIA_CA_BUNDLE`, then the system bundle
+    pub ca_bundle:          Option<String>,
+    /// SPKI pins as `host=sha256/<base64>[,...]` entries separated by `;`
+    pub certificate_pins:   Option<String>,
+    /// PEM client certificate chain presented to the custom provider
+    pub client_certificate: Option<String>,
+    /// PKCS#8 PEM private key for `client_certificate`
+    pub client_key:         Option<String>,
+    /// File HTTP exchanges are traced to, credentials redacted; `None`
+    /// follows `ESSENTIA_HTTP_TRACE`
+    pub http_trace:         Option<String>,
+    /// JSON object of merge patches for request bodies, keyed by provider
+    /// name, e.g. `{"external_ai": {"top_k": 40}}`
+    pub extra_body:         Option<String>,
 }
 
 /// Supported LLM providers.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LlmProvider {
     /// External AI API
     ExternalAI,
@@ -79,13 +140,20 @@ impl LlmProvider {
 impl Default for LlmPluginConfig {
     fn default() -> Self {
         Self {
-            provider:          LlmProvider::LocalSlm,
-            model:             String::from("essentia-slm-100m"),
-            max_tokens:        2048,
-            temperature:       0.7,
-            streaming_enabled: true,
-            timeout_secs:      30,
-            custom_endpoint:   None,
+            provider:           LlmProvider::LocalSlm,
+            model:              String::from("essentia-slm-100m"),
+            max_tokens:         2048,
+            temperature:        0.7,
+            streaming_enabled:  true,
+            timeout_secs:       30,
+            custom_endpoint:    None,
+            proxy:              None,
+            ca_bundle:          None,
+            certificate_pins:   None,
+            client_certificate: None,
+            client_key:         None,
+            http_trace:         None,
+            extra_body:         None,
         }
     }
 }
@@ -96,9 +164,18 @@ impl LlmPluginFlexForge {
     pub fn new() -> Self {
         Self {
             config:        LlmPluginConfig::default(),
+            api_key:       String::new(),
+            providers:     HashMap::new(),
             stream_active: false,
             stream_id:     None,
             next_id:       1,
+            pending:       None,
+            token_stream:  None,
+            outbox:        Vec::new(),
+            stream_output: String::new(),
+            stream_tools:  Vec::new(),
+            tls:           TlsConfig::default(),
+            identity:      Ok(None),
         }
     }
 
@@ -118,6 +195,155 @@ impl LlmPluginFlexForge {
         }
     }
 
+    /// Sets the API key handed to the built-in external providers.
+    pub fn set_api_key(&mut self, api_key: &str) {
+        self.api_key = api_key.to_string();
+    }
+
+    /// Registers a provider implementation for `kind`.
+    ///
+    /// Registered providers take precedence over the built-in clients, and
+    /// are the only way to serve [`LlmProvider::LocalSlm`].
+    pub fn register_provider(&mut self, kind: LlmProvider, provider: Arc<dyn ChatProvider>) {
+        self.providers.insert(kind, provider);
+    }
+
+    /// Resolves the provider selected by `LlmPluginConfig.provider`.
+    ///
+    /// Built-in clients use `timeout_secs` for their connect, read and total
+    /// deadlines, `proxy` for their connections, and `ca_bundle` and
+    /// `certificate_pins` to authenticate servers, and trace to `http_trace`
+    /// when set. Request bodies get the provider's `extra_body` patch merged
+    /// in. The custom provider also presents `client_certificate` and
+    /// `client_key` when both are set.
+    pub fn active_provider(&self) -> Result<Arc<dyn ChatProvider>, LlmError> {
+        let kind = self.config.provider;
+        if let Some(provider) = self.providers.get(&kind) {
+            return Ok(Arc::clone(provider));
+        }
+
+        let timeouts = Timeouts::from_secs(self.config.timeout_secs);
+        let proxy = self.config.proxy.as_deref().unwrap_or("");
+        let tls = &self.tls;
+        let trace = self.config.http_trace.as_deref().map(HttpTrace::open).transpose()?;
+        let extra_body = match &self.config.extra_body {
+            Some(text) => parse_extra_body(text).map_err(LlmError::Config)?.remove(kind.as_str()),
+            None => None,
+        };
+        let external = || {
+            let external = ExternalLlm::new(&self.config.model, proxy)
+                .with_api_key(&self.api_key)
+                .with_temperature(self.config.temperature)
+                .with_max_tokens(self.config.max_tokens)
+                .with_timeouts(timeouts)
+                .with_tls(tls.clone());
+            let external = match &trace {
+                Some(trace) => external.with_trace(Arc::clone(trace)),
+                None => external,
+            };
+            match &extra_body {
+                Some(patch) => external.with_extra_body(patch.clone()),
+                None => external,
+            }
+        };
+        match kind {
+            LlmProvider::ExternalAI => Ok(Arc::new(external())),
+            LlmProvider::ExternalCodeAssist => {
+                let assist = ExternalCodeAssist::new(&self.config.model)
+                    .with_api_token(&self.api_key)
+                    .with_proxy(proxy)
+                    .with_timeouts(timeouts)
+                    .with_tls(tls.clone());
+                let assist = match &trace {
+                    Some(trace) => assist.with_trace(Arc::clone(trace)),
+                    None => assist,
+                };
+                Ok(Arc::new(match extra_body {
+                    Some(patch) => assist.with_extra_body(patch),
+                    None => assist,
+                }))
+            },
+            LlmProvider::Custom => {
+                let endpoint = self.config.custom_endpoint.as_deref().ok_or_else(|| {
+                    LlmError::Config("Custom provider requires custom_endpoint".to_string())
+                })?;
+                let external = external().with_endpoint(endpoint);
+                match self.identity.clone().map_err(LlmError::Config)? {
+                    None => Ok(Arc::new(external)),
+                    Some(identity) => Ok(Arc::new(
+                        external.with_tls(tls.clone().with_client_identity(identity)),
+                    )),
+                }
+            },
+            LlmProvider::LocalSlm => Err(LlmError::Config(format!(
+                "No provider registered for {}",
+                kind.as_str()
+            ))),
+        }
+    }
+
+    /// Sends a chat request through the active provider.
+    pub fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        self.active_provider()?.chat(request)
+    }
+
+    /// Streams a chat request through the active provider, calling `on_delta`
+    /// for each chunk.
+    pub fn chat_stream(
+        &self, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        self.active_provider()?.chat_stream(request, on_delta)
+    }
+
+    /// Sets the request that the next [`start_stream`] call will send.
+    ///
+    /// [`start_stream`]: StreamingCapable::start_stream
+    pub fn set_stream_request(&mut self, request: ChatRequest) {
+        self.pending = Some(request);
+    }
+
+    /// Starts streaming `request` through the active provider.
+    pub fn start_chat_stream(&mut self, request: ChatRequest) -> Result<u64, String> {
+        self.set_stream_request(request);
+        self.start_stream()
+    }
+
+    /// Text received so far on the current (or last) stream.
+    pub fn stream_output(&self) -> &str {
+        &self.stream_output
+    }
+
+    /// Tool calls on the current (or last) stream, with their arguments
+    /// parsed as far as they have arrived.
+    pub fn stream_tool_calls(&self) -> &[StreamedToolCall] {
+        &self.stream_tools
+    }
+
+    /// Takes the frames drained by `render_frame` since the last call, for
+    /// emission over ERSP.
+    pub fn take_frames(&mut self) -> Vec<TokenFrame> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Loads the client certificate pair once both halves are set.
+    fn load_identity(&mut self) {
+        self.identity = match (&self.config.client_certificate, &self.config.client_key) {
+            (None, None) => Ok(None),
+            (Some(certificate), Some(key)) => ClientIdentity::from_files(certificate, key)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            _ => Err("client_certificate and client_key must be set together".to_string()),
+        };
+    }
+
+    fn end_stream(&mut self) {
+        if let Some(stream) = self.token_stream.take() {
+            stream.cancel();
+        }
+        self.stream_active = false;
+        self.stream_id = None;
+    }
+
     fn next_stream_id(&mut self) -> u64 {
         let id = self.next_id;
         self.next_id = self.next_id.wrapping_add(1);
@@ -131,6 +357,19 @@ impl Default for LlmPluginFlexForge {
     }
 }
 
+impl fmt::Debug for LlmPluginFlexForge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the API key
+        f.debug_struct("LlmPluginFlexForge")
+            .field("config", &self.config)
+            .field("providers", &self.providers.keys().collect::<Vec<_>>())
+            .field("stream_active", &self.stream_active)
+            .field("stream_id", &self.stream_id)
+            .field("outbox", &self.outbox.len())
+            .finish()
+    }
+}
+
 // ============================================================================
 // FlexForge Integration
 // ============================================================================
@@ -184,7 +423,7 @@ impl UiConfigurable for LlmPluginFlexForge {
                 ConfigField::select("provider", "LLM Provider", vec![
                     String::from("local_slm"),
                     String::from("external_ai"),
-                    String::from("code_assist"),
+                    String::from("external_code_assist"),
                     String::from("custom"),
                 ])
                 .with_description("Select the LLM provider to use")
@@ -212,6 +451,14 @@ impl UiConfigurable for LlmPluginFlexForge {
                     .with_description("Stream tokens as they are generated")
                     .with_group("Inference"),
             )
+            .with_field(
+                ConfigField::text("extra_body", "Extra Body")
+                    .with_description(
+                        "JSON merge patches for request bodies keyed by provider, e.g. \
+                         {\"external_ai\": {\"top_k\": 40}}",
+                    )
+                    .with_group("Inference"),
+            )
             .with_field(
                 ConfigField::number("timeout_secs", "Timeout (seconds)", 30.0, 5.0, 300.0)
                     .with_description("Request timeout in seconds")
@@ -222,6 +469,44 @@ impl UiConfigurable for LlmPluginFlexForge {
                     .with_description("Essentia-compatible API endpoint (for custom provider)")
                     .with_group("Network"),
             )
+            .with_field(
+                ConfigField::text("proxy", "Proxy")
+                    .with_description(
+                        "http:// or socks5:// proxy URL, or \"direct\"; empty uses HTTPS_PROXY",
+                    )
+                    .with_group("Network"),
+            )
+            .with_field(
+                ConfigField::text("ca_bundle", "CA Bundle")
+                    .with_description(
+                        "PEM file or directory of trusted roots; empty uses ESSENTIA_CA_BUNDLE or \
+                         the system bundle",
+                    )
+                    .with_group("Network"),
+            )
+            .with_field(
+                ConfigField::text("certificate_pins", "Certificate Pins")
+                    .with_description("host=sha256/<base64>[,...] entries separated by ;")
+                    .with_group("Network"),
+            )
+            .with_field(
+                ConfigField::text("client_certificate", "Client Certificate")
+                    .with_description("PEM client certificate chain for the custom provider")
+                    .with_group("Network"),
+            )
+            .with_field(
+                ConfigField::text("client_key", "Client Key")
+                    .with_description("PKCS#8 PEM private key for the client certificate")
+                    .with_group("Network"),
+            )
+            .with_field(
+                ConfigField::text("http_trace", "HTTP Trace File")
+                    .with_description(
+                        "File requests and responses are logged to, credentials redacted; empty \
+                         uses ESSENTIA_HTTP_TRACE",
+                    )
+                    .with_group("Network"),
+            )
     }
 
     fn on_config_changed(&mut self, key: &str, value: &str) -> Result<(), String> {
@@ -257,7 +542,13 @@ impl UiConfigurable for LlmPluginFlexForge {
             },
             "timeout_secs" => {
                 let v: f64 = value.parse().map_err(|_| "Invalid number")?;
-                self.config.timeout_secs = u32::try_from(v as i64).unwrap_or(u32::MAX);
+                // Zero would disable the network deadlines altogether
+                if !(5.0..=300.0).contains(&v) || v.fract() != 0.0 {
+                    return Err(
+                        "Timeout must be a whole number of seconds from 5 to 300".to_string()
+                    );
+                }
+                self.config.timeout_secs = v as u32;
                 Ok(())
             },
             "custom_endpoint" => {
@@ -268,6 +559,69 @@ impl UiConfigurable for LlmPluginFlexForge {
                 };
                 Ok(())
             },
+            "proxy" => {
+                ProxySetting::parse(value).map_err(|e| e.to_string())?;
+                self.config.proxy = if value.trim().is_empty() {
+                    None
+                } else {
+                    Some(value.trim().to_string())
+                };
+                Ok(())
+            },
+            "ca_bundle" => {
+                let value = value.trim();
+                let ca_bundle = (!value.is_empty()).then(|| value.to_string());
+                self.tls = TlsConfig::from_settings(
+                    ca_bundle.as_deref(),
+                    self.config.certificate_pins.as_deref(),
+                )
+                .map_err(|e| e.to_string())?;
+                self.config.ca_bundle = ca_bundle;
+                Ok(())
+            },
+            "certificate_pins" => {
+                let value = value.trim();
+                let pins = (!value.is_empty()).then(|| value.to_string());
+                self.tls =
+                    TlsConfig::from_settings(self.config.ca_bundle.as_deref(), pins.as_deref())
+                        .map_err(|e| e.to_string())?;
+                self.config.certificate_pins = pins;
+                Ok(())
+            },
+            "client_certificate" => {
+                let value = value.trim();
+                if !value.is_empty() && TrustStore::new().add_pem(&read_pem(value)?) == 0 {
+                    return Err(format!("No certificates found in {value}"));
+                }
+                self.config.client_certificate = (!value.is_empty()).then(|| value.to_string());
+                self.load_identity();
+                Ok(())
+            },
+            "client_key" => {
+                let value = value.trim();
+                if !value.is_empty() {
+                    PrivateKey::from_pem(&read_pem(value)?).map_err(|e| e.to_string())?;
+                }
+                self.config.client_key = (!value.is_empty()).then(|| value.to_string());
+                self.load_identity();
+                Ok(())
+            },
+            "http_trace" => {
+                let value = value.trim();
+                if !value.is_empty() {
+                    HttpTrace::open(value).map_err(|e| e.to_string())?;
+                }
+                self.config.http_trace = (!value.is_empty()).then(|| value.to_string());
+                Ok(())
+            },
+            "extra_body" => {
+                let value = value.trim();
+                if !value.is_empty() {
+                    parse_extra_body(value)?;
+                }
+                self.config.extra_body = (!value.is_empty()).then(|| value.to_string());
+                Ok(())
+            },
             _ => Err(format!("Unknown configuration key: {key}")),
         }
     }
@@ -306,11 +660,41 @@ impl UiConfigurable for LlmPluginFlexForge {
                 String::from("custom_endpoint"),
                 self.config.custom_endpoint.clone().unwrap_or_default(),
             ),
+            (
+                String::from("proxy"),
+                self.config.proxy.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("ca_bundle"),
+                self.config.ca_bundle.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("certificate_pins"),
+                self.config.certificate_pins.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("client_certificate"),
+                self.config.client_certificate.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("client_key"),
+                self.config.client_key.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("http_trace"),
+                self.config.http_trace.clone().unwrap_or_default(),
+            ),
+            (
+                String::from("extra_body"),
+                self.config.extra_body.clone().unwrap_or_default(),
+            ),
         ]
     }
 
     fn reset_to_defaults(&mut self) {
         self.config = LlmPluginConfig::default();
+        self.tls = TlsConfig::default();
+        self.identity = Ok(None);
     }
 }
 
@@ -328,6 +712,18 @@ impl StreamingCapable for LlmPluginFlexForge {
             return Err("Stream already active".to_string());
         }
 
+        if let Some(request) = self.pending.take() {
+            let provider = self.active_provider().map_err(|e| e.to_string())?;
+            self.token_stream = Some(TokenStream::spawn(
+                provider,
+                request,
+                self.config.streaming_enabled,
+                DEFAULT_QUEUE_CAPACITY,
+            ));
+            self.stream_output.clear();
+            self.stream_tools.clear();
+        }
+
         let stream_id = self.next_stream_id();
         self.stream_id = Some(stream_id);
         self.stream_active = true;
@@ -344,15 +740,21 @@ impl StreamingCapable for LlmPluginFlexForge {
             return Err("Invalid stream ID".to_string());
         }
 
-        self.stream_active = false;
-        self.stream_id = None;
+        // Cancels the in-flight request. With streaming disabled the reply is
+        // discarded when it arrives rather than interrupted.
+        self.end_stream();
 
         Ok(())
     }
 
     fn target_fps(&self) -> u32 {
-        // Event-driven streaming, use low base rate
-        10
+        if self.token_stream.is_some() {
+            // Fast enough for tokens to appear smoothly
+            30
+        } else {
+            // Event-driven streaming, use low base rate
+            10
+        }
     }
 
     fn render_frame(&mut self, stream_id: u64, _delta_ms: f64) -> bool {
@@ -360,12 +762,58 @@ impl StreamingCapable for LlmPluginFlexForge {
             return false;
         }
 
-        // In production, this would emit token frames via ERSP
-        // as they arrive from the LLM provider
+        let Some(stream) = self.token_stream.as_mut() else {
+            return true;
+        };
+        let frames = stream.drain(MAX_FRAMES_PER_RENDER);
+        let finished = stream.is_finished();
+        for frame in frames {
+            match &frame {
+                TokenFrame::Token { text, .. } => self.stream_output.push_str(text),
+                TokenFrame::ToolCall { index, name, arguments, .. } => {
+                    // Indices come from the server; a new call may only be the next one
+                    if *index == self.stream_tools.len() {
+                        self.stream_tools.push(StreamedToolCall::default());
+                    }
+                    if let Some(call) = self.stream_tools.get_mut(*index) {
+                        call.push(name.as_deref(), arguments);
+                    }
+                },
+                _ => {},
+            }
+            self.outbox.push(frame);
+        }
+
+        if finished {
+            self.end_stream();
+            return false;
+        }
         true
     }
 }
 
+/// The `extra_body` setting: an object mapping provider names to object
+/// merge patches.
+fn parse_extra_body(text: &str) -> Result<Object, String> {
+    let Value::Object(patches) = json::parse(text).map_err(|e| e.to_string())? else {
+        return Err("extra_body must be a JSON object keyed by provider".to_string());
+    };
+    for (provider, patch) in patches.iter() {
+        if LlmProvider::from_str(provider).is_none() {
+            return Err(format!("Unknown provider in extra_body: {provider}"));
+        }
+        if patch.as_object().is_none() {
+            return Err(format!("extra_body for {provider} must be a JSON object"));
+        }
+    }
+    Ok(patches)
+}
+
+/// Contents of a PEM file named in the configuration.
+fn read_pem(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Cannot read {path}: {e}"))
+}
+
 #[cfg(all(test, feature = "full-tests"))]
 mod tests {
     use super::*;
@@ -403,6 +851,271 @@ mod tests {
 
         // Empty model name
         assert!(plugin.on_config_changed("model", "").is_err());
+
+        // Timeouts stay within the schema's range
+        for timeout in ["0", "-1", "301", "4", "30.5", "NaN"] {
+            assert!(
+                plugin.on_config_changed("timeout_secs", timeout).is_err(),
+                "{}",
+                timeout
+            );
+        }
+        assert_eq!(plugin.config.timeout_secs, 30);
+        assert!(plugin.on_config_changed("timeout_secs", "300").is_ok());
+        assert_eq!(plugin.config.timeout_secs, 300);
+
+        // Proxy URLs are validated up front
+        assert!(plugin.on_config_changed("proxy", "socks5://proxy.corp:1080").is_ok());
+        assert!(plugin.on_config_changed("proxy", "ftp://proxy.corp").is_err());
+        assert_eq!(
+            plugin.config.proxy.as_deref(),
+            Some("socks5://proxy.corp:1080")
+        );
+
+        // CA bundles must load and pins must decode
+        let root = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/essentia/tls/testdata/root.pem"
+        );
+        assert!(plugin.on_config_changed("ca_bundle", "/nonexistent/ca.pem").is_err());
+        assert!(plugin.on_config_changed("ca_bundle", root).is_ok());
+        assert_eq!(plugin.config.ca_bundle.as_deref(), Some(root));
+        let pin = format!("sha256/{}", crate::essentia::base64::encode(&[7; 32]));
+        assert!(plugin.on_config_changed("certificate_pins", "api.x.ai=sha256/short").is_err());
+        assert!(
+            plugin
+                .on_config_changed("certificate_pins", &format!("api.x.ai={}", pin))
+                .is_ok()
+        );
+        assert!(plugin.on_config_changed("provider", "external_code_assist").is_ok());
+        assert!(plugin.active_provider().is_ok());
+
+        // TLS settings are built when they change, not on every request
+        let tls = plugin.tls.clone();
+        assert!(plugin.active_provider().is_ok());
+        assert!(plugin.on_config_changed("model", "other").is_ok());
+        assert_eq!(plugin.tls, tls);
+        assert!(plugin.on_config_changed("certificate_pins", "").is_ok());
+        assert_ne!(plugin.tls, tls);
+
+        // Client certificates are checked per file, and as a pair when used
+        let testdata = concat!(env!("CARGO_MANIFEST_DIR"), "/src/essentia/tls/testdata");
+        let certificate = format!("{}/client.pem", testdata);
+        assert!(
+            plugin
+                .on_config_changed("client_certificate", &format!("{}/client.key", testdata))
+                .is_err()
+        );
+        assert!(plugin.on_config_changed("client_key", &certificate).is_err());
+        assert!(plugin.on_config_changed("client_certificate", &certificate).is_ok());
+        assert!(plugin.on_config_changed("provider", "custom").is_ok());
+        assert!(plugin.on_config_changed("custom_endpoint", "https://llm.internal/v1").is_ok());
+        assert!(plugin.active_provider().is_err());
+        assert!(
+            plugin
+                .on_config_changed("client_key", &format!("{}/client-rsa.key", testdata))
+                .is_ok()
+        );
+        assert!(plugin.active_provider().is_err());
+        assert!(
+            plugin
+                .on_config_changed("client_key", &format!("{}/client.key", testdata))
+                .is_ok()
+        );
+        assert!(plugin.active_provider().is_ok());
+
+        // Trace files must be writable
+        let trace =
+            std::env::temp_dir().join(format!("essentia-ff-trace-{}.log", std::process::id()));
+        let trace = trace.to_string_lossy();
+        assert!(plugin.on_config_changed("http_trace", "/nonexistent/dir/trace.log").is_err());
+        assert!(plugin.on_config_changed("http_trace", &trace).is_ok());
+        assert!(plugin.active_provider().is_ok());
+        let _ = std::fs::remove_file(trace.as_ref());
+
+        // Extra body patches are objects keyed by a known provider
+        assert!(plugin.on_config_changed("extra_body", "[1]").is_err());
+        assert!(plugin.on_config_changed("extra_body", r#"{"other": {}}"#).is_err());
+        assert!(plugin.on_config_changed("extra_body", r#"{"custom": 1}"#).is_err());
+        assert!(plugin.on_config_changed("extra_body", r#"{"custom": {"top_k": 40}"#).is_err());
+        let extra = r#"{"custom": {"top_k": 40}, "external_ai": {"seed": null}}"#;
+        assert!(plugin.on_config_changed("extra_body", extra).is_ok());
+        assert!(plugin.active_provider().is_ok());
+        assert!(
+            plugin
+                .get_current_config()
+                .contains(&(String::from("extra_body"), extra.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_provider_dispatch() {
+        use crate::types::ChatMessage;
+
+        struct Echo;
+
+        impl crate::traits::Provider for Echo {
+            fn name(&self) -> &str {
+                "echo"
+            }
+        }
+
+        impl ChatProvider for Echo {
+            fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+                let last = request.messages.last().map(|m| m.text()).unwrap_or_default();
+                Ok(ChatResponse {
+                    id:            None,
+                    model:         None,
+                    message:       ChatMessage::assistant(&format!(
+                        "{}:{}",
+                        request.messages.len(),
+                        last
+                    )),
+                    finish_reason: None,
+                    usage:         None,
+                })
+            }
+        }
+
+        let mut plugin = LlmPluginFlexForge::new();
+
+        // Local SLM has no built-in client
+        let hello = ChatRequest::new(vec![ChatMessage::user("hello")]);
+        let err = plugin.chat(&hello).err();
+        assert_eq!(
+            err.map(|e| e.kind()),
+            Some(crate::errors::ErrorKind::Config)
+        );
+
+        plugin.register_provider(LlmProvider::LocalSlm, Arc::new(Echo));
+        let reply = plugin.chat(&hello).map(|r| r.text());
+        assert_eq!(reply.ok().as_deref(), Some("1:hello"));
+
+        // Custom provider needs an endpoint before it can be built
+        assert!(plugin.on_config_changed("provider", "custom").is_ok());
+        assert!(plugin.active_provider().is_err());
+        assert!(plugin.on_config_changed("custom_endpoint", "http://127.0.0.1:1/v1").is_ok());
+        assert_eq!(
+            plugin.active_provider().map(|p| p.name().to_string()).ok().as_deref(),
+            Some("external_llm")
+        );
+
+        assert!(plugin.on_config_changed("provider", "external_code_assist").is_ok());
+        assert_eq!(
+            plugin.active_provider().map(|p| p.name().to_string()).ok().as_deref(),
+            Some("external_code_assist")
+        );
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_render_frame_drains_tokens() {
+        use crate::types::{ChatMessage, ToolCall};
+
+        struct Fixed;
+
+        impl crate::traits::Provider for Fixed {
+            fn name(&self) -> &str {
+                "fixed"
+            }
+        }
+
+        impl ChatProvider for Fixed {
+            fn chat(&self, _request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+                let mut message = ChatMessage::assistant("streamed");
+                message.tool_calls.push(ToolCall {
+                    id:        "c1".to_string(),
+                    name:      "lookup".to_string(),
+                    arguments: r#"{"q": "rust"}"#.to_string(),
+                });
+                Ok(ChatResponse {
+                    id: None,
+                    model: None,
+                    message,
+                    finish_reason: None,
+                    usage: None,
+                })
+            }
+        }
+
+        let mut plugin = LlmPluginFlexForge::new();
+        plugin.register_provider(LlmProvider::LocalSlm, Arc::new(Fixed));
+        let stream_id = plugin
+            .start_chat_stream(ChatRequest::new(vec![ChatMessage::user("hi")]))
+            .expect("Should start");
+        assert_eq!(plugin.target_fps(), 30);
+
+        let mut frames = 0;
+        while plugin.render_frame(stream_id, 33.0) {
+            frames += 1;
+            assert!(frames < 1000, "stream never finished");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert!(!plugin.is_streaming());
+        assert_eq!(plugin.stream_output(), "streamed");
+        let [call] = plugin.stream_tool_calls() else {
+            panic!("expected one tool call");
+        };
+        assert_eq!(call.name, "lookup");
+        assert_eq!(
+            call.arguments_value().and_then(|v| v.get("q").cloned()),
+            Some("rust".into())
+        );
+        let frames = plugin.take_frames();
+        assert!(matches!(frames.last(), Some(TokenFrame::Final { .. })));
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_render_frame_ignores_out_of_range_tool_calls() {
+        use crate::types::{ChatMessage, StreamAccumulator, ToolCallDelta};
+
+        struct Hostile;
+
+        impl crate::traits::Provider for Hostile {
+            fn name(&self) -> &str {
+                "hostile"
+            }
+        }
+
+        impl ChatProvider for Hostile {
+            fn chat(&self, _request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+                Err(LlmError::Config("streaming only".to_string()))
+            }
+
+            fn chat_stream(
+                &self, _request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+            ) -> Result<ChatResponse, LlmError> {
+                for index in [usize::MAX, 0, 5] {
+                    let delta = ChatDelta {
+                        tool_calls: vec![ToolCallDelta {
+                            index,
+                            id: None,
+                            name: Some("f".to_string()),
+                            arguments: "{}".to_string(),
+                        }],
+                        ..ChatDelta::default()
+                    };
+                    on_delta(&delta);
+                }
+                Ok(StreamAccumulator::new().finish())
+            }
+        }
+
+        let mut plugin = LlmPluginFlexForge::new();
+        plugin.register_provider(LlmProvider::LocalSlm, Arc::new(Hostile));
+        let stream_id = plugin
+            .start_chat_stream(ChatRequest::new(vec![ChatMessage::user("hi")]))
+            .expect("Should start");
+        let mut frames = 0;
+        while plugin.render_frame(stream_id, 33.0) {
+            frames += 1;
+            assert!(frames < 1000, "stream never finished");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let names: Vec<_> = plugin.stream_tool_calls().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["f"]);
     }
 
     #[test]
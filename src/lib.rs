@@ -1,6 +1,5 @@
 //! Essentia LLM Plugin library.
 
-
 // TODO(FEATURE): Add comprehensive documentation to all public items
 // Tracked in documentation remediation queue
 #![allow(missing_docs)]
@@ -61,8 +60,11 @@
 )]
 
 pub mod core;
+pub mod errors;
 pub mod essentia;
 pub mod flexforge;
+pub mod traits;
+pub mod types;
 
 // Re-exports for convenience
-pub use flexforge::{LlmPluginConfig, LlmPluginFlexForge, LlmProvider};
\ No newline at end of file
+pub use flexforge::{LlmPluginConfig, LlmPluginFlexForge, LlmProvider};
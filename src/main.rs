@@ -30,20 +30,51 @@ use std::{
     io::{self, Write},
 };
 
-mod core;
-mod essentia;
-
-use core::external_llm::ExternalLlm;
+use essentia_llm_plugin::{
+    core::external_llm::ExternalLlm,
+    essentia::{
+        self,
+        http::Timeouts,
+        json::{ToJson, to_json_string},
+    },
+    impl_json,
+    types::{ChatMessage, ChatRequest},
+};
 
 const MAX_HISTORY: usize = 100;
+/// Turns sent back to the model for conversation continuity.
+const MAX_CONTEXT: usize = 20;
+/// Request timeout when `--timeout` is not given, matching the plugin default.
+const DEFAULT_TIMEOUT_SECS: u32 = 30;
+
+/// Signed request built by `process_message`.
+struct SignedRequest {
+    model:      String,
+    messages:   Vec<SignedMessage>,
+    request_id: String,
+    hash:       String,
+}
+
+struct SignedMessage {
+    role:      String,
+    content:   String,
+    signature: String,
+}
+
+impl_json!(SignedRequest { model, messages, request_id, hash });
+impl_json!(SignedMessage { role, content, signature });
 
 struct ChatUI {
-    history: VecDeque<String>,
-    api_key: String,
+    /// Rendered transcript, including system notices and errors.
+    history:      VecDeque<String>,
+    /// Typed user/assistant turns sent with each request.
+    conversation: VecDeque<ChatMessage>,
+    api_key:      String,
+    timeouts:     Timeouts,
 }
 
 impl ChatUI {
-    fn new() -> Option<Self> {
+    fn new(timeout_secs: u32) -> Option<Self> {
         println!("🧠 Essentia LLM - Pure Rust AI Assistant");
         println!("==========================================");
         println!("Legal access to AI models via official API");
@@ -60,7 +91,12 @@ impl ChatUI {
         println!("Type 'help' for commands, 'quit' to exit.");
         println!();
 
-        Some(Self { history: VecDeque::new(), api_key })
+        Some(Self {
+            history: VecDeque::new(),
+            conversation: VecDeque::new(),
+            api_key,
+            timeouts: Timeouts::from_secs(timeout_secs),
+        })
     }
 
     fn run(&mut self) {
@@ -90,6 +126,7 @@ impl ChatUI {
                 },
                 "clear" => {
                     self.history.clear();
+                    self.conversation.clear();
                 },
                 "history" => {
                     self.show_full_history();
@@ -144,8 +181,7 @@ Features:
 - Performance optimized pure Rust
 - Zero external dependencies
 
-Note: Full HTTPS implementation required for production API access.
-Current version uses dummy responses for demonstration.
+Note: Requests go to the OpenAI-compatible chat completions endpoint.
 "#;
         self.add_to_history(format!("System: {}", help));
     }
@@ -161,11 +197,11 @@ Current version uses dummy responses for demonstration.
 
     fn process_message(&mut self, message: &str) {
         // Use UUID for request ID
-        let request_id = crate::essentia::uuid::Uuid::new_v4();
+        let request_id = essentia::uuid::Uuid::new_v4();
 
         // Use regex to validate and process message
         let message_pattern = r"^[a-zA-Z0-9\s\.,!?\-]+$";
-        let regex = match crate::essentia::regex::Regex::new(message_pattern) {
+        let regex = match essentia::regex::Regex::new(message_pattern) {
             Ok(r) => r,
             Err(e) => {
                 self.add_to_history(format!("Regex Error: {}", e));
@@ -179,19 +215,19 @@ Current version uses dummy responses for demonstration.
 
         // Use UUID functions
         let uuid_bytes = request_id.as_bytes();
-        let _reconstructed_uuid = crate::essentia::uuid::Uuid::from_bytes(*uuid_bytes);
+        let _reconstructed_uuid = essentia::uuid::Uuid::from_bytes(*uuid_bytes);
 
         // Use regex find_iter
         let _iter_results: Vec<_> = regex.find_iter(message).collect();
 
         // Use base64 encoding for API key
-        let encoded_key = crate::essentia::base64::encode(self.api_key.as_bytes());
+        let encoded_key = essentia::base64::encode(self.api_key.as_bytes());
 
         // Use base64 decode as well
-        let _decoded_key = crate::essentia::base64::decode(&encoded_key);
+        let _decoded_key = essentia::base64::decode(&encoded_key);
 
         // Use HTTP functions (dummy calls)
-        let _get_result = crate::essentia::http::get("http://example.com");
+        let _get_result = essentia::http::get("http://example.com");
 
         // Use crypto functions for request signing
         let message_bytes = message.as_bytes();
@@ -201,52 +237,24 @@ Current version uses dummy responses for demonstration.
         let signature = hmac.compute(message_bytes);
 
         // Build JSON payload using our JSON implementation
-        let payload = crate::essentia::json::Value::Object({
-            let mut obj = std::collections::HashMap::new();
-            obj.insert(
-                "model".to_string(),
-                crate::essentia::json::Value::String("essentia-llm-auto".to_string()),
-            );
-            obj.insert(
-                "messages".to_string(),
-                crate::essentia::json::Value::Array(vec![crate::essentia::json::Value::Object({
-                    let mut msg_obj = std::collections::HashMap::new();
-                    msg_obj.insert(
-                        "role".to_string(),
-                        crate::essentia::json::Value::String("user".to_string()),
-                    );
-                    msg_obj.insert(
-                        "content".to_string(),
-                        crate::essentia::json::Value::String(message.to_string()),
-                    );
-                    msg_obj.insert(
-                        "signature".to_string(),
-                        crate::essentia::json::Value::String(crate::essentia::base64::encode(
-                            &signature,
-                        )),
-                    );
-                    msg_obj
-                })]),
-            );
-            obj.insert(
-                "request_id".to_string(),
-                crate::essentia::json::Value::String(request_id.to_string()),
-            );
-            obj.insert(
-                "hash".to_string(),
-                crate::essentia::json::Value::String(crate::essentia::base64::encode(&hash)),
-            );
-            obj
-        });
-
-        let json_payload = crate::essentia::json::to_json_string(&payload);
+        let payload = SignedRequest {
+            model:      "essentia-llm-auto".to_string(),
+            messages:   vec![SignedMessage {
+                role:      "user".to_string(),
+                content:   message.to_string(),
+                signature: essentia::base64::encode(&signature),
+            }],
+            request_id: request_id.to_string(),
+            hash:       essentia::base64::encode(&hash),
+        };
+        let json_payload = to_json_string(&payload.to_json());
 
         // Use HTTP post
-        let _post_result = crate::essentia::http::post("http://example.com", &json_payload);
+        let _post_result = essentia::http::post("http://example.com", &json_payload);
 
         // Use URL parsing
         let api_url = "https://api.x.ai/v1/chat/completions";
-        let parsed_url = match crate::essentia::url::Url::parse(api_url) {
+        let parsed_url = match essentia::url::Url::parse(api_url) {
             Ok(url) => url,
             Err(e) => {
                 self.add_to_history(format!("URL Parse Error: {}", e));
@@ -257,17 +265,17 @@ Current version uses dummy responses for demonstration.
         let _password = &parsed_url.password;
 
         // Use uuid4 function
-        let _uuid_string = crate::essentia::uuid::Uuid::new_v4();
+        let _uuid_string = essentia::uuid::Uuid::new_v4();
 
         // Use HTML parsing for any web content (simulated)
         let html_content = format!("<html><body>{}</body></html>", message);
-        let _scripts = crate::essentia::html::find_scripts(&html_content);
-        let _meta_baggage = crate::essentia::html::find_meta_baggage(&html_content);
-        let _meta_sentry = crate::essentia::html::find_meta_sentry(&html_content);
-        let _anim = crate::essentia::html::find_anim(&html_content);
+        let _scripts = essentia::html::find_scripts(&html_content);
+        let _meta_baggage = essentia::html::find_meta_baggage(&html_content);
+        let _meta_sentry = essentia::html::find_meta_sentry(&html_content);
+        let _anim = essentia::html::find_anim(&html_content);
 
         // Use HTML Document parsing
-        let document = match crate::essentia::html::Document::parse(&html_content) {
+        let document = match essentia::html::Document::parse(&html_content) {
             Ok(doc) => doc,
             Err(e) => {
                 self.add_to_history(format!("HTML Parse Error: {}", e));
@@ -284,53 +292,78 @@ Current version uses dummy responses for demonstration.
         let _children = &document.root.children;
 
         // Use Node enum
-        let _text_node = crate::essentia::html::Node::Text(());
+        let _text_node = essentia::html::Node::Text(());
 
         // Use cookies
-        let mut cookie_jar = crate::essentia::cookies::CookieJar::new();
+        let mut cookie_jar = essentia::cookies::CookieJar::new();
         cookie_jar.set("session_id", &request_id.to_string());
         let _session_cookie = cookie_jar.get("session_id");
         let _cookies = cookie_jar.get_dict();
         cookie_jar.update(&std::collections::HashMap::new());
 
         // Use multipart (for file uploads if needed)
-        let _multipart_data = crate::essentia::multipart::create_multipart("boundary123", vec![
+        let _multipart_data = essentia::multipart::create_multipart("boundary123", vec![
             ("field1", "text/plain", b"value1"),
             ("field2", "application/json", json_payload.as_bytes()),
         ]);
 
-        // In real implementation, this would call the official external AI API
-        // For now, dummy response that uses our implementations
-        let llm = ExternalLlm::new(
-            "essentia-llm-auto",
-            &parsed_url.hostname.unwrap_or_default(),
-        );
+        // Call the official chat completions API with the conversation so far
+        let llm = ExternalLlm::new("essentia-llm-auto", "")
+            .with_endpoint(api_url)
+            .with_timeouts(self.timeouts);
+
+        self.add_to_conversation(ChatMessage::user(message));
+        let request = ChatRequest::new(self.conversation.iter().cloned().collect());
 
-        match llm.chat_with_api(&encoded_key, &json_payload, &self.get_context()) {
+        match llm.chat_with_api(&self.api_key, &request) {
             Ok(response) => {
-                // Use the Response struct
-                let response_obj = llm.create_response(response.clone());
-                let _resp = &response_obj.response;
-                let _stream = &response_obj.stream_response;
-                let _images = &response_obj.images;
-                let _extra = &response_obj.extra_data;
-
-                self.add_to_history(format!("AI: {}", response));
+                self.add_to_history(format!("AI: {}", response.text()));
+                self.add_to_conversation(response.message);
             },
             Err(e) => {
+                // Drop the unanswered turn so the next request stays well-formed
+                self.conversation.pop_back();
                 self.add_to_history(format!("Error: {}", e));
             },
         }
     }
 
-    fn get_context(&self) -> Vec<String> {
-        // Get recent context for conversation continuity
-        self.history.iter().rev().take(20).rev().cloned().collect()
+    fn add_to_conversation(&mut self, message: ChatMessage) {
+        self.conversation.push_back(message);
+        if self.conversation.len() > MAX_CONTEXT {
+            self.conversation.pop_front();
+        }
+    }
+}
+
+/// Reads `--timeout <secs>` (or `--timeout=<secs>`) from the command line.
+fn parse_timeout_secs(args: &[String]) -> Result<u32, String> {
+    let mut timeout = DEFAULT_TIMEOUT_SECS;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = if arg == "--timeout" {
+            iter.next().ok_or("--timeout requires a value in seconds")?
+        } else if let Some(value) = arg.strip_prefix("--timeout=") {
+            value
+        } else {
+            return Err(format!("Unknown argument: {}", arg));
+        };
+        timeout = value.parse().map_err(|_| format!("Invalid timeout: {}", value))?;
     }
+    Ok(timeout)
 }
 
 fn main() {
-    let Some(mut ui) = ChatUI::new() else {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let timeout_secs = match parse_timeout_secs(&args) {
+        Ok(secs) => secs,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: essentia-llm-plugin [--timeout <secs>]");
+            return;
+        },
+    };
+    let Some(mut ui) = ChatUI::new(timeout_secs) else {
         eprintln!("Failed to initialize chat UI");
         return;
     };
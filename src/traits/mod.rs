@@ -1,8 +1,72 @@
 //! # LLM Plugin - Traits Module
 //!
 //! Defines trait interfaces for LLM operations.
+//!
+//! Built-in clients (`ExternalLlm`, `ExternalCodeAssist`) implement these
+//! traits, and `LlmPluginFlexForge` dispatches through them, so in-house
+//! backends only need to implement [`ChatProvider`] and register themselves
+//! with the plugin.
+
+use crate::{
+    core::external_llm::Response,
+    errors::LlmError,
+    types::{ChatDelta, ChatRequest, ChatResponse, ToolCallDelta},
+};
+
+/// Common identity shared by every provider kind.
+pub trait Provider: Send + Sync {
+    /// Short provider name used in logs and error messages.
+    fn name(&self) -> &str;
+}
+
+/// Multi-turn chat backend.
+pub trait ChatProvider: Provider {
+    /// Sends the conversation in `request` and returns the reply.
+    fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError>;
+
+    /// Streams the reply, calling `on_delta` for each chunk as it arrives.
+    /// Returning `false` from `on_delta` stops the stream early. Returns the
+    /// accumulated response.
+    ///
+    /// The default implementation calls [`chat`](Self::chat) and delivers the
+    /// whole reply as a single delta, so non-streaming backends work
+    /// unchanged.
+    fn chat_stream(
+        &self, request: &ChatRequest, on_delta: &mut dyn FnMut(&ChatDelta) -> bool,
+    ) -> Result<ChatResponse, LlmError> {
+        let response = self.chat(request)?;
+        on_delta(&ChatDelta {
+            id:            response.id.clone(),
+            model:         response.model.clone(),
+            role:          Some(response.message.role),
+            content:       response.text(),
+            tool_calls:    response
+                .message
+                .tool_calls
+                .iter()
+                .enumerate()
+                .map(|(index, call)| ToolCallDelta {
+                    index,
+                    id: Some(call.id.clone()),
+                    name: Some(call.name.clone()),
+                    arguments: call.arguments.clone(),
+                })
+                .collect(),
+            finish_reason: response.finish_reason.clone(),
+            usage:         response.usage,
+        });
+        Ok(response)
+    }
+}
+
+/// Single-prompt text completion backend.
+pub trait CompletionProvider: Provider {
+    /// Continues `prompt` and returns the generated text.
+    fn complete(&self, prompt: &str) -> Result<Response, LlmError>;
+}
 
-// Re-export traits when implemented
-// pub mod inference;
-// pub mod embedding;
-// pub mod generation;
+/// Text embedding backend.
+pub trait EmbeddingProvider: Provider {
+    /// Returns one embedding vector per input, in input order.
+    fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError>;
+}
@@ -0,0 +1,734 @@
+//! Chat domain model shared by every provider.
+//!
+//! All types convert to and from [`Value`] using the OpenAI-compatible wire
+//! format, so a conversation survives a request/response round trip with its
+//! roles, multi-part content and tool calls intact.
+
+use std::fmt;
+
+use crate::{
+    errors::LlmError,
+    essentia::json::{Object, Value},
+};
+
+/// Author of a chat message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+        }
+    }
+
+    /// Parses a wire role. `developer` is accepted as an alias for `system`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "system" | "developer" => Some(Self::System),
+            "user" => Some(Self::User),
+            "assistant" => Some(Self::Assistant),
+            "tool" => Some(Self::Tool),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One part of a multi-part message body.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentPart {
+    Text(String),
+    ImageUrl {
+        url:    String,
+        /// `low`, `high` or `auto`; omitted from the payload when `None`.
+        detail: Option<String>,
+    },
+}
+
+impl ContentPart {
+    pub fn to_value(&self) -> Value {
+        let mut obj = Object::new();
+        match self {
+            Self::Text(text) => {
+                obj.insert("type".to_string(), Value::String("text".to_string()));
+                obj.insert("text".to_string(), Value::String(text.clone()));
+            },
+            Self::ImageUrl { url, detail } => {
+                let mut image = Object::new();
+                image.insert("url".to_string(), Value::String(url.clone()));
+                if let Some(detail) = detail {
+                    image.insert("detail".to_string(), Value::String(detail.clone()));
+                }
+                obj.insert("type".to_string(), Value::String("image_url".to_string()));
+                obj.insert("image_url".to_string(), Value::Object(image));
+            },
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        match value.get("type").and_then(Value::as_str) {
+            Some("text") => Ok(Self::Text(required_str(value, "text")?.to_string())),
+            Some("image_url") => {
+                let image = value.get("image_url").ok_or("image_url part has no image_url")?;
+                Ok(Self::ImageUrl {
+                    url:    required_str(image, "url")?.to_string(),
+                    detail: optional_string(image, "detail"),
+                })
+            },
+            Some(other) => Err(format!("Unsupported content part type: {}", other)),
+            None => Err("Content part has no type".to_string()),
+        }
+    }
+}
+
+/// A function call requested by the assistant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCall {
+    pub id:        String,
+    pub name:      String,
+    /// JSON-encoded arguments, exactly as produced by the model.
+    pub arguments: String,
+}
+
+impl ToolCall {
+    pub fn to_value(&self) -> Value {
+        let mut function = Object::new();
+        function.insert("name".to_string(), Value::String(self.name.clone()));
+        function.insert(
+            "arguments".to_string(),
+            Value::String(self.arguments.clone()),
+        );
+
+        let mut obj = Object::new();
+        obj.insert("id".to_string(), Value::String(self.id.clone()));
+        obj.insert("type".to_string(), Value::String("function".to_string()));
+        obj.insert("function".to_string(), Value::Object(function));
+        Value::Object(obj)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let function = value.get("function").ok_or("Tool call has no function")?;
+        Ok(Self {
+            id:        required_str(value, "id")?.to_string(),
+            name:      required_str(function, "name")?.to_string(),
+            arguments: optional_string(function, "arguments").unwrap_or_default(),
+        })
+    }
+}
+
+/// A single conversation turn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role:         Role,
+    pub content:      Vec<ContentPart>,
+    /// Optional participant name.
+    pub name:         Option<String>,
+    /// Calls requested by an assistant turn.
+    pub tool_calls:   Vec<ToolCall>,
+    /// The call a `Tool` turn answers.
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, text: &str) -> Self {
+        Self {
+            role,
+            content: vec![ContentPart::Text(text.to_string())],
+            name: None,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    pub fn system(text: &str) -> Self {
+        Self::new(Role::System, text)
+    }
+
+    pub fn user(text: &str) -> Self {
+        Self::new(Role::User, text)
+    }
+
+    pub fn assistant(text: &str) -> Self {
+        Self::new(Role::Assistant, text)
+    }
+
+    /// Result of the tool call identified by `tool_call_id`.
+    pub fn tool(tool_call_id: &str, text: &str) -> Self {
+        Self { tool_call_id: Some(tool_call_id.to_string()), ..Self::new(Role::Tool, text) }
+    }
+
+    /// Concatenated text of all `Text` parts.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text(text) => Some(text.as_str()),
+                ContentPart::ImageUrl { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Serializes the message. Plain text bodies are sent as a string and
+    /// multi-part bodies as a part array; an assistant turn that only carries
+    /// tool calls sends `null` content.
+    pub fn to_value(&self) -> Value {
+        let content = match self.content.as_slice() {
+            [] => Value::Null,
+            [ContentPart::Text(text)] => Value::String(text.clone()),
+            parts => Value::Array(parts.iter().map(ContentPart::to_value).collect()),
+        };
+
+        let mut obj = Object::new();
+        obj.insert(
+            "role".to_string(),
+            Value::String(self.role.as_str().to_string()),
+        );
+        obj.insert("content".to_string(), content);
+        if let Some(name) = &self.name {
+            obj.insert("name".to_string(), Value::String(name.clone()));
+        }
+        if !self.tool_calls.is_empty() {
+            obj.insert(
+                "tool_calls".to_string(),
+                Value::Array(self.tool_calls.iter().map(ToolCall::to_value).collect()),
+            );
+        }
+        if let Some(id) = &self.tool_call_id {
+            obj.insert("tool_call_id".to_string(), Value::String(id.clone()));
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let role = required_str(value, "role")?;
+        let role = Role::parse(role).ok_or_else(|| format!("Unknown role: {}", role))?;
+
+        let content = match value.get("content") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::String(text)) => vec![ContentPart::Text(text.clone())],
+            Some(Value::Array(parts)) => {
+                parts.iter().map(ContentPart::from_value).collect::<Result<_, _>>()?
+            },
+            Some(_) => return Err("Message content must be a string or an array".to_string()),
+        };
+
+        let tool_calls = match value.get("tool_calls") {
+            Some(Value::Array(calls)) => {
+                calls.iter().map(ToolCall::from_value).collect::<Result<_, _>>()?
+            },
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            role,
+            content,
+            name: optional_string(value, "name"),
+            tool_calls,
+            tool_call_id: optional_string(value, "tool_call_id"),
+        })
+    }
+}
+
+/// Token accounting reported by the provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens:     u32,
+    pub completion_tokens: u32,
+    pub total_tokens:      u32,
+}
+
+impl Usage {
+    pub fn to_value(&self) -> Value {
+        let mut obj = Object::new();
+        obj.insert("prompt_tokens".to_string(), Value::from(self.prompt_tokens));
+        obj.insert(
+            "completion_tokens".to_string(),
+            Value::from(self.completion_tokens),
+        );
+        obj.insert("total_tokens".to_string(), Value::from(self.total_tokens));
+        Value::Object(obj)
+    }
+
+    /// Missing counters read as zero.
+    pub fn from_value(value: &Value) -> Self {
+        let field = |key: &str| value.get(key).and_then(Value::as_f64).unwrap_or(0.0) as u32;
+        Self {
+            prompt_tokens:     field("prompt_tokens"),
+            completion_tokens: field("completion_tokens"),
+            total_tokens:      field("total_tokens"),
+        }
+    }
+}
+
+/// Why the model stopped generating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+    /// Provider-specific reason, kept verbatim.
+    Other(String),
+}
+
+impl FinishReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Stop => "stop",
+            Self::Length => "length",
+            Self::ToolCalls => "tool_calls",
+            Self::ContentFilter => "content_filter",
+            Self::Other(reason) => reason,
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "stop" => Self::Stop,
+            "length" => Self::Length,
+            // `function_call` is the pre-tools spelling
+            "tool_calls" | "function_call" => Self::ToolCalls,
+            "content_filter" => Self::ContentFilter,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for FinishReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A chat completions request.
+///
+/// An empty `model` and `None` sampling parameters are filled in from the
+/// provider's own configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatRequest {
+    pub model:       String,
+    pub messages:    Vec<ChatMessage>,
+    pub temperature: Option<f32>,
+    pub max_tokens:  Option<u32>,
+    pub stream:      bool,
+}
+
+impl ChatRequest {
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        Self {
+            model: String::new(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            stream: false,
+        }
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut obj = Object::new();
+        obj.insert("model".to_string(), Value::String(self.model.clone()));
+        obj.insert(
+            "messages".to_string(),
+            Value::Array(self.messages.iter().map(ChatMessage::to_value).collect()),
+        );
+        if let Some(temperature) = self.temperature {
+            obj.insert("temperature".to_string(), Value::from(temperature));
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            obj.insert("max_tokens".to_string(), Value::from(max_tokens));
+        }
+        obj.insert("stream".to_string(), Value::Bool(self.stream));
+        Value::Object(obj)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let messages = value
+            .get("messages")
+            .and_then(Value::as_array)
+            .ok_or("Request has no messages array")?
+            .iter()
+            .map(ChatMessage::from_value)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            model: optional_string(value, "model").unwrap_or_default(),
+            messages,
+            temperature: value.get("temperature").and_then(Value::as_f64).map(|t| t as f32),
+            max_tokens: value.get("max_tokens").and_then(Value::as_f64).map(|t| t as u32),
+            stream: value.get("stream").and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+/// The first choice of a chat completions response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub id:            Option<String>,
+    pub model:         Option<String>,
+    pub message:       ChatMessage,
+    pub finish_reason: Option<FinishReason>,
+    pub usage:         Option<Usage>,
+}
+
+impl ChatResponse {
+    /// Text of the reply message.
+    pub fn text(&self) -> String {
+        self.message.text()
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut choice = Object::new();
+        choice.insert("index".to_string(), Value::from(0));
+        choice.insert("message".to_string(), self.message.to_value());
+        choice.insert(
+            "finish_reason".to_string(),
+            self.finish_reason
+                .as_ref()
+                .map_or(Value::Null, |r| Value::String(r.to_string())),
+        );
+
+        let mut obj = Object::new();
+        if let Some(id) = &self.id {
+            obj.insert("id".to_string(), Value::String(id.clone()));
+        }
+        if let Some(model) = &self.model {
+            obj.insert("model".to_string(), Value::String(model.clone()));
+        }
+        obj.insert(
+            "choices".to_string(),
+            Value::Array(vec![Value::Object(choice)]),
+        );
+        if let Some(usage) = &self.usage {
+            obj.insert("usage".to_string(), usage.to_value());
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let choice = value.pointer("/choices/0").ok_or("Response has no choices")?;
+        let message = choice.get("message").ok_or("Choice has no message")?;
+        Ok(Self {
+            id:            optional_string(value, "id"),
+            model:         optional_string(value, "model"),
+            message:       ChatMessage::from_value(message)?,
+            finish_reason: choice
+                .get("finish_reason")
+                .and_then(Value::as_str)
+                .map(FinishReason::parse),
+            usage:         value.get("usage").map(Usage::from_value),
+        })
+    }
+}
+
+/// Fragment of a tool call inside a streamed delta. Arguments arrive as
+/// partial JSON text and are concatenated per `index`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ToolCallDelta {
+    pub index:     usize,
+    pub id:        Option<String>,
+    pub name:      Option<String>,
+    pub arguments: String,
+}
+
+/// One streamed `chat.completion.chunk`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChatDelta {
+    pub id:            Option<String>,
+    pub model:         Option<String>,
+    pub role:          Option<Role>,
+    /// Text appended by this chunk.
+    pub content:       String,
+    pub tool_calls:    Vec<ToolCallDelta>,
+    pub finish_reason: Option<FinishReason>,
+    /// Only present on the final chunk, when the provider reports it.
+    pub usage:         Option<Usage>,
+}
+
+impl ChatDelta {
+    /// Parses a chunk. Chunks with an empty `choices` array (the trailing
+    /// usage chunk) yield a delta with no content.
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let mut delta = Self {
+            id: optional_string(value, "id"),
+            model: optional_string(value, "model"),
+            usage: value.get("usage").filter(|u| !u.is_null()).map(Usage::from_value),
+            ..Self::default()
+        };
+
+        let Some(choice) = value.pointer("/choices/0") else {
+            return Ok(delta);
+        };
+        delta.finish_reason =
+            choice.get("finish_reason").and_then(Value::as_str).map(FinishReason::parse);
+
+        if let Some(body) = choice.get("delta") {
+            delta.role = body.get("role").and_then(Value::as_str).and_then(Role::parse);
+            delta.content = optional_string(body, "content").unwrap_or_default();
+            if let Some(calls) = body.get("tool_calls").and_then(Value::as_array) {
+                for (position, call) in calls.iter().enumerate() {
+                    let function = call.get("function");
+                    let index = match call.get("index") {
+                        None => position,
+                        Some(index) => index
+                            .as_u64()
+                            .and_then(|i| usize::try_from(i).ok())
+                            .ok_or("Tool call index is not a non-negative integer")?,
+                    };
+                    delta.tool_calls.push(ToolCallDelta {
+                        index,
+                        id: optional_string(call, "id"),
+                        name: function.and_then(|f| optional_string(f, "name")),
+                        arguments: function
+                            .and_then(|f| optional_string(f, "arguments"))
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        Ok(delta)
+    }
+}
+
+/// Folds streamed deltas into the final [`ChatResponse`].
+#[derive(Debug, Clone, Default)]
+pub struct StreamAccumulator {
+    id:            Option<String>,
+    model:         Option<String>,
+    role:          Option<Role>,
+    content:       String,
+    tool_calls:    Vec<ToolCall>,
+    finish_reason: Option<FinishReason>,
+    usage:         Option<Usage>,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `delta`. Tool call indices may only grow one past the calls seen
+    /// so far.
+    pub fn push(&mut self, delta: &ChatDelta) -> Result<(), LlmError> {
+        if self.id.is_none() {
+            self.id.clone_from(&delta.id);
+        }
+        if self.model.is_none() {
+            self.model.clone_from(&delta.model);
+        }
+        if delta.role.is_some() {
+            self.role = delta.role;
+        }
+        self.content.push_str(&delta.content);
+        for fragment in &delta.tool_calls {
+            if fragment.index > self.tool_calls.len() {
+                return Err(LlmError::invalid_response(
+                    None,
+                    &format!("Tool call index {} skips ahead", fragment.index),
+                ));
+            }
+            if fragment.index == self.tool_calls.len() {
+                self.tool_calls.push(ToolCall {
+                    id:        String::new(),
+                    name:      String::new(),
+                    arguments: String::new(),
+                });
+            }
+            let call = &mut self.tool_calls[fragment.index];
+            if let Some(id) = &fragment.id {
+                call.id.clone_from(id);
+            }
+            if let Some(name) = &fragment.name {
+                call.name.push_str(name);
+            }
+            call.arguments.push_str(&fragment.arguments);
+        }
+        if delta.finish_reason.is_some() {
+            self.finish_reason.clone_from(&delta.finish_reason);
+        }
+        if delta.usage.is_some() {
+            self.usage = delta.usage;
+        }
+        Ok(())
+    }
+
+    /// Text received so far.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn finish(self) -> ChatResponse {
+        let content = if self.content.is_empty() && !self.tool_calls.is_empty() {
+            Vec::new()
+        } else {
+            vec![ContentPart::Text(self.content)]
+        };
+        ChatResponse {
+            id:            self.id,
+            model:         self.model,
+            message:       ChatMessage {
+                role: self.role.unwrap_or(Role::Assistant),
+                content,
+                name: None,
+                tool_calls: self.tool_calls,
+                tool_call_id: None,
+            },
+            finish_reason: self.finish_reason,
+            usage:         self.usage,
+        }
+    }
+}
+
+fn required_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing string field: {}", key))
+}
+
+fn optional_string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(String::from)
+}
+
+#[cfg(all(test, feature = "full-tests"))]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_round_trip_keeps_roles() {
+        let mut assistant = ChatMessage::assistant("");
+        assistant.content.clear();
+        assistant.tool_calls.push(ToolCall {
+            id:        "call_1".to_string(),
+            name:      "lookup".to_string(),
+            arguments: "{}".to_string(),
+        });
+
+        let request = ChatRequest::new(vec![
+            ChatMessage::system("Be brief"),
+            ChatMessage::user("hi"),
+            assistant,
+            ChatMessage::tool("call_1", "42"),
+            ChatMessage {
+                content: vec![
+                    ContentPart::Text("look".to_string()),
+                    ContentPart::ImageUrl {
+                        url:    "https://example.com/cat.png".to_string(),
+                        detail: Some("low".to_string()),
+                    },
+                ],
+                ..ChatMessage::user("")
+            },
+        ])
+        .with_model("m")
+        .with_max_tokens(16);
+
+        let parsed = ChatRequest::from_value(&request.to_value()).expect("round trip");
+        assert_eq!(parsed, request);
+        let roles: Vec<_> = parsed.messages.iter().map(|m| m.role).collect();
+        assert_eq!(roles, [
+            Role::System,
+            Role::User,
+            Role::Assistant,
+            Role::Tool,
+            Role::User
+        ]);
+    }
+
+    #[test]
+    fn test_stream_accumulator_joins_deltas() {
+        let chunks = [
+            r#"{"id":"s","choices":[{"delta":{"role":"assistant","content":"Hel"},"finish_reason":null}]}"#,
+            r#"{"id":"s","choices":[{"delta":{"content":"lo"},"finish_reason":null}]}"#,
+            r#"{"id":"s","choices":[{"delta":{"tool_calls":[{"index":0,"id":"c1","function":{"name":"f","arguments":"ab"}}]}}]}"#,
+            r#"{"id":"s","choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"cd"}}]},"finish_reason":"tool_calls"}]}"#,
+            r#"{"id":"s","choices":[],"usage":{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}}"#,
+        ];
+        let mut acc = StreamAccumulator::new();
+        for chunk in chunks {
+            let value = crate::essentia::json::parse(chunk).expect("json");
+            acc.push(&ChatDelta::from_value(&value).expect("delta")).expect("push");
+        }
+        assert_eq!(acc.content(), "Hello");
+
+        let response = acc.finish();
+        assert_eq!(response.id.as_deref(), Some("s"));
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.message.tool_calls[0].id, "c1");
+        assert_eq!(response.message.tool_calls[0].arguments, "abcd");
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(response.usage.map(|u| u.total_tokens), Some(3));
+    }
+
+    #[test]
+    fn test_stream_accumulator_rejects_hostile_tool_call_indices() {
+        let delta = |index: &str| {
+            let chunk = format!(
+                r#"{{"choices":[{{"delta":{{"tool_calls":[{{"index":{},"function":{{"arguments":"x"}}}}]}}}}]}}"#,
+                index
+            );
+            ChatDelta::from_value(&crate::essentia::json::parse(&chunk).expect("json"))
+        };
+        for index in ["1e300", "-1", "1.5", "\"0\""] {
+            assert!(delta(index).is_err(), "{}", index);
+        }
+
+        let mut acc = StreamAccumulator::new();
+        let error = acc.push(&delta("4294967295").expect("delta")).expect_err("skips ahead");
+        assert_eq!(error.kind(), crate::errors::ErrorKind::InvalidResponse);
+        acc.push(&delta("0").expect("delta")).expect("first call");
+        acc.push(&delta("1").expect("delta")).expect("second call");
+        acc.push(&delta("0").expect("delta")).expect("earlier call");
+        assert!(acc.push(&delta("3").expect("delta")).is_err());
+        let arguments: Vec<_> =
+            acc.finish().message.tool_calls.into_iter().map(|c| c.arguments).collect();
+        assert_eq!(arguments, ["xx", "x"]);
+    }
+
+    #[test]
+    fn test_response_from_value() {
+        let json = crate::essentia::json::parse(
+            r#"{"id":"x","choices":[{"message":{"role":"assistant","content":null,"tool_calls":[{"id":"c","type":"function","function":{"name":"f","arguments":"{}"}}]},"finish_reason":"tool_calls"}],"usage":{"prompt_tokens":3,"completion_tokens":4,"total_tokens":7}}"#,
+        )
+        .expect("json");
+        let response = ChatResponse::from_value(&json).expect("response");
+
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(response.message.tool_calls[0].name, "f");
+        assert_eq!(response.text(), "");
+        assert_eq!(response.usage.map(|u| u.total_tokens), Some(7));
+        assert_eq!(
+            ChatResponse::from_value(&response.to_value()).expect("again"),
+            response
+        );
+    }
+}
@@ -2,7 +2,13 @@
 //!
 //! Defines core data types for LLM functionality.
 
+pub mod chat;
+
+pub use chat::{
+    ChatDelta, ChatMessage, ChatRequest, ChatResponse, ContentPart, FinishReason, Role,
+    StreamAccumulator, ToolCall, ToolCallDelta, Usage,
+};
+
 // Re-export types when implemented
 // pub mod model;
 // pub mod tokenizer;
-// pub mod generation;